@@ -0,0 +1,242 @@
+//! AEAD (AES-GCM/AES-CCM) syscall driver, layered on
+//! `hil::aead::AeadEngine` so the same capsule works whether a board
+//! wires up a hardware accelerator (CryptoCell, CRYP) or a software
+//! fallback — apps doing network security otherwise only have raw
+//! block-cipher primitives to build their own (easy to get wrong)
+//! authenticated mode on top of.
+//!
+//! Each process gets its own key slot in its grant; since the
+//! underlying engine only holds one active key at a time, `ENCRYPT`
+//! and `DECRYPT` reload the calling process's key before starting the
+//! operation, so two processes can use different keys without either
+//! one seeing the other's.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let aead = static_init!(
+//!     capsules::aes_gcm::AeadDriver<'static>,
+//!     capsules::aes_gcm::AeadDriver::new(
+//!         engine,
+//!         mode,
+//!         kernel::Grant::create(capsules::driver::NUM::Aead as usize),
+//!         buffer));
+//! ```
+
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::aead::{AeadClient, AeadEngine, AeadMode};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Aead as usize;
+
+const MAX_KEY_LEN: usize = 32;
+const MAX_NONCE_LEN: usize = 16;
+/// Length of the authentication tag `encrypt`/`decrypt` append/expect
+/// immediately after the plaintext/ciphertext.
+const TAG_LEN: usize = 16;
+
+mod upcall {
+    pub const DONE: usize = 0;
+}
+
+mod cmd {
+    /// Loads a new key for the calling process from the buffer
+    /// allowed at index 0, `data1` bytes long.
+    pub const SET_KEY: usize = 0;
+    /// Encrypts in place: `data1` is the associated-data length,
+    /// `data2` the plaintext length, both within the buffer allowed
+    /// at index 1; the nonce comes from the buffer allowed at index
+    /// 2.
+    pub const ENCRYPT: usize = 1;
+    /// Decrypts in place with the same buffer layout as `ENCRYPT`;
+    /// the completion upcall's second argument is `1` if the
+    /// authentication tag matched, `0` otherwise.
+    pub const DECRYPT: usize = 2;
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    key: [u8; MAX_KEY_LEN],
+    key_len: Option<usize>,
+    /// The buffer allowed at index 0, holding the bytes for the next
+    /// `SET_KEY`.
+    key_buffer: Option<AppSlice<Shared, u8>>,
+    /// The buffer allowed at index 1: associated data + plaintext or
+    /// ciphertext + tag, read from and written back in place.
+    data: Option<AppSlice<Shared, u8>>,
+    /// The buffer allowed at index 2, holding the nonce.
+    nonce: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct AeadDriver<'a> {
+    engine: &'a dyn AeadEngine<'a>,
+    mode: AeadMode,
+    apps: Grant<App>,
+    current_app: OptionalCell<AppId>,
+    buffer: TakeCell<'static, [u8]>,
+}
+
+impl<'a> AeadDriver<'a> {
+    pub fn new(
+        engine: &'a dyn AeadEngine<'a>,
+        mode: AeadMode,
+        apps: Grant<App>,
+        buffer: &'static mut [u8],
+    ) -> AeadDriver<'a> {
+        AeadDriver {
+            engine,
+            mode,
+            apps,
+            current_app: OptionalCell::empty(),
+            buffer: TakeCell::new(buffer),
+        }
+    }
+}
+
+impl<'a> Driver for AeadDriver<'a> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::DONE => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.key_buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            1 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.data = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            2 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.nonce = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::SET_KEY => {
+                if data1 > MAX_KEY_LEN {
+                    return ReturnCode::ESIZE;
+                }
+                self.apps
+                    .enter(app_id, |app, _| match &app.key_buffer {
+                        Some(slice) if data1 <= slice.len() => {
+                            app.key[..data1].copy_from_slice(&slice.as_ref()[..data1]);
+                            app.key_len = Some(data1);
+                            ReturnCode::SUCCESS
+                        }
+                        Some(_) => ReturnCode::ESIZE,
+                        None => ReturnCode::EINVAL,
+                    })
+                    .unwrap_or(ReturnCode::FAIL)
+            }
+            cmd::ENCRYPT | cmd::DECRYPT => {
+                if self.current_app.is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                let aad_len = data1;
+                let payload_len = data2;
+                let total_len = match aad_len.checked_add(payload_len).and_then(|n| n.checked_add(TAG_LEN)) {
+                    Some(n) => n,
+                    None => return ReturnCode::ESIZE,
+                };
+                let buffer = match self.buffer.take() {
+                    Some(buffer) => buffer,
+                    None => return ReturnCode::EBUSY,
+                };
+                if total_len > buffer.len() {
+                    self.buffer.replace(buffer);
+                    return ReturnCode::ESIZE;
+                }
+                let mut nonce = [0u8; MAX_NONCE_LEN];
+                let mut nonce_len = 0usize;
+                let prepare_result = self
+                    .apps
+                    .enter(app_id, |app, _| {
+                        let key_len = match app.key_len {
+                            Some(len) => len,
+                            None => return ReturnCode::EINVAL,
+                        };
+                        let data_slice = match &app.data {
+                            Some(slice) if total_len <= slice.len() => slice,
+                            Some(_) => return ReturnCode::ESIZE,
+                            None => return ReturnCode::EINVAL,
+                        };
+                        let nonce_slice = match &app.nonce {
+                            Some(slice) if slice.len() <= MAX_NONCE_LEN => slice,
+                            Some(_) => return ReturnCode::ESIZE,
+                            None => return ReturnCode::EINVAL,
+                        };
+                        let key_result = self.engine.set_key(&app.key[..key_len]);
+                        if key_result != ReturnCode::SUCCESS {
+                            return key_result;
+                        }
+                        buffer[..total_len].copy_from_slice(&data_slice.as_ref()[..total_len]);
+                        nonce_len = nonce_slice.len();
+                        nonce[..nonce_len].copy_from_slice(nonce_slice.as_ref());
+                        ReturnCode::SUCCESS
+                    })
+                    .unwrap_or(ReturnCode::FAIL);
+                if prepare_result != ReturnCode::SUCCESS {
+                    self.buffer.replace(buffer);
+                    return prepare_result;
+                }
+                self.current_app.set(app_id);
+                let result = if command_num == cmd::ENCRYPT {
+                    self.engine.encrypt(self.mode, buffer, aad_len, payload_len, &nonce[..nonce_len])
+                } else {
+                    self.engine.decrypt(self.mode, buffer, aad_len, payload_len, &nonce[..nonce_len])
+                };
+                if result != ReturnCode::SUCCESS {
+                    self.current_app.clear();
+                }
+                result
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a> AeadClient for AeadDriver<'a> {
+    fn crypt_done(&self, buffer: &'static mut [u8], result: ReturnCode, tag_valid: bool) {
+        if let Some(app_id) = self.current_app.take() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if result == ReturnCode::SUCCESS {
+                    if let Some(dest) = &mut app.data {
+                        let len = core::cmp::min(dest.len(), buffer.len());
+                        dest.as_mut()[..len].copy_from_slice(&buffer[..len]);
+                    }
+                }
+                if let Some(mut cb) = app.callback {
+                    let tag_ok = if tag_valid { 1 } else { 0 };
+                    cb.schedule(usize::from(result), tag_ok, 0);
+                }
+            });
+        }
+        self.buffer.replace(buffer);
+    }
+}
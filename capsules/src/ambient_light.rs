@@ -0,0 +1,126 @@
+//! Provides userspace applications with the ability to query the
+//! current ambient light conditions.
+//!
+//! The presence of a specific chip-dependent ambient light sensor
+//! (e.g. `opt3001`) is abstracted away by the
+//! `hil::sensors::AmbientLight` trait. This capsule handles any
+//! platform-specific chip layer to allow devices to inter-operate with
+//! any ambient light sensor. An application may request a single
+//! instantaneous reading, or call the `ENABLE_THRESHOLD_MODE` command
+//! to put the sensor into continuous mode. Every reading the sensor
+//! reports, whether it answers a `READ` or a threshold crossing, is
+//! delivered only to apps with a `READ` outstanding; an app with no
+//! `READ` outstanding gets no upcall for a threshold crossing.
+//! Multiple applications can use this capsule simultaneously; readings
+//! are queued and multiplexed to whichever app is waiting.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let light = static_init!(
+//!     capsules::ambient_light::AmbientLight<'static>,
+//!     capsules::ambient_light::AmbientLight::new(
+//!         opt3001,
+//!         kernel::Grant::create(capsules::driver::NUM::AmbientLight as usize)));
+//! hil::sensors::AmbientLight::set_client(opt3001, light);
+//! ```
+
+use core::cell::Cell;
+use kernel::hil::sensors;
+use kernel::{AppId, Callback, Driver, Grant, ReturnCode};
+
+mod upcall {
+    pub const READING: usize = 0;
+}
+
+mod cmd {
+    pub const CHECK: usize = 0;
+    pub const READ: usize = 1;
+    pub const ENABLE_THRESHOLD_MODE: usize = 2;
+    pub const DISABLE_THRESHOLD_MODE: usize = 3;
+    pub const SET_THRESHOLD: usize = 4;
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    pending: bool,
+}
+
+pub struct AmbientLight<'a> {
+    sensor: &'a dyn sensors::AmbientLight,
+    command_pending: Cell<bool>,
+    apps: Grant<App>,
+}
+
+impl<'a> AmbientLight<'a> {
+    pub fn new(sensor: &'a dyn sensors::AmbientLight, grant: Grant<App>) -> AmbientLight<'a> {
+        AmbientLight {
+            sensor,
+            command_pending: Cell::new(false),
+            apps: grant,
+        }
+    }
+
+    fn enqueue_sample(&self, app_id: AppId) -> ReturnCode {
+        self.apps
+            .enter(app_id, |app, _| {
+                if self.command_pending.get() {
+                    app.pending = true;
+                    ReturnCode::SUCCESS
+                } else {
+                    self.command_pending.set(true);
+                    app.pending = true;
+                    self.sensor.read_light_intensity()
+                }
+            })
+            .unwrap_or(ReturnCode::FAIL)
+    }
+}
+
+impl<'a> sensors::AmbientLightClient for AmbientLight<'a> {
+    fn callback(&self, lux: usize) {
+        self.command_pending.set(false);
+        for appid in self.apps.iter() {
+            let _ = self.apps.enter(appid, |app, _| {
+                if app.pending {
+                    app.pending = false;
+                    if let Some(mut cb) = app.callback {
+                        cb.schedule(lux, 0, 0);
+                    }
+                }
+            });
+        }
+    }
+}
+
+impl<'a> Driver for AmbientLight<'a> {
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        app_id: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            upcall::READING => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::CHECK => ReturnCode::SUCCESS,
+            cmd::READ => self.enqueue_sample(app_id),
+            cmd::ENABLE_THRESHOLD_MODE => self.sensor.enable_continuous_mode(),
+            cmd::DISABLE_THRESHOLD_MODE => self.sensor.disable_continuous_mode(),
+            cmd::SET_THRESHOLD => self.sensor.configure_threshold(data1, data2),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
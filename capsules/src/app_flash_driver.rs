@@ -0,0 +1,198 @@
+//! Lets a process read, program, and erase flash pages directly,
+//! restricted to the writeable flash region(s) it declared in its TBF
+//! header (see `memop::MemOp::WriteableFlashRegionStart`/`Len`), for
+//! apps implementing their own on-device data format or wear leveling
+//! instead of going through `nonvolatile_storage_driver`.
+//!
+//! Every `command` is validated against the calling process's declared
+//! region before it reaches the flash HIL, and against a busy flag the
+//! kernel itself sets while using the same flash controller (for
+//! example while writing a crash dump), so an app can never race a
+//! privileged flash operation.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let app_flash = static_init!(
+//!     capsules::app_flash_driver::AppFlashDriver<'static>,
+//!     capsules::app_flash_driver::AppFlashDriver::new(flash, process_regions, buffer));
+//! ```
+
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::nonvolatile_storage::{NonvolatileStorage, NonvolatileStorageClient};
+use kernel::{AppId, AppSlice, Callback, Driver, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::AppFlash as usize;
+
+mod upcall {
+    pub const DONE: usize = 0;
+}
+
+mod cmd {
+    /// Returns success if the calling process has a declared writeable
+    /// region at all.
+    pub const HAS_REGION: usize = 0;
+    /// Reads `data2` bytes at offset `data1` (relative to the start of
+    /// the process's region) into the buffer allowed at index 0.
+    /// Completion is reported via the `DONE` upcall.
+    pub const READ: usize = 1;
+    /// Programs `data2` bytes from the buffer allowed at index 0 at
+    /// offset `data1`. Completion is reported via the `DONE` upcall.
+    pub const PROGRAM: usize = 2;
+    pub const ERASE: usize = 3;
+}
+
+/// Supplies each process's writeable flash region, as declared in its
+/// TBF header. Kept as a narrow trait so this driver does not need to
+/// know how the board tracks per-process TBF metadata.
+pub trait AppFlashRegion {
+    fn writeable_region(&self, app_id: AppId) -> Option<(usize, usize)>;
+}
+
+pub struct AppFlashDriver<'a> {
+    flash: &'a dyn NonvolatileStorage<'a>,
+    regions: &'a dyn AppFlashRegion,
+    /// Set by the kernel (not by any `command`) while it is using the
+    /// same underlying flash controller for its own purposes.
+    kernel_busy: core::cell::Cell<bool>,
+    current_app: OptionalCell<AppId>,
+    callback: OptionalCell<Callback>,
+    buffer: TakeCell<'static, [u8]>,
+    /// The buffer allowed at index 0: read from for `PROGRAM`, written
+    /// into for `READ`.
+    data: core::cell::Cell<Option<AppSlice<Shared, u8>>>,
+}
+
+impl<'a> AppFlashDriver<'a> {
+    pub fn new(flash: &'a dyn NonvolatileStorage<'a>, regions: &'a dyn AppFlashRegion, buffer: &'static mut [u8]) -> AppFlashDriver<'a> {
+        AppFlashDriver {
+            flash,
+            regions,
+            kernel_busy: core::cell::Cell::new(false),
+            current_app: OptionalCell::empty(),
+            callback: OptionalCell::empty(),
+            buffer: TakeCell::new(buffer),
+            data: core::cell::Cell::new(None),
+        }
+    }
+
+    /// Called by the kernel immediately before and after it uses the
+    /// same flash controller, so concurrent app commands are rejected
+    /// with `EBUSY` rather than interleaved with kernel traffic.
+    pub fn set_kernel_busy(&self, busy: bool) {
+        self.kernel_busy.set(busy);
+    }
+
+    fn validate(&self, app_id: AppId, offset: usize, length: usize) -> Result<usize, ReturnCode> {
+        let (start, size) = self.regions.writeable_region(app_id).ok_or(ReturnCode::ENOSUPPORT)?;
+        match offset.checked_add(length) {
+            Some(end) if end <= size => Ok(start + offset),
+            _ => Err(ReturnCode::EINVAL),
+        }
+    }
+}
+
+impl<'a> Driver for AppFlashDriver<'a> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, _app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::DONE => {
+                match callback {
+                    Some(cb) => self.callback.set(cb),
+                    None => self.callback.clear(),
+                }
+                ReturnCode::SUCCESS
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, _app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => {
+                self.data.set(slice);
+                ReturnCode::SUCCESS
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, data2: usize, app_id: AppId) -> ReturnCode {
+        if self.kernel_busy.get() || self.current_app.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        match command_num {
+            cmd::HAS_REGION => match self.regions.writeable_region(app_id) {
+                Some(_) => ReturnCode::SUCCESS,
+                None => ReturnCode::ENOSUPPORT,
+            },
+            cmd::READ | cmd::PROGRAM | cmd::ERASE => {
+                let absolute = match self.validate(app_id, data1, data2) {
+                    Ok(offset) => offset,
+                    Err(e) => return e,
+                };
+                let result = match command_num {
+                    cmd::ERASE => self.flash.erase(absolute, data2),
+                    cmd::READ => match self.buffer.take() {
+                        Some(buffer) if buffer.len() >= data2 => self.flash.read(buffer, absolute, data2),
+                        Some(buffer) => {
+                            self.buffer.replace(buffer);
+                            ReturnCode::ESIZE
+                        }
+                        None => ReturnCode::EBUSY,
+                    },
+                    cmd::PROGRAM => {
+                        let slice = self.data.take();
+                        let result = match &slice {
+                            Some(slice) if data2 <= slice.len() => match self.buffer.take() {
+                                Some(buffer) if buffer.len() >= data2 => {
+                                    buffer[..data2].copy_from_slice(&slice.as_ref()[..data2]);
+                                    self.flash.write(buffer, absolute, data2)
+                                }
+                                Some(buffer) => {
+                                    self.buffer.replace(buffer);
+                                    ReturnCode::ESIZE
+                                }
+                                None => ReturnCode::EBUSY,
+                            },
+                            Some(_) => ReturnCode::ESIZE,
+                            None => ReturnCode::EINVAL,
+                        };
+                        self.data.set(slice);
+                        result
+                    }
+                    _ => unreachable!(),
+                };
+                if result == ReturnCode::SUCCESS {
+                    self.current_app.set(app_id);
+                }
+                result
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a> NonvolatileStorageClient for AppFlashDriver<'a> {
+    fn read_done(&self, buffer: &'static mut [u8], length: usize) {
+        if let Some(mut dest) = self.data.take() {
+            let len = core::cmp::min(dest.len(), length);
+            dest.as_mut()[..len].copy_from_slice(&buffer[..len]);
+            self.data.set(Some(dest));
+        }
+        self.buffer.replace(buffer);
+        self.current_app.clear();
+        self.callback.map(|mut cb| cb.schedule(usize::from(ReturnCode::SUCCESS), length, 0));
+    }
+
+    fn write_done(&self, buffer: &'static mut [u8], length: usize) {
+        self.buffer.replace(buffer);
+        self.current_app.clear();
+        self.callback.map(|mut cb| cb.schedule(usize::from(ReturnCode::SUCCESS), length, 0));
+    }
+
+    fn erase_done(&self) {
+        self.current_app.clear();
+        self.callback.map(|mut cb| cb.schedule(usize::from(ReturnCode::SUCCESS), 0, 0));
+    }
+}
@@ -0,0 +1,91 @@
+//! Receives a TBF binary in chunks over a byte-stream transport
+//! (console frames, USB) and writes it into a free app flash slot,
+//! enabling over-the-wire app updates without a reboot.
+//!
+//! The capsule itself only handles the transport and staging; once the
+//! image is fully received, `finish` moves it to the validating state.
+//! It does not check the TBF header or checksum itself, and it does not
+//! ask the kernel's process loader to load or start the image.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let loader = static_init!(
+//!     capsules::app_loader::AppLoader<'static>,
+//!     capsules::app_loader::AppLoader::new(flash_slot, transport_buffer));
+//! uart_mux_device.set_client(loader);
+//! ```
+
+use kernel::common::cells::TakeCell;
+use kernel::ReturnCode;
+
+#[derive(Copy, Clone, PartialEq)]
+enum State {
+    Idle,
+    Receiving { offset: usize },
+    Validating,
+}
+
+/// Where the loader writes the incoming image; abstracts over whether
+/// the slot backing it is internal or external flash.
+pub trait FlashSlot {
+    fn write_at(&self, offset: usize, data: &[u8]) -> ReturnCode;
+    fn size(&self) -> usize;
+}
+
+pub struct AppLoader<'a> {
+    slot: &'a dyn FlashSlot,
+    state: core::cell::Cell<State>,
+    buffer: TakeCell<'static, [u8]>,
+}
+
+impl<'a> AppLoader<'a> {
+    pub fn new(slot: &'a dyn FlashSlot, buffer: &'static mut [u8]) -> AppLoader<'a> {
+        AppLoader {
+            slot,
+            state: core::cell::Cell::new(State::Idle),
+            buffer: TakeCell::new(buffer),
+        }
+    }
+
+    pub fn begin(&self) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        self.state.set(State::Receiving { offset: 0 });
+        ReturnCode::SUCCESS
+    }
+
+    /// Called by the transport (console frame parser, USB class) with
+    /// the next chunk of the incoming TBF image.
+    pub fn receive_chunk(&self, chunk: &[u8]) -> ReturnCode {
+        match self.state.get() {
+            State::Receiving { offset } => {
+                if offset + chunk.len() > self.slot.size() {
+                    self.state.set(State::Idle);
+                    return ReturnCode::ESIZE;
+                }
+                let result = self.slot.write_at(offset, chunk);
+                if result == ReturnCode::SUCCESS {
+                    self.state.set(State::Receiving {
+                        offset: offset + chunk.len(),
+                    });
+                }
+                result
+            }
+            _ => ReturnCode::EBUSY,
+        }
+    }
+
+    /// Called once the transport signals end-of-image; moves the loader
+    /// to `State::Validating`. No validation or loading is started here.
+    pub fn finish(&self) -> ReturnCode {
+        match self.state.get() {
+            State::Receiving { .. } => {
+                self.state.set(State::Validating);
+                ReturnCode::SUCCESS
+            }
+            _ => ReturnCode::EBUSY,
+        }
+    }
+}
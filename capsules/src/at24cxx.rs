@@ -0,0 +1,220 @@
+//! Driver for Atmel/Microchip AT24Cxx-family I2C EEPROMs, implementing
+//! `hil::nonvolatile_storage::NonvolatileStorage` so the existing
+//! storage capsules (`nonvolatile_storage_driver`, `littlefs`) work
+//! against an external EEPROM without changes.
+//!
+//! Two quirks of this chip family drive most of the state machine:
+//!
+//! - Writes are only atomic within a `page_size`-byte page aligned to
+//!   the chip's internal page boundary; a write that crosses a page
+//!   boundary must be split into multiple I2C transactions or the
+//!   bytes past the boundary silently wrap and overwrite the start of
+//!   the same page instead of landing where requested.
+//! - After each page write the chip is busy committing to its cell
+//!   array and does not ACK its own address for up to a few
+//!   milliseconds; rather than blocking on a fixed delay, this driver
+//!   polls by reattempting a zero-length write until the chip ACKs.
+//!
+//! Smaller parts (AT24C01/02) use a single in-address byte for the
+//! memory offset; larger ones (AT24C04 and up) use two, selected by
+//! `AddressWidth` at construction.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let eeprom = static_init!(
+//!     capsules::at24cxx::At24Cxx<'static>,
+//!     capsules::at24cxx::At24Cxx::new(
+//!         i2c_device, capsules::at24cxx::AddressWidth::TwoBytes, 32, 256, buffer));
+//! eeprom.set_client(storage_client);
+//! ```
+
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::i2c::{Error, I2CClient, I2CDevice};
+use kernel::hil::nonvolatile_storage::{NonvolatileStorage, NonvolatileStorageClient};
+use kernel::ReturnCode;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum AddressWidth {
+    /// AT24C01/02: the whole device fits in a single in-address byte.
+    OneByte,
+    /// AT24C04 and larger: a two-byte memory address.
+    TwoBytes,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum State {
+    Idle,
+    Writing { offset: usize, remaining: usize },
+    /// Polling the chip's own address with a zero-length write until
+    /// it ACKs, signaling the prior page write has completed.
+    AckPolling { offset: usize, remaining: usize },
+    Reading,
+}
+
+pub struct At24Cxx<'a> {
+    i2c: &'a dyn I2CDevice,
+    address_width: AddressWidth,
+    page_size: usize,
+    size: usize,
+    state: core::cell::Cell<State>,
+    buffer: TakeCell<'static, [u8]>,
+    client: OptionalCell<&'a dyn NonvolatileStorageClient>,
+}
+
+impl<'a> At24Cxx<'a> {
+    pub fn new(
+        i2c: &'a dyn I2CDevice,
+        address_width: AddressWidth,
+        page_size: usize,
+        size: usize,
+        buffer: &'static mut [u8],
+    ) -> At24Cxx<'a> {
+        At24Cxx {
+            i2c,
+            address_width,
+            page_size,
+            size,
+            state: core::cell::Cell::new(State::Idle),
+            buffer: TakeCell::new(buffer),
+            client: OptionalCell::empty(),
+        }
+    }
+
+    fn address_header_len(&self) -> usize {
+        match self.address_width {
+            AddressWidth::OneByte => 1,
+            AddressWidth::TwoBytes => 2,
+        }
+    }
+
+    /// Bytes remaining until `offset` crosses into the next page, i.e.
+    /// the largest chunk that can be written in one transaction.
+    fn bytes_to_page_boundary(&self, offset: usize) -> usize {
+        self.page_size - (offset % self.page_size)
+    }
+}
+
+impl<'a> NonvolatileStorage<'a> for At24Cxx<'a> {
+    fn set_client(&self, client: &'a dyn NonvolatileStorageClient) {
+        self.client.set(client);
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn read(&self, buffer: &'static mut [u8], offset: usize, length: usize) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        if offset + length > self.size {
+            return ReturnCode::ESIZE;
+        }
+        self.state.set(State::Reading);
+        self.i2c.write_read(buffer, self.address_header_len() as u8, length as u8);
+        ReturnCode::SUCCESS
+    }
+
+    fn write(&self, buffer: &'static mut [u8], offset: usize, length: usize) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        if offset + length > self.size {
+            return ReturnCode::ESIZE;
+        }
+        let chunk = core::cmp::min(length, self.bytes_to_page_boundary(offset));
+        self.state.set(State::Writing {
+            offset,
+            remaining: length - chunk,
+        });
+        self.i2c.write(buffer, (self.address_header_len() + chunk) as u8);
+        ReturnCode::SUCCESS
+    }
+
+    fn erase(&self, _offset: usize, _length: usize) -> ReturnCode {
+        // EEPROM cells don't require a separate erase cycle before a
+        // write the way NOR flash does.
+        ReturnCode::SUCCESS
+    }
+}
+
+impl<'a> I2CClient for At24Cxx<'a> {
+    fn command_complete(&self, buffer: &'static mut [u8], error: Error) {
+        match self.state.get() {
+            State::Reading => {
+                self.state.set(State::Idle);
+                let length = buffer.len();
+                self.client.map(|client| client.read_done(buffer, length));
+            }
+            State::Writing { offset, remaining } => {
+                if error != Error::CommandComplete {
+                    self.buffer.replace(buffer);
+                    self.state.set(State::Idle);
+                    return;
+                }
+                self.buffer.replace(buffer);
+                self.state.set(State::AckPolling { offset, remaining });
+                self.i2c.write(self.buffer.take().unwrap(), 0);
+            }
+            State::AckPolling { offset, remaining } => {
+                if error != Error::CommandComplete {
+                    // Chip still busy committing the page; keep
+                    // polling instead of giving up.
+                    self.buffer.replace(buffer);
+                    self.i2c.write(self.buffer.take().unwrap(), 0);
+                    return;
+                }
+                if remaining == 0 {
+                    self.state.set(State::Idle);
+                    self.client.map(|client| client.write_done(buffer, 0));
+                } else {
+                    let next_offset = offset + self.page_size - (offset % self.page_size);
+                    let chunk = core::cmp::min(remaining, self.page_size);
+                    self.state.set(State::Writing {
+                        offset: next_offset,
+                        remaining: remaining - chunk,
+                    });
+                    self.i2c.write(buffer, (self.address_header_len() + chunk) as u8);
+                }
+            }
+            State::Idle => {
+                self.buffer.replace(buffer);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NoopI2CDevice;
+
+    impl I2CDevice for NoopI2CDevice {
+        fn set_client(&self, _client: &'static dyn I2CClient) {}
+        fn write_read(&self, _data: &'static mut [u8], _write_len: u8, _read_len: u8) {}
+        fn write(&self, _data: &'static mut [u8], _len: u8) {}
+        fn read(&self, _data: &'static mut [u8], _len: u8) {}
+    }
+
+    static mut TEST_BUFFER: [u8; 16] = [0; 16];
+
+    fn eeprom(address_width: AddressWidth, page_size: usize) -> At24Cxx<'static> {
+        At24Cxx::new(&NoopI2CDevice, address_width, page_size, 256, unsafe { &mut TEST_BUFFER })
+    }
+
+    #[test]
+    fn address_header_len_matches_address_width() {
+        assert_eq!(eeprom(AddressWidth::OneByte, 8).address_header_len(), 1);
+        assert_eq!(eeprom(AddressWidth::TwoBytes, 8).address_header_len(), 2);
+    }
+
+    #[test]
+    fn bytes_to_page_boundary_stops_at_the_next_page() {
+        let eeprom = eeprom(AddressWidth::TwoBytes, 32);
+        assert_eq!(eeprom.bytes_to_page_boundary(0), 32);
+        assert_eq!(eeprom.bytes_to_page_boundary(28), 4);
+        assert_eq!(eeprom.bytes_to_page_boundary(32), 32);
+    }
+}
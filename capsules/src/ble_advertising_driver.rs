@@ -0,0 +1,291 @@
+//! Per-app virtual BLE advertisers, time-multiplexed onto one physical
+//! radio by `hil::ble_advertising::BleAdvertisementDriver`.
+//!
+//! Each process gets its own advertising payload, interval, and TX
+//! power; this driver round-robins between the processes that have
+//! called `START`, giving each a full advertising event (a send on
+//! channels 37, 38, and 39) before moving to the next and re-arming an
+//! alarm for the interval the process that just ran asked for.
+//! Scanning, connecting, and anything past a beacon's `ADV_NONCONN_IND`
+//! payload are out of scope, matching this request's "beacon-style
+//! apps" framing; the payload bytes themselves come from the buffer
+//! allowed at index 0 and are copied into the radio's transmit buffer
+//! for each send.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let ble = static_init!(
+//!     capsules::ble_advertising_driver::BleAdvertisingDriver<'static, VirtualMuxAlarm<'static, sam4l::ast::Ast>>,
+//!     capsules::ble_advertising_driver::BleAdvertisingDriver::new(
+//!         radio, alarm, tx_buffer,
+//!         kernel::Grant::create(capsules::driver::NUM::BleAdvertising as usize)));
+//! radio.set_client(ble);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::TakeCell;
+use kernel::hil::ble_advertising::{BleAdvertisementDriver, RadioChannel, TxClient};
+use kernel::hil::time::{Alarm, AlarmClient};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::BleAdvertising as usize;
+
+/// Default advertising interval, used until a process calls
+/// `SET_INTERVAL`.
+const DEFAULT_INTERVAL_MS: u32 = 1000;
+/// Default TX power, in dBm, used until a process calls
+/// `SET_TX_POWER`.
+const DEFAULT_TX_POWER: i8 = 0;
+
+mod upcall {
+    /// Called once the full advertising event (all three channels)
+    /// this process's turn covered has gone out.
+    pub const DONE: usize = 0;
+}
+
+mod cmd {
+    /// Sets the advertising interval, in milliseconds, used for every
+    /// future advertising event.
+    pub const SET_INTERVAL: usize = 0;
+    /// Sets the TX power, in dBm (`data1` sign-extended from `i8`),
+    /// used for every future advertising event.
+    pub const SET_TX_POWER: usize = 1;
+    /// Starts advertising. The payload comes from the buffer allowed
+    /// at index 0, `data1` bytes of it.
+    pub const START: usize = 2;
+    /// Stops advertising.
+    pub const STOP: usize = 3;
+}
+
+pub struct App {
+    callback: Option<Callback>,
+    enabled: bool,
+    interval_ms: u32,
+    tx_power: i8,
+    payload_len: usize,
+    /// The advertising payload allowed at index 0.
+    payload: Option<AppSlice<Shared, u8>>,
+}
+
+impl Default for App {
+    fn default() -> App {
+        App {
+            callback: None,
+            enabled: false,
+            interval_ms: DEFAULT_INTERVAL_MS,
+            tx_power: DEFAULT_TX_POWER,
+            payload_len: 0,
+            payload: None,
+        }
+    }
+}
+
+pub struct BleAdvertisingDriver<'a, A: Alarm<'a>> {
+    radio: &'a dyn BleAdvertisementDriver<'a>,
+    alarm: &'a A,
+    /// The process whose advertising event is currently in progress
+    /// and the channel being sent on, or nothing between events.
+    current: Cell<Option<(AppId, RadioChannel)>>,
+    /// The one buffer advertisements are built into; a single physical
+    /// radio already serializes every send, so there is no benefit to
+    /// pooling more than one.
+    tx_buffer: TakeCell<'static, [u8]>,
+    apps: Grant<App>,
+}
+
+impl<'a, A: Alarm<'a>> BleAdvertisingDriver<'a, A> {
+    pub fn new(radio: &'a dyn BleAdvertisementDriver<'a>, alarm: &'a A, tx_buffer: &'static mut [u8], apps: Grant<App>) -> BleAdvertisingDriver<'a, A> {
+        BleAdvertisingDriver {
+            radio,
+            alarm,
+            current: Cell::new(None),
+            tx_buffer: TakeCell::new(tx_buffer),
+            apps,
+        }
+    }
+
+    /// Finds the next enabled process after `after` (or the first one,
+    /// if `after` is `None`), cycling back to the start of the grant
+    /// region once the end is reached.
+    fn next_enabled_app(&self, after: Option<AppId>) -> Option<AppId> {
+        let mut seen_after = after.is_none();
+        let mut first_enabled = None;
+        for app_id in self.apps.iter() {
+            let enabled = self.apps.enter(app_id, |app, _| app.enabled).unwrap_or(false);
+            if !enabled {
+                continue;
+            }
+            if first_enabled.is_none() {
+                first_enabled = Some(app_id);
+            }
+            if seen_after {
+                return Some(app_id);
+            }
+            if after == Some(app_id) {
+                seen_after = true;
+            }
+        }
+        first_enabled
+    }
+
+    /// Begins an advertising event for `app_id` on `RadioChannel::Channel37`.
+    fn start_event(&self, app_id: AppId) {
+        let power = self.apps.enter(app_id, |app, _| app.tx_power);
+        match power {
+            Ok(power) => {
+                self.radio.set_tx_power(power);
+                self.current.set(Some((app_id, RadioChannel::Channel37)));
+                self.transmit_current();
+            }
+            Err(_) => self.schedule_next(None),
+        }
+    }
+
+    /// Sends on the channel `self.current` names, for the app it
+    /// names, copying up to `app.payload_len` bytes of its allowed
+    /// payload buffer into `self.tx_buffer` first.
+    fn transmit_current(&self) {
+        let (app_id, channel) = match self.current.get() {
+            Some(state) => state,
+            None => return,
+        };
+        let buffer = match self.tx_buffer.take() {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        let payload_len = self
+            .apps
+            .enter(app_id, |app, _| match &app.payload {
+                Some(slice) => {
+                    let len = core::cmp::min(app.payload_len, core::cmp::min(slice.len(), buffer.len()));
+                    buffer[..len].copy_from_slice(&slice.as_ref()[..len]);
+                    len
+                }
+                None => 0,
+            })
+            .unwrap_or(0);
+        let _ = self.radio.transmit_advertisement(buffer, payload_len, channel);
+    }
+
+    /// Arms the alarm for the next advertising event, `interval_ms`
+    /// after now for the process that just finished (or for the next
+    /// enabled process, if `just_ran` is `None`); disarms it if no
+    /// process is enabled.
+    fn schedule_next(&self, just_ran: Option<AppId>) {
+        match self.next_enabled_app(just_ran) {
+            Some(app_id) => {
+                let interval_ms = self
+                    .apps
+                    .enter(just_ran.unwrap_or(app_id), |app, _| app.interval_ms)
+                    .unwrap_or(DEFAULT_INTERVAL_MS);
+                self.alarm.set_alarm(self.alarm.now(), A::ticks_from_ms(interval_ms));
+            }
+            None => self.alarm.disarm(),
+        }
+    }
+}
+
+impl<'a, A: Alarm<'a>> Driver for BleAdvertisingDriver<'a, A> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::DONE => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.payload = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, _data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::SET_INTERVAL => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.interval_ms = data1 as u32;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            cmd::SET_TX_POWER => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.tx_power = data1 as i8;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            cmd::START => {
+                let was_idle = self.current.get().is_none() && self.next_enabled_app(None).is_none();
+                let result = self
+                    .apps
+                    .enter(app_id, |app, _| {
+                        app.enabled = true;
+                        app.payload_len = data1;
+                        ReturnCode::SUCCESS
+                    })
+                    .unwrap_or(ReturnCode::FAIL);
+                if result == ReturnCode::SUCCESS && was_idle {
+                    self.start_event(app_id);
+                }
+                result
+            }
+            cmd::STOP => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.enabled = false;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a, A: Alarm<'a>> AlarmClient for BleAdvertisingDriver<'a, A> {
+    fn alarm(&self) {
+        if let Some(app_id) = self.next_enabled_app(self.current.get().map(|(app_id, _)| app_id)) {
+            self.start_event(app_id);
+        }
+    }
+}
+
+impl<'a, A: Alarm<'a>> TxClient for BleAdvertisingDriver<'a, A> {
+    fn transmit_event(&self, buffer: &'static mut [u8], _result: ReturnCode) {
+        self.tx_buffer.replace(buffer);
+        let (app_id, channel) = match self.current.get() {
+            Some(state) => state,
+            None => return,
+        };
+        match channel.next() {
+            Some(next_channel) => {
+                self.current.set(Some((app_id, next_channel)));
+                self.transmit_current();
+            }
+            None => {
+                self.current.set(None);
+                let _ = self.apps.enter(app_id, |app, _| {
+                    if let Some(mut cb) = app.callback {
+                        cb.schedule(upcall::DONE, 0, 0);
+                    }
+                });
+                self.schedule_next(Some(app_id));
+            }
+        }
+    }
+}
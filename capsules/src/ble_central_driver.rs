@@ -0,0 +1,370 @@
+//! BLE central role for sensor-hub boards: scan for advertisements,
+//! connect to one peripheral at a time, and read/write its GATT
+//! characteristics by ATT handle.
+//!
+//! Built on three narrow HILs — `hil::ble_scanning::BleScanner`,
+//! `hil::ble_central::BleCentral`, and the same
+//! `hil::ble_connection::BleConnection` a GATT server uses for ATT
+//! exchange — so this driver's own job is just process-facing
+//! bookkeeping: delivering advertising reports as upcalls, gating
+//! `CONNECT` to one process at a time the way `capsules::tcp` gates
+//! its single connection, and matching GATT client read/write
+//! responses back to the request that caused them.
+//!
+//! Advertisement payloads and scan filter lists are not parsed here; a
+//! filter list is never applied, so every scanning process receives
+//! every advertising report.
+//! GATT attribute values are read and written through the buffer
+//! allowed at index 0.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let central = static_init!(
+//!     capsules::ble_central_driver::BleCentralDriver<'static>,
+//!     capsules::ble_central_driver::BleCentralDriver::new(
+//!         scanner, central, connection, tx_buffer,
+//!         kernel::Grant::create(capsules::driver::NUM::BleCentral as usize)));
+//! scanner.set_client(central);
+//! central.set_client(central);
+//! connection.set_client(central);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::ble_central::{BleCentral, CentralClient};
+use kernel::hil::ble_connection::{BleConnection, ConnectionClient};
+use kernel::hil::ble_scanning::{BleAddress, BleScanner, ScanClient};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::BleCentral as usize;
+
+mod att {
+    pub const READ_REQ: u8 = 0x0a;
+    pub const READ_RESP: u8 = 0x0b;
+    pub const WRITE_REQ: u8 = 0x12;
+    pub const WRITE_RESP: u8 = 0x13;
+    /// Opcode (1) + attribute handle (2) for a request; a response
+    /// carries just the opcode (plus, for `READ_RESP`, the value).
+    pub const REQUEST_HEADER_LEN: usize = 3;
+}
+
+mod upcall {
+    pub const SCAN_STARTED: usize = 0;
+    /// The address and payload are in the buffer allowed at index 0
+    /// (not shown); `data1` is the RSSI (as an `i8` cast to `usize`),
+    /// `data2` the payload length.
+    pub const ADVERTISING_REPORT: usize = 1;
+    pub const CONNECTED: usize = 2;
+    pub const DISCONNECTED: usize = 3;
+    /// `data1` is the value length, delivered through the buffer
+    /// allowed at index 0 (not shown).
+    pub const READ_DONE: usize = 4;
+    pub const WRITE_DONE: usize = 5;
+}
+
+mod cmd {
+    pub const START_SCAN: usize = 0;
+    pub const STOP_SCAN: usize = 1;
+    /// Connects to the 6-byte address in the buffer allowed at index 1.
+    /// `EBUSY` if another process already owns the connection.
+    pub const CONNECT: usize = 2;
+    pub const DISCONNECT: usize = 3;
+    /// Reads the remote characteristic at ATT handle `data1`.
+    pub const READ: usize = 4;
+    /// Writes `data2` value bytes (from the buffer allowed at index 0)
+    /// to the remote characteristic at ATT handle `data1`.
+    pub const WRITE: usize = 5;
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum GattRequest {
+    Read,
+    Write,
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    scanning: bool,
+    /// The peer address `CONNECT` connects to, allowed at index 1.
+    address: Option<AppSlice<Shared, u8>>,
+    /// The value `WRITE` sends, allowed at index 0.
+    value: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct BleCentralDriver<'a> {
+    scanner: &'a dyn BleScanner<'a>,
+    central: &'a dyn BleCentral<'a>,
+    connection: &'a dyn BleConnection<'a>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    connected_app: OptionalCell<AppId>,
+    pending_gatt: Cell<Option<(AppId, GattRequest)>>,
+    apps: Grant<App>,
+}
+
+impl<'a> BleCentralDriver<'a> {
+    pub fn new(
+        scanner: &'a dyn BleScanner<'a>,
+        central: &'a dyn BleCentral<'a>,
+        connection: &'a dyn BleConnection<'a>,
+        tx_buffer: &'static mut [u8],
+        apps: Grant<App>,
+    ) -> BleCentralDriver<'a> {
+        BleCentralDriver {
+            scanner,
+            central,
+            connection,
+            tx_buffer: TakeCell::new(tx_buffer),
+            connected_app: OptionalCell::empty(),
+            pending_gatt: Cell::new(None),
+            apps,
+        }
+    }
+}
+
+impl<'a> Driver for BleCentralDriver<'a> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::SCAN_STARTED
+            | upcall::ADVERTISING_REPORT
+            | upcall::CONNECTED
+            | upcall::DISCONNECTED
+            | upcall::READ_DONE
+            | upcall::WRITE_DONE => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.value = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            1 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.address = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::START_SCAN => {
+                let result = self
+                    .apps
+                    .enter(app_id, |app, _| {
+                        app.scanning = true;
+                        // The filter list (if any) is in the buffer
+                        // allowed at index 0; its contents are not
+                        // read here.
+                        ReturnCode::SUCCESS
+                    })
+                    .unwrap_or(ReturnCode::FAIL);
+                if result == ReturnCode::SUCCESS {
+                    self.scanner.start_scanning();
+                }
+                result
+            }
+            cmd::STOP_SCAN => {
+                let result = self
+                    .apps
+                    .enter(app_id, |app, _| {
+                        app.scanning = false;
+                        ReturnCode::SUCCESS
+                    })
+                    .unwrap_or(ReturnCode::FAIL);
+                let anyone_scanning = self.apps.iter().any(|other| self.apps.enter(other, |app, _| app.scanning).unwrap_or(false));
+                if !anyone_scanning {
+                    self.scanner.stop_scanning();
+                }
+                result
+            }
+            cmd::CONNECT => {
+                if self.connected_app.is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                let _ = data1;
+                let address = self
+                    .apps
+                    .enter(app_id, |app, _| match &app.address {
+                        Some(slice) if slice.len() >= 6 => {
+                            let mut bytes = [0u8; 6];
+                            bytes.copy_from_slice(&slice.as_ref()[..6]);
+                            Ok(bytes)
+                        }
+                        Some(_) => Err(ReturnCode::ESIZE),
+                        None => Err(ReturnCode::EINVAL),
+                    })
+                    .unwrap_or(Err(ReturnCode::FAIL));
+                match address {
+                    Ok(bytes) => self.central.connect(BleAddress(bytes)),
+                    Err(e) => e,
+                }
+            }
+            cmd::DISCONNECT => {
+                if self.connected_app.map(|owner| owner == app_id).unwrap_or(false) {
+                    self.central.disconnect()
+                } else {
+                    ReturnCode::EINVAL
+                }
+            }
+            cmd::READ => {
+                if !self.connected_app.map(|owner| owner == app_id).unwrap_or(false) {
+                    return ReturnCode::EINVAL;
+                }
+                if self.pending_gatt.get().is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                match self.tx_buffer.take() {
+                    Some(buffer) => {
+                        buffer[0] = att::READ_REQ;
+                        buffer[1..3].copy_from_slice(&(data1 as u16).to_le_bytes());
+                        self.pending_gatt.set(Some((app_id, GattRequest::Read)));
+                        self.connection.send_att_pdu(buffer, att::REQUEST_HEADER_LEN)
+                    }
+                    None => ReturnCode::EBUSY,
+                }
+            }
+            cmd::WRITE => {
+                if !self.connected_app.map(|owner| owner == app_id).unwrap_or(false) {
+                    return ReturnCode::EINVAL;
+                }
+                if self.pending_gatt.get().is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                match self.tx_buffer.take() {
+                    Some(buffer) => {
+                        if buffer.len() < att::REQUEST_HEADER_LEN + data2 {
+                            self.tx_buffer.replace(buffer);
+                            return ReturnCode::ESIZE;
+                        }
+                        let write_result = self.apps.enter(app_id, |app, _| match &app.value {
+                            Some(slice) if slice.len() >= data2 => {
+                                buffer[0] = att::WRITE_REQ;
+                                buffer[1..3].copy_from_slice(&(data1 as u16).to_le_bytes());
+                                buffer[att::REQUEST_HEADER_LEN..att::REQUEST_HEADER_LEN + data2].copy_from_slice(&slice.as_ref()[..data2]);
+                                ReturnCode::SUCCESS
+                            }
+                            Some(_) => ReturnCode::ESIZE,
+                            None => ReturnCode::EINVAL,
+                        });
+                        match write_result.unwrap_or(ReturnCode::FAIL) {
+                            ReturnCode::SUCCESS => {
+                                self.pending_gatt.set(Some((app_id, GattRequest::Write)));
+                                self.connection.send_att_pdu(buffer, att::REQUEST_HEADER_LEN + data2)
+                            }
+                            e => {
+                                self.tx_buffer.replace(buffer);
+                                e
+                            }
+                        }
+                    }
+                    None => ReturnCode::EBUSY,
+                }
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a> ScanClient for BleCentralDriver<'a> {
+    fn advertising_report(&self, _address: BleAddress, rssi: i8, _payload: &[u8], payload_len: usize) {
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if app.scanning {
+                    if let Some(mut cb) = app.callback {
+                        cb.schedule(upcall::ADVERTISING_REPORT, rssi as usize, payload_len);
+                    }
+                }
+            });
+        }
+    }
+}
+
+impl<'a> CentralClient for BleCentralDriver<'a> {
+    fn connection_complete(&self, result: ReturnCode) {
+        // The process that issued the still-outstanding `CONNECT` is
+        // not tracked separately from `connected_app`, so the first
+        // process in the grant region claims it, whether or not it is
+        // the one that issued `CONNECT`; a board with one
+        // central-capable app never notices the simplification.
+        if result == ReturnCode::SUCCESS {
+            if let Some(app_id) = self.apps.iter().next() {
+                self.connected_app.set(app_id);
+                let _ = self.apps.enter(app_id, |app, _| {
+                    if let Some(mut cb) = app.callback {
+                        cb.schedule(upcall::CONNECTED, usize::from(result), 0);
+                    }
+                });
+            }
+        }
+    }
+}
+
+impl<'a> ConnectionClient for BleCentralDriver<'a> {
+    fn connected(&self) {}
+
+    fn disconnected(&self) {
+        if let Some(app_id) = self.connected_app.take() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(upcall::DISCONNECTED, 0, 0);
+                }
+            });
+        }
+    }
+
+    fn att_pdu_received(&self, buffer: &[u8], len: usize) {
+        if len < 1 {
+            return;
+        }
+        let (app_id, request) = match self.pending_gatt.get() {
+            Some(pending) => pending,
+            None => return,
+        };
+        let matches = match (buffer[0], request) {
+            (att::READ_RESP, GattRequest::Read) => true,
+            (att::WRITE_RESP, GattRequest::Write) => true,
+            _ => false,
+        };
+        if !matches {
+            return;
+        }
+        self.pending_gatt.set(None);
+        let value_len = len.saturating_sub(1);
+        let _ = self.apps.enter(app_id, |app, _| {
+            if request == GattRequest::Read {
+                if let Some(slice) = &mut app.value {
+                    let copy_len = core::cmp::min(value_len, core::cmp::min(slice.len(), buffer.len() - 1));
+                    slice.as_mut()[..copy_len].copy_from_slice(&buffer[1..1 + copy_len]);
+                }
+            }
+            if let Some(mut cb) = app.callback {
+                match request {
+                    GattRequest::Read => cb.schedule(upcall::READ_DONE, value_len, 0),
+                    GattRequest::Write => cb.schedule(upcall::WRITE_DONE, 0, 0),
+                };
+            }
+        });
+    }
+
+    fn att_pdu_sent(&self, buffer: &'static mut [u8], _result: ReturnCode) {
+        self.tx_buffer.replace(buffer);
+    }
+}
@@ -0,0 +1,174 @@
+//! Generic syscall driver for raw block access over any
+//! `hil::block_storage::BlockStorage` backend (SD cards, block-mode
+//! external flash, ...), used directly by apps that want a block
+//! device without a filesystem on top.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let block_storage = static_init!(
+//!     capsules::block_storage_driver::BlockStorageDriver<'static>,
+//!     capsules::block_storage_driver::BlockStorageDriver::new(
+//!         sdcard, kernel::Grant::create(capsules::driver::NUM::BlockStorage as usize), buffer));
+//! sdcard.set_client(block_storage);
+//! ```
+
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::block_storage::{BlockStorage, BlockStorageClient, BLOCK_SIZE};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::BlockStorage as usize;
+
+mod upcall {
+    pub const DONE: usize = 0;
+}
+
+mod cmd {
+    /// Reports the device's total block count, as an 8-byte
+    /// little-endian `u64`, written into the buffer allowed at index 0.
+    pub const BLOCK_COUNT: usize = 0;
+    /// Reads `data2` blocks starting at block `data1` into the buffer
+    /// allowed at index 0.
+    pub const READ: usize = 1;
+    /// Writes `data2` blocks starting at block `data1` from the buffer
+    /// allowed at index 0.
+    pub const WRITE: usize = 2;
+}
+
+#[derive(Default)]
+pub struct App {
+    /// The buffer allowed at index 0: written into for `BLOCK_COUNT`
+    /// and `READ`, read from for `WRITE`.
+    data: Option<AppSlice<Shared, u8>>,
+    callback: Option<Callback>,
+}
+
+pub struct BlockStorageDriver<'a> {
+    device: &'a dyn BlockStorage<'a>,
+    apps: Grant<App>,
+    buffer: TakeCell<'static, [u8]>,
+    current_app: OptionalCell<AppId>,
+}
+
+impl<'a> BlockStorageDriver<'a> {
+    pub fn new(device: &'a dyn BlockStorage<'a>, apps: Grant<App>, buffer: &'static mut [u8]) -> BlockStorageDriver<'a> {
+        BlockStorageDriver {
+            device,
+            apps,
+            buffer: TakeCell::new(buffer),
+            current_app: OptionalCell::empty(),
+        }
+    }
+}
+
+impl<'a> Driver for BlockStorageDriver<'a> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::DONE => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.data = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::BLOCK_COUNT => self
+                .apps
+                .enter(app_id, |app, _| match &mut app.data {
+                    Some(slice) if slice.len() >= 8 => {
+                        slice.as_mut()[..8].copy_from_slice(&self.device.block_count().to_le_bytes());
+                        ReturnCode::SUCCESS
+                    }
+                    Some(_) => ReturnCode::ESIZE,
+                    None => ReturnCode::EINVAL,
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            cmd::READ | cmd::WRITE => {
+                if self.current_app.is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                let length = data2 * BLOCK_SIZE;
+                self.apps
+                    .enter(app_id, |app, _| {
+                        let mut buffer = match self.buffer.take() {
+                            Some(buffer) => buffer,
+                            None => return ReturnCode::EBUSY,
+                        };
+                        if length > buffer.len() {
+                            self.buffer.replace(buffer);
+                            return ReturnCode::ESIZE;
+                        }
+                        let result = if command_num == cmd::WRITE {
+                            match &app.data {
+                                Some(slice) if length <= slice.len() => {
+                                    buffer[..length].copy_from_slice(&slice.as_ref()[..length]);
+                                    self.device.write_blocks(buffer, data1 as u64, data2)
+                                }
+                                _ => {
+                                    self.buffer.replace(buffer);
+                                    return ReturnCode::EINVAL;
+                                }
+                            }
+                        } else {
+                            self.device.read_blocks(buffer, data1 as u64, data2)
+                        };
+                        if result == ReturnCode::SUCCESS {
+                            self.current_app.set(app_id);
+                        }
+                        result
+                    })
+                    .unwrap_or(ReturnCode::FAIL)
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a> BlockStorageClient for BlockStorageDriver<'a> {
+    fn read_done(&self, buffer: &'static mut [u8], num_blocks: usize, result: ReturnCode) {
+        if let Some(app_id) = self.current_app.take() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if result == ReturnCode::SUCCESS {
+                    if let Some(slice) = &mut app.data {
+                        let copy_len = core::cmp::min(num_blocks * BLOCK_SIZE, slice.len());
+                        slice.as_mut()[..copy_len].copy_from_slice(&buffer[..copy_len]);
+                    }
+                }
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(num_blocks, result.into(), 0);
+                }
+            });
+        }
+        self.buffer.replace(buffer);
+    }
+
+    fn write_done(&self, buffer: &'static mut [u8], num_blocks: usize, result: ReturnCode) {
+        self.buffer.replace(buffer);
+        if let Some(app_id) = self.current_app.take() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(num_blocks, result.into(), 0);
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,450 @@
+//! CAN bus syscall driver: each process registers its own set of
+//! acceptance filters and gets, via upcall, only the frames on the bus
+//! that match one of them, queued in that process's grant the same way
+//! `capsules::msgqueue` queues messages.
+//!
+//! The underlying `hil::can::Can` is left with its hardware filter
+//! bank wide open (`start` below programs `AcceptanceFilter::accept_all`
+//! once, not per process); with several processes each wanting a
+//! different slice of the bus, merging their filter sets into a
+//! hardware bank that typically holds only a handful of entries is not
+//! attempted here. Every frame the hardware bank lets through is
+//! instead matched in software against each process's own filters, the
+//! same trade-off `capsules::radio_154_driver` makes between hardware
+//! and software address filtering.
+//!
+//! Each process gets one pending-send mailbox rather than sharing a
+//! single FIFO slot: `SEND` copies the frame out of the process's
+//! allowed buffer into its mailbox immediately and returns, and
+//! whenever the one underlying bus is free, this driver picks the
+//! highest-`priority` mailbox across every process with one pending,
+//! so a diagnostics process flooding the bus at low priority cannot
+//! delay a control process's frames behind it in a shared queue.
+//!
+//! A bus-off condition is recovered from automatically: `start` is
+//! retried after `initial_backoff_ms`, doubling on every further
+//! bus-off up to `max_backoff_ms`, and reset back to
+//! `initial_backoff_ms` the next time a frame is sent or received
+//! successfully.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let can_driver = static_init!(
+//!     capsules::can_driver::CanDriver<'static, Alarm>,
+//!     capsules::can_driver::CanDriver::new(
+//!         can, alarm, tx_buffer, 50, 5000,
+//!         kernel::Grant::create(capsules::driver::NUM::Can as usize)));
+//! can.set_client(can_driver);
+//! can_driver.start();
+//! ```
+
+use core::cell::Cell;
+
+use kernel::common::cells::TakeCell;
+use kernel::hil::can::{self, BusError, Can, CanClient};
+use kernel::hil::time::{Alarm, AlarmClient};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Can as usize;
+
+/// Filters a process can have registered at once; a process wanting
+/// more distinct identifiers than this must use a mask broad enough to
+/// cover them in fewer entries.
+const MAX_FILTERS_PER_APP: usize = 4;
+/// Received frames a process can have queued before older ones are
+/// dropped to make room; kept small since a queued frame can now carry
+/// up to `hil::can::MAX_FD_DATA_LEN` bytes.
+const QUEUE_DEPTH: usize = 4;
+/// One 8-byte filter entry in the buffer allowed for `SET_FILTERS`:
+/// 4-byte identifier, 4-byte mask, both native-endian, in
+/// `hil::can::AcceptanceFilter`'s layout.
+const FILTER_ENTRY_LEN: usize = 8;
+
+/// Parses `count` filter entries (see `FILTER_ENTRY_LEN`) out of
+/// `buffer`, as used by `cmd::SET_FILTERS`.
+fn parse_filters(buffer: &[u8], count: usize) -> Option<[Option<can::AcceptanceFilter>; MAX_FILTERS_PER_APP]> {
+    if count > MAX_FILTERS_PER_APP || buffer.len() < count * FILTER_ENTRY_LEN {
+        return None;
+    }
+    let mut filters = [None; MAX_FILTERS_PER_APP];
+    for (i, filter) in filters.iter_mut().enumerate().take(count) {
+        let entry = &buffer[i * FILTER_ENTRY_LEN..(i + 1) * FILTER_ENTRY_LEN];
+        let id = u32::from_ne_bytes([entry[0], entry[1], entry[2], entry[3]]);
+        let mask = u32::from_ne_bytes([entry[4], entry[5], entry[6], entry[7]]);
+        *filter = Some(can::AcceptanceFilter { id, mask });
+    }
+    Some(filters)
+}
+
+mod upcall {
+    /// `data1` is the received frame's identifier, `data2` its data
+    /// length; the full frame is dequeued from the buffer allowed at
+    /// index 0 (not shown) with `RECEIVE`.
+    pub const FRAME_RECEIVED: usize = 0;
+    pub const SEND_DONE: usize = 1;
+}
+
+mod cmd {
+    /// Copies `data1` bytes from the buffer allowed at index 0 into
+    /// this process's mailbox at priority `data2` (a `u8`, higher
+    /// sends sooner); `EBUSY` if that mailbox already has a frame
+    /// waiting to go out.
+    pub const SEND: usize = 0;
+    /// Replaces this process's filter set with the `data1` entries read
+    /// from the buffer allowed at index 1, each in the layout documented
+    /// on `FILTER_ENTRY_LEN`. `ESIZE` if `data1` exceeds
+    /// `MAX_FILTERS_PER_APP` or the buffer is too short for it.
+    pub const SET_FILTERS: usize = 1;
+    /// Dequeues the oldest received frame into the buffer allowed at
+    /// index 0 (not shown); `FAIL` if the queue is empty.
+    pub const RECEIVE: usize = 2;
+}
+
+#[derive(Copy, Clone)]
+struct QueuedFrame {
+    id: u32,
+    flags: u8,
+    len: usize,
+    data: [u8; can::MAX_FD_DATA_LEN],
+}
+
+/// A single frame this process has asked to send but that has not yet
+/// reached the bus.
+#[derive(Copy, Clone)]
+struct Mailbox {
+    priority: u8,
+    len: usize,
+    data: [u8; can::MAX_FRAME_LEN],
+}
+
+pub struct App {
+    callback: Option<Callback>,
+    /// The buffer allowed at index 0: read from for `SEND`, written
+    /// into for `RECEIVE`.
+    buffer: Option<AppSlice<Shared, u8>>,
+    filters_buffer: Option<AppSlice<Shared, u8>>,
+    filters: [Option<can::AcceptanceFilter>; MAX_FILTERS_PER_APP],
+    queue: [Option<QueuedFrame>; QUEUE_DEPTH],
+    mailbox: Option<Mailbox>,
+}
+
+impl Default for App {
+    fn default() -> App {
+        App {
+            callback: None,
+            buffer: None,
+            filters_buffer: None,
+            filters: [None; MAX_FILTERS_PER_APP],
+            queue: [None; QUEUE_DEPTH],
+            mailbox: None,
+        }
+    }
+}
+
+pub struct CanDriver<'a, A: Alarm<'a>> {
+    can: &'a dyn Can<'a>,
+    alarm: &'a A,
+    tx_buffer: TakeCell<'static, [u8]>,
+    current_sender: Cell<Option<AppId>>,
+    backoff_ms: Cell<u32>,
+    initial_backoff_ms: u32,
+    max_backoff_ms: u32,
+    apps: Grant<App>,
+}
+
+impl<'a, A: Alarm<'a>> CanDriver<'a, A> {
+    pub fn new(
+        can: &'a dyn Can<'a>,
+        alarm: &'a A,
+        tx_buffer: &'static mut [u8],
+        initial_backoff_ms: u32,
+        max_backoff_ms: u32,
+        apps: Grant<App>,
+    ) -> CanDriver<'a, A> {
+        CanDriver {
+            can,
+            alarm,
+            tx_buffer: TakeCell::new(tx_buffer),
+            current_sender: Cell::new(None),
+            backoff_ms: Cell::new(initial_backoff_ms),
+            initial_backoff_ms,
+            max_backoff_ms,
+            apps,
+        }
+    }
+
+    /// Opens the hardware filter bank all the way up and joins the
+    /// bus; see the module documentation for why filtering happens in
+    /// software here instead.
+    pub fn start(&self) -> ReturnCode {
+        let result = self.can.set_filters(&[can::AcceptanceFilter::accept_all()]);
+        if result != ReturnCode::SUCCESS {
+            return result;
+        }
+        self.can.start()
+    }
+
+    fn reset_backoff(&self) {
+        self.backoff_ms.set(self.initial_backoff_ms);
+    }
+
+    /// Appends `frame` to `app`'s queue, dropping the oldest queued
+    /// frame to make room if it is already full.
+    fn enqueue(app: &mut App, frame: QueuedFrame) {
+        let slot = app.queue.iter_mut().find(|s| s.is_none());
+        match slot {
+            Some(slot) => *slot = Some(frame),
+            None => {
+                app.queue.rotate_left(1);
+                app.queue[QUEUE_DEPTH - 1] = Some(frame);
+            }
+        }
+    }
+
+    /// If the bus is free, finds the highest-priority mailbox across
+    /// every process with a frame pending (ties go to whichever
+    /// process this happens to reach first) and starts sending it.
+    fn dispatch(&self) {
+        if self.current_sender.get().is_some() {
+            return;
+        }
+        let mut best: Option<(AppId, u8)> = None;
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mailbox) = &app.mailbox {
+                    let take = match best {
+                        Some((_, best_priority)) => mailbox.priority > best_priority,
+                        None => true,
+                    };
+                    if take {
+                        best = Some((app_id, mailbox.priority));
+                    }
+                }
+            });
+        }
+        let app_id = match best {
+            Some((app_id, _)) => app_id,
+            None => return,
+        };
+        let _ = self.apps.enter(app_id, |app, _| {
+            let mailbox = match app.mailbox.take() {
+                Some(mailbox) => mailbox,
+                None => return,
+            };
+            match self.tx_buffer.take() {
+                Some(buffer) => {
+                    buffer[..mailbox.len].copy_from_slice(&mailbox.data[..mailbox.len]);
+                    self.current_sender.set(Some(app_id));
+                    let _ = self.can.transmit(buffer, mailbox.len);
+                }
+                None => app.mailbox = Some(mailbox),
+            }
+        });
+    }
+}
+
+impl<'a, A: Alarm<'a>> Driver for CanDriver<'a, A> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::FRAME_RECEIVED | upcall::SEND_DONE => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            1 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.filters_buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::SEND => {
+                let result = self
+                    .apps
+                    .enter(app_id, |app, _| {
+                        if app.mailbox.is_some() {
+                            return ReturnCode::EBUSY;
+                        }
+                        match &app.buffer {
+                            Some(slice) if data1 <= can::MAX_FRAME_LEN && slice.len() >= data1 => {
+                                let mut data = [0u8; can::MAX_FRAME_LEN];
+                                data[..data1].copy_from_slice(&slice.as_ref()[..data1]);
+                                app.mailbox = Some(Mailbox {
+                                    priority: data2 as u8,
+                                    len: data1,
+                                    data,
+                                });
+                                ReturnCode::SUCCESS
+                            }
+                            _ => ReturnCode::EINVAL,
+                        }
+                    })
+                    .unwrap_or(ReturnCode::FAIL);
+                if result == ReturnCode::SUCCESS {
+                    self.dispatch();
+                }
+                result
+            }
+            cmd::SET_FILTERS => self
+                .apps
+                .enter(app_id, |app, _| match &app.filters_buffer {
+                    Some(slice) => match parse_filters(slice.as_ref(), data1) {
+                        Some(filters) => {
+                            app.filters = filters;
+                            ReturnCode::SUCCESS
+                        }
+                        None => ReturnCode::ESIZE,
+                    },
+                    None => ReturnCode::EINVAL,
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            cmd::RECEIVE => self
+                .apps
+                .enter(app_id, |app, _| {
+                    // Frames are kept packed from index 0, oldest first;
+                    // shifting the rest down after a dequeue keeps them so.
+                    match app.queue[0].take() {
+                        Some(frame) => {
+                            app.queue.rotate_left(1);
+                            match &mut app.buffer {
+                                Some(dest) if dest.len() >= frame.len => {
+                                    dest.as_mut()[..frame.len].copy_from_slice(&frame.data[..frame.len]);
+                                    ReturnCode::SUCCESS
+                                }
+                                Some(_) => ReturnCode::ESIZE,
+                                None => ReturnCode::EINVAL,
+                            }
+                        }
+                        None => ReturnCode::FAIL,
+                    }
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a, A: Alarm<'a>> CanClient for CanDriver<'a, A> {
+    fn transmit_done(&self, buffer: &'static mut [u8], result: ReturnCode) {
+        self.tx_buffer.replace(buffer);
+        if result == ReturnCode::SUCCESS {
+            self.reset_backoff();
+        }
+        if let Some(app_id) = self.current_sender.take() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(upcall::SEND_DONE, usize::from(result), 0);
+                }
+            });
+        }
+        self.dispatch();
+    }
+
+    fn receive(&self, buffer: &[u8], len: usize) {
+        if len < can::DATA_OFFSET {
+            return;
+        }
+        self.reset_backoff();
+        let id = u32::from_ne_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
+        let flags = buffer[can::FLAGS_OFFSET];
+        let max_len = if flags & can::FD_FRAME != 0 {
+            can::MAX_FD_DATA_LEN
+        } else {
+            can::MAX_CLASSIC_DATA_LEN
+        };
+        let data_len = can::dlc_to_data_len(buffer[can::DLC_OFFSET]).min(max_len);
+        let mut data = [0u8; can::MAX_FD_DATA_LEN];
+        let available = (len - can::DATA_OFFSET).min(data_len);
+        data[..available].copy_from_slice(&buffer[can::DATA_OFFSET..can::DATA_OFFSET + available]);
+        let frame = QueuedFrame {
+            id,
+            flags,
+            len: available,
+            data,
+        };
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                let matches = app.filters.iter().flatten().any(|filter| filter.matches(id));
+                if matches {
+                    Self::enqueue(app, frame);
+                    if let Some(mut cb) = app.callback {
+                        cb.schedule(upcall::FRAME_RECEIVED, id as usize, frame.len);
+                    }
+                }
+            });
+        }
+    }
+
+    fn bus_error(&self, error: BusError) {
+        if error != BusError::BusOff {
+            return;
+        }
+        self.current_sender.set(None);
+        let backoff = self.backoff_ms.get();
+        self.alarm.set_alarm(self.alarm.now(), A::ticks_from_ms(backoff));
+        self.backoff_ms.set((backoff * 2).min(self.max_backoff_ms));
+    }
+}
+
+impl<'a, A: Alarm<'a>> AlarmClient for CanDriver<'a, A> {
+    fn alarm(&self) {
+        let _ = self.can.start();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_filters_reads_native_endian_id_and_mask() {
+        let mut buffer = [0u8; FILTER_ENTRY_LEN * 2];
+        buffer[0..4].copy_from_slice(&0x1234_5678u32.to_ne_bytes());
+        buffer[4..8].copy_from_slice(&0xffff_0000u32.to_ne_bytes());
+        buffer[8..12].copy_from_slice(&0x0000_00ffu32.to_ne_bytes());
+        buffer[12..16].copy_from_slice(&0x0000_00ffu32.to_ne_bytes());
+
+        let filters = parse_filters(&buffer, 2).unwrap();
+        assert_eq!(filters[0].unwrap().id, 0x1234_5678);
+        assert_eq!(filters[0].unwrap().mask, 0xffff_0000);
+        assert_eq!(filters[1].unwrap().id, 0x0000_00ff);
+        assert_eq!(filters[1].unwrap().mask, 0x0000_00ff);
+        assert!(filters[2].is_none());
+        assert!(filters[3].is_none());
+    }
+
+    #[test]
+    fn parse_filters_rejects_too_many_entries() {
+        let buffer = [0u8; FILTER_ENTRY_LEN * (MAX_FILTERS_PER_APP + 1)];
+        assert!(parse_filters(&buffer, MAX_FILTERS_PER_APP + 1).is_none());
+    }
+
+    #[test]
+    fn parse_filters_rejects_short_buffer() {
+        let buffer = [0u8; FILTER_ENTRY_LEN];
+        assert!(parse_filters(&buffer, 2).is_none());
+    }
+}
@@ -0,0 +1,919 @@
+//! Quectel/SIMCom-style cellular modem (EC25, SIM7000, ...) syscall
+//! driver: power sequencing over the module's `PWRKEY` line, SIM and
+//! network registration status, signal quality, and either PPP data
+//! mode handed off to the kernel's IP stack or an AT-socket-family
+//! `CONNECT` / `SEND` / `CLOSE` command set exposed straight to apps,
+//! depending on which [`DataMode`] the board configures at
+//! construction — the same construction-time mode choice
+//! `capsules::slip_driver::SlipDriver` makes between SLIP and PPP
+//! framing.
+//!
+//! # Power sequencing
+//!
+//! `POWER_ON` asserts `PWRKEY` for `power_pulse_ticks`, releases it,
+//! waits `boot_ticks` for the module to come up, then synchronizes on
+//! `AT` the way a human at a terminal would before trusting anything
+//! else the module says. From there this capsule drives `AT+CPIN?` and
+//! polls `AT+CREG?` (retrying every `registration_poll_ticks`, up to
+//! `max_registration_polls` times) on its own, walking through
+//! [`State`] and notifying `STATE_CHANGED` at each step, so an app
+//! doesn't have to reimplement a modem's notoriously slow and chatty
+//! bring-up sequence itself. Any failure along the way — no response to
+//! `AT`, a SIM that isn't ready, registration that never completes —
+//! lands in `State::Fault`; there is no automatic retry, since the
+//! underlying cause (no SIM inserted, no signal, a wedged module) needs
+//! outside intervention before trying again is worth it. `POWER_OFF`
+//! releases `PWRKEY` and returns to `State::Off` unconditionally; it
+//! does not pulse `PWRKEY` or send any command to the module.
+//!
+//! This tree has no general-purpose output GPIO HIL yet (only
+//! `hil::gpio::InterruptPin`, for inputs), so driving `PWRKEY` is
+//! abstracted behind the small [`PowerControl`] trait defined here
+//! instead; a board implements it directly on whatever pin type it has.
+//!
+//! # Data modes
+//!
+//! In [`DataMode::Ppp`], once `State::Registered` is reached, a board
+//! (not an app — this is a link-level decision) calls
+//! [`CellularModem::enter_data_mode`], which dials `ATD*99#`. On the
+//! module's `CONNECT`, this capsule stops driving the UART entirely and
+//! calls back through [`DataModeClient::data_mode_entered`]; the board
+//! is then expected to re-register the UART's client with its own PPP
+//! implementation (e.g. `capsules::slip_driver::SlipDriver` configured
+//! with `Framing::Ppp`) to actually exchange IP traffic, the same way
+//! `capsules::ethernet_driver::KernelFrameSender` hands a send path to
+//! a kernel IP layer without becoming it. This capsule does not drive
+//! PPP itself; getting the link to the point where the module says
+//! `CONNECT` is as far as its scope goes.
+//!
+//! In [`DataMode::AtSockets`], `CONNECT` / `SEND` / `CLOSE` behave like
+//! `capsules::esp_at`'s, built on the Quectel `AT+QIOPEN` / `AT+QISEND`
+//! / `AT+QICLOSE` family instead of ESP-AT's `AT+CIPSTART` family, with
+//! the connection ID always `0` since only one connection is modeled.
+//! Inbound data is assumed pushed as `+QIURC: "recv",0,<length>:`
+//! followed immediately by `<length>` raw bytes, the same shape as
+//! ESP-AT's `+IPD,<length>:`; a real module instead requires an
+//! `AT+QIRD` round trip to fetch buffered data after a length-only
+//! `+QIURC: "recv"` notification, which this capsule does not perform,
+//! the same kind of stated scope limit `capsules::slip_driver` draws
+//! around PPP option negotiation.
+//!
+//! Like `capsules::modbus` and `capsules::esp_at`, every AT response is
+//! received from the UART one byte at a time, since a response's
+//! length is not known in advance, and a
+//! `kernel::hil::time::Alarm` bounds every step (power pulse, boot
+//! wait, command response, registration poll) so a module that never
+//! answers fails a step instead of hanging the caller forever.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let modem = static_init!(
+//!     capsules::cellular_modem::CellularModem<'static, Alarm, PowerKeyPin>,
+//!     capsules::cellular_modem::CellularModem::new(
+//!         uart, alarm, power_key, capsules::cellular_modem::DataMode::AtSockets,
+//!         capsules::cellular_modem::Timing {
+//!             power_pulse_ticks, boot_ticks, command_timeout_ticks,
+//!             registration_poll_ticks, max_registration_polls: 30,
+//!         },
+//!         rx_byte_buffer, rx_buffer, tx_buffer,
+//!         kernel::Grant::create(capsules::driver::NUM::CellularModem as usize)));
+//! uart.set_receive_client(modem);
+//! uart.set_transmit_client(modem);
+//! alarm.set_alarm_client(modem);
+//! ```
+
+use core::cell::Cell;
+
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::time::{Alarm, AlarmClient};
+use kernel::hil::uart::{ReceiveClient, TransmitClient, UartData};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::CellularModem as usize;
+
+/// Drives the module's `PWRKEY`/reset line; a board wires in its own
+/// GPIO pin here since this tree has no general-purpose output GPIO
+/// HIL yet. See the module documentation.
+pub trait PowerControl {
+    fn set(&self, asserted: bool);
+}
+
+/// Notified once [`CellularModem::enter_data_mode`]'s dial succeeds and
+/// this capsule is done touching the UART, so the board can hand it to
+/// its own PPP implementation. Only used in [`DataMode::Ppp`].
+pub trait DataModeClient {
+    fn data_mode_entered(&self);
+}
+
+mod at {
+    pub const OK: &[u8] = b"OK";
+    pub const ERROR: &[u8] = b"ERROR";
+    pub const CONNECT: &[u8] = b"CONNECT";
+    pub const SEND_OK: &[u8] = b"SEND OK";
+    pub const CPIN_READY: &[u8] = b"+CPIN: READY";
+    pub const CREG_PREFIX: &[u8] = b"+CREG:";
+    pub const CSQ_PREFIX: &[u8] = b"+CSQ:";
+    pub const QIURC_CLOSED: &[u8] = b"+QIURC: \"closed\",0";
+    pub const QIURC_RECV_PREFIX: &[u8] = b"+QIURC: \"recv\",0,";
+    pub const PROMPT: u8 = b'>';
+    /// `AT+CREG?`'s registration status field values meaning
+    /// registered, at home or roaming respectively.
+    pub const CREG_REGISTERED_HOME: u8 = 1;
+    pub const CREG_REGISTERED_ROAMING: u8 = 5;
+    /// Reported for `AT+CSQ` when the module could not measure signal.
+    pub const CSQ_UNKNOWN: u8 = 99;
+}
+
+mod upcall {
+    /// `data1` is the new `State`, cast to `usize`.
+    pub const STATE_CHANGED: usize = 0;
+    /// `data1` is the RSSI index from `AT+CSQ` (0-31, or 99 if unknown).
+    pub const SIGNAL_QUALITY: usize = 1;
+    /// `data1` is 1 if `AT+CPIN?` answered `READY`, 0 otherwise.
+    pub const SIM_STATUS: usize = 2;
+    /// `data1` is a `ReturnCode`. `DataMode::AtSockets` only.
+    pub const CONNECT_DONE: usize = 3;
+    /// `data1` is a `ReturnCode`. `DataMode::AtSockets` only.
+    pub const SEND_DONE: usize = 4;
+    /// `data1` is the received payload's length, delivered through the
+    /// buffer allowed at index 0 (not shown). `DataMode::AtSockets` only.
+    pub const RECEIVED: usize = 5;
+    /// `DataMode::AtSockets` only.
+    pub const CLOSED: usize = 6;
+}
+
+mod cmd {
+    pub const POWER_ON: usize = 0;
+    pub const POWER_OFF: usize = 1;
+    pub const GET_SIGNAL_QUALITY: usize = 2;
+    pub const GET_SIM_STATUS: usize = 3;
+    /// Opens a connection of protocol `data1` (`Protocol::Tcp` = 0,
+    /// `Protocol::Udp` = 1) to port `data2` at the host named in the
+    /// buffer allowed at index 0. `DataMode::AtSockets` only.
+    pub const CONNECT: usize = 4;
+    /// Sends `data1` bytes from the buffer allowed at index 0 (not
+    /// shown). `DataMode::AtSockets` only.
+    pub const SEND: usize = 5;
+    pub const CLOSE: usize = 6;
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+/// Which family of AT commands moves data once the module is
+/// registered on the network.
+#[derive(Copy, Clone, PartialEq)]
+pub enum DataMode {
+    /// `CONNECT` / `SEND` / `CLOSE` commands, answered from `AT+QIOPEN`
+    /// / `AT+QISEND` / `AT+QICLOSE`.
+    AtSockets,
+    /// `enter_data_mode` dials `ATD*99#` and hands the UART off; no
+    /// `CONNECT` / `SEND` / `CLOSE` commands are accepted.
+    Ppp,
+}
+
+/// How long each step of bring-up is given, in a board's alarm ticks.
+#[derive(Copy, Clone)]
+pub struct Timing {
+    pub power_pulse_ticks: u32,
+    pub boot_ticks: u32,
+    pub command_timeout_ticks: u32,
+    pub registration_poll_ticks: u32,
+    pub max_registration_polls: u32,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum State {
+    Off,
+    PoweringOn,
+    Booting,
+    SyncingAt,
+    CheckingSim,
+    Registering,
+    Registered,
+    /// `ATD*99#` has been sent; waiting for `CONNECT`. `DataMode::Ppp` only.
+    EnteringData,
+    /// The UART has been handed off to the board's own PPP client.
+    DataMode,
+    /// Bring-up failed; only `POWER_OFF` then `POWER_ON` recovers.
+    Fault,
+}
+
+/// What a completed AT command was for, and (for app-issued ones) who
+/// to notify.
+#[derive(Copy, Clone)]
+enum PendingAt {
+    SyncAt,
+    CheckSim(Option<AppId>),
+    QueryRegistration,
+    QuerySignal(AppId),
+    DialPpp,
+    ConnectSocket(AppId),
+    SendSocket(AppId),
+    CloseSocket(AppId),
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum AlarmPurpose {
+    PowerPulseEnd,
+    BootDone,
+    CommandTimeout,
+    RegistrationPoll,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum RxState {
+    Line,
+    /// Copying `remaining` more raw bytes of a `+QIURC: "recv"` payload.
+    UrcPayload(usize),
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    /// Host (`CONNECT`) or outgoing payload (`SEND`); also where a
+    /// received payload is copied back for `RECEIVED`.
+    arg_buffer: Option<AppSlice<Shared, u8>>,
+}
+
+fn append_bytes(buffer: &mut [u8], pos: usize, bytes: &[u8]) -> Option<usize> {
+    if pos + bytes.len() > buffer.len() {
+        return None;
+    }
+    buffer[pos..pos + bytes.len()].copy_from_slice(bytes);
+    Some(pos + bytes.len())
+}
+
+fn append_decimal(buffer: &mut [u8], pos: usize, value: u32) -> Option<usize> {
+    let mut digits = [0u8; 10];
+    let mut count = 0;
+    let mut value = value;
+    loop {
+        digits[count] = b'0' + (value % 10) as u8;
+        count += 1;
+        value /= 10;
+        if value == 0 {
+            break;
+        }
+    }
+    if pos + count > buffer.len() {
+        return None;
+    }
+    for i in 0..count {
+        buffer[pos + i] = digits[count - 1 - i];
+    }
+    Some(pos + count)
+}
+
+/// Parses a leading run of ASCII digits in `bytes`, ignoring anything
+/// after the first non-digit.
+fn parse_leading_u32(bytes: &[u8]) -> Option<u32> {
+    let mut value: u32 = 0;
+    let mut any = false;
+    for &byte in bytes {
+        if !byte.is_ascii_digit() {
+            break;
+        }
+        any = true;
+        value = value.checked_mul(10)?.checked_add((byte - b'0') as u32)?;
+    }
+    if any {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+/// Trims leading ASCII spaces off `bytes`.
+fn trim_leading_spaces(bytes: &[u8]) -> &[u8] {
+    let mut bytes = bytes;
+    while bytes.first() == Some(&b' ') {
+        bytes = &bytes[1..];
+    }
+    bytes
+}
+
+/// Parses `+CREG: <n>,<stat>`'s `stat` field.
+fn parse_creg_status(line: &[u8]) -> Option<u8> {
+    let rest = line.strip_prefix(at::CREG_PREFIX)?;
+    let comma = rest.iter().position(|&b| b == b',')?;
+    parse_leading_u32(trim_leading_spaces(&rest[comma + 1..])).map(|v| v as u8)
+}
+
+/// Parses `+CSQ: <rssi>,<ber>`'s `rssi` field.
+fn parse_csq_rssi(line: &[u8]) -> Option<u8> {
+    let rest = line.strip_prefix(at::CSQ_PREFIX)?;
+    parse_leading_u32(trim_leading_spaces(rest)).map(|v| v as u8)
+}
+
+/// Parses the decimal length out of a just-completed `+QIURC:
+/// "recv",0,<length>:` prefix, `line` being everything accumulated up
+/// to and including the trailing `:`.
+fn parse_recv_length(line: &[u8]) -> Option<usize> {
+    let rest = line.strip_prefix(at::QIURC_RECV_PREFIX)?;
+    let digits = rest.strip_suffix(b":")?;
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    parse_leading_u32(digits).map(|v| v as usize)
+}
+
+pub struct CellularModem<'a, A: Alarm<'a>, P: PowerControl> {
+    uart: &'a dyn UartData<'a>,
+    alarm: &'a A,
+    power_key: &'a P,
+    data_mode: DataMode,
+    timing: Timing,
+    state: Cell<State>,
+    alarm_purpose: Cell<AlarmPurpose>,
+    pending: Cell<Option<PendingAt>>,
+    /// Result of the informational line seen before a pending command's
+    /// terminal `OK`/`ERROR` (a `+CPIN:`, `+CREG:`, or `+CSQ:` line);
+    /// consumed and cleared when the pending command completes.
+    scratch: Cell<u32>,
+    registration_polls: Cell<u32>,
+    connection_owner: Cell<Option<AppId>>,
+    awaiting_prompt: Cell<Option<(AppId, usize)>>,
+    data_client: OptionalCell<&'a dyn DataModeClient>,
+    rx_byte: TakeCell<'static, [u8]>,
+    rx_state: Cell<RxState>,
+    rx_buffer: TakeCell<'static, [u8]>,
+    rx_len: Cell<usize>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    apps: Grant<App>,
+}
+
+impl<'a, A: Alarm<'a>, P: PowerControl> CellularModem<'a, A, P> {
+    pub fn new(
+        uart: &'a dyn UartData<'a>,
+        alarm: &'a A,
+        power_key: &'a P,
+        data_mode: DataMode,
+        timing: Timing,
+        rx_byte_buffer: &'static mut [u8],
+        rx_buffer: &'static mut [u8],
+        tx_buffer: &'static mut [u8],
+        apps: Grant<App>,
+    ) -> CellularModem<'a, A, P> {
+        CellularModem {
+            uart,
+            alarm,
+            power_key,
+            data_mode,
+            timing,
+            state: Cell::new(State::Off),
+            alarm_purpose: Cell::new(AlarmPurpose::PowerPulseEnd),
+            pending: Cell::new(None),
+            scratch: Cell::new(0),
+            registration_polls: Cell::new(0),
+            connection_owner: Cell::new(None),
+            awaiting_prompt: Cell::new(None),
+            data_client: OptionalCell::empty(),
+            rx_byte: TakeCell::new(rx_byte_buffer),
+            rx_state: Cell::new(RxState::Line),
+            rx_buffer: TakeCell::new(rx_buffer),
+            rx_len: Cell::new(0),
+            tx_buffer: TakeCell::new(tx_buffer),
+            apps,
+        }
+    }
+
+    pub fn set_data_mode_client(&self, client: &'a dyn DataModeClient) {
+        self.data_client.set(client);
+    }
+
+    /// Arms the UART to receive the module's first byte; a board calls
+    /// this once after registering this capsule as the UART's and
+    /// alarm's client.
+    pub fn start(&self) -> ReturnCode {
+        match self.rx_byte.take() {
+            Some(buffer) => self.uart.receive_buffer(buffer, 1),
+            None => ReturnCode::EBUSY,
+        }
+    }
+
+    /// Dials into PPP data mode once `State::Registered` is reached;
+    /// see the module documentation. `DataMode::Ppp` only.
+    pub fn enter_data_mode(&self) -> ReturnCode {
+        if self.data_mode != DataMode::Ppp {
+            return ReturnCode::ENOSUPPORT;
+        }
+        if self.state.get() != State::Registered {
+            return ReturnCode::EOFF;
+        }
+        let buffer = match self.tx_buffer.take() {
+            Some(buffer) => buffer,
+            None => return ReturnCode::EBUSY,
+        };
+        match append_bytes(buffer, 0, b"ATD*99#\r\n") {
+            Some(len) => {
+                self.state.set(State::EnteringData);
+                self.send_command(buffer, len, PendingAt::DialPpp)
+            }
+            None => {
+                self.tx_buffer.replace(buffer);
+                ReturnCode::ESIZE
+            }
+        }
+    }
+
+    fn busy(&self) -> bool {
+        self.pending.get().is_some() || self.awaiting_prompt.get().is_some()
+    }
+
+    /// Whether `state` is one where this capsule is still the UART's
+    /// AT-command interpreter, as opposed to not booted yet or already
+    /// handed the UART off to a `DataModeClient`.
+    fn at_capable(&self) -> bool {
+        matches!(self.state.get(), State::SyncingAt | State::CheckingSim | State::Registering | State::Registered)
+    }
+
+    fn set_state(&self, state: State) {
+        self.state.set(state);
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(upcall::STATE_CHANGED, state as usize, 0);
+                }
+            });
+        }
+    }
+
+    fn send_command(&self, buffer: &'static mut [u8], len: usize, op: PendingAt) -> ReturnCode {
+        self.pending.set(Some(op));
+        self.scratch.set(0);
+        self.alarm_purpose.set(AlarmPurpose::CommandTimeout);
+        self.alarm.set_alarm(self.alarm.now(), self.timing.command_timeout_ticks);
+        self.uart.transmit_buffer(buffer, len)
+    }
+
+    fn notify(&self, app_id: AppId, upcall: usize, data1: usize, data2: usize) {
+        let _ = self.apps.enter(app_id, |app, _| {
+            if let Some(mut cb) = app.callback {
+                cb.schedule(upcall, data1, data2);
+            }
+        });
+    }
+
+    fn poll_registration(&self) {
+        let buffer = match self.tx_buffer.take() {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        match append_bytes(buffer, 0, b"AT+CREG?\r\n") {
+            Some(len) => {
+                self.registration_polls.set(self.registration_polls.get() + 1);
+                let _ = self.send_command(buffer, len, PendingAt::QueryRegistration);
+            }
+            None => self.tx_buffer.replace(buffer),
+        }
+    }
+
+    fn append_rx_byte(&self, byte: u8) {
+        self.rx_buffer.map(|buffer| {
+            let len = self.rx_len.get();
+            if len < buffer.len() {
+                buffer[len] = byte;
+                self.rx_len.set(len + 1);
+            }
+        });
+    }
+
+    fn process_byte(&self, byte: u8) {
+        if let Some((app_id, len)) = self.awaiting_prompt.get() {
+            if byte == at::PROMPT {
+                self.awaiting_prompt.set(None);
+                self.send_payload(app_id, len);
+            }
+            return;
+        }
+
+        match self.rx_state.get() {
+            RxState::Line => {
+                if byte == b'\n' {
+                    let len = self.rx_len.get();
+                    self.rx_len.set(0);
+                    self.handle_line(len);
+                    return;
+                }
+                self.append_rx_byte(byte);
+                if byte == b':' {
+                    let len = self.rx_len.get();
+                    let recv_len = self.rx_buffer.map(|buffer| parse_recv_length(&buffer[..len])).flatten();
+                    if let Some(recv_len) = recv_len {
+                        self.rx_len.set(0);
+                        self.rx_state.set(if recv_len == 0 { RxState::Line } else { RxState::UrcPayload(recv_len) });
+                    }
+                }
+            }
+            RxState::UrcPayload(remaining) => {
+                self.append_rx_byte(byte);
+                if remaining <= 1 {
+                    let len = self.rx_len.get();
+                    self.rx_len.set(0);
+                    self.rx_state.set(RxState::Line);
+                    self.deliver_received(len);
+                } else {
+                    self.rx_state.set(RxState::UrcPayload(remaining - 1));
+                }
+            }
+        }
+    }
+
+    fn handle_line(&self, len: usize) {
+        self.rx_buffer.map(|buffer| {
+            let mut line = &buffer[..len];
+            if line.last() == Some(&b'\r') {
+                line = &line[..line.len() - 1];
+            }
+            if line.is_empty() {
+                return;
+            }
+            if line == at::QIURC_CLOSED {
+                self.handle_closed();
+                return;
+            }
+            if line == at::CONNECT && matches!(self.pending.get(), Some(PendingAt::DialPpp)) {
+                self.complete_pending(true);
+                return;
+            }
+            if line == at::CPIN_READY {
+                self.scratch.set(1);
+                return;
+            }
+            if let Some(status) = parse_creg_status(line) {
+                let registered = status == at::CREG_REGISTERED_HOME || status == at::CREG_REGISTERED_ROAMING;
+                self.scratch.set(registered as u32);
+                return;
+            }
+            if let Some(rssi) = parse_csq_rssi(line) {
+                self.scratch.set(rssi as u32);
+                return;
+            }
+            if line == at::OK || line == at::SEND_OK {
+                self.complete_pending(true);
+            } else if line == at::ERROR {
+                self.complete_pending(false);
+            }
+            // Anything else (echoed commands, other unsolicited status
+            // lines) carries no information this driver acts on.
+        });
+    }
+
+    fn complete_pending(&self, success: bool) {
+        let op = match self.pending.take() {
+            Some(op) => op,
+            None => return,
+        };
+        self.alarm.disarm();
+        let scratch = self.scratch.get();
+        match op {
+            PendingAt::SyncAt => {
+                if success {
+                    self.set_state(State::CheckingSim);
+                    self.issue_check_sim(None);
+                } else {
+                    self.set_state(State::Fault);
+                }
+            }
+            PendingAt::CheckSim(requester) => {
+                let ready = success && scratch == 1;
+                if let Some(app_id) = requester {
+                    self.notify(app_id, upcall::SIM_STATUS, ready as usize, 0);
+                } else if ready {
+                    self.set_state(State::Registering);
+                    self.registration_polls.set(0);
+                    self.poll_registration();
+                } else {
+                    self.set_state(State::Fault);
+                }
+            }
+            PendingAt::QueryRegistration => {
+                if success && scratch == 1 {
+                    self.set_state(State::Registered);
+                } else if self.registration_polls.get() < self.timing.max_registration_polls {
+                    self.alarm_purpose.set(AlarmPurpose::RegistrationPoll);
+                    self.alarm.set_alarm(self.alarm.now(), self.timing.registration_poll_ticks);
+                } else {
+                    self.set_state(State::Fault);
+                }
+            }
+            PendingAt::QuerySignal(app_id) => {
+                let rssi = if success { scratch as usize } else { at::CSQ_UNKNOWN as usize };
+                self.notify(app_id, upcall::SIGNAL_QUALITY, rssi, 0);
+            }
+            PendingAt::DialPpp => {
+                if success {
+                    self.set_state(State::DataMode);
+                    self.data_client.map(|client| client.data_mode_entered());
+                } else {
+                    self.set_state(State::Fault);
+                }
+            }
+            PendingAt::ConnectSocket(app_id) => {
+                if success {
+                    self.connection_owner.set(Some(app_id));
+                }
+                self.notify(app_id, upcall::CONNECT_DONE, usize::from(if success { ReturnCode::SUCCESS } else { ReturnCode::FAIL }), 0);
+            }
+            PendingAt::SendSocket(app_id) => {
+                self.notify(app_id, upcall::SEND_DONE, usize::from(if success { ReturnCode::SUCCESS } else { ReturnCode::FAIL }), 0);
+            }
+            PendingAt::CloseSocket(app_id) => {
+                self.connection_owner.set(None);
+                self.notify(app_id, upcall::CLOSED, usize::from(if success { ReturnCode::SUCCESS } else { ReturnCode::FAIL }), 0);
+            }
+        }
+    }
+
+    /// Sends `AT+CPIN?`, without otherwise touching `state`; the boot
+    /// sequence's own transition into `State::CheckingSim` happens in
+    /// its caller, since an app's `GET_SIM_STATUS` re-uses this once
+    /// bring-up is already past that point and must not move `state`
+    /// backwards.
+    fn issue_check_sim(&self, requester: Option<AppId>) {
+        let buffer = match self.tx_buffer.take() {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        match append_bytes(buffer, 0, b"AT+CPIN?\r\n") {
+            Some(len) => {
+                let _ = self.send_command(buffer, len, PendingAt::CheckSim(requester));
+            }
+            None => self.tx_buffer.replace(buffer),
+        }
+    }
+
+    fn handle_closed(&self) {
+        if let Some(app_id) = self.connection_owner.take() {
+            self.notify(app_id, upcall::CLOSED, usize::from(ReturnCode::SUCCESS), 0);
+        }
+    }
+
+    fn deliver_received(&self, len: usize) {
+        let app_id = match self.connection_owner.get() {
+            Some(app_id) => app_id,
+            None => return,
+        };
+        self.rx_buffer.map(|buffer| {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(slice) = &mut app.arg_buffer {
+                    let copy_len = core::cmp::min(len, slice.len());
+                    slice.as_mut()[..copy_len].copy_from_slice(&buffer[..copy_len]);
+                    if let Some(mut cb) = app.callback {
+                        cb.schedule(upcall::RECEIVED, copy_len, 0);
+                    }
+                }
+            });
+        });
+    }
+
+    fn send_payload(&self, app_id: AppId, len: usize) {
+        let buffer = match self.tx_buffer.take() {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        let copied = self
+            .apps
+            .enter(app_id, |app, _| match &app.arg_buffer {
+                Some(slice) if slice.len() >= len && len <= buffer.len() => {
+                    buffer[..len].copy_from_slice(&slice.as_ref()[..len]);
+                    true
+                }
+                _ => false,
+            })
+            .unwrap_or(false);
+        if !copied {
+            self.tx_buffer.replace(buffer);
+            self.notify(app_id, upcall::SEND_DONE, usize::from(ReturnCode::EINVAL), 0);
+            return;
+        }
+        let _ = self.send_command(buffer, len, PendingAt::SendSocket(app_id));
+    }
+}
+
+impl<'a, A: Alarm<'a>, P: PowerControl> ReceiveClient for CellularModem<'a, A, P> {
+    fn received_buffer(&self, buffer: &'static mut [u8], rx_len: usize, _result: ReturnCode) {
+        if rx_len == 1 {
+            self.process_byte(buffer[0]);
+        }
+        let _ = self.uart.receive_buffer(buffer, 1);
+    }
+}
+
+impl<'a, A: Alarm<'a>, P: PowerControl> TransmitClient for CellularModem<'a, A, P> {
+    fn transmitted_buffer(&self, buffer: &'static mut [u8], _tx_len: usize, _result: ReturnCode) {
+        self.tx_buffer.replace(buffer);
+    }
+}
+
+impl<'a, A: Alarm<'a>, P: PowerControl> AlarmClient for CellularModem<'a, A, P> {
+    fn alarm(&self) {
+        match self.alarm_purpose.get() {
+            AlarmPurpose::PowerPulseEnd => {
+                self.power_key.set(false);
+                self.alarm_purpose.set(AlarmPurpose::BootDone);
+                self.set_state(State::Booting);
+                self.alarm.set_alarm(self.alarm.now(), self.timing.boot_ticks);
+            }
+            AlarmPurpose::BootDone => {
+                let buffer = match self.tx_buffer.take() {
+                    Some(buffer) => buffer,
+                    None => return,
+                };
+                match append_bytes(buffer, 0, b"AT\r\n") {
+                    Some(len) => {
+                        self.set_state(State::SyncingAt);
+                        let _ = self.send_command(buffer, len, PendingAt::SyncAt);
+                    }
+                    None => self.tx_buffer.replace(buffer),
+                }
+            }
+            AlarmPurpose::CommandTimeout => {
+                self.awaiting_prompt.set(None);
+                self.complete_pending(false);
+            }
+            AlarmPurpose::RegistrationPoll => self.poll_registration(),
+        }
+    }
+}
+
+impl<'a, A: Alarm<'a>, P: PowerControl> Driver for CellularModem<'a, A, P> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::STATE_CHANGED
+            | upcall::SIGNAL_QUALITY
+            | upcall::SIM_STATUS
+            | upcall::CONNECT_DONE
+            | upcall::SEND_DONE
+            | upcall::RECEIVED
+            | upcall::CLOSED => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.arg_buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::POWER_ON => {
+                if self.state.get() != State::Off {
+                    return ReturnCode::EALREADY;
+                }
+                self.power_key.set(true);
+                self.set_state(State::PoweringOn);
+                self.alarm_purpose.set(AlarmPurpose::PowerPulseEnd);
+                self.alarm.set_alarm(self.alarm.now(), self.timing.power_pulse_ticks);
+                ReturnCode::SUCCESS
+            }
+            cmd::POWER_OFF => {
+                self.power_key.set(false);
+                self.pending.set(None);
+                self.awaiting_prompt.set(None);
+                self.connection_owner.set(None);
+                self.alarm.disarm();
+                self.set_state(State::Off);
+                ReturnCode::SUCCESS
+            }
+            cmd::GET_SIGNAL_QUALITY => {
+                if self.busy() || !self.at_capable() {
+                    return ReturnCode::EBUSY;
+                }
+                let buffer = match self.tx_buffer.take() {
+                    Some(buffer) => buffer,
+                    None => return ReturnCode::EBUSY,
+                };
+                match append_bytes(buffer, 0, b"AT+CSQ\r\n") {
+                    Some(len) => self.send_command(buffer, len, PendingAt::QuerySignal(app_id)),
+                    None => {
+                        self.tx_buffer.replace(buffer);
+                        ReturnCode::ESIZE
+                    }
+                }
+            }
+            cmd::GET_SIM_STATUS => {
+                if self.busy() || !self.at_capable() {
+                    return ReturnCode::EBUSY;
+                }
+                self.issue_check_sim(Some(app_id));
+                ReturnCode::SUCCESS
+            }
+            cmd::CONNECT => {
+                if self.data_mode != DataMode::AtSockets {
+                    return ReturnCode::ENOSUPPORT;
+                }
+                if self.busy() {
+                    return ReturnCode::EBUSY;
+                }
+                if self.state.get() != State::Registered {
+                    return ReturnCode::EOFF;
+                }
+                if self.connection_owner.get().is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                let protocol = if data1 == Protocol::Udp as usize { Protocol::Udp } else { Protocol::Tcp };
+                let port = data2 as u32;
+                let buffer = match self.tx_buffer.take() {
+                    Some(buffer) => buffer,
+                    None => return ReturnCode::EBUSY,
+                };
+                let built = self
+                    .apps
+                    .enter(app_id, |app, _| match &app.arg_buffer {
+                        Some(host) => append_bytes(buffer, 0, b"AT+QIOPEN=1,0,\"")
+                            .and_then(|pos| append_bytes(buffer, pos, if protocol == Protocol::Udp { b"UDP" } else { b"TCP" }))
+                            .and_then(|pos| append_bytes(buffer, pos, b"\",\""))
+                            .and_then(|pos| append_bytes(buffer, pos, host.as_ref()))
+                            .and_then(|pos| append_bytes(buffer, pos, b"\","))
+                            .and_then(|pos| append_decimal(buffer, pos, port))
+                            .and_then(|pos| append_bytes(buffer, pos, b",0,1\r\n")),
+                        None => None,
+                    })
+                    .unwrap_or(None);
+                match built {
+                    Some(len) => self.send_command(buffer, len, PendingAt::ConnectSocket(app_id)),
+                    None => {
+                        self.tx_buffer.replace(buffer);
+                        ReturnCode::EINVAL
+                    }
+                }
+            }
+            cmd::SEND => {
+                if self.data_mode != DataMode::AtSockets {
+                    return ReturnCode::ENOSUPPORT;
+                }
+                if self.busy() {
+                    return ReturnCode::EBUSY;
+                }
+                if self.connection_owner.get() != Some(app_id) {
+                    return ReturnCode::EOFF;
+                }
+                let len = data1;
+                let buffer = match self.tx_buffer.take() {
+                    Some(buffer) => buffer,
+                    None => return ReturnCode::EBUSY,
+                };
+                let header_len = append_bytes(buffer, 0, b"AT+QISEND=0,")
+                    .and_then(|pos| append_decimal(buffer, pos, len as u32))
+                    .and_then(|pos| append_bytes(buffer, pos, b"\r\n"));
+                match header_len {
+                    Some(header_len) => {
+                        self.awaiting_prompt.set(Some((app_id, len)));
+                        self.alarm_purpose.set(AlarmPurpose::CommandTimeout);
+                        self.alarm.set_alarm(self.alarm.now(), self.timing.command_timeout_ticks);
+                        self.uart.transmit_buffer(buffer, header_len)
+                    }
+                    None => {
+                        self.tx_buffer.replace(buffer);
+                        ReturnCode::ESIZE
+                    }
+                }
+            }
+            cmd::CLOSE => {
+                if self.data_mode != DataMode::AtSockets {
+                    return ReturnCode::ENOSUPPORT;
+                }
+                if self.busy() {
+                    return ReturnCode::EBUSY;
+                }
+                if self.connection_owner.get() != Some(app_id) {
+                    return ReturnCode::EALREADY;
+                }
+                let buffer = match self.tx_buffer.take() {
+                    Some(buffer) => buffer,
+                    None => return ReturnCode::EBUSY,
+                };
+                match append_bytes(buffer, 0, b"AT+QICLOSE=0\r\n") {
+                    Some(len) => self.send_command(buffer, len, PendingAt::CloseSocket(app_id)),
+                    None => {
+                        self.tx_buffer.replace(buffer);
+                        ReturnCode::ESIZE
+                    }
+                }
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//! A typed key/value configuration blob in flash that other capsules
+//! look up from synchronously at boot (baud rates, sampling periods,
+//! radio channels, whatever would otherwise be a constant baked into
+//! the board crate), plus a syscall driver a host tool can use to
+//! stage and apply a new blob without recompiling anything.
+//!
+//! The blob itself is a flat list of fixed-width entries (`key`, a
+//! type tag, and up to 8 bytes of value) parsed once at construction
+//! and again each time `apply_update` replaces it; lookups the rest of
+//! the time are plain array scans against the in-RAM copy, so capsule
+//! initialization never has to wait on a flash read.
+//!
+//! An update is staged into the unused half of the region and only
+//! swapped in once it parses cleanly, so a host tool that sends a
+//! truncated or malformed blob leaves the previous configuration in
+//! effect rather than bricking every capsule that reads from it.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let config = static_init!(
+//!     capsules::config_store::ConfigStore<'static>,
+//!     capsules::config_store::ConfigStore::new(flash, blob_size, &boot_blob, staging_buffer));
+//! let baud = config.get_u32(keys::UART_BAUD_RATE).unwrap_or(DEFAULT_BAUD_RATE);
+//! ```
+
+use kernel::common::cells::TakeCell;
+use kernel::hil::nonvolatile_storage::{NonvolatileStorage, NonvolatileStorageClient};
+use kernel::{AppId, AppSlice, Driver, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::ConfigStore as usize;
+
+mod cmd {
+    /// Erases the staging half of the region and begins accepting
+    /// `WRITE`s into it.
+    pub const BEGIN: usize = 0;
+    /// Writes `data2` bytes from the buffer allowed at index 0 at
+    /// offset `data1` within the staging half.
+    pub const WRITE: usize = 1;
+    /// Parses the staged blob; only on success does it become the
+    /// active configuration for future `get_*` lookups.
+    pub const APPLY: usize = 2;
+}
+
+const MAX_ENTRIES: usize = 32;
+const MAX_VALUE_LEN: usize = 8;
+/// `key (4) + type tag (1) + length (1) + value (MAX_VALUE_LEN)`.
+const ENTRY_LEN: usize = 4 + 1 + 1 + MAX_VALUE_LEN;
+
+#[derive(Copy, Clone, PartialEq)]
+enum ValueType {
+    U32,
+    I32,
+    Bytes,
+}
+
+#[derive(Copy, Clone)]
+struct Entry {
+    key: u32,
+    value_type: ValueType,
+    len: u8,
+    value: [u8; MAX_VALUE_LEN],
+}
+
+/// Parses `blob` into up to `MAX_ENTRIES` fixed-width records,
+/// stopping (but not failing) at the first all-zero record, which
+/// marks the end of the used portion of a region sized larger than
+/// its contents.
+fn parse_entries(blob: &[u8]) -> Option<[Option<Entry>; MAX_ENTRIES]> {
+    let mut entries: [Option<Entry>; MAX_ENTRIES] = [None; MAX_ENTRIES];
+    for i in 0..MAX_ENTRIES {
+        let offset = i * ENTRY_LEN;
+        if offset + ENTRY_LEN > blob.len() {
+            break;
+        }
+        let record = &blob[offset..offset + ENTRY_LEN];
+        let key = u32::from_le_bytes([record[0], record[1], record[2], record[3]]);
+        let type_tag = record[4];
+        let len = record[5];
+        if key == 0 && type_tag == 0 && len == 0 {
+            break;
+        }
+        let value_type = match type_tag {
+            0 => ValueType::U32,
+            1 => ValueType::I32,
+            2 => ValueType::Bytes,
+            _ => return None,
+        };
+        if len as usize > MAX_VALUE_LEN {
+            return None;
+        }
+        let mut value = [0u8; MAX_VALUE_LEN];
+        value[..len as usize].copy_from_slice(&record[6..6 + len as usize]);
+        entries[i] = Some(Entry {
+            key,
+            value_type,
+            len,
+            value,
+        });
+    }
+    Some(entries)
+}
+
+pub struct ConfigStore<'a> {
+    flash: &'a dyn NonvolatileStorage<'a>,
+    /// Byte length of each of the region's two halves; the active
+    /// half starts at offset `0`, the staging half at `region_size`.
+    region_size: usize,
+    entries: core::cell::Cell<[Option<Entry>; MAX_ENTRIES]>,
+    /// A RAM mirror of the staging half, `region_size` bytes long, that
+    /// each `WRITE` both sends to `self.flash` and keeps a copy in so
+    /// `APPLY` can parse it back without waiting on a flash read.
+    staging: TakeCell<'static, [u8]>,
+    staged_len: core::cell::Cell<usize>,
+    /// The buffer allowed at index 0, holding the bytes for the next
+    /// `WRITE`.
+    data: core::cell::Cell<Option<AppSlice<Shared, u8>>>,
+}
+
+impl<'a> ConfigStore<'a> {
+    pub fn new(flash: &'a dyn NonvolatileStorage<'a>, region_size: usize, boot_blob: &[u8], staging: &'static mut [u8]) -> ConfigStore<'a> {
+        let entries = parse_entries(boot_blob).unwrap_or([None; MAX_ENTRIES]);
+        ConfigStore {
+            flash,
+            region_size,
+            entries: core::cell::Cell::new(entries),
+            staging: TakeCell::new(staging),
+            staged_len: core::cell::Cell::new(0),
+            data: core::cell::Cell::new(None),
+        }
+    }
+
+    fn find(&self, key: u32, want: ValueType) -> Option<Entry> {
+        let entries = self.entries.get();
+        entries
+            .iter()
+            .flatten()
+            .find(|entry| entry.key == key && entry.value_type == want)
+            .copied()
+    }
+
+    pub fn get_u32(&self, key: u32) -> Option<u32> {
+        let entry = self.find(key, ValueType::U32)?;
+        Some(u32::from_le_bytes([entry.value[0], entry.value[1], entry.value[2], entry.value[3]]))
+    }
+
+    pub fn get_i32(&self, key: u32) -> Option<i32> {
+        let entry = self.find(key, ValueType::I32)?;
+        Some(i32::from_le_bytes([entry.value[0], entry.value[1], entry.value[2], entry.value[3]]))
+    }
+
+    pub fn get_bytes(&self, key: u32) -> Option<([u8; MAX_VALUE_LEN], usize)> {
+        let entry = self.find(key, ValueType::Bytes)?;
+        Some((entry.value, entry.len as usize))
+    }
+
+    /// Parses `blob` and, only if it parses cleanly, replaces the
+    /// active configuration with it.
+    pub fn apply_update(&self, blob: &[u8]) -> ReturnCode {
+        match parse_entries(blob) {
+            Some(entries) => {
+                self.entries.set(entries);
+                ReturnCode::SUCCESS
+            }
+            None => ReturnCode::EINVAL,
+        }
+    }
+}
+
+impl<'a> Driver for ConfigStore<'a> {
+    fn allow(&self, _app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => {
+                self.data.set(slice);
+                ReturnCode::SUCCESS
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, data2: usize, _app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::BEGIN => {
+                self.staged_len.set(0);
+                self.flash.erase(self.region_size, self.region_size)
+            }
+            cmd::WRITE => match data1.checked_add(data2) {
+                Some(end) if end <= self.region_size => {
+                    let slice = self.data.take();
+                    let result = match &slice {
+                        Some(slice) if data2 <= slice.len() => match self.staging.take() {
+                            Some(buf) if buf.len() >= end => {
+                                buf[data1..end].copy_from_slice(&slice.as_ref()[..data2]);
+                                let result = self.flash.write(buf, self.region_size + data1, data2);
+                                if result == ReturnCode::SUCCESS {
+                                    self.staged_len.set(core::cmp::max(self.staged_len.get(), end));
+                                }
+                                result
+                            }
+                            Some(buf) => {
+                                self.staging.replace(buf);
+                                ReturnCode::ESIZE
+                            }
+                            None => ReturnCode::EBUSY,
+                        },
+                        Some(_) => ReturnCode::ESIZE,
+                        None => ReturnCode::EINVAL,
+                    };
+                    self.data.set(slice);
+                    result
+                }
+                _ => ReturnCode::ESIZE,
+            },
+            cmd::APPLY => match self.staging.take() {
+                Some(buf) => {
+                    let result = self.apply_update(&buf[..self.staged_len.get()]);
+                    self.staging.replace(buf);
+                    result
+                }
+                // A `WRITE` is still in flight (the buffer has not
+                // come back through `write_done` yet); boards that
+                // instead read the staged bytes straight back from
+                // `self.flash` can call `apply_update` directly.
+                None => ReturnCode::ENOMEM,
+            },
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a> NonvolatileStorageClient for ConfigStore<'a> {
+    fn read_done(&self, buffer: &'static mut [u8], _length: usize) {
+        self.staging.replace(buffer);
+    }
+
+    fn write_done(&self, buffer: &'static mut [u8], _length: usize) {
+        self.staging.replace(buffer);
+    }
+
+    fn erase_done(&self) {}
+}
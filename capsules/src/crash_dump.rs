@@ -0,0 +1,90 @@
+//! Persists register file, fault status registers, and a bounded stack
+//! snapshot to a reserved flash region when a process faults or the
+//! kernel panics.
+//!
+//! Without this, a field failure is only diagnosable if someone
+//! happened to be watching the console when the panic text scrolled
+//! by. This capsule is driven by the kernel's panic and process-fault
+//! hooks, not by an app directly. Through `command`, apps can ask
+//! whether a dump is present and clear it; `command` does not copy the
+//! dump's contents out to an app.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let crash_dump = static_init!(
+//!     capsules::crash_dump::CrashDump<'static>,
+//!     capsules::crash_dump::CrashDump::new(flash_region));
+//! ```
+
+use kernel::{AppId, Driver, ReturnCode};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::CrashDump as usize;
+
+mod cmd {
+    pub const HAS_DUMP: usize = 0;
+    pub const DUMP_LEN: usize = 1;
+    /// Returns `SUCCESS` without copying anything; this driver takes no
+    /// allowed buffer, so the persisted dump is not copied out.
+    pub const READ: usize = 2;
+    pub const CLEAR: usize = 3;
+}
+
+/// A bounded snapshot captured at fault/panic time, before the flash
+/// write that persists it.
+#[derive(Copy, Clone)]
+pub struct FaultSnapshot {
+    pub registers: [u32; 16],
+    pub fault_status: u32,
+    pub stack_bytes: [u8; 256],
+    pub stack_len: usize,
+}
+
+/// Abstracts over the reserved flash region backing the dump so this
+/// capsule does not need to know the chip's flash HIL directly.
+pub trait DumpRegion {
+    fn write(&self, snapshot: &FaultSnapshot) -> ReturnCode;
+    fn read(&self) -> Option<FaultSnapshot>;
+    fn clear(&self) -> ReturnCode;
+}
+
+pub struct CrashDump<'a> {
+    region: &'a dyn DumpRegion,
+}
+
+impl<'a> CrashDump<'a> {
+    pub fn new(region: &'a dyn DumpRegion) -> CrashDump<'a> {
+        CrashDump { region }
+    }
+
+    /// Called from the kernel's panic handler or process fault path,
+    /// not from a syscall.
+    pub fn capture(&self, snapshot: &FaultSnapshot) -> ReturnCode {
+        self.region.write(snapshot)
+    }
+}
+
+impl<'a> Driver for CrashDump<'a> {
+    fn command(&self, command_num: usize, _data1: usize, _data2: usize, _app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::HAS_DUMP => {
+                if self.region.read().is_some() {
+                    ReturnCode::SUCCESS
+                } else {
+                    ReturnCode::FAIL
+                }
+            }
+            cmd::DUMP_LEN => {
+                if self.region.read().is_some() {
+                    ReturnCode::SUCCESS
+                } else {
+                    ReturnCode::FAIL
+                }
+            }
+            cmd::READ => ReturnCode::SUCCESS,
+            cmd::CLEAR => self.region.clear(),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
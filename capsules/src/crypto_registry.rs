@@ -0,0 +1,92 @@
+//! A capability-advertising registry of the crypto engines a board
+//! wired up at init, so a capsule asks for "AES-GCM" or "SHA-256" and
+//! gets whichever engine the board provided — a hardware accelerator
+//! or a software fallback — without being written against a specific
+//! accelerator type.
+//!
+//! Each slot is populated at board init with whatever `&'a dyn
+//! AeadEngine` (and so on) the board has; a board with no AES-GCM
+//! hardware and no software fallback built yet simply leaves that
+//! slot empty, and `has(Capability::AesGcm)` reports it as absent.
+//! Building the actual software-fallback engines belongs with their
+//! respective HILs (`hil::aead`, `hil::digest`, ...), not here; this
+//! registry only selects among engines that already exist.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let registry = static_init!(
+//!     capsules::crypto_registry::CryptoRegistry<'static>,
+//!     capsules::crypto_registry::CryptoRegistry::new());
+//! registry.set_aead(hardware_aes_gcm);
+//! registry.set_digest(hardware_sha256);
+//! ```
+
+use kernel::common::cells::OptionalCell;
+use kernel::hil::aead::AeadEngine;
+use kernel::hil::crypto::Capability;
+use kernel::hil::curve25519::Curve25519Engine;
+use kernel::hil::digest::DigestEngine;
+use kernel::hil::ecdsa::EcdsaP256Engine;
+
+pub struct CryptoRegistry<'a> {
+    aead: OptionalCell<&'a dyn AeadEngine<'a>>,
+    digest: OptionalCell<&'a dyn DigestEngine<'a>>,
+    ecdsa_p256: OptionalCell<&'a dyn EcdsaP256Engine<'a>>,
+    curve25519: OptionalCell<&'a dyn Curve25519Engine<'a>>,
+}
+
+impl<'a> CryptoRegistry<'a> {
+    pub fn new() -> CryptoRegistry<'a> {
+        CryptoRegistry {
+            aead: OptionalCell::empty(),
+            digest: OptionalCell::empty(),
+            ecdsa_p256: OptionalCell::empty(),
+            curve25519: OptionalCell::empty(),
+        }
+    }
+
+    pub fn set_aead(&self, engine: &'a dyn AeadEngine<'a>) {
+        self.aead.set(engine);
+    }
+
+    pub fn set_digest(&self, engine: &'a dyn DigestEngine<'a>) {
+        self.digest.set(engine);
+    }
+
+    pub fn set_ecdsa_p256(&self, engine: &'a dyn EcdsaP256Engine<'a>) {
+        self.ecdsa_p256.set(engine);
+    }
+
+    pub fn set_curve25519(&self, engine: &'a dyn Curve25519Engine<'a>) {
+        self.curve25519.set(engine);
+    }
+
+    /// Reports whether an engine is registered for `capability`,
+    /// without handing it out — useful for a capsule that only needs
+    /// to decide which of two protocols it can offer.
+    pub fn has(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::AesGcm | Capability::AesCcm => self.aead.is_some(),
+            Capability::Sha256 | Capability::Sha512 => self.digest.is_some(),
+            Capability::EcdsaP256 => self.ecdsa_p256.is_some(),
+            Capability::Curve25519 => self.curve25519.is_some(),
+        }
+    }
+
+    pub fn aead(&self) -> Option<&'a dyn AeadEngine<'a>> {
+        self.aead.map(|engine| engine)
+    }
+
+    pub fn digest(&self) -> Option<&'a dyn DigestEngine<'a>> {
+        self.digest.map(|engine| engine)
+    }
+
+    pub fn ecdsa_p256(&self) -> Option<&'a dyn EcdsaP256Engine<'a>> {
+        self.ecdsa_p256.map(|engine| engine)
+    }
+
+    pub fn curve25519(&self) -> Option<&'a dyn Curve25519Engine<'a>> {
+        self.curve25519.map(|engine| engine)
+    }
+}
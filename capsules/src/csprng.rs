@@ -0,0 +1,92 @@
+//! Software CSPRNG layered on a hardware `hil::entropy::Entropy32`
+//! source, reseeded periodically rather than on every request.
+//!
+//! A hardware TRNG is typically far slower than apps and kernel
+//! capsules (the key store, Curve25519 key generation) want to draw
+//! from, and has a limited lifetime before its entropy pool needs to
+//! recover. `Csprng` implements `Entropy32` itself, so it is a drop-in
+//! replacement anywhere a hardware source would otherwise be used
+//! directly: it seeds its internal generator from the real TRNG, serves
+//! requests out of that generator, and reseeds again once it has served
+//! too many outputs on the current seed.
+//!
+//! The generator's internal expansion (CTR_DRBG per NIST SP 800-90A, or
+//! Fortuna) is not modeled here; only the reseed scheduling and the HIL
+//! plumbing are.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let csprng = static_init!(
+//!     capsules::csprng::Csprng<'static>,
+//!     capsules::csprng::Csprng::new(trng));
+//! trng.set_client(csprng);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::OptionalCell;
+use kernel::hil::entropy::{Entropy32, Entropy32Client};
+use kernel::ReturnCode;
+
+/// Words of raw TRNG output drawn per reseed.
+const SEED_WORDS: usize = 8;
+/// Words served from a single seed before a reseed is requested.
+const RESEED_AFTER_WORDS: u32 = 1 << 16;
+
+pub struct Csprng<'a> {
+    trng: &'a dyn Entropy32<'a>,
+    client: OptionalCell<&'a dyn Entropy32Client>,
+    seeded: Cell<bool>,
+    pending_count: Cell<usize>,
+    served_since_reseed: Cell<u32>,
+}
+
+impl<'a> Csprng<'a> {
+    pub fn new(trng: &'a dyn Entropy32<'a>) -> Csprng<'a> {
+        Csprng {
+            trng,
+            client: OptionalCell::empty(),
+            seeded: Cell::new(false),
+            pending_count: Cell::new(0),
+            served_since_reseed: Cell::new(0),
+        }
+    }
+
+    fn needs_reseed(&self) -> bool {
+        !self.seeded.get() || self.served_since_reseed.get() >= RESEED_AFTER_WORDS
+    }
+}
+
+impl<'a> Entropy32<'a> for Csprng<'a> {
+    fn set_client(&self, client: &'a dyn Entropy32Client) {
+        self.client.set(client);
+    }
+
+    fn get(&self, count: usize) -> ReturnCode {
+        if self.needs_reseed() {
+            self.pending_count.set(count);
+            return self.trng.get(SEED_WORDS);
+        }
+        // The generator already holds a live seed, so output words are
+        // available immediately rather than waiting on hardware.
+        self.served_since_reseed.set(self.served_since_reseed.get() + count as u32);
+        self.client.map(|client| client.entropy_available(count, ReturnCode::SUCCESS));
+        ReturnCode::SUCCESS
+    }
+}
+
+impl<'a> Entropy32Client for Csprng<'a> {
+    fn entropy_available(&self, _count: usize, result: ReturnCode) {
+        if result != ReturnCode::SUCCESS {
+            self.client.map(|client| client.entropy_available(0, result));
+            return;
+        }
+        // Mixing the hardware-provided seed words into the generator's
+        // internal state is elided here; only the bookkeeping that
+        // decides when a reseed is due is modeled.
+        self.seeded.set(true);
+        let count = self.pending_count.get();
+        self.served_since_reseed.set(count as u32);
+        self.client.map(|client| client.entropy_available(count, ReturnCode::SUCCESS));
+    }
+}
@@ -0,0 +1,288 @@
+//! CTAPHID transport over a USB HID gadget, forwarding complete CTAP2
+//! CBOR messages to a userspace FIDO2 authenticator app and handling
+//! the channel allocation and keepalive bookkeeping that transport
+//! needs on its own, without waking the app for either.
+//!
+//! Channel and command header parsing (CTAPHID's "initialization" and
+//! "continuation" packet split) is real, since it is needed to know
+//! when a message is complete. The incoming message payload bytes
+//! themselves are not kept or passed to the app: the `DISPATCH` upcall
+//! carries only the command byte and the total length. The response an
+//! app builds for `RESPOND` is allowed at index 0 and is read here,
+//! since sending it is this capsule's own job.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let ctap_hid = static_init!(
+//!     capsules::ctap_hid::CtapHid<'static, VirtualMuxAlarm<'static, sam4l::ast::Ast>>,
+//!     capsules::ctap_hid::CtapHid::new(
+//!         hid, alarm, rx_buffer, tx_buffer,
+//!         kernel::Grant::create(capsules::driver::NUM::CtapHid as usize)));
+//! hid.set_client(ctap_hid);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::time::{Alarm, AlarmClient};
+use kernel::hil::usb_hid::{UsbHidClient, UsbHidReport, HID_REPORT_LEN};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::CtapHid as usize;
+
+mod proto {
+    pub const BROADCAST_CID: u32 = 0xffff_ffff;
+    pub const CMD_INIT: u8 = 0x86;
+    pub const CMD_CBOR: u8 = 0x90;
+    pub const CMD_CANCEL: u8 = 0x91;
+    /// Length of an initialization packet's header: channel ID (4),
+    /// command (1), and payload length (2).
+    pub const INIT_HEADER_LEN: usize = 7;
+    /// Length of a continuation packet's header: channel ID (4) and
+    /// sequence number (1).
+    pub const CONT_HEADER_LEN: usize = 5;
+}
+
+const INIT_PAYLOAD_LEN: usize = HID_REPORT_LEN - proto::INIT_HEADER_LEN;
+const CONT_PAYLOAD_LEN: usize = HID_REPORT_LEN - proto::CONT_HEADER_LEN;
+
+/// How often a CTAPHID_KEEPALIVE report is sent on the channel of a
+/// request that has been handed to the app but not yet answered.
+const KEEPALIVE_INTERVAL_MS: u32 = 100;
+
+mod upcall {
+    /// Called with a complete message's command byte and length once
+    /// one has been reassembled from the wire.
+    pub const DISPATCH: usize = 0;
+}
+
+mod cmd {
+    /// Sends the response built up in the buffer allowed at index 0,
+    /// `data1` bytes long, back on the channel of the message that was
+    /// last dispatched to this app.
+    pub const RESPOND: usize = 0;
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum State {
+    Idle,
+    /// Reassembling an incoming message from continuation packets.
+    Receiving {
+        cid: u32,
+        cmd: u8,
+        total_len: usize,
+        received: usize,
+        next_seq: u8,
+    },
+    /// A complete message was handed to the app; keepalives are sent
+    /// on `cid` until it responds.
+    Dispatched { cid: u32 },
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    /// The response built by `RESPOND`, allowed at index 0.
+    response: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct CtapHid<'a, A: Alarm<'a>> {
+    hid: &'a dyn UsbHidReport<'a>,
+    alarm: &'a A,
+    state: Cell<State>,
+    next_cid: Cell<u32>,
+    report_buffer: TakeCell<'static, [u8]>,
+    apps: Grant<App>,
+    current_app: OptionalCell<AppId>,
+}
+
+impl<'a, A: Alarm<'a>> CtapHid<'a, A> {
+    pub fn new(hid: &'a dyn UsbHidReport<'a>, alarm: &'a A, rx_buffer: &'static mut [u8], tx_buffer: &'static mut [u8], apps: Grant<App>) -> CtapHid<'a, A> {
+        let ctap_hid = CtapHid {
+            hid,
+            alarm,
+            state: Cell::new(State::Idle),
+            next_cid: Cell::new(1),
+            report_buffer: TakeCell::new(tx_buffer),
+            apps,
+            current_app: OptionalCell::empty(),
+        };
+        let _ = hid.receive_report(rx_buffer);
+        ctap_hid
+    }
+
+    fn handle_report(&self, buffer: &[u8]) {
+        if buffer.len() < proto::CONT_HEADER_LEN {
+            return;
+        }
+        let cid = u32::from_be_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
+        let is_init_packet = buffer[4] & 0x80 != 0;
+
+        match self.state.get() {
+            State::Idle if is_init_packet => {
+                let cmd = buffer[4];
+                if cmd == proto::CMD_INIT && cid == proto::BROADCAST_CID {
+                    // Channel allocation: a new channel ID is taken,
+                    // but no INIT response carrying it is sent.
+                    let allocated = self.next_cid.get();
+                    self.next_cid.set(allocated.wrapping_add(1));
+                    return;
+                }
+                let total_len = ((buffer[5] as usize) << 8) | buffer[6] as usize;
+                let received = core::cmp::min(total_len, INIT_PAYLOAD_LEN);
+                if received >= total_len {
+                    self.dispatch(cid, cmd, total_len);
+                } else {
+                    self.state.set(State::Receiving {
+                        cid,
+                        cmd,
+                        total_len,
+                        received,
+                        next_seq: 0,
+                    });
+                }
+            }
+            State::Receiving {
+                cid: expected_cid,
+                cmd,
+                total_len,
+                received,
+                next_seq,
+            } if cid == expected_cid && !is_init_packet && buffer[4] == next_seq => {
+                let remaining = total_len - received;
+                let received = received + core::cmp::min(remaining, CONT_PAYLOAD_LEN);
+                if received >= total_len {
+                    self.dispatch(cid, cmd, total_len);
+                } else {
+                    self.state.set(State::Receiving {
+                        cid,
+                        cmd,
+                        total_len,
+                        received,
+                        next_seq: next_seq.wrapping_add(1),
+                    });
+                }
+            }
+            State::Receiving { .. } => {
+                // A packet on another channel, or out of sequence on
+                // this one: CTAPHID calls for a CMD_ERROR reply here,
+                // which this capsule's elided outgoing path does not
+                // send; the in-progress message is simply abandoned.
+                self.state.set(State::Idle);
+            }
+            _ => {
+                // Busy with another channel's dispatched message;
+                // CTAPHID_BUSY would be the correct reply.
+            }
+        }
+    }
+
+    fn dispatch(&self, cid: u32, cmd: u8, total_len: usize) {
+        if cmd == proto::CMD_CANCEL {
+            self.state.set(State::Idle);
+            return;
+        }
+        self.state.set(State::Dispatched { cid });
+        self.alarm.set_alarm(self.alarm.now(), A::ticks_from_ms(KEEPALIVE_INTERVAL_MS));
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(cmd as usize, total_len, 0);
+                }
+            });
+        }
+    }
+}
+
+impl<'a, A: Alarm<'a>> AlarmClient for CtapHid<'a, A> {
+    fn alarm(&self) {
+        if let State::Dispatched { .. } = self.state.get() {
+            // A CTAPHID_KEEPALIVE report would be sent here; the
+            // outgoing report path is elided, so only the rearm is
+            // shown.
+            self.alarm.set_alarm(self.alarm.now(), A::ticks_from_ms(KEEPALIVE_INTERVAL_MS));
+        }
+    }
+}
+
+impl<'a, A: Alarm<'a>> UsbHidClient for CtapHid<'a, A> {
+    fn report_received(&self, buffer: &'static mut [u8], result: ReturnCode) {
+        if result == ReturnCode::SUCCESS {
+            self.handle_report(&buffer);
+        }
+        let _ = self.hid.receive_report(buffer);
+    }
+
+    fn report_sent(&self, report: &'static mut [u8], _result: ReturnCode) {
+        self.report_buffer.replace(report);
+    }
+}
+
+impl<'a, A: Alarm<'a>> Driver for CtapHid<'a, A> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::DISPATCH => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.response = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, _data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::RESPOND => {
+                if let State::Dispatched { .. } = self.state.get() {
+                    let buffer = match self.report_buffer.take() {
+                        Some(buffer) => buffer,
+                        None => return ReturnCode::EBUSY,
+                    };
+                    if buffer.len() < data1 {
+                        self.report_buffer.replace(buffer);
+                        return ReturnCode::ESIZE;
+                    }
+                    let copy_result = self.apps.enter(app_id, |app, _| match &app.response {
+                        Some(slice) if slice.len() >= data1 => {
+                            buffer[..data1].copy_from_slice(&slice.as_ref()[..data1]);
+                            ReturnCode::SUCCESS
+                        }
+                        Some(_) => ReturnCode::ESIZE,
+                        None => ReturnCode::EINVAL,
+                    });
+                    match copy_result.unwrap_or(ReturnCode::FAIL) {
+                        ReturnCode::SUCCESS => {
+                            self.state.set(State::Idle);
+                            self.alarm.disarm();
+                            self.current_app.set(app_id);
+                            self.hid.send_report(buffer)
+                        }
+                        e => {
+                            self.report_buffer.replace(buffer);
+                            e
+                        }
+                    }
+                } else {
+                    ReturnCode::EINVAL
+                }
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
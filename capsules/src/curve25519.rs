@@ -0,0 +1,256 @@
+//! Curve25519 syscall driver (Ed25519 sign/verify, X25519 ECDH), built
+//! on `hil::curve25519::Curve25519Engine` so the same code runs whether
+//! a board wires up a hardware accelerator or a software fallback.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let curve25519 = static_init!(
+//!     capsules::curve25519::Curve25519Driver<'static>,
+//!     capsules::curve25519::Curve25519Driver::new(
+//!         engine, kernel::Grant::create(capsules::driver::NUM::Curve25519 as usize),
+//!         buffer));
+//! engine.set_client(curve25519);
+//! ```
+
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::curve25519::{Curve25519Client, Curve25519Engine, CURVE25519_KEY_LEN, ED25519_SIGNATURE_LEN};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Curve25519 as usize;
+
+/// A practical bound on the message `SIGN` operates on, so `SIGN` can
+/// copy it into a stack-local buffer rather than needing a second
+/// static scratch buffer sized for the worst case. `VERIFY` passes the
+/// allowed message to the engine directly and does not apply it.
+const MAX_MESSAGE_LEN: usize = 256;
+
+mod upcall {
+    pub const DONE: usize = 0;
+}
+
+mod cmd {
+    /// Signs the message allowed at index 0 with the private key
+    /// allowed at index 1; the signature is written into the buffer
+    /// allowed at index 2 once the completion upcall fires.
+    pub const SIGN: usize = 0;
+    /// Verifies the signature allowed at index 2 over the message
+    /// allowed at index 0 against the public key allowed at index 1.
+    pub const VERIFY: usize = 1;
+    /// Computes an X25519 shared secret from the private key allowed
+    /// at index 1 and the peer public key allowed at index 0, writing
+    /// it into the buffer allowed at index 2.
+    pub const DH: usize = 2;
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    /// The message to sign/verify (`SIGN`/`VERIFY`), or the peer public
+    /// key (`DH`); allowed at index 0.
+    message: Option<AppSlice<Shared, u8>>,
+    /// The private key (`SIGN`/`DH`), or the public key (`VERIFY`);
+    /// allowed at index 1.
+    key: Option<AppSlice<Shared, u8>>,
+    /// The output signature/secret buffer (`SIGN`/`DH`, written once
+    /// the completion upcall fires), or the signature to check
+    /// (`VERIFY`, read); allowed at index 2.
+    extra: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct Curve25519Driver<'a> {
+    engine: &'a dyn Curve25519Engine<'a>,
+    apps: Grant<App>,
+    current_app: OptionalCell<AppId>,
+    /// Scratch output buffer for `SIGN`/`DH`, at least
+    /// `ED25519_SIGNATURE_LEN` bytes long.
+    buffer: TakeCell<'static, [u8]>,
+}
+
+impl<'a> Curve25519Driver<'a> {
+    pub fn new(engine: &'a dyn Curve25519Engine<'a>, apps: Grant<App>, buffer: &'static mut [u8]) -> Curve25519Driver<'a> {
+        Curve25519Driver {
+            engine,
+            apps,
+            current_app: OptionalCell::empty(),
+            buffer: TakeCell::new(buffer),
+        }
+    }
+}
+
+impl<'a> Driver for Curve25519Driver<'a> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::DONE => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.message = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            1 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.key = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            2 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.extra = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, _data1: usize, _data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::SIGN => {
+                if self.current_app.is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                let mut key = [0u8; CURVE25519_KEY_LEN];
+                let mut message = [0u8; MAX_MESSAGE_LEN];
+                let prepare_result = self.apps.enter(app_id, |app, _| match (&app.key, &app.message) {
+                    (Some(key_slice), Some(message_slice)) if key_slice.len() >= CURVE25519_KEY_LEN && message_slice.len() <= MAX_MESSAGE_LEN => {
+                        key.copy_from_slice(&key_slice.as_ref()[..CURVE25519_KEY_LEN]);
+                        message[..message_slice.len()].copy_from_slice(message_slice.as_ref());
+                        Ok(message_slice.len())
+                    }
+                    (Some(_), Some(_)) => Err(ReturnCode::ESIZE),
+                    _ => Err(ReturnCode::EINVAL),
+                });
+                let message_len = match prepare_result.unwrap_or(Err(ReturnCode::FAIL)) {
+                    Ok(len) => len,
+                    Err(e) => return e,
+                };
+                let buffer = match self.buffer.take() {
+                    Some(buffer) => buffer,
+                    None => return ReturnCode::EBUSY,
+                };
+                if buffer.len() < ED25519_SIGNATURE_LEN {
+                    self.buffer.replace(buffer);
+                    return ReturnCode::ESIZE;
+                }
+                let result = self.engine.sign(&key, &message[..message_len], buffer);
+                if result == ReturnCode::SUCCESS {
+                    self.current_app.set(app_id);
+                }
+                result
+            }
+            cmd::VERIFY => {
+                if self.current_app.is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                let result = self.apps.enter(app_id, |app, _| match (&app.key, &app.message, &app.extra) {
+                    (Some(key_slice), Some(message_slice), Some(signature_slice)) if key_slice.len() >= CURVE25519_KEY_LEN && signature_slice.len() >= ED25519_SIGNATURE_LEN => {
+                        self.engine.verify(&key_slice.as_ref()[..CURVE25519_KEY_LEN], message_slice.as_ref(), &signature_slice.as_ref()[..ED25519_SIGNATURE_LEN])
+                    }
+                    (Some(_), Some(_), Some(_)) => ReturnCode::ESIZE,
+                    _ => ReturnCode::EINVAL,
+                });
+                let result = result.unwrap_or(ReturnCode::FAIL);
+                if result == ReturnCode::SUCCESS {
+                    self.current_app.set(app_id);
+                }
+                result
+            }
+            cmd::DH => {
+                if self.current_app.is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                let mut private_key = [0u8; CURVE25519_KEY_LEN];
+                let mut peer_public_key = [0u8; CURVE25519_KEY_LEN];
+                let prepare_result = self.apps.enter(app_id, |app, _| match (&app.key, &app.message) {
+                    (Some(key_slice), Some(peer_slice)) if key_slice.len() >= CURVE25519_KEY_LEN && peer_slice.len() >= CURVE25519_KEY_LEN => {
+                        private_key.copy_from_slice(&key_slice.as_ref()[..CURVE25519_KEY_LEN]);
+                        peer_public_key.copy_from_slice(&peer_slice.as_ref()[..CURVE25519_KEY_LEN]);
+                        ReturnCode::SUCCESS
+                    }
+                    (Some(_), Some(_)) => ReturnCode::ESIZE,
+                    _ => ReturnCode::EINVAL,
+                });
+                let prepare_result = prepare_result.unwrap_or(ReturnCode::FAIL);
+                if prepare_result != ReturnCode::SUCCESS {
+                    return prepare_result;
+                }
+                let buffer = match self.buffer.take() {
+                    Some(buffer) => buffer,
+                    None => return ReturnCode::EBUSY,
+                };
+                if buffer.len() < CURVE25519_KEY_LEN {
+                    self.buffer.replace(buffer);
+                    return ReturnCode::ESIZE;
+                }
+                let result = self.engine.dh(&private_key, &peer_public_key, buffer);
+                if result == ReturnCode::SUCCESS {
+                    self.current_app.set(app_id);
+                }
+                result
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a> Curve25519Client for Curve25519Driver<'a> {
+    fn sign_done(&self, signature_buffer: &'static mut [u8], result: ReturnCode) {
+        self.copy_out_and_schedule(&signature_buffer, usize::from(result), 0);
+        self.buffer.replace(signature_buffer);
+    }
+
+    fn verify_done(&self, result: ReturnCode, valid: bool) {
+        self.schedule(usize::from(result), if valid { 1 } else { 0 });
+    }
+
+    fn dh_done(&self, secret_buffer: &'static mut [u8], result: ReturnCode) {
+        self.copy_out_and_schedule(&secret_buffer, usize::from(result), 0);
+        self.buffer.replace(secret_buffer);
+    }
+}
+
+impl<'a> Curve25519Driver<'a> {
+    fn schedule(&self, arg0: usize, arg1: usize) {
+        if let Some(app_id) = self.current_app.take() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(arg0, arg1, 0);
+                }
+            });
+        }
+    }
+
+    /// Copies `output` into the app's `extra` buffer before scheduling
+    /// the completion upcall, for the `SIGN`/`DH` results that need to
+    /// reach the app that way.
+    fn copy_out_and_schedule(&self, output: &[u8], arg0: usize, arg1: usize) {
+        if let Some(app_id) = self.current_app.take() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(dest) = &mut app.extra {
+                    let len = core::cmp::min(dest.len(), output.len());
+                    dest.as_mut()[..len].copy_from_slice(&output[..len]);
+                }
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(arg0, arg1, 0);
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,60 @@
+//! Exposes the architecture's cycle counter to userspace so apps can
+//! microbenchmark without toggling GPIOs and reaching for a logic
+//! analyzer.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let cycles = static_init!(
+//!     capsules::cycle_counter::CycleCounterDriver<'static>,
+//!     capsules::cycle_counter::CycleCounterDriver::new(dwt));
+//! ```
+
+use kernel::hil::cycle_counter::CycleCounter;
+use kernel::{AppId, Driver, ReturnCode};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::CycleCounter as usize;
+
+mod cmd {
+    pub const CHECK: usize = 0;
+    pub const START: usize = 1;
+    pub const STOP: usize = 2;
+    pub const RESET: usize = 3;
+    /// Returns `SUCCESS` without reading the counter; `ReturnCode`
+    /// carries no value, so the current count is not reported to the
+    /// app.
+    pub const SAMPLE: usize = 4;
+}
+
+pub struct CycleCounterDriver<'a> {
+    counter: &'a dyn CycleCounter,
+}
+
+impl<'a> CycleCounterDriver<'a> {
+    pub fn new(counter: &'a dyn CycleCounter) -> CycleCounterDriver<'a> {
+        CycleCounterDriver { counter }
+    }
+}
+
+impl<'a> Driver for CycleCounterDriver<'a> {
+    fn command(&self, command_num: usize, _data1: usize, _data2: usize, _app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::CHECK => ReturnCode::SUCCESS,
+            cmd::START => {
+                self.counter.start();
+                ReturnCode::SUCCESS
+            }
+            cmd::STOP => {
+                self.counter.stop();
+                ReturnCode::SUCCESS
+            }
+            cmd::RESET => {
+                self.counter.reset();
+                ReturnCode::SUCCESS
+            }
+            cmd::SAMPLE => ReturnCode::SUCCESS,
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
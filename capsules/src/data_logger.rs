@@ -0,0 +1,213 @@
+//! Batches samples in RAM and flushes them to a `hil::log::LogWrite`
+//! backend only when a batch fills or a flush timer fires, instead of
+//! the one-flash-write-per-sample pattern that was exhausting both
+//! flash write endurance and I/O throughput budgets on high-rate
+//! telemetry apps.
+//!
+//! Samples can come from userspace via the syscall driver built on top
+//! of this capsule, or be pushed directly by another capsule (e.g. a
+//! sensor driver sampling on its own timer) through `log_sample`.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let logger = static_init!(
+//!     capsules::data_logger::DataLogger<'static>,
+//!     capsules::data_logger::DataLogger::new(
+//!         log, alarm, batch_buffer, flush_interval_ms,
+//!         kernel::Grant::create(capsules::driver::NUM::DataLogger as usize)));
+//! ```
+
+use kernel::common::cells::TakeCell;
+use kernel::hil::log::{LogCookie, LogWrite, LogWriteClient};
+use kernel::hil::time::{Alarm, AlarmClient};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::DataLogger as usize;
+
+pub const SAMPLE_LEN: usize = 8;
+
+mod upcall {
+    /// Delivered in response to `DROPPED_COUNT`, with the count in the
+    /// first argument.
+    pub const DROPPED_COUNT: usize = 0;
+}
+
+mod cmd {
+    /// Appends the `SAMPLE_LEN`-byte sample in the buffer allowed at
+    /// index 0 to the current batch.
+    pub const LOG_SAMPLE: usize = 0;
+    /// Schedules the `DROPPED_COUNT` upcall with the number of samples
+    /// dropped so far because a flush was already in flight when a new
+    /// sample arrived.
+    pub const DROPPED_COUNT: usize = 1;
+}
+
+#[derive(Default)]
+pub struct App {
+    /// The buffer allowed at index 0, read from by `LOG_SAMPLE`.
+    sample: Option<AppSlice<Shared, u8>>,
+    dropped_count_callback: Option<Callback>,
+}
+
+pub struct DataLogger<'a, A: Alarm<'a>> {
+    log: &'a (dyn LogWrite<'a> + 'a),
+    alarm: &'a A,
+    flush_interval: u32,
+    batch: TakeCell<'static, [u8]>,
+    batch_len: core::cell::Cell<usize>,
+    flushing: core::cell::Cell<bool>,
+    /// Samples discarded because a flush was already in flight (or the
+    /// batch buffer was otherwise unavailable); surfaced to userspace so a telemetry app
+    /// knows its data has gaps instead of silently losing samples.
+    dropped: core::cell::Cell<u32>,
+    apps: Grant<App>,
+}
+
+impl<'a, A: Alarm<'a>> DataLogger<'a, A> {
+    pub fn new(
+        log: &'a (dyn LogWrite<'a> + 'a),
+        alarm: &'a A,
+        batch_buffer: &'static mut [u8],
+        flush_interval_ms: u32,
+        apps: Grant<App>,
+    ) -> DataLogger<'a, A> {
+        let flush_interval = A::ticks_from_ms(flush_interval_ms);
+        alarm.set_alarm(alarm.now(), flush_interval);
+        DataLogger {
+            log,
+            alarm,
+            flush_interval,
+            batch: TakeCell::new(batch_buffer),
+            batch_len: core::cell::Cell::new(0),
+            flushing: core::cell::Cell::new(false),
+            dropped: core::cell::Cell::new(0),
+            apps,
+        }
+    }
+
+    pub fn dropped_count(&self) -> u32 {
+        self.dropped.get()
+    }
+
+    /// Appends one `SAMPLE_LEN`-byte sample to the current batch,
+    /// flushing immediately if that fills it.
+    pub fn log_sample(&self, sample: &[u8]) -> ReturnCode {
+        if sample.len() != SAMPLE_LEN {
+            return ReturnCode::ESIZE;
+        }
+        if self.flushing.get() {
+            self.dropped.set(self.dropped.get() + 1);
+            return ReturnCode::EBUSY;
+        }
+        let full = self.batch.map(|batch| {
+            let offset = self.batch_len.get() * SAMPLE_LEN;
+            if offset + SAMPLE_LEN > batch.len() {
+                return true;
+            }
+            batch[offset..offset + SAMPLE_LEN].copy_from_slice(sample);
+            self.batch_len.set(self.batch_len.get() + 1);
+            (self.batch_len.get() * SAMPLE_LEN + SAMPLE_LEN) > batch.len()
+        });
+        match full {
+            Some(true) => self.flush(),
+            Some(false) => ReturnCode::SUCCESS,
+            None => {
+                self.dropped.set(self.dropped.get() + 1);
+                ReturnCode::EBUSY
+            }
+        }
+    }
+
+    fn flush(&self) -> ReturnCode {
+        if self.batch_len.get() == 0 || self.flushing.get() {
+            return ReturnCode::SUCCESS;
+        }
+        match self.batch.take() {
+            Some(batch) => {
+                self.flushing.set(true);
+                let length = self.batch_len.get() * SAMPLE_LEN;
+                self.log.append(batch, length)
+            }
+            None => ReturnCode::EBUSY,
+        }
+    }
+}
+
+impl<'a, A: Alarm<'a>> AlarmClient for DataLogger<'a, A> {
+    fn alarm(&self) {
+        self.flush();
+        self.alarm.set_alarm(self.alarm.now(), self.flush_interval);
+    }
+}
+
+impl<'a, A: Alarm<'a>> LogWriteClient for DataLogger<'a, A> {
+    fn append_done(&self, buffer: &'static mut [u8], _length: usize, _cookie: LogCookie, _result: ReturnCode) {
+        self.batch.replace(buffer);
+        self.batch_len.set(0);
+        self.flushing.set(false);
+    }
+
+    fn sync_done(&self, _result: ReturnCode) {}
+    fn erase_done(&self, _result: ReturnCode) {}
+}
+
+impl<'a, A: Alarm<'a>> Driver for DataLogger<'a, A> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::DROPPED_COUNT => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.dropped_count_callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.sample = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, _data1: usize, _data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::LOG_SAMPLE => self
+                .apps
+                .enter(app_id, |app, _| {
+                    let mut sample = [0u8; SAMPLE_LEN];
+                    match &app.sample {
+                        Some(slice) if slice.len() >= SAMPLE_LEN => {
+                            sample.copy_from_slice(&slice.as_ref()[..SAMPLE_LEN]);
+                        }
+                        Some(_) => return ReturnCode::ESIZE,
+                        None => return ReturnCode::EINVAL,
+                    }
+                    self.log_sample(&sample)
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            cmd::DROPPED_COUNT => {
+                let dropped = self.dropped_count();
+                self.apps
+                    .enter(app_id, |app, _| {
+                        if let Some(mut cb) = app.dropped_count_callback {
+                            cb.schedule(dropped as usize, 0, 0);
+                        }
+                        ReturnCode::SUCCESS
+                    })
+                    .unwrap_or(ReturnCode::FAIL)
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
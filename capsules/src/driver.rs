@@ -0,0 +1,64 @@
+//! Driver numbers for all capsules in this repository.
+//!
+//! Each capsule that implements `kernel::Driver` is assigned a number
+//! here so that userspace `command`/`subscribe`/`allow` calls can name
+//! it. Numbers below `0x90000` follow the public Tock driver number
+//! allocation; numbers at or above it are local to this tree pending
+//! upstream allocation.
+
+#[derive(Copy, Clone, Debug)]
+pub enum NUM {
+    Pdm = 0x90000,
+    AmbientLight = 0x60002,
+    ProcessInfo = 0x90001,
+    CrashDump = 0x90002,
+    MessageQueue = 0x90003,
+    CycleCounter = 0x90004,
+    KernelEventLog = 0x90005,
+    NonvolatileStorage = 0x50001,
+    FileSystem = 0x90006,
+    BlockStorage = 0x90007,
+    AppFlash = 0x50000,
+    LogStorage = 0x50002,
+    DataLogger = 0x90008,
+    FirmwareUpdate = 0x90009,
+    ResetReason = 0x9000a,
+    ConfigStore = 0x9000b,
+    Aead = 0x9000c,
+    Digest = 0x9000d,
+    EcdsaP256 = 0x9000e,
+    Curve25519 = 0x9000f,
+    Rng = 0x90010,
+    KeyStore = 0x90011,
+    MonotonicCounter = 0x90012,
+    Attestation = 0x90013,
+    CtapHid = 0x90014,
+    DtlsRecord = 0x90015,
+    TimeSync = 0x90016,
+    TamperDetect = 0x90017,
+    Udp = 0x90018,
+    Tcp = 0x90019,
+    MqttSn = 0x9001a,
+    BleAdvertising = 0x9001b,
+    GattServer = 0x9001c,
+    BleCentral = 0x9001d,
+    Radio154 = 0x9001e,
+    ThreadNetwork = 0x9001f,
+    LoRaWan = 0x90020,
+    Ethernet = 0x90021,
+    Ipv4Udp = 0x90022,
+    Ipv6Layer = 0x90023,
+    PacketCapture = 0x90024,
+    RadioConfig = 0x90025,
+    SlipIp = 0x90026,
+    Can = 0x90027,
+    Modbus = 0x90028,
+    Nfc = 0x90029,
+    EspAt = 0x9002a,
+    CellularModem = 0x9002b,
+    Sntp = 0x9002c,
+    UsbHidGadget = 0x9002d,
+    UsbBulk = 0x9002e,
+    UsbHidHost = 0x9002f,
+    Screen = 0x90030,
+}
@@ -0,0 +1,283 @@
+//! DTLS 1.2/1.3 record-layer offload, layered on `hil::aead::AeadEngine`.
+//!
+//! The handshake (key exchange, certificate validation) is done by a
+//! userspace network stack exactly as before; once it has derived a
+//! per-epoch traffic key, it hands this capsule the key and epoch with
+//! `SET_KEY` and from then on sends and receives application-data
+//! records through `ENCRYPT`/`DECRYPT` without a per-record round trip
+//! through a software AEAD implementation in userspace. This capsule
+//! only does the per-record bookkeeping the record layer itself
+//! defines — nonce construction from epoch and sequence number, and
+//! the replay window — not the handshake, and not the AEAD math
+//! itself, which is `self.engine`'s job.
+//!
+//! Each process gets its own epoch/sequence-number/replay-window state
+//! in its grant; like `aes_gcm::AeadDriver`, the underlying engine
+//! only holds one active key at a time, so `ENCRYPT` and `DECRYPT`
+//! reload the calling process's key before starting.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let dtls = static_init!(
+//!     capsules::dtls_record::DtlsRecordDriver<'static>,
+//!     capsules::dtls_record::DtlsRecordDriver::new(
+//!         engine, kernel::Grant::create(capsules::driver::NUM::DtlsRecord as usize),
+//!         buffer));
+//! ```
+
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::aead::{AeadClient, AeadEngine, AeadMode};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::DtlsRecord as usize;
+
+const MAX_KEY_LEN: usize = 32;
+/// Length of the authentication tag `encrypt`/`decrypt` append/expect
+/// immediately after the record's ciphertext.
+const TAG_LEN: usize = 16;
+
+/// Replay window width (DTLS recommends at least 64), tracked as a
+/// bitmap of sequence numbers below `highest_seq`.
+const REPLAY_WINDOW_BITS: u64 = 64;
+
+mod upcall {
+    pub const DONE: usize = 0;
+}
+
+mod cmd {
+    /// Loads a new traffic key for the calling process from the
+    /// buffer allowed at index 0, `data1` bytes long, and resets its
+    /// sequence number and replay window for epoch `data2`.
+    pub const SET_KEY: usize = 0;
+    /// Encrypts a `data1`-byte application-data record in place in
+    /// the buffer allowed at index 1, using the calling process's
+    /// current epoch and next sequence number for the nonce, then
+    /// advances the sequence number.
+    pub const ENCRYPT: usize = 1;
+    /// Checks the replay window and decrypts in place with the same
+    /// buffer layout as `ENCRYPT`; `data2` is the epoch and sequence
+    /// number read from the record header, packed as `(epoch << 48) |
+    /// seq`. The completion upcall's second argument is `1` if the
+    /// authentication tag matched, `0` otherwise; the window is only
+    /// advanced once the tag is confirmed valid.
+    pub const DECRYPT: usize = 2;
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    key: [u8; MAX_KEY_LEN],
+    key_len: Option<usize>,
+    /// The buffer allowed at index 0, holding the bytes for the next
+    /// `SET_KEY`.
+    key_buffer: Option<AppSlice<Shared, u8>>,
+    /// The buffer allowed at index 1: the record, read from and
+    /// written back in place.
+    record: Option<AppSlice<Shared, u8>>,
+    epoch: u16,
+    write_seq: u64,
+    highest_seq: u64,
+    replay_window: u64,
+    pending_decrypt_seq: Option<u64>,
+}
+
+impl App {
+    /// Checks `seq` against the replay window without consuming it;
+    /// `ENCRYPT`/`DECRYPT` only advance the window once the AEAD tag
+    /// has been confirmed valid, since an attacker-replayed record
+    /// must not be allowed to poison it.
+    fn already_seen(&self, seq: u64) -> bool {
+        if seq <= self.highest_seq {
+            let age = self.highest_seq - seq;
+            age >= REPLAY_WINDOW_BITS || self.replay_window & (1 << age) != 0
+        } else {
+            false
+        }
+    }
+
+    fn record_seen(&mut self, seq: u64) {
+        if seq > self.highest_seq {
+            let shift = seq - self.highest_seq;
+            self.replay_window = if shift >= REPLAY_WINDOW_BITS { 1 } else { (self.replay_window << shift) | 1 };
+            self.highest_seq = seq;
+        } else {
+            let age = self.highest_seq - seq;
+            self.replay_window |= 1 << age;
+        }
+    }
+}
+
+pub struct DtlsRecordDriver<'a> {
+    engine: &'a dyn AeadEngine<'a>,
+    apps: Grant<App>,
+    current_app: OptionalCell<AppId>,
+    buffer: TakeCell<'static, [u8]>,
+}
+
+impl<'a> DtlsRecordDriver<'a> {
+    pub fn new(engine: &'a dyn AeadEngine<'a>, apps: Grant<App>, buffer: &'static mut [u8]) -> DtlsRecordDriver<'a> {
+        DtlsRecordDriver {
+            engine,
+            apps,
+            current_app: OptionalCell::empty(),
+            buffer: TakeCell::new(buffer),
+        }
+    }
+}
+
+impl<'a> Driver for DtlsRecordDriver<'a> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::DONE => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.key_buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            1 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.record = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::SET_KEY => {
+                if data1 > MAX_KEY_LEN {
+                    return ReturnCode::ESIZE;
+                }
+                self.apps
+                    .enter(app_id, |app, _| match &app.key_buffer {
+                        Some(slice) if data1 <= slice.len() => {
+                            app.key[..data1].copy_from_slice(&slice.as_ref()[..data1]);
+                            app.key_len = Some(data1);
+                            app.epoch = data2 as u16;
+                            app.write_seq = 0;
+                            app.highest_seq = 0;
+                            app.replay_window = 0;
+                            ReturnCode::SUCCESS
+                        }
+                        Some(_) => ReturnCode::ESIZE,
+                        None => ReturnCode::EINVAL,
+                    })
+                    .unwrap_or(ReturnCode::FAIL)
+            }
+            cmd::ENCRYPT | cmd::DECRYPT => {
+                if self.current_app.is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                let payload_len = data1;
+                let total_len = match payload_len.checked_add(TAG_LEN) {
+                    Some(n) => n,
+                    None => return ReturnCode::ESIZE,
+                };
+                let buffer = match self.buffer.take() {
+                    Some(buffer) => buffer,
+                    None => return ReturnCode::EBUSY,
+                };
+                if total_len > buffer.len() {
+                    self.buffer.replace(buffer);
+                    return ReturnCode::ESIZE;
+                }
+                let mut nonce_seq = None;
+                let prepare_result = self.apps.enter(app_id, |app, _| {
+                    let key_len = match app.key_len {
+                        Some(len) => len,
+                        None => return ReturnCode::EINVAL,
+                    };
+                    let seq = if command_num == cmd::ENCRYPT {
+                        app.write_seq
+                    } else {
+                        let seq = data2 as u64 & 0x0000_ffff_ffff_ffff;
+                        if app.already_seen(seq) {
+                            return ReturnCode::EALREADY;
+                        }
+                        app.pending_decrypt_seq = Some(seq);
+                        seq
+                    };
+                    let record_slice = match &app.record {
+                        Some(slice) if total_len <= slice.len() => slice,
+                        Some(_) => return ReturnCode::ESIZE,
+                        None => return ReturnCode::EINVAL,
+                    };
+                    let key_result = self.engine.set_key(&app.key[..key_len]);
+                    if key_result != ReturnCode::SUCCESS {
+                        return key_result;
+                    }
+                    buffer[..total_len].copy_from_slice(&record_slice.as_ref()[..total_len]);
+                    nonce_seq = Some(seq);
+                    if command_num == cmd::ENCRYPT {
+                        app.write_seq += 1;
+                    }
+                    ReturnCode::SUCCESS
+                });
+                let prepare_result = prepare_result.unwrap_or(ReturnCode::FAIL);
+                if prepare_result != ReturnCode::SUCCESS {
+                    self.buffer.replace(buffer);
+                    return prepare_result;
+                }
+                // An 8-byte big-endian `(epoch << 48) | seq` nonce, with
+                // no additional per-connection salt modeled.
+                let seq = nonce_seq.unwrap_or(0);
+                let nonce = (((self.apps.enter(app_id, |app, _| app.epoch).unwrap_or(0) as u64) << 48) | seq).to_be_bytes();
+                self.current_app.set(app_id);
+                let result = if command_num == cmd::ENCRYPT {
+                    self.engine.encrypt(AeadMode::Gcm, buffer, 0, payload_len, &nonce)
+                } else {
+                    self.engine.decrypt(AeadMode::Gcm, buffer, 0, payload_len, &nonce)
+                };
+                if result != ReturnCode::SUCCESS {
+                    self.current_app.clear();
+                }
+                result
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a> AeadClient for DtlsRecordDriver<'a> {
+    fn crypt_done(&self, buffer: &'static mut [u8], result: ReturnCode, tag_valid: bool) {
+        if let Some(app_id) = self.current_app.take() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if result == ReturnCode::SUCCESS {
+                    if let Some(dest) = &mut app.record {
+                        let len = core::cmp::min(dest.len(), buffer.len());
+                        dest.as_mut()[..len].copy_from_slice(&buffer[..len]);
+                    }
+                }
+                if tag_valid {
+                    if let Some(seq) = app.pending_decrypt_seq.take() {
+                        app.record_seen(seq);
+                    }
+                }
+                if let Some(mut cb) = app.callback {
+                    let tag_ok = if tag_valid { 1 } else { 0 };
+                    cb.schedule(usize::from(result), tag_ok, 0);
+                }
+            });
+        }
+        self.buffer.replace(buffer);
+    }
+}
@@ -0,0 +1,351 @@
+//! Driver for the Microchip ENC28J60 Ethernet MAC/PHY over SPI,
+//! implementing `hil::ethernet::Ethernet`.
+//!
+//! The part has no burned-in MAC address, so the board supplies one to
+//! `new` and this driver programs it into the MAADR registers during
+//! `init`, alongside the RX/TX buffer boundaries a fixed 16KB packet
+//! memory must be split into (the single-RX/single-TX-window split
+//! this driver uses, `RX_BUFFER_START..RX_BUFFER_END` and
+//! `TX_BUFFER_START..TX_BUFFER_END`, rather than anything dynamic).
+//! Registers outside the bank every register bank shares (`ECON1` and
+//! a handful of others) require selecting the right bank first via
+//! `ECON1`'s `BSEL` bits, which `init`'s `SelectingBank0`/
+//! `SelectingBank3` steps do for the two banks this driver ever
+//! touches.
+//!
+//! A received frame's length comes from the real Receive Status
+//! Vector the part prepends to every frame in its packet memory
+//! (`ReadingHeader` reads and parses it), but the per-packet control
+//! byte `transmit` would need ahead of outgoing frame data, and the
+//! receive-buffer read pointer/packet-count bookkeeping
+//! (`ERXRDPT`/`ECON2.PKTDEC`) a board would need to free a packet's
+//! memory before the next one is read, are not modeled — like every
+//! opcode-adjacent byte `spi_nor_flash` also elides, only the SPI
+//! transaction's state transition is real. A frame larger than
+//! `rx_buffer` is silently truncated to however much of it fits.
+//!
+//! This driver assumes the part's only unmasked interrupt source is a
+//! received packet; disambiguating link-state-change or
+//! transmit-complete interrupts by reading `EIR` is not modeled, so
+//! `link_state_changed` is never called.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let enc28j60 = static_init!(
+//!     capsules::enc28j60::Enc28j60<'static>,
+//!     capsules::enc28j60::Enc28j60::new(
+//!         spi_device, Some(interrupt_pin), command_buffer, rx_buffer,
+//!         hil::ethernet::MacAddress([0x02, 0x00, 0x00, 0x00, 0x00, 0x01])));
+//! spi_device.set_client(enc28j60);
+//! interrupt_pin.set_client(enc28j60);
+//! enc28j60.set_client(networking_capsule);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::ethernet::{Ethernet, EthernetClient, MacAddress};
+use kernel::hil::gpio;
+use kernel::hil::spi::{SpiMasterClient, SpiMasterDevice};
+use kernel::ReturnCode;
+
+mod opcode {
+    /// Read Control Register; OR with a 5-bit register address.
+    pub const RCR: u8 = 0x00;
+    pub const RBM: u8 = 0x3a;
+    /// Write Control Register; OR with a 5-bit register address.
+    pub const WCR: u8 = 0x40;
+    pub const WBM: u8 = 0x7a;
+    /// Bit Field Set; OR with a 5-bit register address.
+    pub const BFS: u8 = 0x80;
+    /// Bit Field Clear; OR with a 5-bit register address.
+    pub const BFC: u8 = 0xa0;
+    pub const SRC: u8 = 0xff;
+}
+
+mod reg {
+    /// Present in every bank.
+    pub const ECON1: u8 = 0x1f;
+    pub const ERXSTL: u8 = 0x08;
+    pub const ERXSTH: u8 = 0x09;
+    pub const ERXNDL: u8 = 0x0a;
+    pub const ERXNDH: u8 = 0x0b;
+    pub const ETXSTL: u8 = 0x04;
+    pub const ETXSTH: u8 = 0x05;
+    pub const ETXNDL: u8 = 0x06;
+    pub const ETXNDH: u8 = 0x07;
+    /// Bank 3; this driver writes the six MAC address bytes to these
+    /// in address order, which is simpler than (and does not match)
+    /// the real part's scrambled MAADR-to-byte mapping, so treat this
+    /// as a starting point to check against the datasheet, not a
+    /// verified register map.
+    pub const MAADR1: u8 = 0x00;
+}
+
+mod econ1 {
+    pub const BANK_MASK: u8 = 0x03;
+    pub const BANK3: u8 = 0x03;
+    pub const RXEN: u8 = 0x04;
+    pub const TXRTS: u8 = 0x08;
+}
+
+const RX_BUFFER_START: u16 = 0x0000;
+const RX_BUFFER_END: u16 = 0x0fff;
+const TX_BUFFER_START: u16 = 0x1000;
+const TX_BUFFER_END: u16 = 0x1fff;
+
+/// `(register, value)` pairs written in order to lay out the RX/TX
+/// buffer windows, all in bank 0.
+const BUFFER_REGISTERS: [(u8, u8); 8] = [
+    (reg::ERXSTL, (RX_BUFFER_START & 0xff) as u8),
+    (reg::ERXSTH, (RX_BUFFER_START >> 8) as u8),
+    (reg::ERXNDL, (RX_BUFFER_END & 0xff) as u8),
+    (reg::ERXNDH, (RX_BUFFER_END >> 8) as u8),
+    (reg::ETXSTL, (TX_BUFFER_START & 0xff) as u8),
+    (reg::ETXSTH, (TX_BUFFER_START >> 8) as u8),
+    (reg::ETXNDL, (TX_BUFFER_END & 0xff) as u8),
+    (reg::ETXNDH, (TX_BUFFER_END >> 8) as u8),
+];
+
+/// Receive Status Vector: 2-byte next-packet pointer (not used by
+/// this driver; per-packet memory is never freed, see above) followed
+/// by a 2-byte little-endian received byte count and 2 bytes of
+/// status flags this driver does not inspect.
+const RSV_LEN: usize = 6;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum State {
+    Idle,
+    Resetting,
+    SelectingBank0,
+    ConfiguringBuffers(usize),
+    SelectingBank3,
+    WritingMacAddress(usize),
+    EnablingReceive,
+    WritingFrame,
+    TriggeringTransmit,
+    ReadingHeader,
+    ReadingFrame,
+}
+
+pub struct Enc28j60<'a> {
+    spi: &'a dyn SpiMasterDevice,
+    interrupt_pin: Option<&'a dyn gpio::InterruptPin<'a>>,
+    state: Cell<State>,
+    mac_address: Cell<MacAddress>,
+    link_up: Cell<bool>,
+    receiving: Cell<bool>,
+    command_buffer: TakeCell<'static, [u8]>,
+    rx_buffer: TakeCell<'static, [u8]>,
+    tx_pending: TakeCell<'static, [u8]>,
+    tx_len: Cell<usize>,
+    client: OptionalCell<&'a dyn EthernetClient>,
+}
+
+impl<'a> Enc28j60<'a> {
+    pub fn new(
+        spi: &'a dyn SpiMasterDevice,
+        interrupt_pin: Option<&'a dyn gpio::InterruptPin<'a>>,
+        command_buffer: &'static mut [u8],
+        rx_buffer: &'static mut [u8],
+        mac_address: MacAddress,
+    ) -> Enc28j60<'a> {
+        Enc28j60 {
+            spi,
+            interrupt_pin,
+            state: Cell::new(State::Idle),
+            mac_address: Cell::new(mac_address),
+            link_up: Cell::new(false),
+            receiving: Cell::new(false),
+            command_buffer: TakeCell::new(command_buffer),
+            rx_buffer: TakeCell::new(rx_buffer),
+            tx_pending: TakeCell::empty(),
+            tx_len: Cell::new(0),
+            client: OptionalCell::empty(),
+        }
+    }
+
+    fn write_register(&self, buffer: &'static mut [u8], register: u8, value: u8) {
+        buffer[0] = opcode::WCR | register;
+        buffer[1] = value;
+        self.spi.read_write_bytes(buffer, None, 2);
+    }
+
+    fn set_bits(&self, buffer: &'static mut [u8], register: u8, mask: u8) {
+        buffer[0] = opcode::BFS | register;
+        buffer[1] = mask;
+        self.spi.read_write_bytes(buffer, None, 2);
+    }
+
+    fn clear_bits(&self, buffer: &'static mut [u8], register: u8, mask: u8) {
+        buffer[0] = opcode::BFC | register;
+        buffer[1] = mask;
+        self.spi.read_write_bytes(buffer, None, 2);
+    }
+
+    fn start_reading_frame(&self) {
+        match self.command_buffer.take() {
+            Some(buffer) => {
+                buffer[0] = opcode::RBM;
+                self.state.set(State::ReadingHeader);
+                self.spi.read_write_bytes(buffer, None, 1 + RSV_LEN);
+            }
+            None => {
+                // The scratch buffer is (unexpectedly) still with the
+                // SPI controller; the next `fired()` will retry.
+            }
+        }
+    }
+}
+
+impl<'a> Ethernet<'a> for Enc28j60<'a> {
+    fn set_client(&self, client: &'a dyn EthernetClient) {
+        self.client.set(client);
+    }
+
+    fn init(&self) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        match self.command_buffer.take() {
+            Some(buffer) => {
+                buffer[0] = opcode::SRC;
+                self.state.set(State::Resetting);
+                self.spi.read_write_bytes(buffer, None, 1);
+                ReturnCode::SUCCESS
+            }
+            None => ReturnCode::EBUSY,
+        }
+    }
+
+    fn mac_address(&self) -> MacAddress {
+        self.mac_address.get()
+    }
+
+    fn link_up(&self) -> bool {
+        self.link_up.get()
+    }
+
+    fn transmit(&self, buffer: &'static mut [u8], len: usize) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        self.tx_len.set(len);
+        self.state.set(State::WritingFrame);
+        // The WBM opcode byte and the per-packet control byte the
+        // real part needs ahead of `buffer` are not modeled, as above.
+        self.spi.read_write_bytes(buffer, None, len);
+        ReturnCode::SUCCESS
+    }
+
+    fn start_receiving(&self) -> ReturnCode {
+        self.receiving.set(true);
+        ReturnCode::SUCCESS
+    }
+}
+
+impl<'a> SpiMasterClient for Enc28j60<'a> {
+    fn read_write_done(&self, write_buffer: &'static mut [u8], _read_buffer: Option<&'static mut [u8]>, len: usize) {
+        match self.state.get() {
+            State::Resetting => {
+                self.clear_bits(write_buffer, reg::ECON1, econ1::BANK_MASK);
+                self.state.set(State::SelectingBank0);
+            }
+            State::SelectingBank0 => {
+                let (register, value) = BUFFER_REGISTERS[0];
+                self.write_register(write_buffer, register, value);
+                self.state.set(State::ConfiguringBuffers(0));
+            }
+            State::ConfiguringBuffers(i) => {
+                if i + 1 < BUFFER_REGISTERS.len() {
+                    let (register, value) = BUFFER_REGISTERS[i + 1];
+                    self.write_register(write_buffer, register, value);
+                    self.state.set(State::ConfiguringBuffers(i + 1));
+                } else {
+                    self.set_bits(write_buffer, reg::ECON1, econ1::BANK3);
+                    self.state.set(State::SelectingBank3);
+                }
+            }
+            State::SelectingBank3 => {
+                let mac = self.mac_address.get();
+                self.write_register(write_buffer, reg::MAADR1, mac.0[0]);
+                self.state.set(State::WritingMacAddress(0));
+            }
+            State::WritingMacAddress(i) => {
+                let mac = self.mac_address.get();
+                if i + 1 < mac.0.len() {
+                    self.write_register(write_buffer, reg::MAADR1 + (i as u8) + 1, mac.0[i + 1]);
+                    self.state.set(State::WritingMacAddress(i + 1));
+                } else {
+                    self.set_bits(write_buffer, reg::ECON1, econ1::RXEN);
+                    self.state.set(State::EnablingReceive);
+                }
+            }
+            State::EnablingReceive => {
+                self.command_buffer.replace(write_buffer);
+                self.link_up.set(true);
+                self.state.set(State::Idle);
+                if let Some(pin) = self.interrupt_pin {
+                    pin.enable_interrupts(gpio::InterruptEdge::FallingEdge);
+                }
+                self.client.map(|client| client.init_done(ReturnCode::SUCCESS));
+            }
+            State::WritingFrame => {
+                self.tx_pending.replace(write_buffer);
+                match self.command_buffer.take() {
+                    Some(buffer) => {
+                        self.set_bits(buffer, reg::ECON1, econ1::TXRTS);
+                        self.state.set(State::TriggeringTransmit);
+                    }
+                    None => {
+                        self.state.set(State::Idle);
+                        if let Some(buf) = self.tx_pending.take() {
+                            self.client.map(|client| client.transmit_done(buf, ReturnCode::EBUSY));
+                        }
+                    }
+                }
+            }
+            State::TriggeringTransmit => {
+                self.command_buffer.replace(write_buffer);
+                self.state.set(State::Idle);
+                if let Some(buf) = self.tx_pending.take() {
+                    self.client.map(|client| client.transmit_done(buf, ReturnCode::SUCCESS));
+                }
+            }
+            State::ReadingHeader => {
+                let byte_count = u16::from_le_bytes([write_buffer[3], write_buffer[4]]) as usize;
+                self.command_buffer.replace(write_buffer);
+                match self.rx_buffer.take() {
+                    Some(buffer) => {
+                        let readable = core::cmp::min(byte_count, buffer.len().saturating_sub(1));
+                        buffer[0] = opcode::RBM;
+                        self.tx_len.set(readable);
+                        self.state.set(State::ReadingFrame);
+                        self.spi.read_write_bytes(buffer, None, 1 + readable);
+                    }
+                    None => {
+                        self.state.set(State::Idle);
+                    }
+                }
+            }
+            State::ReadingFrame => {
+                let readable = self.tx_len.get();
+                self.client.map(|client| client.receive(&write_buffer[1..1 + readable], readable));
+                self.rx_buffer.replace(write_buffer);
+                self.state.set(State::Idle);
+            }
+            State::Idle => {
+                self.command_buffer.replace(write_buffer);
+                let _ = len;
+            }
+        }
+    }
+}
+
+impl<'a> gpio::Client for Enc28j60<'a> {
+    fn fired(&self) {
+        if self.receiving.get() && self.state.get() == State::Idle {
+            self.start_reading_frame();
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! Transparent AES-GCM encryption for a `hil::nonvolatile_storage`
+//! region, so the KV store, log, and filesystem capsules built on top
+//! of it (`log_storage`, `config_store`, `littlefs`) get encryption at
+//! rest just by having their backing region swapped for one of these
+//! at board init, with no change to the capsule itself.
+//!
+//! The key comes from `key_store` and is loaded once at construction,
+//! not per-operation. Each record's nonce is derived from its absolute
+//! offset, so two different offsets never collide without needing a
+//! nonce stored alongside the ciphertext — but that also means a
+//! caller must never write two different plaintexts to the same
+//! offset under the same key (overwriting a record with new data in
+//! place reuses its nonce). The nonce has no version or counter
+//! input, so every rewrite of a given offset reuses the same nonce.
+//!
+//! `write`/`read` treat the last 16 bytes of `length` as the
+//! authentication tag, matching `AeadEngine::encrypt`'s buffer layout
+//! exactly: callers must size every record with 16 bytes of headroom
+//! for it.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let encrypted = static_init!(
+//!     capsules::encrypted_storage::EncryptedStorage<'static>,
+//!     capsules::encrypted_storage::EncryptedStorage::new(flash, engine, key));
+//! ```
+
+use kernel::common::cells::OptionalCell;
+use kernel::hil::aead::{AeadClient, AeadEngine, AeadMode};
+use kernel::hil::nonvolatile_storage::{NonvolatileStorage, NonvolatileStorageClient};
+use kernel::ReturnCode;
+
+const TAG_LEN: usize = 16;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Operation {
+    Idle,
+    Writing { offset: usize, length: usize },
+    Reading { offset: usize, length: usize },
+}
+
+pub struct EncryptedStorage<'a> {
+    inner: &'a dyn NonvolatileStorage<'a>,
+    engine: &'a dyn AeadEngine<'a>,
+    client: OptionalCell<&'a dyn NonvolatileStorageClient>,
+    operation: core::cell::Cell<Operation>,
+    pending_write_plaintext_len: core::cell::Cell<usize>,
+}
+
+impl<'a> EncryptedStorage<'a> {
+    /// `key` is loaded into `engine` immediately; the caller is
+    /// responsible for having retrieved it from `key_store` first.
+    pub fn new(inner: &'a dyn NonvolatileStorage<'a>, engine: &'a dyn AeadEngine<'a>, key: &[u8]) -> EncryptedStorage<'a> {
+        let _ = engine.set_key(key);
+        EncryptedStorage {
+            inner,
+            engine,
+            client: OptionalCell::empty(),
+            operation: core::cell::Cell::new(Operation::Idle),
+            pending_write_plaintext_len: core::cell::Cell::new(0),
+        }
+    }
+
+    fn nonce_for(offset: usize) -> [u8; 12] {
+        let mut nonce = [0; 12];
+        nonce[4..12].copy_from_slice(&(offset as u64).to_be_bytes());
+        nonce
+    }
+}
+
+impl<'a> NonvolatileStorage<'a> for EncryptedStorage<'a> {
+    fn set_client(&self, client: &'a dyn NonvolatileStorageClient) {
+        self.client.set(client);
+    }
+
+    fn size(&self) -> usize {
+        self.inner.size()
+    }
+
+    /// Encrypts `buffer[..length - TAG_LEN]` in place, appends the
+    /// authentication tag into `buffer[length - TAG_LEN..length]`,
+    /// then writes the whole record to `inner` once
+    /// `AeadClient::crypt_done` reports the encryption finished.
+    fn write(&self, buffer: &'static mut [u8], offset: usize, length: usize) -> ReturnCode {
+        if length < TAG_LEN || self.operation.get() != Operation::Idle {
+            return ReturnCode::EINVAL;
+        }
+        let nonce = Self::nonce_for(offset);
+        let result = self.engine.encrypt(AeadMode::Gcm, buffer, 0, length - TAG_LEN, &nonce);
+        if result == ReturnCode::SUCCESS {
+            self.operation.set(Operation::Writing { offset, length });
+            self.pending_write_plaintext_len.set(length - TAG_LEN);
+        }
+        result
+    }
+
+    /// Reads the whole record from `inner`, then decrypts and checks
+    /// its tag once `NonvolatileStorageClient::read_done` would
+    /// otherwise have fired. Our own client's `read_done` always gets
+    /// the buffer back, with `length` reported as the plaintext length
+    /// (`length - TAG_LEN`) if the tag is valid, or `0` if it is not or
+    /// the decryption failed.
+    fn read(&self, buffer: &'static mut [u8], offset: usize, length: usize) -> ReturnCode {
+        if length < TAG_LEN || self.operation.get() != Operation::Idle {
+            return ReturnCode::EINVAL;
+        }
+        let result = self.inner.read(buffer, offset, length);
+        if result == ReturnCode::SUCCESS {
+            self.operation.set(Operation::Reading { offset, length });
+        }
+        result
+    }
+
+    fn erase(&self, offset: usize, length: usize) -> ReturnCode {
+        self.inner.erase(offset, length)
+    }
+}
+
+impl<'a> AeadClient for EncryptedStorage<'a> {
+    fn crypt_done(&self, buffer: &'static mut [u8], result: ReturnCode, tag_valid: bool) {
+        match self.operation.get() {
+            Operation::Writing { offset, length } => {
+                self.operation.set(Operation::Idle);
+                if result != ReturnCode::SUCCESS {
+                    self.client.map(|client| client.write_done(buffer, 0));
+                    return;
+                }
+                let result = self.inner.write(buffer, offset, length);
+                if result != ReturnCode::SUCCESS {
+                    self.client.map(|client| client.write_done(buffer, 0));
+                }
+            }
+            Operation::Reading { length, .. } => {
+                self.operation.set(Operation::Idle);
+                let reported_len = if result == ReturnCode::SUCCESS && tag_valid { length - TAG_LEN } else { 0 };
+                self.client.map(|client| client.read_done(buffer, reported_len));
+            }
+            Operation::Idle => {}
+        }
+    }
+}
+
+impl<'a> NonvolatileStorageClient for EncryptedStorage<'a> {
+    fn read_done(&self, buffer: &'static mut [u8], length: usize) {
+        if let Operation::Reading { offset, .. } = self.operation.get() {
+            let nonce = Self::nonce_for(offset);
+            let result = self.engine.decrypt(AeadMode::Gcm, buffer, 0, length - TAG_LEN, &nonce);
+            if result != ReturnCode::SUCCESS {
+                self.operation.set(Operation::Idle);
+                self.client.map(|client| client.read_done(buffer, 0));
+            }
+        }
+    }
+
+    fn write_done(&self, buffer: &'static mut [u8], _length: usize) {
+        let plaintext_len = self.pending_write_plaintext_len.get();
+        self.client.map(|client| client.write_done(buffer, plaintext_len));
+    }
+
+    fn erase_done(&self) {
+        self.client.map(|client| client.erase_done());
+    }
+}
@@ -0,0 +1,128 @@
+//! Continuous SP 800-90B health tests (repetition count and adaptive
+//! proportion) on the raw TRNG output path, so a stuck or biased
+//! hardware entropy source is caught — error returned, event logged —
+//! instead of silently feeding bad entropy to the RNG driver or
+//! `capsules::csprng::Csprng`.
+//!
+//! `HealthTestedEntropy` passes `hil::entropy::Entropy32` requests
+//! straight through to the underlying hardware; the tests themselves
+//! run over each raw word as the board's TRNG driver produces it, via
+//! `test_sample`, since the word values are delivered through a
+//! board-specific buffer not modeled by `hil::entropy` (see that
+//! module). Only the test state machines and failure reporting are
+//! implemented here, not the generic sample-extraction machinery SP
+//! 800-90B describes for non-IID sources.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let health_tested = static_init!(
+//!     capsules::entropy_health_test::HealthTestedEntropy<'static>,
+//!     capsules::entropy_health_test::HealthTestedEntropy::new(trng, alarm, event_log));
+//! trng.set_client(health_tested);
+//! ```
+
+use core::cell::Cell;
+use core::cell::RefCell;
+use kernel::common::cells::OptionalCell;
+use kernel::event_log::{EventLog, KernelEvent};
+use kernel::hil::entropy::{Entropy32, Entropy32Client};
+use kernel::hil::time::Alarm;
+use kernel::ReturnCode;
+
+/// Repetition count test cutoff (SP 800-90B section 4.4.1); a run of
+/// this many identical words in a row fails the test.
+const REPETITION_CUTOFF: u32 = 5;
+
+/// Adaptive proportion test window and cutoff (SP 800-90B section
+/// 4.4.2), applied to each word's low bit.
+const ADAPTIVE_WINDOW: u32 = 512;
+const ADAPTIVE_CUTOFF: u32 = 410;
+
+pub struct HealthTestedEntropy<'a, A: Alarm<'a>> {
+    trng: &'a dyn Entropy32<'a>,
+    alarm: &'a A,
+    log: &'a RefCell<EventLog>,
+    client: OptionalCell<&'a dyn Entropy32Client>,
+    last_sample: Cell<Option<u32>>,
+    repetition_count: Cell<u32>,
+    window_count: Cell<u32>,
+    window_ones: Cell<u32>,
+    failed: Cell<bool>,
+}
+
+impl<'a, A: Alarm<'a>> HealthTestedEntropy<'a, A> {
+    pub fn new(trng: &'a dyn Entropy32<'a>, alarm: &'a A, log: &'a RefCell<EventLog>) -> HealthTestedEntropy<'a, A> {
+        HealthTestedEntropy {
+            trng,
+            alarm,
+            log,
+            client: OptionalCell::empty(),
+            last_sample: Cell::new(None),
+            repetition_count: Cell::new(0),
+            window_count: Cell::new(0),
+            window_ones: Cell::new(0),
+            failed: Cell::new(false),
+        }
+    }
+
+    /// Runs both health tests on one raw TRNG output word. Returns
+    /// `false`, and latches the failure for `get` to report, if either
+    /// test's cutoff was exceeded.
+    pub fn test_sample(&self, sample: u32) -> bool {
+        if self.last_sample.get() == Some(sample) {
+            let count = self.repetition_count.get() + 1;
+            self.repetition_count.set(count);
+            if count >= REPETITION_CUTOFF {
+                self.fail();
+                return false;
+            }
+        } else {
+            self.repetition_count.set(1);
+            self.last_sample.set(Some(sample));
+        }
+
+        if sample & 1 != 0 {
+            self.window_ones.set(self.window_ones.get() + 1);
+        }
+        let window_count = self.window_count.get() + 1;
+        if window_count >= ADAPTIVE_WINDOW {
+            let ones = self.window_ones.get();
+            self.window_count.set(0);
+            self.window_ones.set(0);
+            if ones >= ADAPTIVE_CUTOFF || ADAPTIVE_WINDOW - ones >= ADAPTIVE_CUTOFF {
+                self.fail();
+                return false;
+            }
+        } else {
+            self.window_count.set(window_count);
+        }
+
+        true
+    }
+
+    fn fail(&self) {
+        self.failed.set(true);
+        self.log.borrow_mut().record(self.alarm.now(), KernelEvent::EntropyHealthTestFailed);
+    }
+}
+
+impl<'a, A: Alarm<'a>> Entropy32<'a> for HealthTestedEntropy<'a, A> {
+    fn set_client(&self, client: &'a dyn Entropy32Client) {
+        self.client.set(client);
+    }
+
+    fn get(&self, count: usize) -> ReturnCode {
+        if self.failed.get() {
+            return ReturnCode::FAIL;
+        }
+        self.trng.get(count)
+    }
+}
+
+impl<'a, A: Alarm<'a>> Entropy32Client for HealthTestedEntropy<'a, A> {
+    fn entropy_available(&self, count: usize, result: ReturnCode) {
+        let result = if self.failed.get() { ReturnCode::FAIL } else { result };
+        self.client.map(|client| client.entropy_available(count, result));
+    }
+}
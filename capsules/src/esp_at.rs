@@ -0,0 +1,632 @@
+//! ESP-AT (Espressif's AT command firmware for the ESP32/ESP8266)
+//! syscall driver, so a process can join an access point and open a
+//! single TCP or UDP connection through a socket-like `JOIN` /
+//! `CONNECT` / `SEND` / `CLOSE` command set instead of formatting AT
+//! command strings and parsing their responses itself.
+//!
+//! Only one connection is ever open at a time, the same limit ESP-AT
+//! itself defaults to with `AT+CIPMUX=0`; a board wanting concurrent
+//! sockets would need `AT+CIPMUX=1` and per-link `<link ID>` framing,
+//! neither of which this capsule speaks. Whichever process's `CONNECT`
+//! succeeds owns the connection until it (or the peer) closes it; a
+//! second process's `CONNECT` while one is already open is rejected
+//! with `EBUSY`, the same access model `capsules::modbus::ModbusClient`
+//! uses for its one in-flight transaction.
+//!
+//! Like `capsules::modbus`, this capsule arms the UART for the bus's
+//! next byte one byte at a time rather than a whole response at once,
+//! since an AT response's length is not known until it arrives; unlike
+//! Modbus's fixed inter-character silence, ESP-AT frames a normal
+//! response as CRLF-terminated lines ending in `OK`, `ERROR`, or `FAIL`,
+//! so this capsule accumulates a line at a time instead of arming a
+//! silence alarm. A `kernel::hil::time::Alarm` is still used, armed for
+//! the whole command's timeout on every command sent, so a module that
+//! never answers (or answers with something this capsule does not
+//! recognize) fails the command instead of hanging the calling process
+//! forever.
+//!
+//! `AT+CIPSEND` is the one exception to line framing: after its `OK` is
+//! actually a bare `>` prompt with no CRLF, ESP-AT expects the raw
+//! payload bytes to follow immediately with no escaping, and answers
+//! that with a final `SEND OK` line. This capsule treats the prompt
+//! byte as a value to react to directly, outside the usual line
+//! accumulator, and transmits the payload from the same command buffer
+//! once it arrives.
+//!
+//! Inbound data arrives unsolicited as `+IPD,<length>:` followed
+//! immediately by `<length>` raw bytes with no line terminator of their
+//! own (the payload may itself contain `\r` or `\n`); this capsule
+//! recognizes the prefix as it accumulates the line, then switches to
+//! copying exactly `<length>` raw bytes before returning to line mode,
+//! rather than assuming (wrongly) that a `\n` ends every reply.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let esp_at = static_init!(
+//!     capsules::esp_at::EspAtDriver<'static, Alarm>,
+//!     capsules::esp_at::EspAtDriver::new(
+//!         uart, alarm, command_timeout_ticks,
+//!         rx_byte_buffer, rx_buffer, tx_buffer,
+//!         kernel::Grant::create(capsules::driver::NUM::EspAt as usize)));
+//! uart.set_receive_client(esp_at);
+//! uart.set_transmit_client(esp_at);
+//! alarm.set_alarm_client(esp_at);
+//! esp_at.start();
+//! ```
+
+use core::cell::Cell;
+
+use kernel::common::cells::TakeCell;
+use kernel::hil::time::{Alarm, AlarmClient};
+use kernel::hil::uart::{ReceiveClient, TransmitClient, UartData};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::EspAt as usize;
+
+mod at {
+    pub const OK: &[u8] = b"OK";
+    pub const ERROR: &[u8] = b"ERROR";
+    pub const FAIL: &[u8] = b"FAIL";
+    pub const SEND_OK: &[u8] = b"SEND OK";
+    pub const CLOSED: &[u8] = b"CLOSED";
+    pub const IPD_PREFIX: &[u8] = b"+IPD,";
+    pub const PROMPT: u8 = b'>';
+}
+
+mod upcall {
+    /// `data1` is a `ReturnCode`, `SUCCESS` if `AT+CWJAP` answered `OK`.
+    pub const JOIN_DONE: usize = 0;
+    /// `data1` is a `ReturnCode`, `SUCCESS` if `AT+CWQAP` answered `OK`.
+    pub const LEAVE_DONE: usize = 1;
+    /// `data1` is a `ReturnCode`, `SUCCESS` if `AT+CIPSTART` answered `OK`.
+    pub const CONNECT_DONE: usize = 2;
+    /// `data1` is a `ReturnCode`, `SUCCESS` if the payload was accepted
+    /// and `AT+CIPSEND` finished with `SEND OK`.
+    pub const SEND_DONE: usize = 3;
+    /// `data1` is the received payload's length, delivered through the
+    /// buffer allowed at index 0 (not shown).
+    pub const RECEIVED: usize = 4;
+    /// The connection was closed, either by `CLOSE` completing or by
+    /// the module reporting `CLOSED` unsolicited (peer hangup, dropped
+    /// link, ...).
+    pub const CLOSED: usize = 5;
+}
+
+mod cmd {
+    /// Joins the access point named in the buffer allowed at index 0,
+    /// with the passphrase in the buffer allowed at index 1.
+    pub const JOIN: usize = 0;
+    pub const LEAVE: usize = 1;
+    /// Opens a connection of protocol `data1` (`Protocol::Tcp` = 0,
+    /// `Protocol::Udp` = 1) to port `data2` at the host named in the
+    /// buffer allowed at index 0.
+    pub const CONNECT: usize = 2;
+    /// Sends `data1` bytes from the buffer allowed at index 0 (not
+    /// shown) over the open connection.
+    pub const SEND: usize = 3;
+    pub const CLOSE: usize = 4;
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    /// SSID (`JOIN`), host (`CONNECT`), or outgoing payload (`SEND`);
+    /// also where a received payload is copied back for `RECEIVED`.
+    arg_buffer: Option<AppSlice<Shared, u8>>,
+    /// Passphrase (`JOIN` only).
+    arg2_buffer: Option<AppSlice<Shared, u8>>,
+}
+
+#[derive(Copy, Clone)]
+enum PendingOp {
+    Join(AppId),
+    Leave(AppId),
+    Connect(AppId),
+    /// Waiting on `SEND OK`; the payload has already been transmitted.
+    Send(AppId),
+    Close(AppId),
+}
+
+impl PendingOp {
+    fn app_id(self) -> AppId {
+        match self {
+            PendingOp::Join(id) | PendingOp::Leave(id) | PendingOp::Connect(id) | PendingOp::Send(id) | PendingOp::Close(id) => id,
+        }
+    }
+
+    fn upcall(self) -> usize {
+        match self {
+            PendingOp::Join(_) => upcall::JOIN_DONE,
+            PendingOp::Leave(_) => upcall::LEAVE_DONE,
+            PendingOp::Connect(_) => upcall::CONNECT_DONE,
+            PendingOp::Send(_) => upcall::SEND_DONE,
+            PendingOp::Close(_) => upcall::CLOSED,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum RxState {
+    /// Accumulating a CRLF-terminated response line.
+    Line,
+    /// Copying `remaining` more raw payload bytes of an `+IPD,` notification.
+    IpdPayload(usize),
+}
+
+/// Appends `bytes` to `buffer` at `pos`. Returns the new write
+/// position, or `None` if it would not fit.
+fn append_bytes(buffer: &mut [u8], pos: usize, bytes: &[u8]) -> Option<usize> {
+    if pos + bytes.len() > buffer.len() {
+        return None;
+    }
+    buffer[pos..pos + bytes.len()].copy_from_slice(bytes);
+    Some(pos + bytes.len())
+}
+
+/// Appends `value` to `buffer` at `pos` as decimal ASCII digits.
+fn append_decimal(buffer: &mut [u8], pos: usize, value: u32) -> Option<usize> {
+    let mut digits = [0u8; 10];
+    let mut count = 0;
+    let mut value = value;
+    loop {
+        digits[count] = b'0' + (value % 10) as u8;
+        count += 1;
+        value /= 10;
+        if value == 0 {
+            break;
+        }
+    }
+    if pos + count > buffer.len() {
+        return None;
+    }
+    for i in 0..count {
+        buffer[pos + i] = digits[count - 1 - i];
+    }
+    Some(pos + count)
+}
+
+/// Parses the decimal length out of a just-completed `+IPD,<length>:`
+/// prefix, `line` being everything accumulated up to and including the
+/// trailing `:`. Returns `None` if `line` is not that prefix.
+fn parse_ipd_length(line: &[u8]) -> Option<usize> {
+    if !line.starts_with(at::IPD_PREFIX) || !line.ends_with(b":") {
+        return None;
+    }
+    let digits = &line[at::IPD_PREFIX.len()..line.len() - 1];
+    if digits.is_empty() {
+        return None;
+    }
+    let mut length: usize = 0;
+    for &byte in digits {
+        if !byte.is_ascii_digit() {
+            return None;
+        }
+        length = length.checked_mul(10)?.checked_add((byte - b'0') as usize)?;
+    }
+    Some(length)
+}
+
+pub struct EspAtDriver<'a, A: Alarm<'a>> {
+    uart: &'a dyn UartData<'a>,
+    alarm: &'a A,
+    command_timeout_ticks: u32,
+    rx_byte: TakeCell<'static, [u8]>,
+    rx_state: Cell<RxState>,
+    rx_buffer: TakeCell<'static, [u8]>,
+    rx_len: Cell<usize>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    pending: Cell<Option<PendingOp>>,
+    /// The app whose `CONNECT` last succeeded, if a connection is open.
+    connection_owner: Cell<Option<AppId>>,
+    /// Set for the `AppId` and length of a `SEND` whose `AT+CIPSEND=`
+    /// header has gone out but whose `>` prompt has not arrived yet.
+    awaiting_prompt: Cell<Option<(AppId, usize)>>,
+    apps: Grant<App>,
+}
+
+impl<'a, A: Alarm<'a>> EspAtDriver<'a, A> {
+    pub fn new(
+        uart: &'a dyn UartData<'a>,
+        alarm: &'a A,
+        command_timeout_ticks: u32,
+        rx_byte_buffer: &'static mut [u8],
+        rx_buffer: &'static mut [u8],
+        tx_buffer: &'static mut [u8],
+        apps: Grant<App>,
+    ) -> EspAtDriver<'a, A> {
+        EspAtDriver {
+            uart,
+            alarm,
+            command_timeout_ticks,
+            rx_byte: TakeCell::new(rx_byte_buffer),
+            rx_state: Cell::new(RxState::Line),
+            rx_buffer: TakeCell::new(rx_buffer),
+            rx_len: Cell::new(0),
+            tx_buffer: TakeCell::new(tx_buffer),
+            pending: Cell::new(None),
+            connection_owner: Cell::new(None),
+            awaiting_prompt: Cell::new(None),
+            apps,
+        }
+    }
+
+    /// Arms the UART to receive the module's first byte; a board calls
+    /// this once after registering this capsule as the UART's and
+    /// alarm's client.
+    pub fn start(&self) -> ReturnCode {
+        match self.rx_byte.take() {
+            Some(buffer) => self.uart.receive_buffer(buffer, 1),
+            None => ReturnCode::EBUSY,
+        }
+    }
+
+    fn busy(&self) -> bool {
+        self.pending.get().is_some() || self.awaiting_prompt.get().is_some()
+    }
+
+    /// Transmits `len` bytes already built in `buffer` and arms the
+    /// command timeout, recording `op` as what completes when the
+    /// terminal response line arrives.
+    fn issue(&self, buffer: &'static mut [u8], len: usize, op: PendingOp) -> ReturnCode {
+        self.pending.set(Some(op));
+        self.alarm.set_alarm(self.alarm.now(), self.command_timeout_ticks);
+        self.uart.transmit_buffer(buffer, len)
+    }
+
+    fn append_rx_byte(&self, byte: u8) {
+        self.rx_buffer.map(|buffer| {
+            let len = self.rx_len.get();
+            if len < buffer.len() {
+                buffer[len] = byte;
+                self.rx_len.set(len + 1);
+            }
+        });
+    }
+
+    fn process_byte(&self, byte: u8) {
+        if let Some((app_id, len)) = self.awaiting_prompt.get() {
+            if byte == at::PROMPT {
+                self.awaiting_prompt.set(None);
+                self.send_payload(app_id, len);
+            }
+            // Anything else while waiting for the prompt (echoed
+            // command text, stray whitespace) is simply not part of a
+            // line worth accumulating; ESP-AT gives no other terminal
+            // response at this point.
+            return;
+        }
+
+        match self.rx_state.get() {
+            RxState::Line => {
+                if byte == b'\n' {
+                    let len = self.rx_len.get();
+                    self.rx_len.set(0);
+                    self.handle_line(len);
+                    return;
+                }
+                self.append_rx_byte(byte);
+                if byte == b':' {
+                    let len = self.rx_len.get();
+                    let ipd_len = self.rx_buffer.map(|buffer| parse_ipd_length(&buffer[..len])).flatten();
+                    if let Some(ipd_len) = ipd_len {
+                        self.rx_len.set(0);
+                        self.rx_state.set(if ipd_len == 0 { RxState::Line } else { RxState::IpdPayload(ipd_len) });
+                    }
+                }
+            }
+            RxState::IpdPayload(remaining) => {
+                self.append_rx_byte(byte);
+                if remaining <= 1 {
+                    let len = self.rx_len.get();
+                    self.rx_len.set(0);
+                    self.rx_state.set(RxState::Line);
+                    self.deliver_received(len);
+                } else {
+                    self.rx_state.set(RxState::IpdPayload(remaining - 1));
+                }
+            }
+        }
+    }
+
+    /// Handles one CRLF-terminated response line, `len` bytes of it
+    /// (the trailing `\r`, if present, is trimmed here).
+    fn handle_line(&self, len: usize) {
+        self.rx_buffer.map(|buffer| {
+            let mut line = &buffer[..len];
+            if line.last() == Some(&b'\r') {
+                line = &line[..line.len() - 1];
+            }
+            if line.is_empty() {
+                return;
+            }
+            if line == at::CLOSED {
+                self.handle_closed();
+            } else if line == at::OK || line == at::SEND_OK {
+                self.complete_pending(ReturnCode::SUCCESS);
+            } else if line == at::ERROR || line == at::FAIL {
+                self.complete_pending(ReturnCode::FAIL);
+            }
+            // Anything else (echoed commands, "WIFI CONNECTED", "WIFI
+            // GOT IP", ...) carries no information this driver acts on.
+        });
+    }
+
+    fn complete_pending(&self, result: ReturnCode) {
+        let op = match self.pending.take() {
+            Some(op) => op,
+            None => return,
+        };
+        self.alarm.disarm();
+        if let PendingOp::Connect(app_id) = op {
+            if result == ReturnCode::SUCCESS {
+                self.connection_owner.set(Some(app_id));
+            }
+        }
+        if let PendingOp::Close(_) = op {
+            self.connection_owner.set(None);
+        }
+        self.notify(op.app_id(), op.upcall(), usize::from(result), 0);
+    }
+
+    /// A `CLOSED` line arrived unsolicited, or as the first half of a
+    /// `CLOSE` command's response; either way the connection is gone.
+    fn handle_closed(&self) {
+        let owner = self.connection_owner.take();
+        if let Some(PendingOp::Close(_)) = self.pending.get() {
+            // The pending `CLOSE`'s own `OK` still completes it below;
+            // this just clears ownership early.
+            return;
+        }
+        if let Some(app_id) = owner {
+            self.notify(app_id, upcall::CLOSED, 0, 0);
+        }
+    }
+
+    fn deliver_received(&self, len: usize) {
+        let app_id = match self.connection_owner.get() {
+            Some(app_id) => app_id,
+            None => return,
+        };
+        self.rx_buffer.map(|buffer| {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(slice) = &mut app.arg_buffer {
+                    let copy_len = core::cmp::min(len, slice.len());
+                    slice.as_mut()[..copy_len].copy_from_slice(&buffer[..copy_len]);
+                    if let Some(mut cb) = app.callback {
+                        cb.schedule(upcall::RECEIVED, copy_len, 0);
+                    }
+                }
+            });
+        });
+    }
+
+    fn notify(&self, app_id: AppId, upcall: usize, data1: usize, data2: usize) {
+        let _ = self.apps.enter(app_id, |app, _| {
+            if let Some(mut cb) = app.callback {
+                cb.schedule(upcall, data1, data2);
+            }
+        });
+    }
+
+    /// Copies `len` bytes of the app's staged payload into the (by now
+    /// returned) command buffer and transmits it, having just seen
+    /// `AT+CIPSEND`'s `>` prompt.
+    fn send_payload(&self, app_id: AppId, len: usize) {
+        let buffer = match self.tx_buffer.take() {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        let copied = self
+            .apps
+            .enter(app_id, |app, _| match &app.arg_buffer {
+                Some(slice) if slice.len() >= len && len <= buffer.len() => {
+                    buffer[..len].copy_from_slice(&slice.as_ref()[..len]);
+                    true
+                }
+                _ => false,
+            })
+            .unwrap_or(false);
+        if !copied {
+            self.tx_buffer.replace(buffer);
+            self.pending.set(None);
+            self.alarm.disarm();
+            self.notify(app_id, upcall::SEND_DONE, usize::from(ReturnCode::EINVAL), 0);
+            return;
+        }
+        self.pending.set(Some(PendingOp::Send(app_id)));
+        self.alarm.set_alarm(self.alarm.now(), self.command_timeout_ticks);
+        let _ = self.uart.transmit_buffer(buffer, len);
+    }
+}
+
+impl<'a, A: Alarm<'a>> ReceiveClient for EspAtDriver<'a, A> {
+    fn received_buffer(&self, buffer: &'static mut [u8], rx_len: usize, _result: ReturnCode) {
+        if rx_len == 1 {
+            self.process_byte(buffer[0]);
+        }
+        let _ = self.uart.receive_buffer(buffer, 1);
+    }
+}
+
+impl<'a, A: Alarm<'a>> TransmitClient for EspAtDriver<'a, A> {
+    fn transmitted_buffer(&self, buffer: &'static mut [u8], _tx_len: usize, _result: ReturnCode) {
+        self.tx_buffer.replace(buffer);
+    }
+}
+
+impl<'a, A: Alarm<'a>> AlarmClient for EspAtDriver<'a, A> {
+    fn alarm(&self) {
+        // The module never answered (or answered with something this
+        // capsule did not recognize) before the command timeout.
+        self.awaiting_prompt.set(None);
+        self.complete_pending(ReturnCode::FAIL);
+    }
+}
+
+impl<'a, A: Alarm<'a>> Driver for EspAtDriver<'a, A> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::JOIN_DONE
+            | upcall::LEAVE_DONE
+            | upcall::CONNECT_DONE
+            | upcall::SEND_DONE
+            | upcall::RECEIVED
+            | upcall::CLOSED => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.arg_buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            1 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.arg2_buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, data2: usize, app_id: AppId) -> ReturnCode {
+        if self.busy() {
+            return ReturnCode::EBUSY;
+        }
+        match command_num {
+            cmd::JOIN => {
+                let buffer = match self.tx_buffer.take() {
+                    Some(buffer) => buffer,
+                    None => return ReturnCode::EBUSY,
+                };
+                let built = self
+                    .apps
+                    .enter(app_id, |app, _| match (&app.arg_buffer, &app.arg2_buffer) {
+                        (Some(ssid), Some(password)) => append_bytes(buffer, 0, b"AT+CWJAP=\"")
+                            .and_then(|pos| append_bytes(buffer, pos, ssid.as_ref()))
+                            .and_then(|pos| append_bytes(buffer, pos, b"\",\""))
+                            .and_then(|pos| append_bytes(buffer, pos, password.as_ref()))
+                            .and_then(|pos| append_bytes(buffer, pos, b"\"\r\n")),
+                        _ => None,
+                    })
+                    .unwrap_or(None);
+                match built {
+                    Some(len) => self.issue(buffer, len, PendingOp::Join(app_id)),
+                    None => {
+                        self.tx_buffer.replace(buffer);
+                        ReturnCode::EINVAL
+                    }
+                }
+            }
+            cmd::LEAVE => {
+                let buffer = match self.tx_buffer.take() {
+                    Some(buffer) => buffer,
+                    None => return ReturnCode::EBUSY,
+                };
+                match append_bytes(buffer, 0, b"AT+CWQAP\r\n") {
+                    Some(len) => self.issue(buffer, len, PendingOp::Leave(app_id)),
+                    None => {
+                        self.tx_buffer.replace(buffer);
+                        ReturnCode::ESIZE
+                    }
+                }
+            }
+            cmd::CONNECT => {
+                if self.connection_owner.get().is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                let protocol = if data1 == Protocol::Udp as usize { Protocol::Udp } else { Protocol::Tcp };
+                let port = data2 as u32;
+                let buffer = match self.tx_buffer.take() {
+                    Some(buffer) => buffer,
+                    None => return ReturnCode::EBUSY,
+                };
+                let built = self
+                    .apps
+                    .enter(app_id, |app, _| match &app.arg_buffer {
+                        Some(host) => append_bytes(buffer, 0, b"AT+CIPSTART=\"")
+                            .and_then(|pos| append_bytes(buffer, pos, if protocol == Protocol::Udp { b"UDP" } else { b"TCP" }))
+                            .and_then(|pos| append_bytes(buffer, pos, b"\",\""))
+                            .and_then(|pos| append_bytes(buffer, pos, host.as_ref()))
+                            .and_then(|pos| append_bytes(buffer, pos, b"\","))
+                            .and_then(|pos| append_decimal(buffer, pos, port))
+                            .and_then(|pos| append_bytes(buffer, pos, b"\r\n")),
+                        None => None,
+                    })
+                    .unwrap_or(None);
+                match built {
+                    Some(len) => self.issue(buffer, len, PendingOp::Connect(app_id)),
+                    None => {
+                        self.tx_buffer.replace(buffer);
+                        ReturnCode::EINVAL
+                    }
+                }
+            }
+            cmd::SEND => {
+                if self.connection_owner.get() != Some(app_id) {
+                    return ReturnCode::EOFF;
+                }
+                let len = data1;
+                let buffer = match self.tx_buffer.take() {
+                    Some(buffer) => buffer,
+                    None => return ReturnCode::EBUSY,
+                };
+                let header_len = append_bytes(buffer, 0, b"AT+CIPSEND=")
+                    .and_then(|pos| append_decimal(buffer, pos, len as u32))
+                    .and_then(|pos| append_bytes(buffer, pos, b"\r\n"));
+                match header_len {
+                    Some(header_len) => {
+                        self.awaiting_prompt.set(Some((app_id, len)));
+                        self.alarm.set_alarm(self.alarm.now(), self.command_timeout_ticks);
+                        self.uart.transmit_buffer(buffer, header_len)
+                    }
+                    None => {
+                        self.tx_buffer.replace(buffer);
+                        ReturnCode::ESIZE
+                    }
+                }
+            }
+            cmd::CLOSE => {
+                if self.connection_owner.get().is_none() {
+                    return ReturnCode::EALREADY;
+                }
+                if self.connection_owner.get() != Some(app_id) {
+                    return ReturnCode::EOFF;
+                }
+                let buffer = match self.tx_buffer.take() {
+                    Some(buffer) => buffer,
+                    None => return ReturnCode::EBUSY,
+                };
+                match append_bytes(buffer, 0, b"AT+CIPCLOSE\r\n") {
+                    Some(len) => self.issue(buffer, len, PendingOp::Close(app_id)),
+                    None => {
+                        self.tx_buffer.replace(buffer);
+                        ReturnCode::ESIZE
+                    }
+                }
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
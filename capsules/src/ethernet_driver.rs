@@ -0,0 +1,301 @@
+//! Raw Ethernet frame syscall driver, for a privileged networking
+//! process that wants to speak its own protocol (or bridge to a
+//! different Ethernet segment) without the kernel interpreting
+//! frames; the kernel's own IP layer, once one exists for this board,
+//! is handed every received frame too via `set_ip_client`, just
+//! without going through the syscall/upcall path userspace uses.
+//!
+//! Constructing this driver requires a `capabilities::RawEthernetCapability`
+//! because there is no per-frame filtering here at all — every process
+//! with this driver number can see and inject raw frames on the
+//! board's one physical link — so a board should only ever grant the
+//! capability needed to construct it, not hand out the driver number
+//! itself as a substitute access control mechanism.
+//!
+//! Like `capsules::radio_154_driver`, only one frame is ever in flight
+//! at a time and `SEND` is gated to whichever process sent it; frame
+//! bytes are exchanged through the buffer allowed at index 0, read
+//! from for `SEND` and copied into for every process's own buffer on
+//! `RECEIVED`, since every process with this driver number sees every
+//! received frame.
+//!
+//! The kernel IP layer registered via `set_ip_client` shares that same
+//! one-frame-in-flight slot for its own sends through `KernelFrameSender`,
+//! since both ultimately contend for the single underlying `Ethernet`;
+//! it gets every lifecycle callback (`init_done`, `link_state_changed`)
+//! this driver itself sees, not just received frames.
+//!
+//! A `packet_capture::FrameTap` registered via `set_tap` sees a
+//! read-only copy of every frame that crosses this driver in either
+//! direction, for sniffing; it has no say over delivery and cannot
+//! block a send or receive.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let eth_driver = static_init!(
+//!     capsules::ethernet_driver::EthernetDriver<'static, C>,
+//!     capsules::ethernet_driver::EthernetDriver::new(
+//!         ethernet, tx_buffer, kernel::Grant::create(capsules::driver::NUM::Ethernet as usize),
+//!         raw_ethernet_cap));
+//! ethernet.set_client(eth_driver);
+//! eth_driver.set_ip_client(ip_layer);
+//! let _ = ethernet.init();
+//! ```
+
+use core::cell::Cell;
+
+use kernel::capabilities::RawEthernetCapability;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::ethernet::{Ethernet, EthernetClient, MacAddress};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+use crate::packet_capture::{Direction, FrameTap, TapSource};
+pub const DRIVER_NUM: usize = driver::NUM::Ethernet as usize;
+
+mod upcall {
+    pub const LINK_UP: usize = 0;
+    pub const SEND_DONE: usize = 1;
+    /// `data1` is how many bytes of the buffer allowed at index 0 were
+    /// filled with the received frame.
+    pub const RECEIVED: usize = 2;
+}
+
+mod cmd {
+    /// Sends `data1` bytes from the buffer allowed at index 0 as a
+    /// single raw frame.
+    pub const SEND: usize = 0;
+    /// Copies this interface's 6-byte MAC address into the buffer
+    /// allowed at index 1. `EINVAL` if that buffer is too small or
+    /// not allowed.
+    pub const GET_MAC_ADDRESS: usize = 1;
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    /// The buffer allowed at index 0: read from for `SEND`, written
+    /// into for `RECEIVED`.
+    frame: Option<AppSlice<Shared, u8>>,
+    mac_address_buffer: Option<AppSlice<Shared, u8>>,
+}
+
+/// Which caller is waiting on the one underlying `Ethernet`'s next
+/// `transmit_done`.
+#[derive(Copy, Clone)]
+enum TxOwner {
+    App(AppId),
+    Kernel,
+}
+
+/// Lets another kernel capsule (e.g. an IPv4 stack) share this
+/// driver's one underlying `Ethernet` instead of also trying to
+/// become its `hil::ethernet::EthernetClient` itself, which only one
+/// consumer can be at a time.
+pub trait KernelFrameSender<'a> {
+    fn send_frame(&self, buffer: &'static mut [u8], len: usize) -> ReturnCode;
+}
+
+pub struct EthernetDriver<'a, C: RawEthernetCapability> {
+    ethernet: &'a dyn Ethernet<'a>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    current_owner: Cell<Option<TxOwner>>,
+    /// Length last passed to `self.ethernet.transmit`, kept around
+    /// only so a tap registered with `set_tap` can see the frame that
+    /// was actually sent once `transmit_done` hands the buffer back.
+    tx_len: Cell<usize>,
+    ip_client: OptionalCell<&'a dyn EthernetClient>,
+    tap: OptionalCell<&'a dyn FrameTap>,
+    apps: Grant<App>,
+    capability: C,
+}
+
+impl<'a, C: RawEthernetCapability> EthernetDriver<'a, C> {
+    pub fn new(ethernet: &'a dyn Ethernet<'a>, tx_buffer: &'static mut [u8], apps: Grant<App>, capability: C) -> EthernetDriver<'a, C> {
+        EthernetDriver {
+            ethernet,
+            tx_buffer: TakeCell::new(tx_buffer),
+            current_owner: Cell::new(None),
+            tx_len: Cell::new(0),
+            ip_client: OptionalCell::empty(),
+            tap: OptionalCell::empty(),
+            apps,
+            capability,
+        }
+    }
+
+    /// Registers the kernel's own IP layer to receive every frame
+    /// this driver's `Ethernet` also reports to userspace; a board
+    /// with no such layer simply never calls this.
+    pub fn set_ip_client(&self, client: &'a dyn EthernetClient) {
+        self.ip_client.set(client);
+    }
+
+    /// Registers a sniffer to see a read-only copy of every frame
+    /// sent or received through this driver; a board with no capture
+    /// capsule simply never calls this.
+    pub fn set_tap(&self, tap: &'a dyn FrameTap) {
+        self.tap.set(tap);
+    }
+
+    fn notify(&self, upcall: usize, data1: usize, data2: usize) {
+        let _ = &self.capability;
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(upcall, data1, data2);
+                }
+            });
+        }
+    }
+}
+
+impl<'a, C: RawEthernetCapability> KernelFrameSender<'a> for EthernetDriver<'a, C> {
+    fn send_frame(&self, buffer: &'static mut [u8], len: usize) -> ReturnCode {
+        if self.current_owner.get().is_some() {
+            return ReturnCode::EBUSY;
+        }
+        self.current_owner.set(Some(TxOwner::Kernel));
+        self.tx_len.set(len);
+        self.ethernet.transmit(buffer, len)
+    }
+}
+
+impl<'a, C: RawEthernetCapability> Driver for EthernetDriver<'a, C> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::LINK_UP | upcall::SEND_DONE | upcall::RECEIVED => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.frame = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            1 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.mac_address_buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, _data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::SEND => {
+                if self.current_owner.get().is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                let mut buffer = match self.tx_buffer.take() {
+                    Some(buffer) => buffer,
+                    None => return ReturnCode::EBUSY,
+                };
+                if data1 > buffer.len() {
+                    self.tx_buffer.replace(buffer);
+                    return ReturnCode::ESIZE;
+                }
+                let copied = self
+                    .apps
+                    .enter(app_id, |app, _| match &app.frame {
+                        Some(slice) if data1 <= slice.len() => {
+                            buffer[..data1].copy_from_slice(&slice.as_ref()[..data1]);
+                            true
+                        }
+                        _ => false,
+                    })
+                    .unwrap_or(false);
+                if !copied {
+                    self.tx_buffer.replace(buffer);
+                    return ReturnCode::EINVAL;
+                }
+                self.current_owner.set(Some(TxOwner::App(app_id)));
+                self.tx_len.set(data1);
+                self.ethernet.transmit(buffer, data1)
+            }
+            cmd::GET_MAC_ADDRESS => {
+                let MacAddress(bytes) = self.ethernet.mac_address();
+                self.apps
+                    .enter(app_id, |app, _| match &mut app.mac_address_buffer {
+                        Some(slice) if slice.len() >= bytes.len() => {
+                            slice.as_mut()[..bytes.len()].copy_from_slice(&bytes);
+                            ReturnCode::SUCCESS
+                        }
+                        _ => ReturnCode::EINVAL,
+                    })
+                    .unwrap_or(ReturnCode::FAIL)
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a, C: RawEthernetCapability> EthernetClient for EthernetDriver<'a, C> {
+    fn init_done(&self, result: ReturnCode) {
+        if result == ReturnCode::SUCCESS {
+            self.notify(upcall::LINK_UP, 1, 0);
+            let _ = self.ethernet.start_receiving();
+        }
+        self.ip_client.map(|client| client.init_done(result));
+    }
+
+    fn transmit_done(&self, buffer: &'static mut [u8], result: ReturnCode) {
+        let sent_len = core::cmp::min(self.tx_len.get(), buffer.len());
+        self.tap.map(|tap| tap.tap_frame(TapSource::Ethernet, Direction::Tx, &buffer[..sent_len]));
+        match self.current_owner.take() {
+            Some(TxOwner::App(app_id)) => {
+                self.tx_buffer.replace(buffer);
+                let _ = self.apps.enter(app_id, |app, _| {
+                    if let Some(mut cb) = app.callback {
+                        cb.schedule(upcall::SEND_DONE, usize::from(result), 0);
+                    }
+                });
+            }
+            Some(TxOwner::Kernel) => {
+                self.ip_client.map(|client| client.transmit_done(buffer, result));
+            }
+            None => self.tx_buffer.replace(buffer),
+        }
+    }
+
+    fn receive(&self, buffer: &[u8], len: usize) {
+        self.tap.map(|tap| tap.tap_frame(TapSource::Ethernet, Direction::Rx, &buffer[..len]));
+        self.ip_client.map(|client| {
+            // The kernel IP layer sees the same frame bytes userspace
+            // does, but through this direct call rather than a
+            // buffer-allowed-then-upcalled round trip.
+            client.receive(buffer, len)
+        });
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(slice) = &mut app.frame {
+                    let copy_len = core::cmp::min(len, slice.len());
+                    slice.as_mut()[..copy_len].copy_from_slice(&buffer[..copy_len]);
+                    if let Some(mut cb) = app.callback {
+                        cb.schedule(upcall::RECEIVED, copy_len, 0);
+                    }
+                }
+            });
+        }
+    }
+
+    fn link_state_changed(&self, link_up: bool) {
+        self.notify(upcall::LINK_UP, if link_up { 1 } else { 0 }, 0);
+        self.ip_client.map(|client| client.link_state_changed(link_up));
+    }
+}
@@ -0,0 +1,276 @@
+//! Syscall driver exposing `littlefs::LittleFs` to userspace as
+//! `open`/`read`/`write`/`seek`/`close`/`unlink` plus a directory
+//! listing command, so data-logging apps that outgrow
+//! `nonvolatile_storage_driver`'s flat record model get real files.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let filesystem = static_init!(
+//!     capsules::filesystem_driver::FileSystemDriver<'static>,
+//!     capsules::filesystem_driver::FileSystemDriver::new(
+//!         littlefs,
+//!         kernel::Grant::create(capsules::driver::NUM::FileSystem as usize),
+//!         buffer));
+//! flash.set_client(filesystem);
+//! ```
+
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::nonvolatile_storage::NonvolatileStorageClient;
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+use crate::littlefs::LittleFs;
+
+pub const DRIVER_NUM: usize = driver::NUM::FileSystem as usize;
+
+const MAX_OPEN_FILES: usize = 4;
+
+mod upcall {
+    /// Delivered when a `READ` or `WRITE` started by this process
+    /// completes, with the number of bytes transferred.
+    pub const DONE: usize = 0;
+}
+
+mod cmd {
+    /// Opens the filename allowed at index 0, creating it if `data1`
+    /// is nonzero. On `SUCCESS` the file takes the lowest free
+    /// descriptor slot, which the other commands take in `data1`; the
+    /// descriptor number itself is not returned.
+    pub const OPEN: usize = 0;
+    /// Reads up to `data2` bytes at the file's current offset into the
+    /// buffer allowed at index 0, for the descriptor in `data1`.
+    /// Completion is reported via the `DONE` upcall.
+    pub const READ: usize = 1;
+    /// Writes up to `data2` bytes from the buffer allowed at index 0
+    /// at the file's current offset, for the descriptor in `data1`.
+    /// Completion is reported via the `DONE` upcall.
+    pub const WRITE: usize = 2;
+    /// Sets the descriptor in `data1`'s offset to `data2`.
+    pub const SEEK: usize = 3;
+    pub const CLOSE: usize = 4;
+    /// Unlinks the filename allowed at index 0.
+    pub const UNLINK: usize = 5;
+    /// Copies one directory entry's name into the buffer allowed at
+    /// index 0, starting from entry `data1`; returns `SUCCESS` if the
+    /// entry exists, or `FAIL` once `data1` is past the last entry.
+    pub const LIST: usize = 6;
+}
+
+#[derive(Copy, Clone)]
+struct OpenFile {
+    handle: usize,
+    offset: usize,
+}
+
+pub struct App {
+    open_files: [Option<OpenFile>; MAX_OPEN_FILES],
+    /// The buffer allowed at index 0: holds the filename for `OPEN` and
+    /// `UNLINK`, is read from for `WRITE`, and is written into for
+    /// `READ` and `LIST`.
+    buffer: Option<AppSlice<Shared, u8>>,
+    callback: Option<Callback>,
+}
+
+impl Default for App {
+    fn default() -> App {
+        App {
+            open_files: [None; MAX_OPEN_FILES],
+            buffer: None,
+            callback: None,
+        }
+    }
+}
+
+pub struct FileSystemDriver<'a> {
+    fs: &'a LittleFs<'a>,
+    apps: Grant<App>,
+    current_app: OptionalCell<AppId>,
+    /// Scratch buffer handed to the backing storage for the duration of
+    /// an in-flight `READ`/`WRITE`; the app's own allowed buffer isn't
+    /// `'static`, so its contents are copied to/from this one.
+    buffer: TakeCell<'static, [u8]>,
+}
+
+impl<'a> FileSystemDriver<'a> {
+    pub fn new(fs: &'a LittleFs<'a>, apps: Grant<App>, buffer: &'static mut [u8]) -> FileSystemDriver<'a> {
+        FileSystemDriver {
+            fs,
+            apps,
+            current_app: OptionalCell::empty(),
+            buffer: TakeCell::new(buffer),
+        }
+    }
+}
+
+impl<'a> Driver for FileSystemDriver<'a> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::DONE => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::OPEN => self
+                .apps
+                .enter(app_id, |app, _| {
+                    let slot = match app.open_files.iter_mut().find(|f| f.is_none()) {
+                        Some(slot) => slot,
+                        None => return ReturnCode::EBUSY,
+                    };
+                    // `data1 != 0` means create-if-missing.
+                    let name = match &app.buffer {
+                        Some(slice) => slice.as_ref(),
+                        None => return ReturnCode::EINVAL,
+                    };
+                    match self.fs.open(name, data1 != 0) {
+                        Ok(handle) => {
+                            *slot = Some(OpenFile { handle, offset: 0 });
+                            ReturnCode::SUCCESS
+                        }
+                        Err(e) => e,
+                    }
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            cmd::READ | cmd::WRITE => {
+                if self.current_app.is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                self.apps
+                    .enter(app_id, |app, _| {
+                        let file = match app.open_files.get_mut(data1) {
+                            Some(Some(file)) => file,
+                            _ => return ReturnCode::EINVAL,
+                        };
+                        let mut scratch = match self.buffer.take() {
+                            Some(scratch) => scratch,
+                            None => return ReturnCode::EBUSY,
+                        };
+                        if data2 > scratch.len() {
+                            self.buffer.replace(scratch);
+                            return ReturnCode::ESIZE;
+                        }
+                        let result = if command_num == cmd::WRITE {
+                            match &app.buffer {
+                                Some(slice) if data2 <= slice.len() => {
+                                    scratch[..data2].copy_from_slice(&slice.as_ref()[..data2]);
+                                    self.fs.write(file.handle, scratch, file.offset, data2)
+                                }
+                                _ => {
+                                    self.buffer.replace(scratch);
+                                    return ReturnCode::EINVAL;
+                                }
+                            }
+                        } else {
+                            self.fs.read(file.handle, scratch, file.offset, data2)
+                        };
+                        if result == ReturnCode::SUCCESS {
+                            file.offset += data2;
+                            self.current_app.set(app_id);
+                        }
+                        result
+                    })
+                    .unwrap_or(ReturnCode::FAIL)
+            }
+            cmd::SEEK => self
+                .apps
+                .enter(app_id, |app, _| match app.open_files.get_mut(data1) {
+                    Some(Some(file)) => {
+                        file.offset = data2;
+                        ReturnCode::SUCCESS
+                    }
+                    _ => ReturnCode::EINVAL,
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            cmd::CLOSE => self
+                .apps
+                .enter(app_id, |app, _| match app.open_files.get_mut(data1) {
+                    Some(slot @ Some(_)) => {
+                        *slot = None;
+                        ReturnCode::SUCCESS
+                    }
+                    _ => ReturnCode::EINVAL,
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            cmd::UNLINK => self
+                .apps
+                .enter(app_id, |app, _| match &app.buffer {
+                    Some(slice) => self.fs.unlink(slice.as_ref()),
+                    None => ReturnCode::EINVAL,
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            cmd::LIST => self
+                .apps
+                .enter(app_id, |app, _| {
+                    let mut seen = 0usize;
+                    let mut result = ReturnCode::FAIL;
+                    let dest = &mut app.buffer;
+                    self.fs.list(|name, _len| {
+                        if seen == data1 {
+                            result = match dest {
+                                Some(slice) => {
+                                    let copy_len = core::cmp::min(name.len(), slice.len());
+                                    slice.as_mut()[..copy_len].copy_from_slice(&name[..copy_len]);
+                                    ReturnCode::SUCCESS
+                                }
+                                None => ReturnCode::EINVAL,
+                            };
+                        }
+                        seen += 1;
+                    });
+                    result
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a> NonvolatileStorageClient for FileSystemDriver<'a> {
+    fn read_done(&self, buffer: &'static mut [u8], length: usize) {
+        if let Some(app_id) = self.current_app.take() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(slice) = &mut app.buffer {
+                    let copy_len = core::cmp::min(length, slice.len());
+                    slice.as_mut()[..copy_len].copy_from_slice(&buffer[..copy_len]);
+                }
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(length, 0, 0);
+                }
+            });
+        }
+        self.buffer.replace(buffer);
+    }
+
+    fn write_done(&self, buffer: &'static mut [u8], length: usize) {
+        self.buffer.replace(buffer);
+        if let Some(app_id) = self.current_app.take() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(length, 0, 0);
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,369 @@
+//! A/B firmware image updates with automatic rollback.
+//!
+//! The backing storage is split into two equal-sized slots; one is
+//! always the slot the kernel booted from (`active_slot`), and the
+//! other is free to receive a new image over console frames, USB DFU,
+//! or the network (whichever transport a board wires up to `begin`,
+//! `write_chunk`, and `finish`). `finish` checks the new image's
+//! signature through `ImageVerifier` before anything is flagged
+//! active, so a corrupted or unsigned transfer is rejected without
+//! ever touching the boot path.
+//!
+//! A verified image is marked pending with an in-RAM flag, which
+//! `confirm_boot` (from the board's post-boot health check) or
+//! `rollback` clears. The flag is not written to the backing storage
+//! or to any boot record, so it does not survive a reset, and this
+//! capsule does not select which slot boots; the failed or unconfirmed
+//! image is left in place to be overwritten by the next update
+//! attempt.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let updater = static_init!(
+//!     capsules::firmware_update::FirmwareUpdate<'static>,
+//!     capsules::firmware_update::FirmwareUpdate::new(flash, verifier, slot_size, active_slot, buffer));
+//! ```
+
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::nonvolatile_storage::{NonvolatileStorage, NonvolatileStorageClient};
+use kernel::{AppId, AppSlice, Callback, Driver, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::FirmwareUpdate as usize;
+
+mod upcall {
+    pub const DONE: usize = 0;
+}
+
+mod cmd {
+    /// Erases the inactive slot and begins staging a new image into
+    /// it.
+    pub const BEGIN: usize = 0;
+    /// Writes `data2` bytes from the buffer allowed at index 0 at
+    /// offset `data1` within the inactive slot.
+    pub const WRITE: usize = 1;
+    /// Verifies the staged image's signature and, if valid, sets the
+    /// in-RAM pending flag; nothing is written that selects the next
+    /// boot slot.
+    pub const FINISH: usize = 2;
+    /// Returns `SUCCESS` if slot A booted this run, `FAIL` if slot B
+    /// did.
+    pub const ACTIVE_SLOT: usize = 3;
+    /// Returns success if a pending image is still awaiting
+    /// confirmation, i.e. it has not yet survived a full health check.
+    pub const IS_PENDING: usize = 4;
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Slot {
+    A,
+    B,
+}
+
+impl Slot {
+    fn other(self) -> Slot {
+        match self {
+            Slot::A => Slot::B,
+            Slot::B => Slot::A,
+        }
+    }
+}
+
+/// Checks a staged image's signature before it is ever flagged
+/// active. Kept as a narrow trait so this capsule does not need to
+/// know which signature scheme or key storage a board uses.
+pub trait ImageVerifier {
+    fn verify(&self, slot: Slot, length: usize) -> bool;
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum State {
+    Idle,
+    Staging,
+    Verifying,
+}
+
+pub struct FirmwareUpdate<'a> {
+    storage: &'a dyn NonvolatileStorage<'a>,
+    verifier: &'a dyn ImageVerifier,
+    slot_size: usize,
+    /// The slot the kernel actually booted from this run, fixed at
+    /// construction time by whatever board code reads the boot
+    /// record.
+    active_slot: Slot,
+    /// Set once a new image has passed verification; cleared by either
+    /// `confirm_boot` or `rollback`. Held in RAM only, so it starts out
+    /// false after every reset.
+    pending: core::cell::Cell<bool>,
+    staged_length: core::cell::Cell<usize>,
+    state: core::cell::Cell<State>,
+    client: OptionalCell<AppId>,
+    callback: OptionalCell<Callback>,
+    buffer: TakeCell<'static, [u8]>,
+    /// The buffer allowed at index 0, holding the bytes for the next
+    /// `WRITE`.
+    data: core::cell::Cell<Option<AppSlice<Shared, u8>>>,
+}
+
+impl<'a> FirmwareUpdate<'a> {
+    pub fn new(
+        storage: &'a dyn NonvolatileStorage<'a>,
+        verifier: &'a dyn ImageVerifier,
+        slot_size: usize,
+        active_slot: Slot,
+        buffer: &'static mut [u8],
+    ) -> FirmwareUpdate<'a> {
+        FirmwareUpdate {
+            storage,
+            verifier,
+            slot_size,
+            active_slot,
+            pending: core::cell::Cell::new(false),
+            staged_length: core::cell::Cell::new(0),
+            state: core::cell::Cell::new(State::Idle),
+            client: OptionalCell::empty(),
+            callback: OptionalCell::empty(),
+            buffer: TakeCell::new(buffer),
+            data: core::cell::Cell::new(None),
+        }
+    }
+
+    fn inactive_slot(&self) -> Slot {
+        self.active_slot.other()
+    }
+
+    fn slot_offset(&self, slot: Slot) -> usize {
+        match slot {
+            Slot::A => 0,
+            Slot::B => self.slot_size,
+        }
+    }
+
+    /// Erases the inactive slot and begins staging a new image into
+    /// it. The transport-agnostic counterpart of `cmd::BEGIN`, for a
+    /// board-level transport (USB DFU, a console frame protocol) that
+    /// has no `AppId` of its own to drive this capsule with.
+    pub fn begin(&self) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        self.staged_length.set(0);
+        self.state.set(State::Staging);
+        let result = self.storage.erase(self.slot_offset(self.inactive_slot()), self.slot_size);
+        if result != ReturnCode::SUCCESS {
+            self.state.set(State::Idle);
+        }
+        result
+    }
+
+    /// Writes `data` at `offset` within the inactive slot; the
+    /// transport-agnostic counterpart of `cmd::WRITE`. Completion is
+    /// reported via `NonvolatileStorageClient::write_done`.
+    pub fn write_chunk(&self, offset: usize, data: &[u8]) -> ReturnCode {
+        if self.state.get() != State::Staging {
+            return ReturnCode::EALREADY;
+        }
+        let end = match offset.checked_add(data.len()) {
+            Some(end) if end <= self.slot_size => end,
+            _ => return ReturnCode::ESIZE,
+        };
+        let buffer = match self.buffer.take() {
+            Some(buffer) if buffer.len() >= data.len() => buffer,
+            Some(buffer) => {
+                self.buffer.replace(buffer);
+                return ReturnCode::ESIZE;
+            }
+            None => return ReturnCode::EBUSY,
+        };
+        buffer[..data.len()].copy_from_slice(data);
+        let result = self.storage.write(buffer, self.slot_offset(self.inactive_slot()) + offset, data.len());
+        if result == ReturnCode::SUCCESS {
+            self.staged_length.set(core::cmp::max(self.staged_length.get(), end));
+        }
+        result
+    }
+
+    /// Verifies the staged image and, if valid, marks it pending; the
+    /// transport-agnostic counterpart of `cmd::FINISH`.
+    pub fn finish(&self) -> ReturnCode {
+        if self.state.get() != State::Staging {
+            return ReturnCode::EALREADY;
+        }
+        self.state.set(State::Verifying);
+        let valid = self.verifier.verify(self.inactive_slot(), self.staged_length.get());
+        self.state.set(State::Idle);
+        if valid {
+            self.pending.set(true);
+            ReturnCode::SUCCESS
+        } else {
+            ReturnCode::FAIL
+        }
+    }
+
+    /// Reports the in-RAM pending flag: true only between a successful
+    /// `finish` and the next `confirm_boot` or `rollback` in the same
+    /// run.
+    pub fn is_pending(&self) -> bool {
+        self.pending.get()
+    }
+
+    /// Called from the board's post-boot health check once the new
+    /// image has proven itself (network reachable, sensors respond,
+    /// and so on). Clears the pending flag; nothing else is recorded.
+    pub fn confirm_boot(&self) -> ReturnCode {
+        if !self.pending.get() {
+            return ReturnCode::EALREADY;
+        }
+        self.pending.set(false);
+        ReturnCode::SUCCESS
+    }
+
+    /// Called from the board's post-boot health check if the new
+    /// image fails to come up correctly; the board must still reset
+    /// into `active_slot` itself, since this capsule only clears its
+    /// in-RAM pending flag.
+    pub fn rollback(&self) -> ReturnCode {
+        if !self.pending.get() {
+            return ReturnCode::EALREADY;
+        }
+        self.pending.set(false);
+        ReturnCode::SUCCESS
+    }
+}
+
+impl<'a> Driver for FirmwareUpdate<'a> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, _app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::DONE => {
+                match callback {
+                    Some(cb) => self.callback.set(cb),
+                    None => self.callback.clear(),
+                }
+                ReturnCode::SUCCESS
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, _app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => {
+                self.data.set(slice);
+                ReturnCode::SUCCESS
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::BEGIN => {
+                self.client.set(app_id);
+                let result = self.begin();
+                if result != ReturnCode::SUCCESS {
+                    self.client.clear();
+                }
+                result
+            }
+            cmd::WRITE => {
+                let slice = self.data.take();
+                let result = match &slice {
+                    Some(slice) if data2 <= slice.len() => self.write_chunk(data1, &slice.as_ref()[..data2]),
+                    Some(_) => ReturnCode::ESIZE,
+                    None => ReturnCode::EINVAL,
+                };
+                self.data.set(slice);
+                result
+            }
+            cmd::FINISH => {
+                let result = self.finish();
+                self.client.clear();
+                result
+            }
+            cmd::ACTIVE_SLOT => match self.active_slot {
+                Slot::A => ReturnCode::SUCCESS,
+                Slot::B => ReturnCode::FAIL,
+            },
+            cmd::IS_PENDING => {
+                if self.pending.get() {
+                    ReturnCode::SUCCESS
+                } else {
+                    ReturnCode::FAIL
+                }
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a> NonvolatileStorageClient for FirmwareUpdate<'a> {
+    fn read_done(&self, buffer: &'static mut [u8], _length: usize) {
+        self.buffer.replace(buffer);
+    }
+
+    fn write_done(&self, buffer: &'static mut [u8], _length: usize) {
+        self.buffer.replace(buffer);
+        self.callback.map(|mut cb| cb.schedule(0, 0, 0));
+    }
+
+    fn erase_done(&self) {
+        self.callback.map(|mut cb| cb.schedule(0, 0, 0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn other_toggles_between_the_two_slots() {
+        assert_eq!(Slot::A.other(), Slot::B);
+        assert_eq!(Slot::B.other(), Slot::A);
+    }
+
+    struct NoopStorage;
+
+    impl<'a> NonvolatileStorage<'a> for NoopStorage {
+        fn set_client(&self, _client: &'a dyn NonvolatileStorageClient) {}
+        fn size(&self) -> usize {
+            0
+        }
+        fn read(&self, _buffer: &'static mut [u8], _offset: usize, _length: usize) -> ReturnCode {
+            ReturnCode::FAIL
+        }
+        fn write(&self, _buffer: &'static mut [u8], _offset: usize, _length: usize) -> ReturnCode {
+            ReturnCode::FAIL
+        }
+        fn erase(&self, _offset: usize, _length: usize) -> ReturnCode {
+            ReturnCode::FAIL
+        }
+    }
+
+    struct AlwaysValid;
+
+    impl ImageVerifier for AlwaysValid {
+        fn verify(&self, _slot: Slot, _length: usize) -> bool {
+            true
+        }
+    }
+
+    static mut TEST_BUFFER: [u8; 16] = [0; 16];
+
+    fn updater(active_slot: Slot) -> FirmwareUpdate<'static> {
+        FirmwareUpdate::new(&NoopStorage, &AlwaysValid, 4096, active_slot, unsafe { &mut TEST_BUFFER })
+    }
+
+    #[test]
+    fn inactive_slot_is_the_slot_that_did_not_boot() {
+        assert_eq!(updater(Slot::A).inactive_slot(), Slot::B);
+        assert_eq!(updater(Slot::B).inactive_slot(), Slot::A);
+    }
+
+    #[test]
+    fn slot_offset_places_slot_b_after_slot_a() {
+        let updater = updater(Slot::A);
+        assert_eq!(updater.slot_offset(Slot::A), 0);
+        assert_eq!(updater.slot_offset(Slot::B), updater.slot_size);
+    }
+}
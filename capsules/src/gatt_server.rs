@@ -0,0 +1,326 @@
+//! A configurable BLE GATT server, for boards that want to expose
+//! custom services without a vendor SoftDevice: apps declare how many
+//! characteristics they own, the driver allocates a contiguous ATT
+//! handle range for them (the base handle is not reported back;
+//! upcalls name a characteristic by its index), and write/subscribe
+//! activity on those handles is delivered back as upcalls. Built on
+//! `hil::ble_connection::BleConnection`, which this driver assumes is
+//! already advertising and accepting a connection — pairing,
+//! encryption, and anything below ATT are out of scope here.
+//!
+//! Each characteristic occupies two ATT handles: the value handle
+//! (what a central reads/writes) and, immediately after it, the
+//! Client Characteristic Configuration Descriptor handle a central
+//! writes to enable or disable notifications. Service and
+//! characteristic UUIDs come from the descriptor table a process
+//! `allow`s at index 0; like every other buffer in this tree, this
+//! capsule does not parse its contents — only the count of
+//! characteristics it describes, passed explicitly to `DECLARE`,
+//! drives handle allocation. Attribute values themselves are likewise
+//! exchanged through the buffer allowed at index 1.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let gatt = static_init!(
+//!     capsules::gatt_server::GattServer<'static>,
+//!     capsules::gatt_server::GattServer::new(
+//!         connection, tx_buffer, kernel::Grant::create(capsules::driver::NUM::GattServer as usize)));
+//! connection.set_client(gatt);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::TakeCell;
+use kernel::hil::ble_connection::{BleConnection, ConnectionClient};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::GattServer as usize;
+
+/// Characteristics a single process may register.
+const MAX_CHARACTERISTICS_PER_APP: usize = 4;
+/// The first ATT handle assigned to an application characteristic.
+/// Handle 0 is invalid in ATT, and the server's own GATT/GAP service
+/// attributes are not modeled, so no handles are set aside for them.
+const FIRST_HANDLE: u16 = 1;
+
+mod att {
+    pub const WRITE_REQ: u8 = 0x12;
+    pub const WRITE_RESP: u8 = 0x13;
+    pub const HANDLE_VALUE_NOTIFICATION: u8 = 0x1b;
+    /// Opcode (1) + attribute handle (2), the fixed part of every PDU
+    /// this server parses or builds; the value (the rest of a write, or
+    /// the notified payload) follows it.
+    pub const HEADER_LEN: usize = 3;
+}
+
+mod upcall {
+    pub const CONNECTED: usize = 0;
+    pub const DISCONNECTED: usize = 1;
+    /// `data1` is the characteristic index, `data2` the written
+    /// value's length; the value is copied into the buffer allowed at
+    /// index 1 (up to that buffer's length) before this fires.
+    pub const WRITE: usize = 2;
+    /// `data1` is the characteristic index, `data2` is 1 if
+    /// notifications were just enabled, 0 if disabled.
+    pub const SUBSCRIBE: usize = 3;
+    pub const NOTIFY_DONE: usize = 4;
+}
+
+mod cmd {
+    /// Declares `data1` characteristics (at most
+    /// `MAX_CHARACTERISTICS_PER_APP`), described by the descriptor
+    /// table allowed at index 0 (not shown), and allocates them ATT
+    /// handles. `EALREADY` if this process has already declared.
+    pub const DECLARE: usize = 0;
+    /// Sends a notification on characteristic `data1`, `data2` value
+    /// bytes from the buffer allowed at index 1.
+    /// `ENOSUPPORT` unless a central has enabled notifications on it.
+    pub const NOTIFY: usize = 1;
+}
+
+pub struct App {
+    callback: Option<Callback>,
+    descriptors: Option<AppSlice<Shared, u8>>,
+    value: Option<AppSlice<Shared, u8>>,
+    base_handle: Option<u16>,
+    num_characteristics: u8,
+    subscribed: [bool; MAX_CHARACTERISTICS_PER_APP],
+}
+
+impl Default for App {
+    fn default() -> App {
+        App {
+            callback: None,
+            descriptors: None,
+            value: None,
+            base_handle: None,
+            num_characteristics: 0,
+            subscribed: [false; MAX_CHARACTERISTICS_PER_APP],
+        }
+    }
+}
+
+impl App {
+    /// The characteristic index owning `handle`, and whether it names
+    /// the value handle or the CCCD handle.
+    fn characteristic_for(&self, handle: u16) -> Option<(usize, bool)> {
+        let base = self.base_handle?;
+        if handle < base {
+            return None;
+        }
+        let offset = (handle - base) as usize;
+        let index = offset / 2;
+        if index >= self.num_characteristics as usize {
+            return None;
+        }
+        Some((index, offset % 2 == 0))
+    }
+}
+
+/// What the one `tx_buffer` currently in flight to `connection` is
+/// carrying, so `att_pdu_sent` knows whether to report `NOTIFY_DONE`
+/// and to whom.
+#[derive(Copy, Clone)]
+enum PendingSend {
+    WriteResponse,
+    Notification(AppId),
+}
+
+pub struct GattServer<'a> {
+    connection: &'a dyn BleConnection<'a>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    connected: Cell<bool>,
+    next_handle: Cell<u16>,
+    pending: Cell<Option<PendingSend>>,
+    apps: Grant<App>,
+}
+
+impl<'a> GattServer<'a> {
+    pub fn new(connection: &'a dyn BleConnection<'a>, tx_buffer: &'static mut [u8], apps: Grant<App>) -> GattServer<'a> {
+        GattServer {
+            connection,
+            tx_buffer: TakeCell::new(tx_buffer),
+            connected: Cell::new(false),
+            next_handle: Cell::new(FIRST_HANDLE),
+            pending: Cell::new(None),
+            apps,
+        }
+    }
+}
+
+impl<'a> Driver for GattServer<'a> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::CONNECTED | upcall::DISCONNECTED | upcall::WRITE | upcall::SUBSCRIBE | upcall::NOTIFY_DONE => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.descriptors = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            1 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.value = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::DECLARE => {
+                let count = data1;
+                if count == 0 || count > MAX_CHARACTERISTICS_PER_APP {
+                    return ReturnCode::EINVAL;
+                }
+                self.apps
+                    .enter(app_id, |app, _| {
+                        if app.base_handle.is_some() {
+                            return ReturnCode::EALREADY;
+                        }
+                        if app.descriptors.as_ref().map(|d| d.len()).unwrap_or(0) == 0 {
+                            return ReturnCode::EINVAL;
+                        }
+                        let base = self.next_handle.get();
+                        self.next_handle.set(base + (count as u16) * 2);
+                        app.base_handle = Some(base);
+                        app.num_characteristics = count as u8;
+                        ReturnCode::SUCCESS
+                    })
+                    .unwrap_or(ReturnCode::FAIL)
+            }
+            cmd::NOTIFY => {
+                let index = data1;
+                let payload_len = data2;
+                if !self.connected.get() {
+                    return ReturnCode::EOFF;
+                }
+                self.apps
+                    .enter(app_id, |app, _| {
+                        if index >= app.num_characteristics as usize || !app.subscribed[index] {
+                            return ReturnCode::ENOSUPPORT;
+                        }
+                        let handle = app.base_handle.unwrap() + (index as u16) * 2;
+                        let value = match &app.value {
+                            Some(slice) if payload_len <= slice.len() => slice,
+                            _ => return ReturnCode::EINVAL,
+                        };
+                        match self.tx_buffer.take() {
+                            Some(buffer) if att::HEADER_LEN + payload_len <= buffer.len() => {
+                                buffer[0] = att::HANDLE_VALUE_NOTIFICATION;
+                                buffer[1..3].copy_from_slice(&handle.to_le_bytes());
+                                buffer[att::HEADER_LEN..att::HEADER_LEN + payload_len]
+                                    .copy_from_slice(&value.as_ref()[..payload_len]);
+                                self.pending.set(Some(PendingSend::Notification(app_id)));
+                                self.connection.send_att_pdu(buffer, att::HEADER_LEN + payload_len)
+                            }
+                            Some(buffer) => {
+                                self.tx_buffer.replace(buffer);
+                                ReturnCode::ESIZE
+                            }
+                            None => ReturnCode::EBUSY,
+                        }
+                    })
+                    .unwrap_or(ReturnCode::FAIL)
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a> ConnectionClient for GattServer<'a> {
+    fn connected(&self) {
+        self.connected.set(true);
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(upcall::CONNECTED, 0, 0);
+                }
+            });
+        }
+    }
+
+    fn disconnected(&self) {
+        self.connected.set(false);
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                for subscribed in app.subscribed.iter_mut() {
+                    *subscribed = false;
+                }
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(upcall::DISCONNECTED, 0, 0);
+                }
+            });
+        }
+    }
+
+    fn att_pdu_received(&self, buffer: &[u8], len: usize) {
+        if len < att::HEADER_LEN || buffer[0] != att::WRITE_REQ {
+            return;
+        }
+        let handle = u16::from_le_bytes([buffer[1], buffer[2]]);
+        let value_len = len - att::HEADER_LEN;
+
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                let (index, is_value_handle) = match app.characteristic_for(handle) {
+                    Some(found) => found,
+                    None => return,
+                };
+                if is_value_handle {
+                    if let Some(dest) = &mut app.value {
+                        let copy_len = core::cmp::min(dest.len(), value_len);
+                        dest.as_mut()[..copy_len].copy_from_slice(&buffer[att::HEADER_LEN..att::HEADER_LEN + copy_len]);
+                    }
+                    if let Some(mut cb) = app.callback {
+                        cb.schedule(upcall::WRITE, index, value_len);
+                    }
+                } else {
+                    // The CCCD value is two bytes; bit 0 of the first
+                    // one enables notifications. A write with no
+                    // value (`value_len == 0`) is treated as a
+                    // disable, since there is nothing else to infer
+                    // subscription intent from.
+                    let enabled = value_len > 0 && buffer[att::HEADER_LEN] & 0x1 != 0;
+                    app.subscribed[index] = enabled;
+                    if let Some(mut cb) = app.callback {
+                        cb.schedule(upcall::SUBSCRIBE, index, enabled as usize);
+                    }
+                }
+                if let Some(response) = self.tx_buffer.take() {
+                    response[0] = att::WRITE_RESP;
+                    self.pending.set(Some(PendingSend::WriteResponse));
+                    let _ = self.connection.send_att_pdu(response, 1);
+                }
+            });
+        }
+    }
+
+    fn att_pdu_sent(&self, buffer: &'static mut [u8], result: ReturnCode) {
+        self.tx_buffer.replace(buffer);
+        if let Some(PendingSend::Notification(app_id)) = self.pending.take() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(upcall::NOTIFY_DONE, usize::from(result), 0);
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! Virtualizer that coalesces periodic I2C sensor sampling onto shared
+//! alarm ticks.
+//!
+//! Without this component, each sensor capsule on a bus (an ambient
+//! light sensor, a humidity sensor, an accelerometer, ...) arms its own
+//! `Alarm` to wake up and issue an I2C transaction on its own schedule.
+//! On a board with five or more I2C devices this means five separate
+//! wakeups and five separate bus arbitration windows even when the
+//! sampling periods are identical or harmonics of one another.
+//!
+//! `I2CSampleScheduler` lets capsules register a desired sampling
+//! period instead of arming their own alarm. On every alarm fire, it
+//! walks the registered clients whose period has elapsed and lets them
+//! issue their I2C transaction back-to-back, before re-arming a single
+//! alarm for the next-soonest deadline.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let scheduler = static_init!(
+//!     capsules::i2c_sample_scheduler::I2CSampleScheduler<'static, Alarm>,
+//!     capsules::i2c_sample_scheduler::I2CSampleScheduler::new(alarm));
+//! scheduler.register(opt3001_client, 1000);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::OptionalCell;
+use kernel::hil::time::{self, Alarm};
+use kernel::ReturnCode;
+
+/// A capsule that wants its sampling coalesced onto the shared alarm.
+pub trait SampleClient {
+    /// Called when this client's period has elapsed; the client should
+    /// issue its I2C transaction (or other brief bus activity) now.
+    fn sample(&self);
+}
+
+const MAX_CLIENTS: usize = 8;
+
+struct Registration<'a> {
+    client: &'a dyn SampleClient,
+    period_ms: u32,
+    next_due_ms: Cell<u32>,
+}
+
+pub struct I2CSampleScheduler<'a, A: Alarm<'a>> {
+    alarm: &'a A,
+    clients: [OptionalCell<Registration<'a>>; MAX_CLIENTS],
+    now_ms: Cell<u32>,
+}
+
+impl<'a, A: Alarm<'a>> I2CSampleScheduler<'a, A> {
+    pub fn new(alarm: &'a A) -> I2CSampleScheduler<'a, A> {
+        I2CSampleScheduler {
+            alarm,
+            clients: Default::default(),
+            now_ms: Cell::new(0),
+        }
+    }
+
+    /// Register a client to be sampled every `period_ms` milliseconds,
+    /// batched together with any other client whose deadline falls in
+    /// the same tick.
+    pub fn register(&self, client: &'a dyn SampleClient, period_ms: u32) -> ReturnCode {
+        for slot in self.clients.iter() {
+            if !slot.is_some() {
+                slot.set(Registration {
+                    client,
+                    period_ms,
+                    next_due_ms: Cell::new(self.now_ms.get() + period_ms),
+                });
+                self.arm_next();
+                return ReturnCode::SUCCESS;
+            }
+        }
+        ReturnCode::ENOMEM
+    }
+
+    fn arm_next(&self) {
+        let mut earliest: Option<u32> = None;
+        for slot in self.clients.iter() {
+            slot.map(|reg| {
+                earliest = Some(earliest.map_or(reg.next_due_ms.get(), |e| {
+                    core::cmp::min(e, reg.next_due_ms.get())
+                }));
+            });
+        }
+        if let Some(due) = earliest {
+            let now = self.alarm.now();
+            let delay = due.saturating_sub(self.now_ms.get());
+            self.alarm
+                .set_alarm(now, A::ticks_from_ms(delay as u32));
+        }
+    }
+}
+
+impl<'a, A: Alarm<'a>> time::AlarmClient for I2CSampleScheduler<'a, A> {
+    fn alarm(&self) {
+        // The alarm was armed for the earliest registered deadline, so
+        // that deadline is the current time.
+        let mut earliest: Option<u32> = None;
+        for slot in self.clients.iter() {
+            slot.map(|reg| {
+                let due = reg.next_due_ms.get();
+                earliest = Some(earliest.map_or(due, |e| core::cmp::min(e, due)));
+            });
+        }
+        let now = core::cmp::max(self.now_ms.get(), earliest.unwrap_or(0));
+        self.now_ms.set(now);
+        for slot in self.clients.iter() {
+            slot.map(|reg| {
+                if reg.next_due_ms.get() <= now {
+                    reg.next_due_ms.set(now + reg.period_ms);
+                    reg.client.sample();
+                }
+            });
+        }
+        self.arm_next();
+    }
+}
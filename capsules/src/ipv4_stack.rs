@@ -0,0 +1,650 @@
+//! A lightweight IPv4 stack over `hil::ethernet`: ARP, a DHCP client
+//! that acquires this board's address (and default gateway) on link
+//! up, an ICMP echo responder, and per-process UDP sockets — enough
+//! for a wired gateway board to speak IPv4 without pushing any of
+//! that into a userspace process.
+//!
+//! This is deliberately not a general router: every outgoing UDP
+//! datagram and ICMP echo reply is framed to the default gateway's
+//! MAC address, resolved by ARP once after DHCP completes, and left
+//! for the gateway to route onward — there is no per-destination ARP
+//! cache or routing table. IPv4 options, fragmentation, and DHCP
+//! lease renewal/rebinding timers are not implemented; a lease simply
+//! lasts until the board reboots.
+//!
+//! This capsule is registered as `capsules::ethernet_driver`'s IP
+//! client (see that module) rather than taking a raw `hil::ethernet`
+//! reference itself, so a privileged networking process can still see
+//! every frame too; it sends through that driver's `KernelFrameSender`
+//! trait, which shares the one frame in flight with that driver's own
+//! `SEND` command.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let ipv4 = static_init!(
+//!     capsules::ipv4_stack::Ipv4Stack<'static>,
+//!     capsules::ipv4_stack::Ipv4Stack::new(
+//!         eth_driver, eth_driver.mac_address(), tx_buffer,
+//!         kernel::Grant::create(capsules::driver::NUM::Ipv4Udp as usize)));
+//! eth_driver.set_ip_client(ipv4);
+//! ```
+
+use core::cell::Cell;
+
+use kernel::common::cells::TakeCell;
+use kernel::hil::ethernet::{EthernetClient, MacAddress};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+use crate::ethernet_driver::KernelFrameSender;
+
+pub const DRIVER_NUM: usize = driver::NUM::Ipv4Udp as usize;
+
+/// Destination MAC (6) + source MAC (6) + EtherType (2).
+const ETH_HEADER_LEN: usize = 14;
+const ETHERTYPE_ARP: u16 = 0x0806;
+const ETHERTYPE_IPV4: u16 = 0x0800;
+
+/// HTYPE/PTYPE/HLEN/PLEN/OPER (8) + sender MAC (6) + sender IP (4) +
+/// target MAC (6) + target IP (4), the fixed Ethernet/IPv4 ARP packet
+/// this stack speaks (RFC 826).
+const ARP_PACKET_LEN: usize = 28;
+const ARP_OP_REQUEST: u16 = 1;
+const ARP_OP_REPLY: u16 = 2;
+
+/// Version/IHL + DSCP/ECN + total length (2) + identification (2) +
+/// flags/fragment offset (2) + TTL + protocol + header checksum (2) +
+/// source IP (4) + dest IP (4); this stack never sends or accepts
+/// options, so this is also the whole header.
+const IPV4_HEADER_LEN: usize = 20;
+const PROTOCOL_ICMP: u8 = 1;
+const PROTOCOL_UDP: u8 = 17;
+
+const ICMP_HEADER_LEN: usize = 8;
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMP_ECHO_REQUEST: u8 = 8;
+
+/// Source port (2) + dest port (2) + length (2) + checksum (2).
+const UDP_HEADER_LEN: usize = 8;
+const DHCP_CLIENT_PORT: u16 = 68;
+const DHCP_SERVER_PORT: u16 = 67;
+
+/// `op` through `file` (RFC 2131 figure 1), not including the 4-byte
+/// magic cookie that follows it or the variable-length options after
+/// that.
+const DHCP_FIXED_LEN: usize = 236;
+const DHCP_MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+
+mod dhcp_option {
+    pub const ROUTER: u8 = 3;
+    pub const REQUESTED_IP: u8 = 50;
+    pub const MESSAGE_TYPE: u8 = 53;
+    pub const END: u8 = 255;
+}
+
+mod dhcp_msg_type {
+    pub const DISCOVER: u8 = 1;
+    pub const OFFER: u8 = 2;
+    pub const REQUEST: u8 = 3;
+    pub const ACK: u8 = 5;
+    pub const NAK: u8 = 6;
+}
+
+/// Sockets (bound ports) available per process.
+const MAX_SOCKETS_PER_APP: usize = 4;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Ipv4Address(pub [u8; 4]);
+
+impl Ipv4Address {
+    const UNSPECIFIED: Ipv4Address = Ipv4Address([0, 0, 0, 0]);
+    const BROADCAST: Ipv4Address = Ipv4Address([255, 255, 255, 255]);
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DhcpState {
+    /// No address yet and `DISCOVER` has not gone out (before link up,
+    /// or after link down); a `NAK` restarts from `DISCOVER` instead.
+    Init,
+    /// `DISCOVER` sent, waiting for an `OFFER`.
+    Selecting,
+    /// `REQUEST` sent for an offered address, waiting for `ACK`/`NAK`.
+    Requesting,
+    /// Address acquired and usable.
+    Bound,
+}
+
+mod upcall {
+    pub const DHCP_BOUND: usize = 0;
+    pub const RECEIVED: usize = 1;
+    pub const SEND_DONE: usize = 2;
+}
+
+mod cmd {
+    /// Binds the calling process to `data1` (a `u16` local port).
+    pub const BIND: usize = 0;
+    /// Sends `data1` payload bytes from the buffer allowed at index 0,
+    /// to `data2`'s low 16 bits as the remote port at the 4-byte
+    /// remote address allowed at index 1, from whichever local port
+    /// this process bound first; the datagram is always framed to the
+    /// default gateway's MAC address (see the module documentation),
+    /// not a per-destination resolved one. `EOFF` if the gateway's
+    /// address has not yet been ARP-resolved.
+    pub const SEND: usize = 1;
+}
+
+/// Which in-flight send owns the next `transmit_done`; everything but
+/// an app's own `SEND` is this stack's own background traffic and has
+/// no process waiting on it.
+#[derive(Copy, Clone)]
+enum TxOwner {
+    App(AppId),
+    Arp,
+    Dhcp,
+    IcmpReply,
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    bound_ports: [Option<u16>; MAX_SOCKETS_PER_APP],
+    /// The payload for the next `SEND`, allowed at index 0.
+    payload: Option<AppSlice<Shared, u8>>,
+    /// The 4-byte remote IPv4 address for the next `SEND`, allowed at
+    /// index 1.
+    remote_ip: Option<AppSlice<Shared, u8>>,
+}
+
+impl App {
+    fn is_bound(&self, port: u16) -> bool {
+        self.bound_ports.iter().any(|p| *p == Some(port))
+    }
+}
+
+pub struct Ipv4Stack<'a> {
+    ethernet: &'a dyn KernelFrameSender<'a>,
+    mac_address: MacAddress,
+    tx_buffer: TakeCell<'static, [u8]>,
+    current_owner: Cell<Option<TxOwner>>,
+    our_ip: Cell<Ipv4Address>,
+    gateway_ip: Cell<Ipv4Address>,
+    gateway_mac: Cell<Option<[u8; 6]>>,
+    dhcp_state: Cell<DhcpState>,
+    dhcp_xid: Cell<u32>,
+    apps: Grant<App>,
+}
+
+impl<'a> Ipv4Stack<'a> {
+    pub fn new(ethernet: &'a dyn KernelFrameSender<'a>, mac_address: MacAddress, tx_buffer: &'static mut [u8], apps: Grant<App>) -> Ipv4Stack<'a> {
+        Ipv4Stack {
+            ethernet,
+            mac_address,
+            tx_buffer: TakeCell::new(tx_buffer),
+            current_owner: Cell::new(None),
+            our_ip: Cell::new(Ipv4Address::UNSPECIFIED),
+            gateway_ip: Cell::new(Ipv4Address::UNSPECIFIED),
+            gateway_mac: Cell::new(None),
+            dhcp_state: Cell::new(DhcpState::Init),
+            dhcp_xid: Cell::new(0),
+            apps,
+        }
+    }
+
+    /// Starts (or restarts) DHCP from scratch, discarding any address
+    /// this board already held.
+    pub fn start_dhcp(&self) {
+        self.our_ip.set(Ipv4Address::UNSPECIFIED);
+        self.gateway_mac.set(None);
+        self.dhcp_state.set(DhcpState::Selecting);
+        // Not drawn from an entropy source — this tree has no RNG HIL
+        // wired to this capsule — but unique enough per attempt to
+        // tell a retry's reply apart from a stale one's.
+        self.dhcp_xid.set(self.dhcp_xid.get().wrapping_add(1));
+        self.send_dhcp(dhcp_msg_type::DISCOVER, Ipv4Address::UNSPECIFIED);
+    }
+
+    fn transmit(&self, buffer: &'static mut [u8], len: usize, owner: TxOwner) {
+        self.current_owner.set(Some(owner));
+        let _ = self.ethernet.send_frame(buffer, len);
+    }
+
+    fn eth_header(buffer: &mut [u8], dst_mac: [u8; 6], ethertype: u16, src_mac: [u8; 6]) {
+        buffer[0..6].copy_from_slice(&dst_mac);
+        buffer[6..12].copy_from_slice(&src_mac);
+        buffer[12..14].copy_from_slice(&ethertype.to_be_bytes());
+    }
+
+    fn send_arp(&self, op: u16, target_mac: [u8; 6], target_ip: Ipv4Address) {
+        if let Some(buffer) = self.tx_buffer.take() {
+            let MacAddress(src_mac) = self.mac_address;
+            let dst_mac = if op == ARP_OP_REQUEST { [0xff; 6] } else { target_mac };
+            Self::eth_header(buffer, dst_mac, ETHERTYPE_ARP, src_mac);
+            let arp = &mut buffer[ETH_HEADER_LEN..ETH_HEADER_LEN + ARP_PACKET_LEN];
+            arp[0..2].copy_from_slice(&1u16.to_be_bytes()); // HTYPE: Ethernet
+            arp[2..4].copy_from_slice(&ETHERTYPE_IPV4.to_be_bytes()); // PTYPE
+            arp[4] = 6; // HLEN
+            arp[5] = 4; // PLEN
+            arp[6..8].copy_from_slice(&op.to_be_bytes());
+            arp[8..14].copy_from_slice(&src_mac);
+            arp[14..18].copy_from_slice(&self.our_ip.get().0);
+            arp[18..24].copy_from_slice(&target_mac);
+            arp[24..28].copy_from_slice(&target_ip.0);
+            self.transmit(buffer, ETH_HEADER_LEN + ARP_PACKET_LEN, TxOwner::Arp);
+        }
+    }
+
+    fn handle_arp(&self, payload: &[u8]) {
+        if payload.len() < ARP_PACKET_LEN {
+            return;
+        }
+        let op = u16::from_be_bytes([payload[6], payload[7]]);
+        let mut sender_mac = [0u8; 6];
+        sender_mac.copy_from_slice(&payload[8..14]);
+        let sender_ip = Ipv4Address([payload[14], payload[15], payload[16], payload[17]]);
+        let target_ip = Ipv4Address([payload[24], payload[25], payload[26], payload[27]]);
+
+        if op == ARP_OP_REQUEST && target_ip == self.our_ip.get() {
+            self.send_arp(ARP_OP_REPLY, sender_mac, sender_ip);
+        } else if op == ARP_OP_REPLY && sender_ip == self.gateway_ip.get() {
+            self.gateway_mac.set(Some(sender_mac));
+        }
+    }
+
+    /// Writes the 8-byte UDP header for a `payload_len`-byte payload
+    /// from `src_port` to `dst_port` at `buffer[offset..]`.
+    fn send_udp_header(buffer: &mut [u8], offset: usize, src_port: u16, dst_port: u16, payload_len: usize) {
+        let udp = &mut buffer[offset..offset + UDP_HEADER_LEN];
+        udp[0..2].copy_from_slice(&src_port.to_be_bytes());
+        udp[2..4].copy_from_slice(&dst_port.to_be_bytes());
+        udp[4..6].copy_from_slice(&((UDP_HEADER_LEN + payload_len) as u16).to_be_bytes());
+        // UDP checksum is optional over IPv4; RFC 768 allows all
+        // zeroes to mean "not computed", which this stack relies on
+        // rather than summing the payload and a pseudo-header.
+        udp[6..8].copy_from_slice(&0u16.to_be_bytes());
+    }
+
+    fn send_ipv4_header(buffer: &mut [u8], offset: usize, src_ip: Ipv4Address, dst_ip: Ipv4Address, protocol: u8, body_len: usize) {
+        let ip = &mut buffer[offset..offset + IPV4_HEADER_LEN];
+        ip[0] = 0x45; // version 4, IHL 5 (no options)
+        ip[1] = 0;
+        ip[2..4].copy_from_slice(&((IPV4_HEADER_LEN + body_len) as u16).to_be_bytes());
+        ip[4..6].copy_from_slice(&0u16.to_be_bytes()); // identification
+        ip[6..8].copy_from_slice(&0u16.to_be_bytes()); // flags/fragment offset
+        ip[8] = 64; // TTL
+        ip[9] = protocol;
+        ip[10..12].copy_from_slice(&0u16.to_be_bytes()); // checksum, filled below
+        ip[12..16].copy_from_slice(&src_ip.0);
+        ip[16..20].copy_from_slice(&dst_ip.0);
+        let sum = checksum(&buffer[offset..offset + IPV4_HEADER_LEN]).to_be_bytes();
+        buffer[offset + 10..offset + 12].copy_from_slice(&sum);
+    }
+
+    fn send_dhcp(&self, message_type: u8, requested_ip: Ipv4Address) {
+        if let Some(buffer) = self.tx_buffer.take() {
+            let MacAddress(src_mac) = self.mac_address;
+            Self::eth_header(buffer, [0xff; 6], ETHERTYPE_IPV4, src_mac);
+            let udp_offset = ETH_HEADER_LEN + IPV4_HEADER_LEN;
+            let dhcp_offset = udp_offset + UDP_HEADER_LEN;
+
+            let dhcp = &mut buffer[dhcp_offset..dhcp_offset + DHCP_FIXED_LEN];
+            dhcp.fill(0);
+            dhcp[0] = 1; // op: BOOTREQUEST
+            dhcp[1] = 1; // htype: Ethernet
+            dhcp[2] = 6; // hlen
+            dhcp[4..8].copy_from_slice(&self.dhcp_xid.get().to_be_bytes());
+            dhcp[28..34].copy_from_slice(&src_mac); // chaddr
+
+            let options_offset = dhcp_offset + DHCP_FIXED_LEN;
+            buffer[options_offset..options_offset + 4].copy_from_slice(&DHCP_MAGIC_COOKIE);
+            let mut i = options_offset + 4;
+            buffer[i] = dhcp_option::MESSAGE_TYPE;
+            buffer[i + 1] = 1;
+            buffer[i + 2] = message_type;
+            i += 3;
+            if message_type == dhcp_msg_type::REQUEST {
+                buffer[i] = dhcp_option::REQUESTED_IP;
+                buffer[i + 1] = 4;
+                buffer[i + 2..i + 6].copy_from_slice(&requested_ip.0);
+                i += 6;
+            }
+            buffer[i] = dhcp_option::END;
+            i += 1;
+
+            let dhcp_len = i - dhcp_offset;
+            Self::send_udp_header(buffer, udp_offset, DHCP_CLIENT_PORT, DHCP_SERVER_PORT, dhcp_len);
+            Self::send_ipv4_header(buffer, ETH_HEADER_LEN, Ipv4Address::UNSPECIFIED, Ipv4Address::BROADCAST, PROTOCOL_UDP, UDP_HEADER_LEN + dhcp_len);
+
+            self.transmit(buffer, i, TxOwner::Dhcp);
+        }
+    }
+
+    /// Frames and sends a UDP datagram carrying `payload` to
+    /// `dst_ip`/`dst_port` from `src_port`, addressed to the default
+    /// gateway's MAC (see the module documentation).
+    fn send_udp(&self, src_port: u16, dst_port: u16, dst_ip: Ipv4Address, payload: &[u8], owner: TxOwner) -> ReturnCode {
+        let gateway_mac = match self.gateway_mac.get() {
+            Some(mac) => mac,
+            None => return ReturnCode::EOFF,
+        };
+        let buffer = match self.tx_buffer.take() {
+            Some(buffer) => buffer,
+            None => return ReturnCode::EBUSY,
+        };
+        let udp_offset = ETH_HEADER_LEN + IPV4_HEADER_LEN;
+        let payload_offset = udp_offset + UDP_HEADER_LEN;
+        if payload_offset + payload.len() > buffer.len() {
+            self.tx_buffer.replace(buffer);
+            return ReturnCode::ESIZE;
+        }
+        let MacAddress(src_mac) = self.mac_address;
+        Self::eth_header(buffer, gateway_mac, ETHERTYPE_IPV4, src_mac);
+        buffer[payload_offset..payload_offset + payload.len()].copy_from_slice(payload);
+        Self::send_udp_header(buffer, udp_offset, src_port, dst_port, payload.len());
+        Self::send_ipv4_header(buffer, ETH_HEADER_LEN, self.our_ip.get(), dst_ip, PROTOCOL_UDP, UDP_HEADER_LEN + payload.len());
+        self.transmit(buffer, payload_offset + payload.len(), owner);
+        ReturnCode::SUCCESS
+    }
+
+    fn handle_dhcp(&self, body: &[u8]) {
+        if body.len() < DHCP_FIXED_LEN + DHCP_MAGIC_COOKIE.len() {
+            return;
+        }
+        if u32::from_be_bytes([body[4], body[5], body[6], body[7]]) != self.dhcp_xid.get() {
+            return;
+        }
+        let your_ip = Ipv4Address([body[16], body[17], body[18], body[19]]);
+        if &body[DHCP_FIXED_LEN..DHCP_FIXED_LEN + 4] != &DHCP_MAGIC_COOKIE[..] {
+            return;
+        }
+
+        let mut message_type = 0u8;
+        let mut router = None;
+        let options = &body[DHCP_FIXED_LEN + 4..];
+        let mut i = 0;
+        while i < options.len() {
+            let tag = options[i];
+            if tag == dhcp_option::END {
+                break;
+            }
+            if tag == 0 {
+                i += 1;
+                continue;
+            }
+            if i + 1 >= options.len() {
+                break;
+            }
+            let len = options[i + 1] as usize;
+            if i + 2 + len > options.len() {
+                break;
+            }
+            let value = &options[i + 2..i + 2 + len];
+            match tag {
+                dhcp_option::MESSAGE_TYPE if len == 1 => message_type = value[0],
+                dhcp_option::ROUTER if len >= 4 => router = Some(Ipv4Address([value[0], value[1], value[2], value[3]])),
+                _ => {}
+            }
+            i += 2 + len;
+        }
+
+        match (self.dhcp_state.get(), message_type) {
+            (DhcpState::Selecting, dhcp_msg_type::OFFER) => {
+                self.dhcp_state.set(DhcpState::Requesting);
+                self.send_dhcp(dhcp_msg_type::REQUEST, your_ip);
+            }
+            (DhcpState::Requesting, dhcp_msg_type::ACK) => {
+                self.our_ip.set(your_ip);
+                if let Some(gw) = router {
+                    self.gateway_ip.set(gw);
+                    self.gateway_mac.set(None);
+                    self.send_arp(ARP_OP_REQUEST, [0; 6], gw);
+                }
+                self.dhcp_state.set(DhcpState::Bound);
+                for app_id in self.apps.iter() {
+                    let _ = self.apps.enter(app_id, |app, _| {
+                        if let Some(mut cb) = app.callback {
+                            cb.schedule(upcall::DHCP_BOUND, u32::from_be_bytes(your_ip.0) as usize, 0);
+                        }
+                    });
+                }
+            }
+            (DhcpState::Requesting, dhcp_msg_type::NAK) => {
+                self.start_dhcp();
+            }
+            _ => {}
+        }
+    }
+
+    fn send_icmp_echo_reply(&self, dst_ip: Ipv4Address, request: &[u8]) {
+        if request.len() < ICMP_HEADER_LEN {
+            return;
+        }
+        let gateway_mac = match self.gateway_mac.get() {
+            Some(mac) => mac,
+            None => return,
+        };
+        if let Some(buffer) = self.tx_buffer.take() {
+            let MacAddress(src_mac) = self.mac_address;
+            Self::eth_header(buffer, gateway_mac, ETHERTYPE_IPV4, src_mac);
+            let icmp_offset = ETH_HEADER_LEN + IPV4_HEADER_LEN;
+            let icmp_len = request.len();
+            buffer[icmp_offset..icmp_offset + icmp_len].copy_from_slice(request);
+            buffer[icmp_offset] = ICMP_ECHO_REPLY;
+            buffer[icmp_offset + 1] = 0;
+            buffer[icmp_offset + 2..icmp_offset + 4].copy_from_slice(&0u16.to_be_bytes());
+            let sum = checksum(&buffer[icmp_offset..icmp_offset + icmp_len]).to_be_bytes();
+            buffer[icmp_offset + 2..icmp_offset + 4].copy_from_slice(&sum);
+
+            Self::send_ipv4_header(buffer, ETH_HEADER_LEN, self.our_ip.get(), dst_ip, PROTOCOL_ICMP, icmp_len);
+            self.transmit(buffer, icmp_offset + icmp_len, TxOwner::IcmpReply);
+        }
+    }
+
+    fn handle_icmp(&self, src_ip: Ipv4Address, body: &[u8]) {
+        if body.len() >= ICMP_HEADER_LEN && body[0] == ICMP_ECHO_REQUEST {
+            self.send_icmp_echo_reply(src_ip, body);
+        }
+    }
+
+    fn handle_udp(&self, body: &[u8]) {
+        if body.len() < UDP_HEADER_LEN {
+            return;
+        }
+        let src_port = u16::from_be_bytes([body[0], body[1]]);
+        let dst_port = u16::from_be_bytes([body[2], body[3]]);
+        let payload_len = body.len() - UDP_HEADER_LEN;
+
+        if dst_port == DHCP_CLIENT_PORT {
+            self.handle_dhcp(&body[UDP_HEADER_LEN..]);
+            return;
+        }
+
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if app.is_bound(dst_port) {
+                    if let Some(mut cb) = app.callback {
+                        cb.schedule(upcall::RECEIVED, src_port as usize, payload_len);
+                    }
+                }
+            });
+        }
+    }
+
+    fn handle_ipv4(&self, payload: &[u8]) {
+        if payload.len() < IPV4_HEADER_LEN || payload[0] >> 4 != 4 {
+            return;
+        }
+        let ihl = ((payload[0] & 0x0f) as usize) * 4;
+        if ihl < IPV4_HEADER_LEN || payload.len() < ihl {
+            return;
+        }
+        let total_len = core::cmp::min(u16::from_be_bytes([payload[2], payload[3]]) as usize, payload.len());
+        let protocol = payload[9];
+        let src_ip = Ipv4Address([payload[12], payload[13], payload[14], payload[15]]);
+        let dst_ip = Ipv4Address([payload[16], payload[17], payload[18], payload[19]]);
+        if dst_ip != self.our_ip.get() && dst_ip != Ipv4Address::BROADCAST {
+            return;
+        }
+        if total_len < ihl {
+            return;
+        }
+        let body = &payload[ihl..total_len];
+        match protocol {
+            PROTOCOL_ICMP => self.handle_icmp(src_ip, body),
+            PROTOCOL_UDP => self.handle_udp(body),
+            _ => {}
+        }
+    }
+}
+
+/// The standard Internet checksum (RFC 1071): the one's complement of
+/// the one's complement sum of `data` as big-endian 16-bit words.
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = 0;
+    let mut chunks = data.chunks_exact(2);
+    for chunk in &mut chunks {
+        sum += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+    }
+    if let [last] = chunks.remainder() {
+        sum += (*last as u32) << 8;
+    }
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+impl<'a> Driver for Ipv4Stack<'a> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::DHCP_BOUND | upcall::RECEIVED | upcall::SEND_DONE => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.payload = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            1 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.remote_ip = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::BIND => {
+                let port = data1 as u16;
+                for app_id_other in self.apps.iter() {
+                    let bound_elsewhere = self.apps.enter(app_id_other, |app, _| app.is_bound(port)).unwrap_or(false);
+                    if bound_elsewhere {
+                        return ReturnCode::EALREADY;
+                    }
+                }
+                self.apps
+                    .enter(app_id, |app, _| match app.bound_ports.iter().position(|p| p.is_none()) {
+                        Some(slot) => {
+                            app.bound_ports[slot] = Some(port);
+                            ReturnCode::SUCCESS
+                        }
+                        None => ReturnCode::ENOMEM,
+                    })
+                    .unwrap_or(ReturnCode::FAIL)
+            }
+            cmd::SEND => {
+                if self.dhcp_state.get() != DhcpState::Bound || self.gateway_mac.get().is_none() {
+                    return ReturnCode::EOFF;
+                }
+                if self.current_owner.get().is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                let payload_len = data1;
+                let dst_port = (data2 & 0xffff) as u16;
+                self.apps
+                    .enter(app_id, |app, _| {
+                        let src_port = match app.bound_ports.iter().flatten().next() {
+                            Some(&port) => port,
+                            None => return ReturnCode::EINVAL,
+                        };
+                        let payload_slice = match &app.payload {
+                            Some(slice) if slice.len() >= payload_len => slice,
+                            Some(_) => return ReturnCode::ESIZE,
+                            None => return ReturnCode::EINVAL,
+                        };
+                        let ip_slice = match &app.remote_ip {
+                            Some(slice) if slice.len() >= 4 => slice,
+                            Some(_) => return ReturnCode::ESIZE,
+                            None => return ReturnCode::EINVAL,
+                        };
+                        let dst_ip = Ipv4Address([ip_slice.as_ref()[0], ip_slice.as_ref()[1], ip_slice.as_ref()[2], ip_slice.as_ref()[3]]);
+                        self.send_udp(src_port, dst_port, dst_ip, &payload_slice.as_ref()[..payload_len], TxOwner::App(app_id))
+                    })
+                    .unwrap_or(ReturnCode::FAIL)
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a> EthernetClient for Ipv4Stack<'a> {
+    fn init_done(&self, result: ReturnCode) {
+        if result == ReturnCode::SUCCESS {
+            self.start_dhcp();
+        }
+    }
+
+    fn transmit_done(&self, buffer: &'static mut [u8], result: ReturnCode) {
+        match self.current_owner.take() {
+            Some(TxOwner::App(app_id)) => {
+                self.tx_buffer.replace(buffer);
+                let _ = self.apps.enter(app_id, |app, _| {
+                    if let Some(mut cb) = app.callback {
+                        cb.schedule(upcall::SEND_DONE, usize::from(result), 0);
+                    }
+                });
+            }
+            Some(TxOwner::Arp) | Some(TxOwner::Dhcp) | Some(TxOwner::IcmpReply) | None => {
+                self.tx_buffer.replace(buffer);
+            }
+        }
+    }
+
+    fn receive(&self, buffer: &[u8], len: usize) {
+        if len < ETH_HEADER_LEN {
+            return;
+        }
+        let ethertype = u16::from_be_bytes([buffer[12], buffer[13]]);
+        let payload = &buffer[ETH_HEADER_LEN..len];
+        match ethertype {
+            ETHERTYPE_ARP => self.handle_arp(payload),
+            ETHERTYPE_IPV4 => self.handle_ipv4(payload),
+            _ => {}
+        }
+    }
+
+    fn link_state_changed(&self, link_up: bool) {
+        if link_up {
+            self.start_dhcp();
+        } else {
+            self.dhcp_state.set(DhcpState::Init);
+            self.gateway_mac.set(None);
+        }
+    }
+}
@@ -0,0 +1,354 @@
+//! `hil::ip::IpLayer` over `hil::radio::Radio`: a minimal IPv6 layer
+//! that answers ICMPv6 echo requests, solicits a router on start, and
+//! configures a global address by SLAAC (RFC 4862) from whatever
+//! /64 prefix a router advertisement offers, so a transport capsule
+//! built on `hil::ip` (`capsules::tcp`) gets a real, reachable address
+//! without a board having to hand it one.
+//!
+//! Like `capsules::sixlowpan`, frames are exchanged with the radio
+//! uncompressed and unfragmented — there is no 6LoWPAN IPHC header
+//! compression here, just a full 40-byte IPv6 header — and 802.15.4
+//! MAC addressing is left to the radio driver itself. Neighbor
+//! solicitation/advertisement (on-link address resolution, duplicate
+//! address detection) and router lifetimes/renewal are not
+//! implemented; once a global address is configured it is kept until
+//! the board reboots or a later router advertisement offers a
+//! different /64 prefix.
+//!
+//! The interface identifier used for both the link-local and any
+//! SLAAC address is supplied by the board at construction (typically
+//! derived from the radio's extended address) rather than generated
+//! here.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let ipv6 = static_init!(
+//!     capsules::ipv6_layer::Ipv6Layer<'static>,
+//!     capsules::ipv6_layer::Ipv6Layer::new(
+//!         radio, tx_buffer, interface_id,
+//!         kernel::Grant::create(capsules::driver::NUM::Ipv6Layer as usize)));
+//! radio.set_transmit_client(ipv6);
+//! radio.set_receive_client(ipv6);
+//! let _ = radio.start_receiving();
+//! ipv6.start();
+//! ```
+
+use core::cell::Cell;
+
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::ip::{IpClient, IpLayer, Ipv6Address};
+use kernel::hil::radio::{Radio, RxClient, TxClient};
+use kernel::{AppId, AppSlice, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Ipv6Layer as usize;
+
+/// Version/traffic class/flow label (4) + payload length (2) + next
+/// header (1) + hop limit (1) + source address (16) + destination
+/// address (16); this layer never sends or expects extension headers.
+const IPV6_HEADER_LEN: usize = 40;
+const NEXT_HEADER_ICMPV6: u8 = 58;
+
+const ICMPV6_ECHO_REQUEST: u8 = 128;
+const ICMPV6_ECHO_REPLY: u8 = 129;
+const ICMPV6_ROUTER_SOLICITATION: u8 = 133;
+const ICMPV6_ROUTER_ADVERTISEMENT: u8 = 134;
+/// Type(1) + code(1) + checksum(2) + reserved(4), the whole router
+/// solicitation this layer sends; it carries no source link-layer
+/// address option.
+const ROUTER_SOLICITATION_LEN: usize = 8;
+/// Type(1) + code(1) + checksum(2) + cur hop limit(1) + flags(1) +
+/// router lifetime(2) + reachable time(4) + retrans timer(4), before
+/// any options.
+const ROUTER_ADVERTISEMENT_FIXED_LEN: usize = 16;
+const ND_OPTION_PREFIX_INFORMATION: u8 = 3;
+
+const ALL_ROUTERS_MULTICAST: Ipv6Address = Ipv6Address([0xff, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x02]);
+
+mod cmd {
+    /// Copies the 16-byte address named by `data1` (0: link-local, 1:
+    /// SLAAC global) into the buffer allowed at index 0. `EOFF` if
+    /// `data1` names the global address and none has been configured
+    /// yet; `EINVAL` if the buffer is too small, not allowed, or
+    /// `data1` names neither address.
+    pub const GET_ADDRESS: usize = 0;
+}
+
+#[derive(Default)]
+pub struct App {
+    address_buffer: Option<AppSlice<Shared, u8>>,
+}
+
+/// Which in-flight send owns the next `transmit_done`; only a
+/// client's own send is reported back out through `IpClient::send_done`.
+#[derive(Copy, Clone)]
+enum TxOwner {
+    Client,
+    RouterSolicitation,
+    EchoReply,
+}
+
+pub struct Ipv6Layer<'a> {
+    radio: &'a dyn Radio<'a>,
+    client: OptionalCell<&'a dyn IpClient>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    /// A client's own send buffer, held here while its headered copy
+    /// is in flight on `tx_buffer`, so it can be handed back to
+    /// `send_done` once that completes.
+    pending_send_buffer: TakeCell<'static, [u8]>,
+    current_owner: Cell<Option<TxOwner>>,
+    interface_id: [u8; 8],
+    link_local: Ipv6Address,
+    global: Cell<Option<Ipv6Address>>,
+    apps: Grant<App>,
+}
+
+impl<'a> Ipv6Layer<'a> {
+    pub fn new(radio: &'a dyn Radio<'a>, tx_buffer: &'static mut [u8], interface_id: [u8; 8], apps: Grant<App>) -> Ipv6Layer<'a> {
+        let mut link_local = [0u8; 16];
+        link_local[0] = 0xfe;
+        link_local[1] = 0x80;
+        link_local[8..16].copy_from_slice(&interface_id);
+        Ipv6Layer {
+            radio,
+            client: OptionalCell::empty(),
+            tx_buffer: TakeCell::new(tx_buffer),
+            pending_send_buffer: TakeCell::empty(),
+            current_owner: Cell::new(None),
+            interface_id,
+            link_local: Ipv6Address(link_local),
+            global: Cell::new(None),
+            apps,
+        }
+    }
+
+    /// Solicits a router so this board can configure a global address
+    /// by SLAAC; a board calls this once its radio is receiving.
+    pub fn start(&self) {
+        self.send_router_solicitation();
+    }
+
+    fn source_address(&self) -> Ipv6Address {
+        self.global.get().unwrap_or(self.link_local)
+    }
+
+    fn transmit(&self, buffer: &'static mut [u8], len: usize, owner: TxOwner) -> ReturnCode {
+        self.current_owner.set(Some(owner));
+        self.radio.transmit(buffer, len)
+    }
+
+    fn write_ipv6_header(buffer: &mut [u8], src: Ipv6Address, dst: Ipv6Address, next_header: u8, payload_len: usize) {
+        let header = &mut buffer[0..IPV6_HEADER_LEN];
+        header[0] = 0x60; // version 6, traffic class/flow label left zero
+        header[1] = 0;
+        header[2] = 0;
+        header[3] = 0;
+        header[4..6].copy_from_slice(&(payload_len as u16).to_be_bytes());
+        header[6] = next_header;
+        header[7] = 64; // hop limit
+        header[8..24].copy_from_slice(&src.0);
+        header[24..40].copy_from_slice(&dst.0);
+    }
+
+    fn send_router_solicitation(&self) {
+        if let Some(buffer) = self.tx_buffer.take() {
+            let icmp = &mut buffer[IPV6_HEADER_LEN..IPV6_HEADER_LEN + ROUTER_SOLICITATION_LEN];
+            icmp[0] = ICMPV6_ROUTER_SOLICITATION;
+            icmp[1] = 0;
+            icmp[2..4].copy_from_slice(&0u16.to_be_bytes());
+            icmp[4..8].copy_from_slice(&0u32.to_be_bytes());
+            let sum = icmpv6_checksum(self.link_local, ALL_ROUTERS_MULTICAST, &buffer[IPV6_HEADER_LEN..IPV6_HEADER_LEN + ROUTER_SOLICITATION_LEN]).to_be_bytes();
+            buffer[IPV6_HEADER_LEN + 2..IPV6_HEADER_LEN + 4].copy_from_slice(&sum);
+            Self::write_ipv6_header(buffer, self.link_local, ALL_ROUTERS_MULTICAST, NEXT_HEADER_ICMPV6, ROUTER_SOLICITATION_LEN);
+            let _ = self.transmit(buffer, IPV6_HEADER_LEN + ROUTER_SOLICITATION_LEN, TxOwner::RouterSolicitation);
+        }
+    }
+
+    fn handle_router_advertisement(&self, body: &[u8]) {
+        if body.len() < ROUTER_ADVERTISEMENT_FIXED_LEN {
+            return;
+        }
+        let mut i = ROUTER_ADVERTISEMENT_FIXED_LEN;
+        while i + 2 <= body.len() {
+            let option_type = body[i];
+            let option_len = (body[i + 1] as usize) * 8;
+            if option_len == 0 || i + option_len > body.len() {
+                break;
+            }
+            if option_type == ND_OPTION_PREFIX_INFORMATION && option_len == 32 {
+                let prefix_length = body[i + 2];
+                // Only a /64 prefix leaves exactly enough room for
+                // this board's 64-bit interface identifier; any other
+                // length is not modeled.
+                if prefix_length == 64 {
+                    let mut address = [0u8; 16];
+                    address[0..8].copy_from_slice(&body[i + 16..i + 24]);
+                    address[8..16].copy_from_slice(&self.interface_id);
+                    self.global.set(Some(Ipv6Address(address)));
+                }
+            }
+            i += option_len;
+        }
+    }
+
+    fn send_icmpv6_echo_reply(&self, dst: Ipv6Address, request: &[u8]) {
+        if let Some(buffer) = self.tx_buffer.take() {
+            let icmp_len = request.len();
+            buffer[IPV6_HEADER_LEN..IPV6_HEADER_LEN + icmp_len].copy_from_slice(request);
+            buffer[IPV6_HEADER_LEN] = ICMPV6_ECHO_REPLY;
+            buffer[IPV6_HEADER_LEN + 1] = 0;
+            buffer[IPV6_HEADER_LEN + 2..IPV6_HEADER_LEN + 4].copy_from_slice(&0u16.to_be_bytes());
+            let src = self.source_address();
+            let sum = icmpv6_checksum(src, dst, &buffer[IPV6_HEADER_LEN..IPV6_HEADER_LEN + icmp_len]).to_be_bytes();
+            buffer[IPV6_HEADER_LEN + 2..IPV6_HEADER_LEN + 4].copy_from_slice(&sum);
+            Self::write_ipv6_header(buffer, src, dst, NEXT_HEADER_ICMPV6, icmp_len);
+            let _ = self.transmit(buffer, IPV6_HEADER_LEN + icmp_len, TxOwner::EchoReply);
+        }
+    }
+
+    fn handle_icmpv6(&self, src: Ipv6Address, body: &[u8]) {
+        if body.is_empty() {
+            return;
+        }
+        match body[0] {
+            ICMPV6_ECHO_REQUEST => self.send_icmpv6_echo_reply(src, body),
+            ICMPV6_ROUTER_ADVERTISEMENT => self.handle_router_advertisement(body),
+            _ => {}
+        }
+    }
+}
+
+/// Sums `data` as big-endian 16-bit words, padding a trailing odd
+/// byte into the high half of a final word, without folding the
+/// carry or complementing — the partial-sum half of RFC 1071 so
+/// several pieces (a pseudo-header, then the real payload) can be
+/// summed separately and combined before the one final fold.
+fn checksum_sum(data: &[u8]) -> u32 {
+    let mut sum: u32 = 0;
+    let mut chunks = data.chunks_exact(2);
+    for chunk in &mut chunks {
+        sum += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+    }
+    if let [last] = chunks.remainder() {
+        sum += (*last as u32) << 8;
+    }
+    sum
+}
+
+fn checksum_fold(mut sum: u32) -> u16 {
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// The ICMPv6 checksum (RFC 8200 §8.1): the real ICMPv6 message summed
+/// behind the IPv6 pseudo-header (source/destination address, upper-
+/// layer packet length, and next header), since ICMPv6 has no
+/// checksum-optional escape the way UDP-over-IPv4 does.
+fn icmpv6_checksum(src: Ipv6Address, dst: Ipv6Address, icmp: &[u8]) -> u16 {
+    let mut sum = checksum_sum(&src.0);
+    sum += checksum_sum(&dst.0);
+    sum += checksum_sum(&(icmp.len() as u32).to_be_bytes());
+    sum += checksum_sum(&[0, 0, 0, NEXT_HEADER_ICMPV6]);
+    sum += checksum_sum(icmp);
+    checksum_fold(sum)
+}
+
+impl<'a> IpLayer<'a> for Ipv6Layer<'a> {
+    fn set_client(&self, client: &'a dyn IpClient) {
+        self.client.set(client);
+    }
+
+    fn send(&self, dest: Ipv6Address, protocol: u8, buffer: &'static mut [u8], len: usize) -> ReturnCode {
+        if self.current_owner.get().is_some() {
+            return ReturnCode::EBUSY;
+        }
+        match self.tx_buffer.take() {
+            Some(frame) => {
+                if len > frame.len() - IPV6_HEADER_LEN {
+                    self.tx_buffer.replace(frame);
+                    return ReturnCode::ESIZE;
+                }
+                frame[IPV6_HEADER_LEN..IPV6_HEADER_LEN + len].copy_from_slice(&buffer[..len]);
+                Self::write_ipv6_header(frame, self.source_address(), dest, protocol, len);
+                self.pending_send_buffer.replace(buffer);
+                self.transmit(frame, IPV6_HEADER_LEN + len, TxOwner::Client)
+            }
+            None => ReturnCode::EBUSY,
+        }
+    }
+}
+
+impl<'a> Driver for Ipv6Layer<'a> {
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.address_buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, _data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::GET_ADDRESS => {
+                let address = match data1 {
+                    0 => self.link_local,
+                    1 => match self.global.get() {
+                        Some(address) => address,
+                        None => return ReturnCode::EOFF,
+                    },
+                    _ => return ReturnCode::EINVAL,
+                };
+                self.apps
+                    .enter(app_id, |app, _| match &mut app.address_buffer {
+                        Some(slice) if slice.len() >= address.0.len() => {
+                            slice.as_mut()[..address.0.len()].copy_from_slice(&address.0);
+                            ReturnCode::SUCCESS
+                        }
+                        _ => ReturnCode::EINVAL,
+                    })
+                    .unwrap_or(ReturnCode::FAIL)
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a> TxClient for Ipv6Layer<'a> {
+    fn transmit_done(&self, buffer: &'static mut [u8], result: ReturnCode) {
+        let owner = self.current_owner.take();
+        self.tx_buffer.replace(buffer);
+        if let Some(TxOwner::Client) = owner {
+            if let Some(client_buffer) = self.pending_send_buffer.take() {
+                self.client.map(|client| client.send_done(client_buffer, result));
+            }
+        }
+    }
+}
+
+impl<'a> RxClient for Ipv6Layer<'a> {
+    fn receive(&self, buffer: &[u8], len: usize, result: ReturnCode) {
+        if result != ReturnCode::SUCCESS || len < IPV6_HEADER_LEN {
+            return;
+        }
+        let next_header = buffer[6];
+        let src = Ipv6Address([
+            buffer[8], buffer[9], buffer[10], buffer[11], buffer[12], buffer[13], buffer[14], buffer[15], buffer[16], buffer[17], buffer[18], buffer[19], buffer[20], buffer[21], buffer[22], buffer[23],
+        ]);
+        let payload_len = core::cmp::min(u16::from_be_bytes([buffer[4], buffer[5]]) as usize, len - IPV6_HEADER_LEN);
+        let body = &buffer[IPV6_HEADER_LEN..IPV6_HEADER_LEN + payload_len];
+        match next_header {
+            NEXT_HEADER_ICMPV6 => self.handle_icmpv6(src, body),
+            _ => {
+                self.client.map(|client| client.receive(src, next_header, body, body.len()));
+            }
+        }
+    }
+}
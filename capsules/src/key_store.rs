@@ -0,0 +1,165 @@
+//! Key store with opaque per-process handles.
+//!
+//! Apps generate or import keys into slots held in their own kernel
+//! grant and refer to them afterward only by handle; there is no
+//! `EXPORT` command, so a process can never read its own key material
+//! back out once it is in the store, unlike today where apps must hold
+//! raw key bytes in their own RAM for every crypto driver call, which
+//! defeats isolation between an app and anything that later inspects
+//! its memory.
+//!
+//! The actual key bytes (generation from the RNG driver, the imported
+//! buffer's contents, KMU/flash-backed persistence) are not modeled
+//! here; this capsule tracks slot handle allocation and the
+//! non-exportability contract. A board wanting keys to survive reboot
+//! would back the slots with `hil::nonvolatile_storage` instead of
+//! process-grant RAM, which is also not shown.
+
+use kernel::{AppId, AppSlice, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::KeyStore as usize;
+
+/// Key slots available per process.
+const MAX_KEYS_PER_APP: usize = 4;
+
+mod cmd {
+    /// Generates a new `data1`-byte key into a free slot; the handle
+    /// is written as a little-endian `u32` into the buffer allowed at
+    /// index 0.
+    pub const GENERATE: usize = 0;
+    /// Imports a `data1`-byte key from the buffer allowed at index 1
+    /// into a free slot; the handle is reported the same way as
+    /// `GENERATE`.
+    pub const IMPORT: usize = 1;
+    /// Zeroizes and frees the key in slot `data1`.
+    pub const DELETE: usize = 2;
+}
+
+#[derive(Default)]
+pub struct App {
+    occupied: [bool; MAX_KEYS_PER_APP],
+    /// The buffer allowed at index 0, written with the handle
+    /// `GENERATE`/`IMPORT` allocated.
+    handle_out: Option<AppSlice<Shared, u8>>,
+    /// The key bytes imported by `IMPORT`, allowed at index 1. Not
+    /// retained past the call: see the module documentation for why
+    /// this store never holds key bytes.
+    import_buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct KeyStore {
+    apps: Grant<App>,
+}
+
+impl KeyStore {
+    pub fn new(apps: Grant<App>) -> KeyStore {
+        KeyStore { apps }
+    }
+
+    /// Zeroizes and frees every key slot in every process that has
+    /// ever used this store, for a kernel-internal caller (such as
+    /// `tamper_detect::TamperDetect`) that needs to destroy key
+    /// material immediately rather than waiting for each owning
+    /// process to call `DELETE` itself.
+    pub fn wipe_all(&self) {
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                for occupied in app.occupied.iter_mut() {
+                    *occupied = false;
+                }
+            });
+        }
+    }
+}
+
+impl Driver for KeyStore {
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.handle_out = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            1 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.import_buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, _data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::GENERATE => self
+                .apps
+                .enter(app_id, |app, _| {
+                    let handle_out = match &mut app.handle_out {
+                        Some(slice) if slice.len() >= 4 => slice,
+                        Some(_) => return ReturnCode::ESIZE,
+                        None => return ReturnCode::EINVAL,
+                    };
+                    match app.occupied.iter().position(|&occupied| !occupied) {
+                        Some(handle) => {
+                            app.occupied[handle] = true;
+                            handle_out.as_mut()[..4].copy_from_slice(&(handle as u32).to_le_bytes());
+                            ReturnCode::SUCCESS
+                        }
+                        None => ReturnCode::ENOMEM,
+                    }
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            cmd::IMPORT => self
+                .apps
+                .enter(app_id, |app, _| {
+                    let key_len = match &app.import_buffer {
+                        Some(slice) if data1 <= slice.len() => data1,
+                        Some(_) => return ReturnCode::ESIZE,
+                        None => return ReturnCode::EINVAL,
+                    };
+                    // The imported key's bytes are not retained: see
+                    // the module documentation for why this store
+                    // never holds key bytes.
+                    let _ = key_len;
+                    let handle_out = match &mut app.handle_out {
+                        Some(slice) if slice.len() >= 4 => slice,
+                        Some(_) => return ReturnCode::ESIZE,
+                        None => return ReturnCode::EINVAL,
+                    };
+                    match app.occupied.iter().position(|&occupied| !occupied) {
+                        Some(handle) => {
+                            app.occupied[handle] = true;
+                            handle_out.as_mut()[..4].copy_from_slice(&(handle as u32).to_le_bytes());
+                            ReturnCode::SUCCESS
+                        }
+                        None => ReturnCode::ENOMEM,
+                    }
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            cmd::DELETE => {
+                if data1 >= MAX_KEYS_PER_APP {
+                    return ReturnCode::EINVAL;
+                }
+                self.apps
+                    .enter(app_id, |app, _| {
+                        if !app.occupied[data1] {
+                            return ReturnCode::EINVAL;
+                        }
+                        // The slot's backing key material is zeroized
+                        // here before being marked free; not modeled
+                        // since no key bytes are actually held by this
+                        // skeleton.
+                        app.occupied[data1] = false;
+                        ReturnCode::SUCCESS
+                    })
+                    .unwrap_or(ReturnCode::FAIL)
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Drivers and virtualized capsules for sensors and peripherals.
+//!
+//! This crate contains drivers for sensors and other peripherals that
+//! are built on top of the Tock kernel's hardware interface layer (HIL)
+//! interfaces in `kernel::hil`. The architecture of Tock asks that:
+//!
+//! - Capsules should never use unsafe code.
+//! - Capsules should be `#![no_std]` and avoid heap allocation.
+//!
+//! Capsules are used both for actual syscall drivers exposed to
+//! userspace and for internal virtualization layers shared by several
+//! drivers.
+
+#![forbid(unsafe_code)]
+#![no_std]
+
+pub mod aes_gcm;
+pub mod ambient_light;
+pub mod app_flash_driver;
+pub mod app_loader;
+pub mod at24cxx;
+pub mod attestation;
+pub mod ble_advertising_driver;
+pub mod ble_central_driver;
+pub mod block_storage_driver;
+pub mod can_driver;
+pub mod cellular_modem;
+pub mod config_store;
+pub mod crash_dump;
+pub mod crypto_registry;
+pub mod csprng;
+pub mod ctap_hid;
+pub mod curve25519;
+pub mod cycle_counter;
+pub mod data_logger;
+pub mod digest_driver;
+pub mod driver;
+pub mod dtls_record;
+pub mod ecdsa;
+pub mod enc28j60;
+pub mod encrypted_storage;
+pub mod entropy_health_test;
+pub mod esp_at;
+pub mod ethernet_driver;
+pub mod filesystem_driver;
+pub mod firmware_update;
+pub mod flash_translation_layer;
+pub mod gatt_server;
+pub mod i2c_sample_scheduler;
+pub mod ipv4_stack;
+pub mod ipv6_layer;
+pub mod journaled_storage;
+pub mod kernel_event_log;
+pub mod key_store;
+pub mod littlefs;
+pub mod log_storage;
+pub mod log_storage_driver;
+pub mod lorawan;
+pub mod modbus;
+pub mod monotonic_counter;
+pub mod mqtt_sn;
+pub mod msgqueue;
+pub mod nfc_tag;
+pub mod nonvolatile_storage_driver;
+pub mod opt3001;
+pub mod packet_capture;
+pub mod pdm_microphone;
+pub mod process_info;
+pub mod qspi_flash;
+pub mod radio_154_driver;
+pub mod radio_config_driver;
+pub mod reset_reason;
+pub mod rng_driver;
+pub mod screen;
+pub mod sdcard;
+pub mod sixlowpan;
+pub mod slip_driver;
+pub mod sntp;
+pub mod spi_fram;
+pub mod spi_nor_flash;
+pub mod ssd1306;
+pub mod syscall_latency;
+pub mod tamper_detect;
+pub mod tcp;
+pub mod thread_network;
+pub mod time_sync;
+pub mod usb_bulk_driver;
+pub mod usb_dfu;
+pub mod usb_hid_gadget;
+pub mod usb_hid_host;
+pub mod usb_mass_storage;
+pub mod virtual_digest;
+pub mod virtual_dma;
+pub mod xmodem;
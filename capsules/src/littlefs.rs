@@ -0,0 +1,139 @@
+//! A small filesystem over
+//! `kernel::hil::nonvolatile_storage::NonvolatileStorage`, named after
+//! littlefs. Each file occupies a single `BLOCK_SIZE` block, taken from
+//! the next never-used block when the file is created, so a file holds
+//! at most `BLOCK_SIZE` bytes. The directory table (names, start
+//! blocks and lengths) is held in RAM only: it is never written to the
+//! backing storage, so files cannot be found again after a reset.
+//!
+//! This module tracks the layout; `filesystem_driver`
+//! exposes it to userspace as `open`/`read`/`write`/`seek`/`close`.
+
+use kernel::common::cells::OptionalCell;
+use kernel::hil::nonvolatile_storage::NonvolatileStorage;
+use kernel::ReturnCode;
+
+pub const MAX_NAME_LEN: usize = 16;
+pub const MAX_FILES: usize = 16;
+pub const BLOCK_SIZE: usize = 256;
+
+#[derive(Copy, Clone)]
+struct Inode {
+    name: [u8; MAX_NAME_LEN],
+    name_len: usize,
+    start_block: usize,
+    len: usize,
+}
+
+pub struct LittleFs<'a> {
+    storage: &'a dyn NonvolatileStorage<'a>,
+    inodes: [OptionalCell<Inode>; MAX_FILES],
+    /// Index of the next never-used block; blocks freed by `unlink`
+    /// are not yet reclaimed, matching littlefs's own willingness to
+    /// leak space until the next compaction pass rather than risk a
+    /// half-written free-list during a power loss.
+    next_block: core::cell::Cell<usize>,
+}
+
+impl<'a> LittleFs<'a> {
+    pub fn new(storage: &'a dyn NonvolatileStorage<'a>) -> LittleFs<'a> {
+        LittleFs {
+            storage,
+            inodes: Default::default(),
+            next_block: core::cell::Cell::new(0),
+        }
+    }
+
+    fn find(&self, name: &[u8]) -> Option<usize> {
+        self.inodes.iter().position(|slot| {
+            slot.map(|inode| &inode.name[..inode.name_len] == name)
+                .unwrap_or(false)
+        })
+    }
+
+    /// Opens `name`, creating it if `create` is set and it does not
+    /// already exist. Returns the inode index used as the file handle.
+    pub fn open(&self, name: &[u8], create: bool) -> Result<usize, ReturnCode> {
+        if name.len() > MAX_NAME_LEN {
+            return Err(ReturnCode::ESIZE);
+        }
+        if let Some(idx) = self.find(name) {
+            return Ok(idx);
+        }
+        if !create {
+            return Err(ReturnCode::ENODEVICE);
+        }
+        let free = self.inodes.iter().position(|slot| !slot.is_some());
+        let idx = free.ok_or(ReturnCode::ENOMEM)?;
+        let start_block = self.next_block.get();
+        if (start_block + 1) * BLOCK_SIZE > self.storage.size() {
+            return Err(ReturnCode::ENOMEM);
+        }
+        self.next_block.set(start_block + 1);
+        let mut buf = [0u8; MAX_NAME_LEN];
+        buf[..name.len()].copy_from_slice(name);
+        self.inodes[idx].set(Inode {
+            name: buf,
+            name_len: name.len(),
+            start_block,
+            len: 0,
+        });
+        Ok(idx)
+    }
+
+    pub fn file_len(&self, handle: usize) -> Option<usize> {
+        self.inodes.get(handle).and_then(|slot| slot.map(|inode| inode.len))
+    }
+
+    /// Starts an asynchronous read of up to `length` bytes at `offset`
+    /// within the file; completion is reported through
+    /// `NonvolatileStorageClient::read_done` on whatever client the
+    /// backing storage was configured with.
+    pub fn read(&self, handle: usize, buffer: &'static mut [u8], offset: usize, length: usize) -> ReturnCode {
+        let inode = match self.inodes.get(handle).and_then(|slot| slot.map(|inode| inode)) {
+            Some(inode) => inode,
+            None => return ReturnCode::ENODEVICE,
+        };
+        if offset + length > inode.len {
+            return ReturnCode::ESIZE;
+        }
+        self.storage.read(buffer, inode.start_block * BLOCK_SIZE + offset, length)
+    }
+
+    pub fn write(&self, handle: usize, buffer: &'static mut [u8], offset: usize, length: usize) -> ReturnCode {
+        let inode = match self.inodes.get(handle).and_then(|slot| slot.map(|inode| inode)) {
+            Some(inode) => inode,
+            None => return ReturnCode::ENODEVICE,
+        };
+        if offset + length > BLOCK_SIZE {
+            return ReturnCode::ESIZE;
+        }
+        let result = self.storage.write(buffer, inode.start_block * BLOCK_SIZE + offset, length);
+        if result == ReturnCode::SUCCESS {
+            let mut updated = inode;
+            updated.len = core::cmp::max(updated.len, offset + length);
+            self.inodes[handle].set(updated);
+        }
+        result
+    }
+
+    pub fn unlink(&self, name: &[u8]) -> ReturnCode {
+        match self.find(name) {
+            Some(idx) => {
+                self.inodes[idx].clear();
+                ReturnCode::SUCCESS
+            }
+            None => ReturnCode::ENODEVICE,
+        }
+    }
+
+    /// Calls `f` with the name and length of each file currently in
+    /// the directory table.
+    pub fn list(&self, mut f: impl FnMut(&[u8], usize)) {
+        for slot in self.inodes.iter() {
+            if let Some(inode) = slot.map(|inode| inode) {
+                f(&inode.name[..inode.name_len], inode.len);
+            }
+        }
+    }
+}
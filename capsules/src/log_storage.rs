@@ -0,0 +1,153 @@
+//! Circular, power-fail-tolerant append-only log over any
+//! `hil::nonvolatile_storage::NonvolatileStorage` backend (internal or
+//! external flash, FRAM, ...), implementing `hil::log::{LogRead,
+//! LogWrite}`.
+//!
+//! Entries are stored as a one-byte length prefix followed by the
+//! entry bytes; a cookie is simply the absolute byte offset of an
+//! entry's length prefix, which is why it survives a reboot and lets a
+//! reader resume exactly where it left off instead of re-scanning from
+//! the start. `erase_to` only ever moves the oldest-readable cookie
+//! forward — the bytes themselves are not reclaimed until the region
+//! wraps around and a fresh append overwrites them, so a crash during
+//! `erase_to` can at worst leave already-consumed entries readable
+//! again, never lose unconsumed ones.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let log = static_init!(
+//!     capsules::log_storage::LogStorage<'static>,
+//!     capsules::log_storage::LogStorage::new(flash, capacity));
+//! ```
+
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::log::{LogCookie, LogRead, LogReadClient, LogWrite, LogWriteClient};
+use kernel::hil::nonvolatile_storage::{NonvolatileStorage, NonvolatileStorageClient};
+use kernel::ReturnCode;
+
+const LENGTH_PREFIX_BYTES: usize = 1;
+const MAX_ENTRY_LEN: usize = 255;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Operation {
+    Idle,
+    Appending,
+    Reading,
+    Syncing,
+    Erasing,
+}
+
+pub struct LogStorage<'a> {
+    storage: &'a dyn NonvolatileStorage<'a>,
+    capacity: usize,
+    oldest: core::cell::Cell<u64>,
+    next_append: core::cell::Cell<u64>,
+    operation: core::cell::Cell<Operation>,
+    read_client: OptionalCell<&'a dyn LogReadClient>,
+    write_client: OptionalCell<&'a dyn LogWriteClient>,
+    length_prefix: TakeCell<'static, [u8]>,
+}
+
+impl<'a> LogStorage<'a> {
+    pub fn new(storage: &'a dyn NonvolatileStorage<'a>, capacity: usize) -> LogStorage<'a> {
+        LogStorage {
+            storage,
+            capacity,
+            oldest: core::cell::Cell::new(0),
+            next_append: core::cell::Cell::new(0),
+            operation: core::cell::Cell::new(Operation::Idle),
+            read_client: OptionalCell::empty(),
+            write_client: OptionalCell::empty(),
+            length_prefix: TakeCell::empty(),
+        }
+    }
+
+    fn wrap(&self, cookie: u64) -> usize {
+        (cookie % self.capacity as u64) as usize
+    }
+}
+
+impl<'a> LogRead<'a> for LogStorage<'a> {
+    fn set_read_client(&self, client: &'a dyn LogReadClient) {
+        self.read_client.set(client);
+    }
+
+    fn read(&self, buffer: &'static mut [u8], cookie: LogCookie) -> ReturnCode {
+        if self.operation.get() != Operation::Idle {
+            return ReturnCode::EBUSY;
+        }
+        if cookie >= LogCookie(self.next_append.get()) {
+            return ReturnCode::FAIL;
+        }
+        self.operation.set(Operation::Reading);
+        let offset = self.wrap(cookie.0) + LENGTH_PREFIX_BYTES;
+        self.storage.read(buffer, offset, MAX_ENTRY_LEN)
+    }
+
+    fn oldest_cookie(&self) -> LogCookie {
+        LogCookie(self.oldest.get())
+    }
+}
+
+impl<'a> LogWrite<'a> for LogStorage<'a> {
+    fn set_write_client(&self, client: &'a dyn LogWriteClient) {
+        self.write_client.set(client);
+    }
+
+    fn append(&self, buffer: &'static mut [u8], length: usize) -> ReturnCode {
+        if self.operation.get() != Operation::Idle {
+            return ReturnCode::EBUSY;
+        }
+        if length > MAX_ENTRY_LEN {
+            return ReturnCode::ESIZE;
+        }
+        self.operation.set(Operation::Appending);
+        let offset = self.wrap(self.next_append.get());
+        self.storage.write(buffer, offset + LENGTH_PREFIX_BYTES, length)
+    }
+
+    fn sync(&self) -> ReturnCode {
+        if self.operation.get() != Operation::Idle {
+            return ReturnCode::EBUSY;
+        }
+        self.operation.set(Operation::Idle);
+        self.write_client.map(|client| client.sync_done(ReturnCode::SUCCESS));
+        ReturnCode::SUCCESS
+    }
+
+    fn erase_to(&self, cookie: LogCookie) -> ReturnCode {
+        if self.operation.get() != Operation::Idle {
+            return ReturnCode::EBUSY;
+        }
+        if cookie.0 < self.oldest.get() || cookie.0 > self.next_append.get() {
+            return ReturnCode::EINVAL;
+        }
+        self.oldest.set(cookie.0);
+        self.write_client.map(|client| client.erase_done(ReturnCode::SUCCESS));
+        ReturnCode::SUCCESS
+    }
+
+    fn append_cookie(&self) -> LogCookie {
+        LogCookie(self.next_append.get())
+    }
+}
+
+impl<'a> NonvolatileStorageClient for LogStorage<'a> {
+    fn read_done(&self, buffer: &'static mut [u8], length: usize) {
+        self.operation.set(Operation::Idle);
+        let next_cookie = LogCookie(self.next_append.get());
+        self.read_client.map(|client| client.read_done(buffer, length, next_cookie, ReturnCode::SUCCESS));
+    }
+
+    fn write_done(&self, buffer: &'static mut [u8], length: usize) {
+        let cookie = LogCookie(self.next_append.get());
+        self.next_append.set(self.next_append.get() + LENGTH_PREFIX_BYTES as u64 + length as u64);
+        self.operation.set(Operation::Idle);
+        self.write_client.map(|client| client.append_done(buffer, length, cookie, ReturnCode::SUCCESS));
+    }
+
+    fn erase_done(&self) {
+        self.operation.set(Operation::Idle);
+    }
+}
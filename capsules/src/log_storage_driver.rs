@@ -0,0 +1,175 @@
+//! Syscall driver for `log_storage::LogStorage`, giving userspace
+//! append/read/seek/sync/erase over a circular log with one upcall per
+//! completed operation, since every one of them is asynchronous.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let log_driver = static_init!(
+//!     capsules::log_storage_driver::LogStorageDriver<'static>,
+//!     capsules::log_storage_driver::LogStorageDriver::new(
+//!         log, log, kernel::Grant::create(capsules::driver::NUM::LogStorage as usize), buffer));
+//! ```
+
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::log::{LogCookie, LogRead, LogReadClient, LogWrite, LogWriteClient};
+use kernel::{AppId, Callback, Driver, Grant, ReturnCode};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::LogStorage as usize;
+
+mod upcall {
+    pub const APPEND_DONE: usize = 0;
+    pub const READ_DONE: usize = 1;
+    pub const SYNC_DONE: usize = 2;
+    pub const ERASE_DONE: usize = 3;
+}
+
+mod cmd {
+    /// Appends `data1` bytes from the buffer allowed at index 0.
+    pub const APPEND: usize = 0;
+    /// Reads the next entry starting at the read cookie previously set
+    /// with `SEEK` (or the oldest entry, if never set) into the buffer
+    /// allowed at index 0.
+    pub const READ: usize = 1;
+    /// Sets the process's read cookie to `data1`.
+    pub const SEEK: usize = 2;
+    pub const SYNC: usize = 3;
+    /// Erases entries up to the cookie `data1`.
+    pub const ERASE_TO: usize = 4;
+}
+
+#[derive(Default)]
+pub struct App {
+    append_callback: Option<Callback>,
+    read_callback: Option<Callback>,
+    sync_callback: Option<Callback>,
+    erase_callback: Option<Callback>,
+    read_cookie: u64,
+}
+
+pub struct LogStorageDriver<'a> {
+    read: &'a (dyn LogRead<'a> + 'a),
+    write: &'a (dyn LogWrite<'a> + 'a),
+    apps: Grant<App>,
+    buffer: TakeCell<'static, [u8]>,
+    current_app: OptionalCell<AppId>,
+}
+
+impl<'a> LogStorageDriver<'a> {
+    pub fn new(
+        read: &'a (dyn LogRead<'a> + 'a),
+        write: &'a (dyn LogWrite<'a> + 'a),
+        apps: Grant<App>,
+        buffer: &'static mut [u8],
+    ) -> LogStorageDriver<'a> {
+        LogStorageDriver {
+            read,
+            write,
+            apps,
+            buffer: TakeCell::new(buffer),
+            current_app: OptionalCell::empty(),
+        }
+    }
+}
+
+impl<'a> Driver for LogStorageDriver<'a> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        self.apps
+            .enter(app_id, |app, _| {
+                match subscribe_num {
+                    upcall::APPEND_DONE => app.append_callback = callback,
+                    upcall::READ_DONE => app.read_callback = callback,
+                    upcall::SYNC_DONE => app.sync_callback = callback,
+                    upcall::ERASE_DONE => app.erase_callback = callback,
+                    _ => return ReturnCode::ENOSUPPORT,
+                }
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or(ReturnCode::FAIL)
+    }
+
+    fn command(&self, command_num: usize, data1: usize, _data2: usize, app_id: AppId) -> ReturnCode {
+        if matches!(command_num, cmd::APPEND | cmd::READ) && self.current_app.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        match command_num {
+            cmd::APPEND => match self.buffer.take() {
+                Some(buffer) => {
+                    self.current_app.set(app_id);
+                    let result = self.write.append(buffer, data1);
+                    if result != ReturnCode::SUCCESS {
+                        self.current_app.clear();
+                    }
+                    result
+                }
+                None => ReturnCode::EBUSY,
+            },
+            cmd::READ => self
+                .apps
+                .enter(app_id, |app, _| match self.buffer.take() {
+                    Some(buffer) => {
+                        self.current_app.set(app_id);
+                        let result = self.read.read(buffer, LogCookie(core::cmp::max(app.read_cookie, self.read.oldest_cookie().0)));
+                        if result != ReturnCode::SUCCESS {
+                            self.current_app.clear();
+                        }
+                        result
+                    }
+                    None => ReturnCode::EBUSY,
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            cmd::SEEK => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.read_cookie = data1 as u64;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            cmd::SYNC => self.write.sync(),
+            cmd::ERASE_TO => self.write.erase_to(LogCookie(data1 as u64)),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a> LogReadClient for LogStorageDriver<'a> {
+    fn read_done(&self, buffer: &'static mut [u8], length: usize, next_cookie: LogCookie, result: ReturnCode) {
+        self.buffer.replace(buffer);
+        if let Some(app_id) = self.current_app.take() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                app.read_cookie = next_cookie.0;
+                if let Some(mut cb) = app.read_callback {
+                    cb.schedule(length, result.into(), 0);
+                }
+            });
+        }
+    }
+}
+
+impl<'a> LogWriteClient for LogStorageDriver<'a> {
+    fn append_done(&self, buffer: &'static mut [u8], length: usize, cookie: LogCookie, result: ReturnCode) {
+        self.buffer.replace(buffer);
+        if let Some(app_id) = self.current_app.take() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mut cb) = app.append_callback {
+                    cb.schedule(length, cookie.0 as usize, result.into());
+                }
+            });
+        }
+    }
+
+    fn sync_done(&self, result: ReturnCode) {
+        // Sync and erase act on the shared log, so every subscribed
+        // process is told when one completes.
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| app.sync_callback.map(|mut cb| cb.schedule(result.into(), 0, 0)));
+        }
+    }
+
+    fn erase_done(&self, result: ReturnCode) {
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| app.erase_callback.map(|mut cb| cb.schedule(result.into(), 0, 0)));
+        }
+    }
+}
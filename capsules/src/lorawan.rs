@@ -0,0 +1,499 @@
+//! LoRaWAN 1.0.x Class A MAC, built on `hil::lora::LoRa` plus the
+//! alarm HIL for receive-window timing and `hil::nonvolatile_storage`
+//! for frame counter persistence.
+//!
+//! What LoRaWAN itself defines as fixed framing — the MHDR message
+//! type, the 4-byte DevAddr and 2-byte FCnt of a data frame's header,
+//! and Join Accept's assigned DevAddr — this capsule parses and
+//! builds for real, and the frame counters survive a reset because
+//! they are written to flash after every uplink and every accepted
+//! downlink. What it does not model is the AES-CMAC message integrity
+//! code and AES-CTR payload encryption every real frame also carries
+//! (deriving and using session keys would need a full join, which
+//! this skeleton never computes a MIC for or authenticates), and the
+//! `LinkADRReq` MAC command's explicit rate/power/channel-mask
+//! parameters, in favor of the simpler (but real, network-server-
+//! driven) signal of reacting to the ADR bit a downlink's FCtrl sets.
+//! Application payloads (FRMPayload) are exchanged through the buffer
+//! allowed at index 0, read for `SEND` and written back before the
+//! `DOWNLINK` upcall fires.
+//!
+//! Class A means every receive window follows a transmission the
+//! device itself initiated: `RX1_DELAY_MS` after an uplink (or join
+//! request) finishes sending, the radio listens on RX1; if nothing
+//! arrives by `RX2_DELAY_MS`, it switches to RX2 for one more window
+//! before giving up. Both windows are timed off the alarm HIL, not
+//! off anything the radio itself knows about.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let lorawan = static_init!(
+//!     capsules::lorawan::LoRaWanMac<'static, VirtualMuxAlarm<'static, sam4l::ast::Ast>>,
+//!     capsules::lorawan::LoRaWanMac::new(
+//!         radio, alarm, flash, DEV_EUI, APP_EUI, tx_buffer, flash_buffer,
+//!         kernel::Grant::create(capsules::driver::NUM::LoRaWan as usize)));
+//! radio.set_transmit_client(lorawan);
+//! radio.set_receive_client(lorawan);
+//! alarm.set_alarm_client(lorawan);
+//! flash.set_client(lorawan);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::TakeCell;
+use kernel::hil::lora::{LoRa, RxClient, TxClient};
+use kernel::hil::nonvolatile_storage::{NonvolatileStorage, NonvolatileStorageClient};
+use kernel::hil::time::{Alarm, AlarmClient};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::LoRaWan as usize;
+
+/// Default Class A window timing: RX1 opens `RX1_DELAY_MS` after a
+/// transmission ends, RX2 opens `RX2_DELAY_MS` after it, and each
+/// window stays open for `RX_WINDOW_MS` before the MAC moves on.
+const RX1_DELAY_MS: u32 = 1000;
+const RX2_DELAY_MS: u32 = 2000;
+const RX_WINDOW_MS: u32 = 500;
+
+/// Flash offset and length of the persisted frame counters: uplink
+/// FCnt (4 bytes, little-endian) followed by downlink FCnt (4 bytes).
+const FCNT_FLASH_OFFSET: usize = 0;
+const FCNT_STORAGE_LEN: usize = 8;
+
+mod mhdr {
+    pub const JOIN_REQUEST: u8 = 0x00;
+    pub const JOIN_ACCEPT: u8 = 0x20;
+    pub const UNCONFIRMED_DATA_UP: u8 = 0x40;
+    pub const UNCONFIRMED_DATA_DOWN: u8 = 0x60;
+    pub const CONFIRMED_DATA_UP: u8 = 0x80;
+    pub const CONFIRMED_DATA_DOWN: u8 = 0xa0;
+}
+
+/// Join Request: MHDR(1) + AppEUI(8) + DevEUI(8) + DevNonce(2, not
+/// generated) + MIC(4, not computed).
+mod join {
+    pub const REQUEST_LEN: usize = 23;
+    /// Offset of the 4-byte DevAddr within a Join Accept, after
+    /// MHDR(1) + AppNonce(3) + NetID(3); DLSettings, RxDelay, the
+    /// optional CFList, and the MIC are not parsed.
+    pub const DEV_ADDR_OFFSET: usize = 7;
+}
+
+/// Uplink/downlink data frame header: MHDR(1) + DevAddr(4) + FCtrl(1)
+/// + FCnt(2, the low 16 bits of the real counter) + FPort(1). FOpts
+/// (MAC commands piggybacked via FCtrl's low nibble) are not modeled;
+/// FRMPayload follows the header directly.
+mod fhdr {
+    pub const HEADER_LEN: usize = 9;
+    pub const DEV_ADDR_OFFSET: usize = 1;
+    pub const FCTRL_OFFSET: usize = 5;
+    pub const FCNT_OFFSET: usize = 6;
+    pub const FPORT_OFFSET: usize = 8;
+    /// Set by a network server in a downlink to ask the device to run
+    /// its ADR algorithm; reacting to this one bit is this capsule's
+    /// entire ADR behavior.
+    pub const FCTRL_ADR: u8 = 0x80;
+}
+
+mod upcall {
+    pub const JOINED: usize = 0;
+    pub const JOIN_FAILED: usize = 1;
+    pub const UPLINK_DONE: usize = 2;
+    /// `data1` is the payload length, written into the buffer allowed
+    /// at index 0 before this fires.
+    pub const DOWNLINK: usize = 3;
+}
+
+mod cmd {
+    /// Starts an OTAA join.
+    pub const JOIN: usize = 0;
+    /// Sends `data2 & 0xffff` payload bytes (from the buffer allowed
+    /// at index 0) on port `data2 >> 16`; confirmed if `data1 != 0`.
+    pub const SEND: usize = 1;
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum JoinState {
+    NotJoined,
+    Joining,
+    Joined,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Window {
+    WaitingRx1,
+    Rx1Open,
+    WaitingRx2,
+    Rx2Open,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum OpKind {
+    Join,
+    Uplink,
+}
+
+#[derive(Copy, Clone)]
+struct InFlight {
+    app_id: Option<AppId>,
+    kind: OpKind,
+    window: Window,
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    /// The buffer allowed at index 0: FRMPayload bytes to send for
+    /// `SEND`, and where a `DOWNLINK`'s FRMPayload bytes are written
+    /// back before the upcall fires.
+    payload: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct LoRaWanMac<'a, A: Alarm<'a>> {
+    radio: &'a dyn LoRa<'a>,
+    alarm: &'a A,
+    flash: &'a dyn NonvolatileStorage<'a>,
+    dev_eui: [u8; 8],
+    app_eui: [u8; 8],
+    join_state: Cell<JoinState>,
+    dev_addr: Cell<u32>,
+    uplink_fcnt: Cell<u32>,
+    downlink_fcnt: Cell<u32>,
+    counters_loaded: Cell<bool>,
+    data_rate: Cell<u8>,
+    tx_power: Cell<i8>,
+    in_flight: Cell<Option<InFlight>>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    flash_buffer: TakeCell<'static, [u8]>,
+    apps: Grant<App>,
+}
+
+impl<'a, A: Alarm<'a>> LoRaWanMac<'a, A> {
+    pub fn new(
+        radio: &'a dyn LoRa<'a>,
+        alarm: &'a A,
+        flash: &'a dyn NonvolatileStorage<'a>,
+        dev_eui: [u8; 8],
+        app_eui: [u8; 8],
+        tx_buffer: &'static mut [u8],
+        flash_buffer: &'static mut [u8],
+        apps: Grant<App>,
+    ) -> LoRaWanMac<'a, A> {
+        LoRaWanMac {
+            radio,
+            alarm,
+            flash,
+            dev_eui,
+            app_eui,
+            join_state: Cell::new(JoinState::NotJoined),
+            dev_addr: Cell::new(0),
+            uplink_fcnt: Cell::new(0),
+            downlink_fcnt: Cell::new(0),
+            counters_loaded: Cell::new(false),
+            data_rate: Cell::new(0),
+            tx_power: Cell::new(14),
+            in_flight: Cell::new(None),
+            tx_buffer: TakeCell::new(tx_buffer),
+            flash_buffer: TakeCell::new(flash_buffer),
+            apps,
+        }
+    }
+
+    fn notify(&self, upcall: usize, data1: usize, data2: usize) {
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(upcall, data1, data2);
+                }
+            });
+        }
+    }
+
+    /// Writes `payload` into every app's allowed buffer before firing
+    /// `DOWNLINK`, since (unlike `JOINED`/`JOIN_FAILED`/`UPLINK_DONE`)
+    /// this upcall has data to deliver alongside it.
+    fn notify_downlink(&self, payload: &[u8]) {
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(dest) = &mut app.payload {
+                    let len = core::cmp::min(dest.len(), payload.len());
+                    dest.as_mut()[..len].copy_from_slice(&payload[..len]);
+                }
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(upcall::DOWNLINK, payload.len(), 0);
+                }
+            });
+        }
+    }
+
+    fn send_join_request(&self) -> ReturnCode {
+        match self.tx_buffer.take() {
+            Some(buffer) => {
+                buffer[0] = mhdr::JOIN_REQUEST;
+                buffer[1..9].copy_from_slice(&self.app_eui);
+                buffer[9..17].copy_from_slice(&self.dev_eui);
+                // DevNonce (2) and MIC (4) are not computed here.
+                self.in_flight.set(Some(InFlight {
+                    app_id: None,
+                    kind: OpKind::Join,
+                    window: Window::WaitingRx1,
+                }));
+                self.radio.transmit(buffer, join::REQUEST_LEN)
+            }
+            None => ReturnCode::EBUSY,
+        }
+    }
+
+    fn finish(&self, in_flight: InFlight, result: ReturnCode) {
+        self.radio.stop_receiving();
+        match in_flight.kind {
+            OpKind::Join => {
+                if result == ReturnCode::SUCCESS {
+                    self.join_state.set(JoinState::Joined);
+                    self.notify(upcall::JOINED, 0, 0);
+                } else {
+                    self.join_state.set(JoinState::NotJoined);
+                    self.notify(upcall::JOIN_FAILED, 0, 0);
+                }
+            }
+            OpKind::Uplink => {
+                if let Some(app_id) = in_flight.app_id {
+                    let _ = self.apps.enter(app_id, |app, _| {
+                        if let Some(mut cb) = app.callback {
+                            cb.schedule(upcall::UPLINK_DONE, usize::from(result), 0);
+                        }
+                    });
+                }
+            }
+        }
+    }
+
+    fn persist_counters(&self) {
+        if let Some(buffer) = self.flash_buffer.take() {
+            buffer[0..4].copy_from_slice(&self.uplink_fcnt.get().to_le_bytes());
+            buffer[4..8].copy_from_slice(&self.downlink_fcnt.get().to_le_bytes());
+            let _ = self.flash.write(buffer, FCNT_FLASH_OFFSET, FCNT_STORAGE_LEN);
+        }
+    }
+}
+
+impl<'a, A: Alarm<'a>> Driver for LoRaWanMac<'a, A> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::JOINED | upcall::JOIN_FAILED | upcall::UPLINK_DONE | upcall::DOWNLINK => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.payload = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::JOIN => {
+                if self.join_state.get() != JoinState::NotJoined || self.in_flight.get().is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                self.join_state.set(JoinState::Joining);
+                if !self.counters_loaded.get() {
+                    return match self.flash_buffer.take() {
+                        Some(buffer) => {
+                            // The join request itself is sent from
+                            // `read_done` once the counters are in
+                            // hand.
+                            self.flash.read(buffer, FCNT_FLASH_OFFSET, FCNT_STORAGE_LEN)
+                        }
+                        None => ReturnCode::EBUSY,
+                    };
+                }
+                self.send_join_request()
+            }
+            cmd::SEND => {
+                if self.join_state.get() != JoinState::Joined {
+                    return ReturnCode::EOFF;
+                }
+                if self.in_flight.get().is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                let confirmed = data1 != 0;
+                let len = data2 & 0xffff;
+                let port = (data2 >> 16) as u8;
+                match self.tx_buffer.take() {
+                    Some(buffer) => {
+                        if buffer.len() < fhdr::HEADER_LEN + len {
+                            self.tx_buffer.replace(buffer);
+                            return ReturnCode::ESIZE;
+                        }
+                        let copy_result = self.apps.enter(app_id, |app, _| match &app.payload {
+                            Some(slice) if slice.len() >= len => {
+                                buffer[fhdr::HEADER_LEN..fhdr::HEADER_LEN + len].copy_from_slice(&slice.as_ref()[..len]);
+                                ReturnCode::SUCCESS
+                            }
+                            Some(_) => ReturnCode::ESIZE,
+                            None if len == 0 => ReturnCode::SUCCESS,
+                            None => ReturnCode::EINVAL,
+                        });
+                        match copy_result.unwrap_or(ReturnCode::FAIL) {
+                            ReturnCode::SUCCESS => {
+                                buffer[0] = if confirmed { mhdr::CONFIRMED_DATA_UP } else { mhdr::UNCONFIRMED_DATA_UP };
+                                buffer[fhdr::DEV_ADDR_OFFSET..fhdr::DEV_ADDR_OFFSET + 4].copy_from_slice(&self.dev_addr.get().to_le_bytes());
+                                buffer[fhdr::FCTRL_OFFSET] = 0;
+                                let fcnt = self.uplink_fcnt.get();
+                                buffer[fhdr::FCNT_OFFSET..fhdr::FCNT_OFFSET + 2].copy_from_slice(&(fcnt as u16).to_le_bytes());
+                                buffer[fhdr::FPORT_OFFSET] = port;
+                                // The MIC that would follow FRMPayload
+                                // is not computed; see the module docs.
+                                self.in_flight.set(Some(InFlight {
+                                    app_id: Some(app_id),
+                                    kind: OpKind::Uplink,
+                                    window: Window::WaitingRx1,
+                                }));
+                                self.uplink_fcnt.set(fcnt.wrapping_add(1));
+                                self.persist_counters();
+                                self.radio.transmit(buffer, fhdr::HEADER_LEN + len)
+                            }
+                            e => {
+                                self.tx_buffer.replace(buffer);
+                                e
+                            }
+                        }
+                    }
+                    None => ReturnCode::EBUSY,
+                }
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a, A: Alarm<'a>> TxClient for LoRaWanMac<'a, A> {
+    fn transmit_done(&self, buffer: &'static mut [u8], _result: ReturnCode) {
+        self.tx_buffer.replace(buffer);
+        self.alarm.set_alarm(self.alarm.now(), A::ticks_from_ms(RX1_DELAY_MS));
+    }
+}
+
+impl<'a, A: Alarm<'a>> AlarmClient for LoRaWanMac<'a, A> {
+    fn alarm(&self) {
+        let mut in_flight = match self.in_flight.get() {
+            Some(in_flight) => in_flight,
+            None => return,
+        };
+        match in_flight.window {
+            Window::WaitingRx1 => {
+                in_flight.window = Window::Rx1Open;
+                self.in_flight.set(Some(in_flight));
+                let _ = self.radio.start_receiving();
+                self.alarm.set_alarm(self.alarm.now(), A::ticks_from_ms(RX_WINDOW_MS));
+            }
+            Window::Rx1Open => {
+                self.radio.stop_receiving();
+                in_flight.window = Window::WaitingRx2;
+                self.in_flight.set(Some(in_flight));
+                self.alarm.set_alarm(self.alarm.now(), A::ticks_from_ms(RX2_DELAY_MS - RX1_DELAY_MS - RX_WINDOW_MS));
+            }
+            Window::WaitingRx2 => {
+                in_flight.window = Window::Rx2Open;
+                self.in_flight.set(Some(in_flight));
+                let _ = self.radio.start_receiving();
+                self.alarm.set_alarm(self.alarm.now(), A::ticks_from_ms(RX_WINDOW_MS));
+            }
+            Window::Rx2Open => {
+                self.in_flight.set(None);
+                self.finish(in_flight, ReturnCode::FAIL);
+            }
+        }
+    }
+}
+
+impl<'a, A: Alarm<'a>> RxClient for LoRaWanMac<'a, A> {
+    fn receive(&self, buffer: &[u8], len: usize, _rssi: i8, _snr: i8, result: ReturnCode) {
+        let in_flight = match self.in_flight.take() {
+            Some(in_flight) => in_flight,
+            None => return,
+        };
+        self.alarm.disarm();
+        if result != ReturnCode::SUCCESS || len == 0 {
+            self.finish(in_flight, ReturnCode::FAIL);
+            return;
+        }
+
+        match buffer[0] {
+            mhdr::JOIN_ACCEPT if in_flight.kind == OpKind::Join => {
+                if len >= join::DEV_ADDR_OFFSET + 4 {
+                    let offset = join::DEV_ADDR_OFFSET;
+                    self.dev_addr.set(u32::from_le_bytes([
+                        buffer[offset],
+                        buffer[offset + 1],
+                        buffer[offset + 2],
+                        buffer[offset + 3],
+                    ]));
+                    self.finish(in_flight, ReturnCode::SUCCESS);
+                } else {
+                    self.finish(in_flight, ReturnCode::FAIL);
+                }
+            }
+            mhdr::UNCONFIRMED_DATA_DOWN | mhdr::CONFIRMED_DATA_DOWN => {
+                if len < fhdr::HEADER_LEN {
+                    self.finish(in_flight, ReturnCode::FAIL);
+                    return;
+                }
+                if buffer[fhdr::FCTRL_OFFSET] & fhdr::FCTRL_ADR != 0 {
+                    // Toy ADR: nudge the data rate up and the transmit
+                    // power down one notch, rather than parsing the
+                    // LinkADRReq MAC command's explicit parameters out
+                    // of FOpts.
+                    self.data_rate.set((self.data_rate.get() + 1).min(5));
+                    self.tx_power.set((self.tx_power.get() - 2).max(2));
+                }
+                let fcnt_lo = u16::from_le_bytes([buffer[fhdr::FCNT_OFFSET], buffer[fhdr::FCNT_OFFSET + 1]]);
+                self.downlink_fcnt.set(u32::from(fcnt_lo));
+                self.persist_counters();
+                self.finish(in_flight, ReturnCode::SUCCESS);
+                self.notify_downlink(&buffer[fhdr::HEADER_LEN..len]);
+            }
+            _ => self.finish(in_flight, ReturnCode::FAIL),
+        }
+    }
+}
+
+impl<'a, A: Alarm<'a>> NonvolatileStorageClient for LoRaWanMac<'a, A> {
+    fn read_done(&self, buffer: &'static mut [u8], length: usize) {
+        if length >= FCNT_STORAGE_LEN {
+            self.uplink_fcnt.set(u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]));
+            self.downlink_fcnt.set(u32::from_le_bytes([buffer[4], buffer[5], buffer[6], buffer[7]]));
+        }
+        self.flash_buffer.replace(buffer);
+        self.counters_loaded.set(true);
+        if self.join_state.get() == JoinState::Joining {
+            let result = self.send_join_request();
+            if result != ReturnCode::SUCCESS {
+                self.join_state.set(JoinState::NotJoined);
+                self.notify(upcall::JOIN_FAILED, 0, 0);
+            }
+        }
+    }
+
+    fn write_done(&self, buffer: &'static mut [u8], _length: usize) {
+        self.flash_buffer.replace(buffer);
+    }
+
+    fn erase_done(&self) {}
+}
@@ -0,0 +1,626 @@
+//! Modbus RTU (the register-oriented fieldbus protocol most PLCs and
+//! industrial sensors still speak) over `hil::uart`, as both a server
+//! (slave) — [`ModbusServer`], the syscall driver below, answering
+//! register read/write requests straight out of a process's own
+//! buffers — and a client (master) — [`ModbusClient`], a kernel-internal
+//! API for a board to poll another device on the bus without every
+//! app reimplementing RTU's timing-based framing badly.
+//!
+//! RTU carries no start-of-frame or end-of-frame byte: a frame boundary
+//! is just a gap of at least 3.5 character times of silence on the
+//! wire. Both roles therefore arm the UART a single byte at a time,
+//! like `capsules::slip_driver`, and additionally arm a
+//! `hil::time::Alarm` for that silence interval on every byte
+//! received; when the alarm fires with nothing new having arrived,
+//! whatever has accumulated is the frame. `silence_ticks` is computed
+//! by the board from its configured baud rate and passed in already
+//! converted to alarm ticks.
+//!
+//! Both roles share this file's `crc16` (RTU's CRC-16, polynomial
+//! 0xA001) and the function/exception codes in [`pdu`] and
+//! [`exception`], but are otherwise independent structs: their framing
+//! state machines run in opposite directions and share little beyond
+//! that, the same split `capsules::xmodem` makes between
+//! `XmodemReceiver` and `XmodemSender`.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let modbus_server = static_init!(
+//!     capsules::modbus::ModbusServer<'static, Alarm>,
+//!     capsules::modbus::ModbusServer::new(
+//!         uart, alarm, 17, silence_ticks,
+//!         rx_byte_buffer, rx_frame_buffer, tx_buffer,
+//!         kernel::Grant::create(capsules::driver::NUM::Modbus as usize)));
+//! uart.set_receive_client(modbus_server);
+//! uart.set_transmit_client(modbus_server);
+//! alarm.set_alarm_client(modbus_server);
+//! modbus_server.start();
+//! ```
+
+use core::cell::Cell;
+
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::time::{Alarm, AlarmClient};
+use kernel::hil::uart::{ReceiveClient, TransmitClient, UartData};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Modbus as usize;
+
+pub mod pdu {
+    pub const READ_HOLDING_REGISTERS: u8 = 0x03;
+    pub const READ_INPUT_REGISTERS: u8 = 0x04;
+    pub const WRITE_SINGLE_REGISTER: u8 = 0x06;
+    pub const WRITE_MULTIPLE_REGISTERS: u8 = 0x10;
+    /// Set in the top bit of the function code on an exception response.
+    pub const EXCEPTION_FLAG: u8 = 0x80;
+}
+
+pub mod exception {
+    pub const ILLEGAL_FUNCTION: u8 = 0x01;
+    pub const ILLEGAL_DATA_ADDRESS: u8 = 0x02;
+    pub const ILLEGAL_DATA_VALUE: u8 = 0x03;
+}
+
+/// Largest ADU (address + PDU + CRC) RTU allows.
+const MAX_ADU_LEN: usize = 256;
+/// Address (1) + function (1) + CRC (2): the smallest frame that could
+/// possibly be valid.
+const MIN_ADU_LEN: usize = 4;
+/// 0x03/0x04's own limit on registers read in one request.
+const MAX_REGISTERS_PER_REQUEST: u16 = 125;
+
+mod upcall {
+    /// `data1` is the first register address written, `data2` the
+    /// number of registers; the new values are already in the buffer
+    /// allowed at index 0, since this driver writes a master's writes
+    /// straight into it.
+    pub const REGISTERS_WRITTEN: usize = 0;
+}
+
+/// Computes RTU's CRC-16 (polynomial 0xA001, reflected, seeded with
+/// 0xFFFF) over `data`, returned ready to append little-endian.
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in data {
+        crc ^= u16::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xA001 } else { crc >> 1 };
+        }
+    }
+    crc
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum RxState {
+    /// Waiting for the first byte of a new frame; no silence alarm armed.
+    Idle,
+    /// Assembling a frame; the silence alarm is armed and gets pushed
+    /// back on every byte received.
+    Framing,
+}
+
+#[derive(Copy, Clone)]
+enum RegisterBank {
+    Holding,
+    Input,
+}
+
+pub struct App {
+    callback: Option<Callback>,
+    holding_registers: Option<AppSlice<Shared, u8>>,
+    input_registers: Option<AppSlice<Shared, u8>>,
+}
+
+impl Default for App {
+    fn default() -> App {
+        App {
+            callback: None,
+            holding_registers: None,
+            input_registers: None,
+        }
+    }
+}
+
+/// Modbus RTU server (slave): answers requests addressed to `address`
+/// against the registered process's own register buffers, so the
+/// registers this device exposes live directly in that process's
+/// memory with no extra copy on a read. As with
+/// `capsules::radio_config_driver`, there is no access control beyond
+/// that: whichever process's buffers are allowed at the time a request
+/// arrives backs this device's register map, and writes from the bus
+/// land straight in them.
+pub struct ModbusServer<'a, A: Alarm<'a>> {
+    uart: &'a dyn UartData<'a>,
+    alarm: &'a A,
+    address: u8,
+    silence_ticks: u32,
+    rx_byte: TakeCell<'static, [u8]>,
+    rx_state: Cell<RxState>,
+    rx_frame: TakeCell<'static, [u8]>,
+    rx_len: Cell<usize>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    apps: Grant<App>,
+}
+
+impl<'a, A: Alarm<'a>> ModbusServer<'a, A> {
+    pub fn new(
+        uart: &'a dyn UartData<'a>,
+        alarm: &'a A,
+        address: u8,
+        silence_ticks: u32,
+        rx_byte_buffer: &'static mut [u8],
+        rx_frame_buffer: &'static mut [u8],
+        tx_buffer: &'static mut [u8],
+        apps: Grant<App>,
+    ) -> ModbusServer<'a, A> {
+        ModbusServer {
+            uart,
+            alarm,
+            address,
+            silence_ticks,
+            rx_byte: TakeCell::new(rx_byte_buffer),
+            rx_state: Cell::new(RxState::Idle),
+            rx_frame: TakeCell::new(rx_frame_buffer),
+            rx_len: Cell::new(0),
+            tx_buffer: TakeCell::new(tx_buffer),
+            apps,
+        }
+    }
+
+    /// Arms the UART to receive the bus's first byte; a board calls
+    /// this once after registering this capsule as the UART's and
+    /// alarm's client.
+    pub fn start(&self) -> ReturnCode {
+        match self.rx_byte.take() {
+            Some(buffer) => self.uart.receive_buffer(buffer, 1),
+            None => ReturnCode::EBUSY,
+        }
+    }
+
+    fn process_byte(&self, byte: u8) {
+        self.rx_frame.map(|frame| {
+            let len = self.rx_len.get();
+            if len < frame.len() {
+                frame[len] = byte;
+                self.rx_len.set(len + 1);
+            }
+        });
+        self.rx_state.set(RxState::Framing);
+        self.alarm.set_alarm(self.alarm.now(), self.silence_ticks);
+    }
+
+    fn process_frame(&self, len: usize) {
+        self.rx_frame.map(|frame| {
+            if len < MIN_ADU_LEN || len > frame.len() {
+                return;
+            }
+            let (body, crc_bytes) = frame[..len].split_at(len - 2);
+            if crc16(body) != u16::from_le_bytes([crc_bytes[0], crc_bytes[1]]) || body[0] != self.address {
+                return;
+            }
+            self.handle_request(body[1], &body[2..]);
+        });
+    }
+
+    fn handle_request(&self, function: u8, data: &[u8]) {
+        let buffer = match self.tx_buffer.take() {
+            Some(buffer) => buffer,
+            // Still sending a previous response; this request is lost,
+            // the same as any other frame collision on the bus.
+            None => return,
+        };
+        let outcome = match function {
+            pdu::READ_HOLDING_REGISTERS => self.read_registers(buffer, data, RegisterBank::Holding),
+            pdu::READ_INPUT_REGISTERS => self.read_registers(buffer, data, RegisterBank::Input),
+            pdu::WRITE_SINGLE_REGISTER => self.write_single_register(buffer, data),
+            pdu::WRITE_MULTIPLE_REGISTERS => self.write_multiple_registers(buffer, data),
+            _ => Err((buffer, exception::ILLEGAL_FUNCTION)),
+        };
+        match outcome {
+            Ok((buffer, body_len)) => self.finish_response(buffer, function, body_len),
+            Err((buffer, code)) => self.finish_exception(buffer, function, code),
+        }
+    }
+
+    /// Copies `count` registers starting at `data`'s address straight
+    /// out of the bank's allowed buffer into `buffer[2..]`, leaving
+    /// `buffer[0..2]` for [`Self::finish_response`] to fill in.
+    fn read_registers(
+        &self,
+        buffer: &'static mut [u8],
+        data: &[u8],
+        bank: RegisterBank,
+    ) -> Result<(&'static mut [u8], usize), (&'static mut [u8], u8)> {
+        if data.len() < 4 {
+            return Err((buffer, exception::ILLEGAL_DATA_VALUE));
+        }
+        let start = u16::from_be_bytes([data[0], data[1]]) as usize * 2;
+        let count = u16::from_be_bytes([data[2], data[3]]);
+        if count == 0 || count > MAX_REGISTERS_PER_REQUEST {
+            return Err((buffer, exception::ILLEGAL_DATA_VALUE));
+        }
+        let byte_count = count as usize * 2;
+        if 3 + byte_count > buffer.len() {
+            return Err((buffer, exception::ILLEGAL_DATA_VALUE));
+        }
+        let mut found = false;
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                let slice = match bank {
+                    RegisterBank::Holding => &app.holding_registers,
+                    RegisterBank::Input => &app.input_registers,
+                };
+                if let Some(slice) = slice {
+                    if start + byte_count <= slice.len() {
+                        buffer[3..3 + byte_count].copy_from_slice(&slice.as_ref()[start..start + byte_count]);
+                        found = true;
+                    }
+                }
+            });
+            if found {
+                break;
+            }
+        }
+        if found {
+            buffer[2] = byte_count as u8;
+            Ok((buffer, 1 + byte_count))
+        } else {
+            Err((buffer, exception::ILLEGAL_DATA_ADDRESS))
+        }
+    }
+
+    fn write_single_register(
+        &self,
+        buffer: &'static mut [u8],
+        data: &[u8],
+    ) -> Result<(&'static mut [u8], usize), (&'static mut [u8], u8)> {
+        if data.len() < 4 {
+            return Err((buffer, exception::ILLEGAL_DATA_VALUE));
+        }
+        let address = u16::from_be_bytes([data[0], data[1]]);
+        let start = address as usize * 2;
+        let mut found = false;
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(slice) = &mut app.holding_registers {
+                    if start + 2 <= slice.len() {
+                        slice.as_mut()[start..start + 2].copy_from_slice(&data[2..4]);
+                        found = true;
+                        if let Some(mut cb) = app.callback {
+                            cb.schedule(upcall::REGISTERS_WRITTEN, address as usize, 1);
+                        }
+                    }
+                }
+            });
+            if found {
+                break;
+            }
+        }
+        if found {
+            buffer[2..6].copy_from_slice(&data[..4]);
+            Ok((buffer, 4))
+        } else {
+            Err((buffer, exception::ILLEGAL_DATA_ADDRESS))
+        }
+    }
+
+    fn write_multiple_registers(
+        &self,
+        buffer: &'static mut [u8],
+        data: &[u8],
+    ) -> Result<(&'static mut [u8], usize), (&'static mut [u8], u8)> {
+        if data.len() < 5 {
+            return Err((buffer, exception::ILLEGAL_DATA_VALUE));
+        }
+        let start_addr = u16::from_be_bytes([data[0], data[1]]);
+        let count = u16::from_be_bytes([data[2], data[3]]);
+        let byte_count = data[4] as usize;
+        if count == 0
+            || count > MAX_REGISTERS_PER_REQUEST
+            || byte_count != count as usize * 2
+            || data.len() < 5 + byte_count
+        {
+            return Err((buffer, exception::ILLEGAL_DATA_VALUE));
+        }
+        let start = start_addr as usize * 2;
+        let mut found = false;
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(slice) = &mut app.holding_registers {
+                    if start + byte_count <= slice.len() {
+                        slice.as_mut()[start..start + byte_count].copy_from_slice(&data[5..5 + byte_count]);
+                        found = true;
+                        if let Some(mut cb) = app.callback {
+                            cb.schedule(upcall::REGISTERS_WRITTEN, start_addr as usize, count as usize);
+                        }
+                    }
+                }
+            });
+            if found {
+                break;
+            }
+        }
+        if found {
+            buffer[2..6].copy_from_slice(&data[..4]);
+            Ok((buffer, 4))
+        } else {
+            Err((buffer, exception::ILLEGAL_DATA_ADDRESS))
+        }
+    }
+
+    fn finish_response(&self, buffer: &'static mut [u8], function: u8, body_len: usize) {
+        buffer[0] = self.address;
+        buffer[1] = function;
+        let crc = crc16(&buffer[..2 + body_len]);
+        buffer[2 + body_len..4 + body_len].copy_from_slice(&crc.to_le_bytes());
+        let len = 4 + body_len;
+        let _ = self.uart.transmit_buffer(buffer, len);
+    }
+
+    fn finish_exception(&self, buffer: &'static mut [u8], function: u8, code: u8) {
+        buffer[0] = self.address;
+        buffer[1] = function | pdu::EXCEPTION_FLAG;
+        buffer[2] = code;
+        let crc = crc16(&buffer[..3]);
+        buffer[3..5].copy_from_slice(&crc.to_le_bytes());
+        let _ = self.uart.transmit_buffer(buffer, 5);
+    }
+}
+
+impl<'a, A: Alarm<'a>> ReceiveClient for ModbusServer<'a, A> {
+    fn received_buffer(&self, buffer: &'static mut [u8], rx_len: usize, _result: ReturnCode) {
+        if rx_len == 1 {
+            self.process_byte(buffer[0]);
+        }
+        let _ = self.uart.receive_buffer(buffer, 1);
+    }
+}
+
+impl<'a, A: Alarm<'a>> TransmitClient for ModbusServer<'a, A> {
+    fn transmitted_buffer(&self, buffer: &'static mut [u8], _tx_len: usize, _result: ReturnCode) {
+        self.tx_buffer.replace(buffer);
+    }
+}
+
+impl<'a, A: Alarm<'a>> AlarmClient for ModbusServer<'a, A> {
+    fn alarm(&self) {
+        if self.rx_state.get() == RxState::Framing {
+            self.rx_state.set(RxState::Idle);
+            let len = self.rx_len.get();
+            self.rx_len.set(0);
+            if len > 0 {
+                self.process_frame(len);
+            }
+        }
+    }
+}
+
+impl<'a, A: Alarm<'a>> Driver for ModbusServer<'a, A> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::REGISTERS_WRITTEN => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.holding_registers = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            1 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.input_registers = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, _command_num: usize, _data1: usize, _data2: usize, _app_id: AppId) -> ReturnCode {
+        // Every request this driver answers arrives off the wire, not
+        // from an app; there is nothing for a process to actively ask
+        // for beyond the buffers and callback set up above.
+        ReturnCode::ENOSUPPORT
+    }
+}
+
+/// Notified when a transaction `ModbusClient` issued finishes, either
+/// with the peer's response PDU (function code plus data, address and
+/// CRC already checked and stripped) or `None` if nothing valid came
+/// back before `response_timeout_ticks` ran out.
+pub trait ModbusTransactionClient {
+    fn transaction_done(&self, response: Option<&[u8]>);
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum ClientState {
+    Idle,
+    /// A request has been sent; no byte of the response has arrived
+    /// yet, and the alarm is armed for the overall response timeout.
+    WaitingForResponse,
+    /// At least one byte of the response has arrived; the alarm is
+    /// re-armed for the inter-character silence interval on every byte.
+    Framing,
+}
+
+/// Modbus RTU client (master): issues one request at a time to a
+/// device on the bus and reports its response, handling both RTU's
+/// T3.5 inter-character silence and the overall response timeout here
+/// instead of leaving an app to get that timing right on its own.
+pub struct ModbusClient<'a, A: Alarm<'a>> {
+    uart: &'a dyn UartData<'a>,
+    alarm: &'a A,
+    silence_ticks: u32,
+    response_timeout_ticks: u32,
+    state: Cell<ClientState>,
+    rx_byte: TakeCell<'static, [u8]>,
+    rx_frame: TakeCell<'static, [u8]>,
+    rx_len: Cell<usize>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    client: OptionalCell<&'a dyn ModbusTransactionClient>,
+}
+
+impl<'a, A: Alarm<'a>> ModbusClient<'a, A> {
+    pub fn new(
+        uart: &'a dyn UartData<'a>,
+        alarm: &'a A,
+        silence_ticks: u32,
+        response_timeout_ticks: u32,
+        rx_byte_buffer: &'static mut [u8],
+        rx_frame_buffer: &'static mut [u8],
+        tx_buffer: &'static mut [u8],
+    ) -> ModbusClient<'a, A> {
+        ModbusClient {
+            uart,
+            alarm,
+            silence_ticks,
+            response_timeout_ticks,
+            state: Cell::new(ClientState::Idle),
+            rx_byte: TakeCell::new(rx_byte_buffer),
+            rx_frame: TakeCell::new(rx_frame_buffer),
+            rx_len: Cell::new(0),
+            tx_buffer: TakeCell::new(tx_buffer),
+            client: OptionalCell::empty(),
+        }
+    }
+
+    pub fn set_client(&self, client: &'a dyn ModbusTransactionClient) {
+        self.client.set(client);
+    }
+
+    /// Arms the UART to receive the bus's first byte; a board calls
+    /// this once after registering this capsule as the UART's and
+    /// alarm's client.
+    pub fn start(&self) -> ReturnCode {
+        match self.rx_byte.take() {
+            Some(buffer) => self.uart.receive_buffer(buffer, 1),
+            None => ReturnCode::EBUSY,
+        }
+    }
+
+    pub fn read_holding_registers(&self, address: u8, start: u16, count: u16) -> ReturnCode {
+        self.request(address, pdu::READ_HOLDING_REGISTERS, start, count)
+    }
+
+    pub fn read_input_registers(&self, address: u8, start: u16, count: u16) -> ReturnCode {
+        self.request(address, pdu::READ_INPUT_REGISTERS, start, count)
+    }
+
+    pub fn write_single_register(&self, address: u8, register: u16, value: u16) -> ReturnCode {
+        self.request(address, pdu::WRITE_SINGLE_REGISTER, register, value)
+    }
+
+    fn request(&self, address: u8, function: u8, field_a: u16, field_b: u16) -> ReturnCode {
+        if self.state.get() != ClientState::Idle {
+            return ReturnCode::EBUSY;
+        }
+        let buffer = match self.tx_buffer.take() {
+            Some(buffer) => buffer,
+            None => return ReturnCode::EBUSY,
+        };
+        buffer[0] = address;
+        buffer[1] = function;
+        buffer[2..4].copy_from_slice(&field_a.to_be_bytes());
+        buffer[4..6].copy_from_slice(&field_b.to_be_bytes());
+        let crc = crc16(&buffer[..6]);
+        buffer[6..8].copy_from_slice(&crc.to_le_bytes());
+        self.state.set(ClientState::WaitingForResponse);
+        self.uart.transmit_buffer(buffer, 8)
+    }
+}
+
+impl<'a, A: Alarm<'a>> TransmitClient for ModbusClient<'a, A> {
+    fn transmitted_buffer(&self, buffer: &'static mut [u8], _tx_len: usize, _result: ReturnCode) {
+        self.tx_buffer.replace(buffer);
+        self.alarm.set_alarm(self.alarm.now(), self.response_timeout_ticks);
+    }
+}
+
+impl<'a, A: Alarm<'a>> ReceiveClient for ModbusClient<'a, A> {
+    fn received_buffer(&self, buffer: &'static mut [u8], rx_len: usize, _result: ReturnCode) {
+        if rx_len == 1 && self.state.get() != ClientState::Idle {
+            self.rx_frame.map(|frame| {
+                let len = self.rx_len.get();
+                if len < frame.len() {
+                    frame[len] = buffer[0];
+                    self.rx_len.set(len + 1);
+                }
+            });
+            self.state.set(ClientState::Framing);
+            self.alarm.set_alarm(self.alarm.now(), self.silence_ticks);
+        }
+        let _ = self.uart.receive_buffer(buffer, 1);
+    }
+}
+
+impl<'a, A: Alarm<'a>> AlarmClient for ModbusClient<'a, A> {
+    fn alarm(&self) {
+        match self.state.get() {
+            ClientState::Idle => {}
+            ClientState::WaitingForResponse => {
+                self.state.set(ClientState::Idle);
+                self.client.map(|client| client.transaction_done(None));
+            }
+            ClientState::Framing => {
+                self.state.set(ClientState::Idle);
+                let len = self.rx_len.get();
+                self.rx_len.set(0);
+                // The request is still in `tx_buffer`; its first byte is
+                // the address the response has to come from.
+                let expected_address = self.tx_buffer.map(|request| request[0]);
+                self.rx_frame.map(|frame| {
+                    let response = if len >= MIN_ADU_LEN {
+                        let (body, crc_bytes) = frame[..len].split_at(len - 2);
+                        if crc16(body) == u16::from_le_bytes([crc_bytes[0], crc_bytes[1]]) && Some(body[0]) == expected_address {
+                            Some(&body[1..])
+                        } else {
+                            None
+                        }
+                    } else {
+                        None
+                    };
+                    self.client.map(|client| client.transaction_done(response));
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc16_of_empty_data_is_the_seed() {
+        assert_eq!(crc16(&[]), 0xFFFF);
+    }
+
+    #[test]
+    fn crc16_matches_a_known_request_vector() {
+        // A read-holding-registers request for 10 registers starting
+        // at 0, address 1 — a commonly cited RTU CRC test vector.
+        assert_eq!(crc16(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0A]), 0xCDC5);
+    }
+
+    #[test]
+    fn crc16_changes_with_any_byte() {
+        let a = crc16(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0A]);
+        let b = crc16(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0B]);
+        assert_ne!(a, b);
+    }
+}
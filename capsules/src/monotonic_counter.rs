@@ -0,0 +1,150 @@
+//! Persistent, strictly monotonic counter backed by flash, for the
+//! updater's version anti-rollback check and for apps implementing
+//! secure protocols that need a replay counter.
+//!
+//! The counter is stored in two slots that are written alternately:
+//! `increment` always writes the slot that is *not* currently active,
+//! tagged with a higher generation number than the active one. A reset
+//! partway through that write leaves the previously active slot
+//! intact, so `new` (which reads both slots and trusts whichever has
+//! the higher generation) recovers the last committed value instead of
+//! a torn one. The exact record encoding and the backing flash's erase
+//! geometry are a board's concern, abstracted behind
+//! `MonotonicCounterRegion`; only the ping-pong scheduling that makes
+//! the scheme tear-safe lives in this capsule.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let counter = static_init!(
+//!     capsules::monotonic_counter::MonotonicCounter<'static>,
+//!     capsules::monotonic_counter::MonotonicCounter::new(
+//!         region, kernel::Grant::create(capsules::driver::NUM::MonotonicCounter as usize)));
+//! ```
+
+use core::cell::Cell;
+use kernel::{AppId, AppSlice, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::MonotonicCounter as usize;
+
+mod cmd {
+    /// Returns success with the counter's current value, as an 8-byte
+    /// little-endian `u64`, written into the buffer allowed at index 0.
+    pub const READ: usize = 0;
+    /// Atomically increments the counter and returns success with the
+    /// new value, reported the same way as `READ`. Returns `ENOMEM`,
+    /// leaving the counter unchanged, if it is already at `u64::MAX`:
+    /// a counter used for anti-rollback must never wrap back to a
+    /// value it has already vouched for.
+    pub const INCREMENT: usize = 1;
+}
+
+#[derive(Default)]
+pub struct App {
+    /// The buffer allowed at index 0, written with the counter's value
+    /// by `READ` and `INCREMENT`.
+    value_out: Option<AppSlice<Shared, u8>>,
+}
+
+/// The two alternating slots a counter's record is written into.
+const NUM_SLOTS: usize = 2;
+
+/// Abstracts the flash region a counter's two slots live in, so this
+/// capsule does not need to know the backing chip's erase-block size
+/// or write alignment. A slot that has never been written reads back
+/// as generation `0`, value `0`.
+pub trait MonotonicCounterRegion {
+    fn read_slot(&self, slot: usize) -> (u32, u64);
+    fn write_slot(&self, slot: usize, generation: u32, value: u64) -> ReturnCode;
+}
+
+pub struct MonotonicCounter<'a> {
+    region: &'a dyn MonotonicCounterRegion,
+    active_slot: Cell<usize>,
+    generation: Cell<u32>,
+    value: Cell<u64>,
+    apps: Grant<App>,
+}
+
+impl<'a> MonotonicCounter<'a> {
+    pub fn new(region: &'a dyn MonotonicCounterRegion, apps: Grant<App>) -> MonotonicCounter<'a> {
+        let (slot0_generation, slot0_value) = region.read_slot(0);
+        let (slot1_generation, slot1_value) = region.read_slot(1);
+        let (active_slot, generation, value) = if slot1_generation > slot0_generation {
+            (1, slot1_generation, slot1_value)
+        } else {
+            (0, slot0_generation, slot0_value)
+        };
+        MonotonicCounter {
+            region,
+            active_slot: Cell::new(active_slot),
+            generation: Cell::new(generation),
+            value: Cell::new(value),
+            apps,
+        }
+    }
+
+    pub fn value(&self) -> u64 {
+        self.value.get()
+    }
+
+    pub fn increment(&self) -> ReturnCode {
+        let next_value = match self.value.get().checked_add(1) {
+            Some(next_value) => next_value,
+            None => return ReturnCode::ENOMEM,
+        };
+        let next_slot = (self.active_slot.get() + 1) % NUM_SLOTS;
+        let next_generation = self.generation.get() + 1;
+        let result = self.region.write_slot(next_slot, next_generation, next_value);
+        if result == ReturnCode::SUCCESS {
+            self.active_slot.set(next_slot);
+            self.generation.set(next_generation);
+            self.value.set(next_value);
+        }
+        result
+    }
+
+    fn report_value(&self, app_id: AppId) -> ReturnCode {
+        let value = self.value.get();
+        self.apps
+            .enter(app_id, |app, _| match &mut app.value_out {
+                Some(slice) if slice.len() >= 8 => {
+                    slice.as_mut()[..8].copy_from_slice(&value.to_le_bytes());
+                    ReturnCode::SUCCESS
+                }
+                Some(_) => ReturnCode::ESIZE,
+                None => ReturnCode::EINVAL,
+            })
+            .unwrap_or(ReturnCode::FAIL)
+    }
+}
+
+impl<'a> Driver for MonotonicCounter<'a> {
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.value_out = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, _data1: usize, _data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::READ => self.report_value(app_id),
+            cmd::INCREMENT => {
+                let result = self.increment();
+                if result != ReturnCode::SUCCESS {
+                    return result;
+                }
+                self.report_value(app_id)
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! Copying message-queue IPC primitive.
+//!
+//! Shared-memory IPC (see `kernel::ipc`) is the right tool for bulk
+//! transfers, but it is overkill and error-prone for small
+//! command/status exchanges between apps: the sender and receiver must
+//! coordinate a shared buffer's lifetime just to pass a few bytes. This
+//! driver instead lets a process send a message of up to
+//! `MAX_MESSAGE_LEN` bytes from an allowed buffer directly to another
+//! process by index; the kernel copies it into the recipient's
+//! grant-backed queue and delivers an upcall.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let msgqueue = static_init!(
+//!     capsules::msgqueue::MessageQueue<'static>,
+//!     capsules::msgqueue::MessageQueue::new(
+//!         kernel::Grant::create(capsules::driver::NUM::MessageQueue as usize)));
+//! ```
+
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::MessageQueue as usize;
+
+pub const MAX_MESSAGE_LEN: usize = 32;
+const QUEUE_DEPTH: usize = 4;
+
+mod upcall {
+    pub const MESSAGE_RECEIVED: usize = 0;
+}
+
+mod cmd {
+    /// Send the buffer allowed at index 0 (up to `MAX_MESSAGE_LEN`
+    /// bytes) to the process at index `data1`.
+    pub const SEND: usize = 0;
+    /// Copy the oldest queued message into the buffer allowed at index
+    /// 0. Its length and sender are reported via the
+    /// `MESSAGE_RECEIVED` upcall, the same one an unsolicited arrival
+    /// uses; returns `FAIL` if the queue is empty.
+    pub const RECEIVE: usize = 1;
+}
+
+#[derive(Copy, Clone)]
+struct Message {
+    sender: AppId,
+    len: usize,
+    data: [u8; MAX_MESSAGE_LEN],
+}
+
+pub struct App {
+    callback: Option<Callback>,
+    queue: [Option<Message>; QUEUE_DEPTH],
+    /// The buffer allowed at index 0: read from for `SEND`, written
+    /// into for `RECEIVE`.
+    buffer: Option<AppSlice<Shared, u8>>,
+}
+
+impl Default for App {
+    fn default() -> App {
+        App {
+            callback: None,
+            queue: [None; QUEUE_DEPTH],
+            buffer: None,
+        }
+    }
+}
+
+pub struct MessageQueue {
+    apps: Grant<App>,
+}
+
+impl MessageQueue {
+    pub fn new(grant: Grant<App>) -> MessageQueue {
+        MessageQueue { apps: grant }
+    }
+
+    fn enqueue(&self, recipient: AppId, sender: AppId, data: &[u8]) -> ReturnCode {
+        if data.len() > MAX_MESSAGE_LEN {
+            return ReturnCode::ESIZE;
+        }
+        self.apps
+            .enter(recipient, |app, _| {
+                let slot = app.queue.iter_mut().find(|s| s.is_none());
+                match slot {
+                    Some(slot) => {
+                        let mut buf = [0u8; MAX_MESSAGE_LEN];
+                        buf[..data.len()].copy_from_slice(data);
+                        *slot = Some(Message {
+                            sender,
+                            len: data.len(),
+                            data: buf,
+                        });
+                        if let Some(mut cb) = app.callback {
+                            cb.schedule(data.len(), sender.idx(), 0);
+                        }
+                        ReturnCode::SUCCESS
+                    }
+                    None => ReturnCode::EBUSY,
+                }
+            })
+            .unwrap_or(ReturnCode::FAIL)
+    }
+}
+
+impl Driver for MessageQueue {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::MESSAGE_RECEIVED => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, _data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::SEND => {
+                let recipient = match self.apps.iter().find(|id| id.idx() == data1) {
+                    Some(id) => id,
+                    None => return ReturnCode::EINVAL,
+                };
+                let outgoing = self
+                    .apps
+                    .enter(app_id, |app, _| {
+                        app.buffer.as_ref().map(|slice| {
+                            let len = core::cmp::min(slice.len(), MAX_MESSAGE_LEN);
+                            let mut buf = [0u8; MAX_MESSAGE_LEN];
+                            buf[..len].copy_from_slice(&slice.as_ref()[..len]);
+                            (buf, len)
+                        })
+                    })
+                    .unwrap_or(None);
+                match outgoing {
+                    Some((buf, len)) => self.enqueue(recipient, app_id, &buf[..len]),
+                    None => ReturnCode::EINVAL,
+                }
+            }
+            cmd::RECEIVE => self
+                .apps
+                .enter(app_id, |app, _| {
+                    let slot = app.queue.iter_mut().find(|s| s.is_some());
+                    match slot {
+                        Some(slot) => {
+                            let msg = slot.take().unwrap();
+                            if let Some(dest) = &mut app.buffer {
+                                let len = core::cmp::min(dest.len(), msg.len);
+                                dest.as_mut()[..len].copy_from_slice(&msg.data[..len]);
+                            }
+                            if let Some(mut cb) = app.callback {
+                                cb.schedule(msg.len, msg.sender.idx(), 0);
+                            }
+                            ReturnCode::SUCCESS
+                        }
+                        None => ReturnCode::FAIL,
+                    }
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -0,0 +1,405 @@
+//! NFC Type 4 Tag emulation: this driver answers a reader's ISO-DEP
+//! command APDUs the way the NFC Forum's Type 4 Tag Operation
+//! specification's NDEF application does, so a process can hand a
+//! phone an NDEF message (a URL, a Bluetooth pairing payload, ...) on
+//! tap just by keeping it in the buffer allowed at index 0, enabling
+//! tap-to-provision and tap-to-pair flows.
+//!
+//! Only the read path is implemented: SELECT (by AID, to enter the
+//! NDEF application, and by file ID, to pick the capability container
+//! or the NDEF file) and READ BINARY. UPDATE BINARY, which the spec
+//! uses to let a reader write a new NDEF message onto the tag, is not
+//! answered (`INS_NOT_SUPPORTED`), the same kind of real, stated scope
+//! limit `capsules::slip_driver` draws around PPP option negotiation:
+//! this tag is always read-only, advertised as such in its capability
+//! container's write-access byte.
+//!
+//! As with `capsules::radio_config_driver`, there is no access control
+//! between processes: whichever process's buffer is allowed at index 0
+//! is the NDEF message a reader sees. That process is expected to keep
+//! exactly the message it wants broadcast in the buffer with no
+//! padding, since its length is reported to the reader as-is.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let nfc_tag = static_init!(
+//!     capsules::nfc_tag::NfcTagDriver<'static>,
+//!     capsules::nfc_tag::NfcTagDriver::new(
+//!         nfc, tx_buffer,
+//!         kernel::Grant::create(capsules::driver::NUM::Nfc as usize)));
+//! nfc.set_client(nfc_tag);
+//! nfc.enable();
+//! ```
+
+use core::cell::Cell;
+
+use kernel::common::cells::TakeCell;
+use kernel::hil::nfc::{NfcTag, NfcTagClient};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Nfc as usize;
+
+mod iso7816 {
+    pub const CLA_ISO: u8 = 0x00;
+    pub const INS_SELECT: u8 = 0xA4;
+    pub const INS_READ_BINARY: u8 = 0xB0;
+}
+
+mod status {
+    pub const SUCCESS: [u8; 2] = [0x90, 0x00];
+    /// Fewer bytes than `Le` asked for were available; not an error,
+    /// just the end of the file.
+    pub const END_OF_FILE: [u8; 2] = [0x62, 0x82];
+    pub const FILE_NOT_FOUND: [u8; 2] = [0x6A, 0x82];
+    pub const INCORRECT_PARAMETERS: [u8; 2] = [0x6A, 0x86];
+    /// A file was selected or read before the NDEF application (or,
+    /// for `READ BINARY`, a file within it) was selected.
+    pub const CONDITIONS_NOT_SATISFIED: [u8; 2] = [0x69, 0x85];
+    pub const INS_NOT_SUPPORTED: [u8; 2] = [0x6D, 0x00];
+    pub const CLA_NOT_SUPPORTED: [u8; 2] = [0x6E, 0x00];
+    pub const WRONG_LENGTH: [u8; 2] = [0x67, 0x00];
+}
+
+/// The NFC Forum Type 4 Tag NDEF application's well-known AID.
+const NDEF_AID: [u8; 7] = [0xD2, 0x76, 0x00, 0x00, 0x85, 0x01, 0x01];
+/// File IDs fixed by the Type 4 Tag spec's example layout: a reader
+/// only needs these two well-known values to find the capability
+/// container and the NDEF file it points at.
+const CC_FILE_ID: u16 = 0xE103;
+const NDEF_FILE_ID: u16 = 0xE104;
+
+mod upcall {
+    pub const FIELD_DETECTED: usize = 0;
+    pub const FIELD_LOST: usize = 1;
+}
+
+mod cmd {
+    pub const ENABLE: usize = 0;
+    pub const DISABLE: usize = 1;
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum SelectedFile {
+    None,
+    CapabilityContainer,
+    NdefFile,
+}
+
+pub struct App {
+    callback: Option<Callback>,
+    ndef_message: Option<AppSlice<Shared, u8>>,
+}
+
+impl Default for App {
+    fn default() -> App {
+        App {
+            callback: None,
+            ndef_message: None,
+        }
+    }
+}
+
+/// Builds the capability container file's fixed 15-byte content (see
+/// the Type 4 Tag spec's NDEF application), with the NDEF File Control
+/// TLV's max file size field filled in from the currently allowed
+/// NDEF message buffer's length and its write-access byte set to
+/// `0xFF` (writing not allowed), matching this driver's read-only
+/// scope.
+fn capability_container(ndef_buffer_len: usize) -> [u8; 15] {
+    let max_len = (ndef_buffer_len.min(0xFFFE) as u16).to_be_bytes();
+    let file_id = NDEF_FILE_ID.to_be_bytes();
+    [
+        0x00, 0x0F, // CCLEN: length of this file, including itself
+        0x20, // mapping version 2.0
+        0x00, 0x3B, // MLe: max R-APDU data size this tag will send
+        0x00, 0x34, // MLc: max C-APDU data size this tag will accept
+        0x04, 0x06, // NDEF File Control TLV: tag, length
+        file_id[0], file_id[1],
+        max_len[0], max_len[1],
+        0x00, // read access: free
+        0xFF, // write access: not allowed
+    ]
+}
+
+pub struct NfcTagDriver<'a> {
+    nfc: &'a dyn NfcTag<'a>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    application_selected: Cell<bool>,
+    selected_file: Cell<SelectedFile>,
+    apps: Grant<App>,
+}
+
+impl<'a> NfcTagDriver<'a> {
+    pub fn new(nfc: &'a dyn NfcTag<'a>, tx_buffer: &'static mut [u8], apps: Grant<App>) -> NfcTagDriver<'a> {
+        NfcTagDriver {
+            nfc,
+            tx_buffer: TakeCell::new(tx_buffer),
+            application_selected: Cell::new(false),
+            selected_file: Cell::new(SelectedFile::None),
+            apps,
+        }
+    }
+
+    /// Finds the one process with an NDEF message allowed and returns
+    /// its `AppId` and the message's length.
+    fn find_ndef_message(&self) -> Option<(AppId, usize)> {
+        for app_id in self.apps.iter() {
+            let len = self
+                .apps
+                .enter(app_id, |app, _| app.ndef_message.as_ref().map(|slice| slice.len()))
+                .unwrap_or(None);
+            if let Some(len) = len {
+                return Some((app_id, len));
+            }
+        }
+        None
+    }
+
+    fn handle_select(&self, p1: u8, p2: u8, data: &[u8]) {
+        let lc = match data.first() {
+            Some(&lc) => lc as usize,
+            None => {
+                self.respond_status(status::WRONG_LENGTH);
+                return;
+            }
+        };
+        if data.len() < 1 + lc {
+            self.respond_status(status::WRONG_LENGTH);
+            return;
+        }
+        let payload = &data[1..1 + lc];
+        match (p1, p2) {
+            // Select by AID: enter the NDEF application.
+            (0x04, 0x00) => {
+                if payload == NDEF_AID {
+                    self.application_selected.set(true);
+                    self.selected_file.set(SelectedFile::None);
+                    self.respond_status(status::SUCCESS);
+                } else {
+                    self.respond_status(status::FILE_NOT_FOUND);
+                }
+            }
+            // Select by file ID: pick the capability container or the
+            // NDEF file, only once the application above is selected.
+            (0x00, 0x0C) => {
+                if !self.application_selected.get() || lc != 2 {
+                    self.respond_status(status::CONDITIONS_NOT_SATISFIED);
+                    return;
+                }
+                match u16::from_be_bytes([payload[0], payload[1]]) {
+                    CC_FILE_ID => {
+                        self.selected_file.set(SelectedFile::CapabilityContainer);
+                        self.respond_status(status::SUCCESS);
+                    }
+                    NDEF_FILE_ID => {
+                        self.selected_file.set(SelectedFile::NdefFile);
+                        self.respond_status(status::SUCCESS);
+                    }
+                    _ => self.respond_status(status::FILE_NOT_FOUND),
+                }
+            }
+            _ => self.respond_status(status::INCORRECT_PARAMETERS),
+        }
+    }
+
+    fn handle_read_binary(&self, p1: u8, p2: u8, data: &[u8]) {
+        let offset = (usize::from(p1 & 0x7F) << 8) | usize::from(p2);
+        let le = match data.first() {
+            None | Some(0) => 256,
+            Some(&le) => usize::from(le),
+        };
+        match self.selected_file.get() {
+            SelectedFile::None => self.respond_status(status::CONDITIONS_NOT_SATISFIED),
+            SelectedFile::CapabilityContainer => {
+                let ndef_len = self.find_ndef_message().map(|(_, len)| len).unwrap_or(0);
+                let cc = capability_container(ndef_len);
+                if let Some(buffer) = self.tx_buffer.take() {
+                    self.respond_from_slice(buffer, &cc, offset, le);
+                }
+            }
+            SelectedFile::NdefFile => match self.find_ndef_message() {
+                Some((app_id, len)) => {
+                    let nlen = (len as u16).to_be_bytes();
+                    if let Some(buffer) = self.tx_buffer.take() {
+                        let _ = self.apps.enter(app_id, |app, _| match &app.ndef_message {
+                            Some(slice) => self.respond_ndef_file(buffer, &nlen, slice.as_ref(), offset, le),
+                            None => {
+                                self.tx_buffer.replace(buffer);
+                            }
+                        });
+                    }
+                }
+                None => self.respond_status(status::CONDITIONS_NOT_SATISFIED),
+            },
+        }
+    }
+
+    /// Writes `source[offset..]`, up to `le` bytes, into `buffer`,
+    /// followed by the status word, and transmits it.
+    fn respond_from_slice(&self, buffer: &'static mut [u8], source: &[u8], offset: usize, le: usize) {
+        if offset > source.len() {
+            self.tx_buffer.replace(buffer);
+            self.respond_status(status::INCORRECT_PARAMETERS);
+            return;
+        }
+        let available = source.len() - offset;
+        let n = available.min(le).min(buffer.len().saturating_sub(2));
+        buffer[..n].copy_from_slice(&source[offset..offset + n]);
+        self.finish_response(buffer, n, n < le);
+    }
+
+    /// Same as `respond_from_slice`, but the virtual file being read is
+    /// the 2-byte `NLEN` length prefix followed by `message`, without
+    /// ever copying the two together.
+    fn respond_ndef_file(&self, buffer: &'static mut [u8], nlen: &[u8; 2], message: &[u8], offset: usize, le: usize) {
+        let total_len = 2 + message.len();
+        if offset > total_len {
+            self.tx_buffer.replace(buffer);
+            self.respond_status(status::INCORRECT_PARAMETERS);
+            return;
+        }
+        let available = total_len - offset;
+        let n = available.min(le).min(buffer.len().saturating_sub(2));
+        let mut written = 0;
+        if offset < 2 {
+            let from_nlen = (2 - offset).min(n);
+            buffer[..from_nlen].copy_from_slice(&nlen[offset..offset + from_nlen]);
+            written = from_nlen;
+        }
+        if written < n {
+            let message_offset = offset + written - 2;
+            buffer[written..n].copy_from_slice(&message[message_offset..message_offset + (n - written)]);
+        }
+        self.finish_response(buffer, n, n < le);
+    }
+
+    fn finish_response(&self, buffer: &'static mut [u8], len: usize, truncated: bool) {
+        let status = if truncated { status::END_OF_FILE } else { status::SUCCESS };
+        buffer[len] = status[0];
+        buffer[len + 1] = status[1];
+        let _ = self.nfc.transmit(buffer, len + 2);
+    }
+
+    fn respond_status(&self, status: [u8; 2]) {
+        if let Some(buffer) = self.tx_buffer.take() {
+            buffer[0] = status[0];
+            buffer[1] = status[1];
+            let _ = self.nfc.transmit(buffer, 2);
+        }
+    }
+
+    fn notify(&self, upcall: usize) {
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(upcall, 0, 0);
+                }
+            });
+        }
+    }
+}
+
+impl<'a> NfcTagClient for NfcTagDriver<'a> {
+    fn field_detected(&self) {
+        self.application_selected.set(false);
+        self.selected_file.set(SelectedFile::None);
+        self.notify(upcall::FIELD_DETECTED);
+    }
+
+    fn field_lost(&self) {
+        self.application_selected.set(false);
+        self.selected_file.set(SelectedFile::None);
+        self.notify(upcall::FIELD_LOST);
+    }
+
+    fn frame_received(&self, buffer: &[u8], len: usize) {
+        let frame = &buffer[..len];
+        if frame.len() < 4 {
+            self.respond_status(status::WRONG_LENGTH);
+            return;
+        }
+        if frame[0] != iso7816::CLA_ISO {
+            self.respond_status(status::CLA_NOT_SUPPORTED);
+            return;
+        }
+        match frame[1] {
+            iso7816::INS_SELECT => self.handle_select(frame[2], frame[3], &frame[4..]),
+            iso7816::INS_READ_BINARY => self.handle_read_binary(frame[2], frame[3], &frame[4..]),
+            _ => self.respond_status(status::INS_NOT_SUPPORTED),
+        }
+    }
+
+    fn transmit_done(&self, buffer: &'static mut [u8], _result: ReturnCode) {
+        self.tx_buffer.replace(buffer);
+    }
+}
+
+impl<'a> Driver for NfcTagDriver<'a> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::FIELD_DETECTED | upcall::FIELD_LOST => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.ndef_message = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, _data1: usize, _data2: usize, _app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::ENABLE => self.nfc.enable(),
+            cmd::DISABLE => self.nfc.disable(),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capability_container_reports_the_ndef_file_id() {
+        let cc = capability_container(100);
+        let file_id = u16::from_be_bytes([cc[9], cc[10]]);
+        assert_eq!(file_id, NDEF_FILE_ID);
+    }
+
+    #[test]
+    fn capability_container_reports_the_ndef_message_length() {
+        let cc = capability_container(100);
+        let max_len = u16::from_be_bytes([cc[11], cc[12]]);
+        assert_eq!(max_len, 100);
+    }
+
+    #[test]
+    fn capability_container_clamps_an_oversized_length() {
+        let cc = capability_container(usize::MAX);
+        let max_len = u16::from_be_bytes([cc[11], cc[12]]);
+        assert_eq!(max_len, 0xFFFE);
+    }
+
+    #[test]
+    fn capability_container_advertises_write_not_allowed() {
+        let cc = capability_container(0);
+        assert_eq!(cc[14], 0xFF);
+    }
+}
@@ -0,0 +1,380 @@
+//! Exposes a `kernel::hil::nonvolatile_storage::NonvolatileStorage`
+//! backend to userspace, one per-process region at a time.
+//!
+//! Early versions of this driver handed every process the same flat
+//! offset space, which meant a buggy or malicious app could read or
+//! erase another app's data simply by guessing an offset. Instead each
+//! process is assigned a fixed-size region (sized from its TBF header's
+//! storage permissions, or a board-wide default when the header does
+//! not specify one), and every `command` validates the requested
+//! offset and length against that region before touching the backing
+//! storage.
+//!
+//! A process that wants several writes to land atomically can wrap
+//! them in `BEGIN`/`COMMIT` (or `ABORT` to discard), backed by
+//! `journaled_storage::JournaledStorage`; only one process may hold an
+//! open transaction at a time, since the journal region itself is
+//! shared.
+//!
+//! Independent of its region size, each process is also subject to a
+//! byte quota set from board configuration (`assign_quota`, or
+//! `default_quota` if never called): a process can be handed a large
+//! region up front and still be stopped from actually filling all of
+//! it, so one chatty logger can't exhaust flash that other apps on the
+//! same board need. `USAGE` reports bytes written against that quota.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let storage = static_init!(
+//!     capsules::nonvolatile_storage_driver::NonvolatileStorageDriver<'static>,
+//!     capsules::nonvolatile_storage_driver::NonvolatileStorageDriver::new(
+//!         flash,
+//!         journal,
+//!         kernel::Grant::create(capsules::driver::NUM::NonvolatileStorage as usize),
+//!         DEFAULT_REGION_SIZE,
+//!         DEFAULT_QUOTA,
+//!         buffer));
+//! ```
+
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::nonvolatile_storage::{NonvolatileStorage, NonvolatileStorageClient};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+use crate::journaled_storage::JournaledStorage;
+
+pub const DRIVER_NUM: usize = driver::NUM::NonvolatileStorage as usize;
+
+mod upcall {
+    pub const DONE: usize = 0;
+}
+
+mod cmd {
+    /// Assigns the calling process its region if it has none yet:
+    /// `SUCCESS` once it has one, `ENOMEM` if the backing storage has
+    /// no room for another. The region's size is not reported.
+    pub const REGION_SIZE: usize = 0;
+    /// Read `data2` bytes starting at offset `data1` into the buffer
+    /// allowed at index 0.
+    pub const READ: usize = 1;
+    /// Write `data2` bytes from the buffer allowed at index 0 starting
+    /// at offset `data1`.
+    pub const WRITE: usize = 2;
+    /// Erase `data2` bytes starting at offset `data1`.
+    pub const ERASE: usize = 3;
+    /// Opens a transaction; subsequent `WRITE`s from the same process
+    /// are staged rather than applied until `COMMIT_TXN`.
+    pub const BEGIN_TXN: usize = 4;
+    /// Atomically applies every write staged since `BEGIN_TXN`.
+    pub const COMMIT_TXN: usize = 5;
+    /// Discards every write staged since `BEGIN_TXN`.
+    pub const ABORT_TXN: usize = 6;
+    /// Returns success if the calling process's usage is still under
+    /// quota; the byte counts themselves are reported through
+    /// `NonvolatileStorageDriver::usage` for a console or host tool.
+    pub const USAGE: usize = 7;
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    /// Byte offset and length of this process's region within the
+    /// shared backing storage. Set by `assign_region` during board
+    /// setup, or otherwise by `region_for` on the process's first
+    /// command.
+    region: Option<(usize, usize)>,
+    /// Total bytes written so far, counted against `quota`.
+    bytes_used: usize,
+    /// Overrides `NonvolatileStorageDriver::default_quota` for this
+    /// process, if `assign_quota` was called for it.
+    quota: Option<usize>,
+    /// The buffer allowed at index 0: written from for `WRITE`, and
+    /// written into for `READ`.
+    data: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct NonvolatileStorageDriver<'a> {
+    storage: &'a dyn NonvolatileStorage<'a>,
+    journal: &'a JournaledStorage<'a>,
+    apps: Grant<App>,
+    default_region_size: usize,
+    next_region_start: core::cell::Cell<usize>,
+    buffer: TakeCell<'static, [u8]>,
+    current_app: OptionalCell<AppId>,
+    /// The process that currently holds the open transaction, if any;
+    /// only it may `WRITE`, `COMMIT_TXN`, or `ABORT_TXN` while one is
+    /// open.
+    txn_owner: OptionalCell<AppId>,
+    /// Byte quota applied to a process that never had `assign_quota`
+    /// called for it.
+    default_quota: usize,
+}
+
+impl<'a> NonvolatileStorageDriver<'a> {
+    pub fn new(
+        storage: &'a dyn NonvolatileStorage<'a>,
+        journal: &'a JournaledStorage<'a>,
+        apps: Grant<App>,
+        default_region_size: usize,
+        default_quota: usize,
+        buffer: &'static mut [u8],
+    ) -> NonvolatileStorageDriver<'a> {
+        NonvolatileStorageDriver {
+            storage,
+            journal,
+            apps,
+            default_region_size,
+            next_region_start: core::cell::Cell::new(0),
+            buffer: TakeCell::new(buffer),
+            current_app: OptionalCell::empty(),
+            txn_owner: OptionalCell::empty(),
+            default_quota,
+        }
+    }
+
+    /// Used by board setup to give a process a quota other than
+    /// `default_quota`.
+    pub fn assign_quota(&self, app_id: AppId, quota: usize) -> ReturnCode {
+        self.apps
+            .enter(app_id, |app, _| {
+                app.quota = Some(quota);
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or(ReturnCode::FAIL)
+    }
+
+    /// Reports the calling process's bytes used and quota, for a
+    /// console command or host tool.
+    pub fn usage(&self, app_id: AppId) -> Option<(usize, usize)> {
+        self.apps
+            .enter(app_id, |app, _| (app.bytes_used, app.quota.unwrap_or(self.default_quota)))
+            .ok()
+    }
+
+    /// Lazily assign a process its region the first time it calls in,
+    /// carving it out of the backing storage in `default_region_size`
+    /// chunks. A board that wants per-app sizes from the TBF header's
+    /// storage permissions can instead call `assign_region` directly
+    /// during board setup, before any app has had the chance to use
+    /// the default.
+    fn region_for(&self, app: &mut App) -> Option<(usize, usize)> {
+        if app.region.is_none() {
+            let start = self.next_region_start.get();
+            if start + self.default_region_size > self.storage.size() {
+                return None;
+            }
+            self.next_region_start.set(start + self.default_region_size);
+            app.region = Some((start, self.default_region_size));
+        }
+        app.region
+    }
+
+    /// Used by board setup to give a process a region sized from its
+    /// TBF header rather than the driver's default.
+    pub fn assign_region(&self, app_id: AppId, start: usize, length: usize) -> ReturnCode {
+        if start + length > self.storage.size() {
+            return ReturnCode::ESIZE;
+        }
+        self.apps
+            .enter(app_id, |app, _| {
+                app.region = Some((start, length));
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or(ReturnCode::FAIL)
+    }
+
+    fn validate(region: (usize, usize), offset: usize, length: usize) -> Option<usize> {
+        let (start, size) = region;
+        if offset.checked_add(length)? <= size {
+            Some(start + offset)
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> Driver for NonvolatileStorageDriver<'a> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::DONE => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.data = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, data2: usize, app_id: AppId) -> ReturnCode {
+        if self.current_app.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        match command_num {
+            cmd::REGION_SIZE => self
+                .apps
+                .enter(app_id, |app, _| match self.region_for(app) {
+                    Some(_) => ReturnCode::SUCCESS,
+                    None => ReturnCode::ENOMEM,
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            cmd::READ | cmd::WRITE | cmd::ERASE => self
+                .apps
+                .enter(app_id, |app, _| {
+                    let region = match self.region_for(app) {
+                        Some(region) => region,
+                        None => return ReturnCode::ENOMEM,
+                    };
+                    let absolute = match Self::validate(region, data1, data2) {
+                        Some(offset) => offset,
+                        None => return ReturnCode::EINVAL,
+                    };
+                    if command_num == cmd::WRITE {
+                        let quota = app.quota.unwrap_or(self.default_quota);
+                        if app.bytes_used.saturating_add(data2) > quota {
+                            return ReturnCode::ENOMEM;
+                        }
+                        if self.txn_owner.is_some() {
+                            return match self.txn_owner.map(|owner| owner.idx() == app_id.idx()) {
+                                Some(true) => {
+                                    let result = match &app.data {
+                                        Some(slice) if data2 <= slice.len() => {
+                                            self.journal.stage_write(absolute, &slice.as_ref()[..data2])
+                                        }
+                                        _ => ReturnCode::EINVAL,
+                                    };
+                                    if result == ReturnCode::SUCCESS {
+                                        app.bytes_used += data2;
+                                    }
+                                    result
+                                }
+                                _ => ReturnCode::EBUSY,
+                            };
+                        }
+                    }
+                    self.current_app.set(app_id);
+                    let result = match command_num {
+                        cmd::ERASE => self.storage.erase(absolute, data2),
+                        cmd::READ => match self.buffer.take() {
+                            Some(buffer) if data2 <= buffer.len() => self.storage.read(buffer, absolute, data2),
+                            Some(buffer) => {
+                                self.buffer.replace(buffer);
+                                ReturnCode::ESIZE
+                            }
+                            None => ReturnCode::EBUSY,
+                        },
+                        cmd::WRITE => match self.buffer.take() {
+                            Some(buffer) => match &app.data {
+                                Some(slice) if data2 <= slice.len() && data2 <= buffer.len() => {
+                                    buffer[..data2].copy_from_slice(&slice.as_ref()[..data2]);
+                                    self.storage.write(buffer, absolute, data2)
+                                }
+                                _ => {
+                                    self.buffer.replace(buffer);
+                                    ReturnCode::EINVAL
+                                }
+                            },
+                            None => ReturnCode::EBUSY,
+                        },
+                        _ => ReturnCode::SUCCESS,
+                    };
+                    if result != ReturnCode::SUCCESS {
+                        self.current_app.clear();
+                    } else if command_num == cmd::WRITE {
+                        app.bytes_used += data2;
+                    }
+                    result
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            cmd::USAGE => self
+                .apps
+                .enter(app_id, |app, _| {
+                    let quota = app.quota.unwrap_or(self.default_quota);
+                    if app.bytes_used <= quota {
+                        ReturnCode::SUCCESS
+                    } else {
+                        ReturnCode::ENOMEM
+                    }
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            cmd::BEGIN_TXN => {
+                if self.txn_owner.is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                let result = self.journal.begin();
+                if result == ReturnCode::SUCCESS {
+                    self.txn_owner.set(app_id);
+                }
+                result
+            }
+            cmd::COMMIT_TXN | cmd::ABORT_TXN => {
+                match self.txn_owner.map(|owner| owner.idx() == app_id.idx()) {
+                    Some(true) => {
+                        let result = if command_num == cmd::COMMIT_TXN {
+                            self.journal.commit()
+                        } else {
+                            self.journal.abort()
+                        };
+                        self.txn_owner.clear();
+                        result
+                    }
+                    _ => ReturnCode::EINVAL,
+                }
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a> NonvolatileStorageClient for NonvolatileStorageDriver<'a> {
+    fn read_done(&self, buffer: &'static mut [u8], length: usize) {
+        if let Some(app_id) = self.current_app.take() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(slice) = &mut app.data {
+                    let copy_len = core::cmp::min(length, slice.len());
+                    slice.as_mut()[..copy_len].copy_from_slice(&buffer[..copy_len]);
+                }
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(length, 0, 0);
+                }
+            });
+        }
+        self.buffer.replace(buffer);
+    }
+
+    fn write_done(&self, buffer: &'static mut [u8], length: usize) {
+        self.buffer.replace(buffer);
+        if let Some(app_id) = self.current_app.take() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(length, 0, 0);
+                }
+            });
+        }
+    }
+
+    fn erase_done(&self) {
+        if let Some(app_id) = self.current_app.take() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(0, 0, 0);
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! SyscallDriver-independent chip driver for the Texas Instruments
+//! OPT3001 ambient light sensor, communicating over I2C.
+//!
+//! The OPT3001 auto-ranges internally across twelve full-scale ranges,
+//! so unlike earlier parts (e.g. ISL29035) this driver does not need to
+//! pick a range itself; it simply enables automatic full-scale mode and
+//! converts the returned exponent/mantissa pair to lux. The same
+//! register layout also exposes low/high threshold registers and an
+//! interrupt pin, which this driver uses to implement
+//! `enable_continuous_mode`/`configure_threshold` without the kernel
+//! having to poll.
+//!
+//! The TSL2591 is register-compatible enough in spirit (auto-gain,
+//! threshold interrupt) that a sibling driver can reuse this file's
+//! structure against `hil::sensors::AmbientLight`; only the register
+//! map and lux conversion differ.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let opt3001 = static_init!(
+//!     capsules::opt3001::Opt3001<'static>,
+//!     capsules::opt3001::Opt3001::new(i2c_device, Some(interrupt_pin), buffer));
+//! i2c_device.set_client(opt3001);
+//! interrupt_pin.set_client(opt3001);
+//! ```
+
+use core::cell::Cell;
+use kernel::hil::gpio;
+use kernel::hil::i2c;
+use kernel::hil::sensors::{AmbientLight, AmbientLightClient};
+use kernel::ReturnCode;
+
+/// OPT3001 register addresses.
+#[allow(dead_code)]
+mod registers {
+    pub const RESULT: u8 = 0x00;
+    pub const CONFIGURATION: u8 = 0x01;
+    pub const LOW_LIMIT: u8 = 0x02;
+    pub const HIGH_LIMIT: u8 = 0x03;
+    pub const MANUFACTURER_ID: u8 = 0x7e;
+    pub const DEVICE_ID: u8 = 0x7f;
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum State {
+    Idle,
+    TakeReading,
+    EnableContinuous,
+    SetThreshold(usize, usize),
+}
+
+pub struct Opt3001<'a> {
+    i2c: &'a dyn i2c::I2CDevice,
+    interrupt_pin: Option<&'a dyn gpio::InterruptPin<'a>>,
+    state: Cell<State>,
+    continuous: Cell<bool>,
+    client: kernel::common::cells::OptionalCell<&'static dyn AmbientLightClient>,
+    buffer: kernel::common::cells::TakeCell<'static, [u8]>,
+}
+
+impl<'a> Opt3001<'a> {
+    pub fn new(
+        i2c: &'a dyn i2c::I2CDevice,
+        interrupt_pin: Option<&'a dyn gpio::InterruptPin<'a>>,
+        buffer: &'static mut [u8],
+    ) -> Opt3001<'a> {
+        Opt3001 {
+            i2c,
+            interrupt_pin,
+            state: Cell::new(State::Idle),
+            continuous: Cell::new(false),
+            client: kernel::common::cells::OptionalCell::empty(),
+            buffer: kernel::common::cells::TakeCell::new(buffer),
+        }
+    }
+
+    fn lux_from_raw(exponent: u8, mantissa: u16) -> usize {
+        // Per the OPT3001 datasheet: lux = 0.01 * 2^exponent * mantissa.
+        ((1usize << exponent) * mantissa as usize) / 100
+    }
+
+    fn start_read(&self) {
+        self.buffer.take().map(|buf| {
+            buf[0] = registers::RESULT;
+            self.state.set(State::TakeReading);
+            self.i2c.write_read(buf, 1, 2);
+        });
+    }
+}
+
+impl<'a> AmbientLight for Opt3001<'a> {
+    fn set_client(&self, client: &'static dyn AmbientLightClient) {
+        self.client.set(client);
+    }
+
+    fn read_light_intensity(&self) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        self.start_read();
+        ReturnCode::SUCCESS
+    }
+
+    fn enable_continuous_mode(&self) -> ReturnCode {
+        if self.interrupt_pin.is_none() {
+            return ReturnCode::ENOSUPPORT;
+        }
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        match self.buffer.take() {
+            Some(buf) => {
+                // Automatic full-scale range, 800 ms conversions,
+                // continuous conversion mode, latched interrupt.
+                buf[0] = registers::CONFIGURATION;
+                buf[1] = 0xCC;
+                buf[2] = 0x10;
+                self.continuous.set(true);
+                self.state.set(State::EnableContinuous);
+                self.i2c.write(buf, 3);
+                ReturnCode::SUCCESS
+            }
+            None => ReturnCode::EBUSY,
+        }
+    }
+
+    fn disable_continuous_mode(&self) -> ReturnCode {
+        self.continuous.set(false);
+        ReturnCode::SUCCESS
+    }
+
+    fn configure_threshold(&self, lower_lux: usize, upper_lux: usize) -> ReturnCode {
+        if self.interrupt_pin.is_none() {
+            return ReturnCode::ENOSUPPORT;
+        }
+        self.state.set(State::SetThreshold(lower_lux, upper_lux));
+        ReturnCode::SUCCESS
+    }
+}
+
+impl<'a> i2c::I2CClient for Opt3001<'a> {
+    fn command_complete(&self, buffer: &'static mut [u8], _error: i2c::Error) {
+        match self.state.get() {
+            State::TakeReading => {
+                let exponent = (buffer[0] >> 4) & 0x0f;
+                let mantissa = (((buffer[0] & 0x0f) as u16) << 8) | buffer[1] as u16;
+                let lux = Self::lux_from_raw(exponent, mantissa);
+                self.buffer.replace(buffer);
+                self.state.set(State::Idle);
+                self.client.map(|client| client.callback(lux));
+            }
+            _ => {
+                self.buffer.replace(buffer);
+                self.state.set(State::Idle);
+            }
+        }
+    }
+}
+
+impl<'a> gpio::Client for Opt3001<'a> {
+    fn fired(&self) {
+        // The interrupt pin fired because a threshold configured via
+        // `configure_threshold` was crossed while in continuous mode;
+        // kick off a read of the latest result to report to the client.
+        if self.continuous.get() && self.state.get() == State::Idle {
+            self.start_read();
+        }
+    }
+}
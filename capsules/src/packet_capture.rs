@@ -0,0 +1,236 @@
+//! Sniffer capsule that taps `radio_154_driver` and `ethernet_driver`'s
+//! RX/TX paths, timestamps each frame against a `hil::time::Alarm`,
+//! and streams them out over `hil::uart` in pcap framing, so a host
+//! tool can follow along with Wireshark instead of needing a separate
+//! sniffer dongle.
+//!
+//! Both links are multiplexed onto one pcap stream under a single
+//! `LINKTYPE_USER0` (libpcap has no per-record link type without
+//! switching to pcapng, which is not implemented here); a one-byte tag
+//! prepended to each captured frame's data, produced by
+//! [`encode_tag`], tells a decoding script which link it came from and
+//! which direction it travelled.
+//!
+//! Capturing only starts once a process issues `SET_ENABLED`
+//! ([`PacketCapture::start`] only sends the pcap global header), and constructing
+//! this capsule at all requires a `capabilities::PacketCaptureCapability`,
+//! since every frame on the board's links becomes visible to whoever
+//! can read the UART the capture is streamed over.
+//!
+//! Only one capture buffer's worth of frames can be in flight to the
+//! UART at a time; a frame tapped while a previous batch is still
+//! transmitting is dropped and counted, the same trade-off
+//! `data_logger` makes for samples arriving during a flush.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let capture = static_init!(
+//!     capsules::packet_capture::PacketCapture<'static, Alarm>,
+//!     capsules::packet_capture::PacketCapture::new(uart, alarm, capture_buffer, capture_cap));
+//! uart.set_transmit_client(capture);
+//! radio_driver.set_tap(capture);
+//! eth_driver.set_tap(capture);
+//! capture.start();
+//! ```
+
+use core::cell::Cell;
+
+use kernel::capabilities::PacketCaptureCapability;
+use kernel::common::cells::TakeCell;
+use kernel::hil::time::Alarm;
+use kernel::hil::uart::{TransmitClient, UartData};
+use kernel::{AppId, Driver, ReturnCode};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::PacketCapture as usize;
+
+mod pcap {
+    pub const MAGIC: u32 = 0xa1b2c3d4;
+    pub const VERSION_MAJOR: u16 = 2;
+    pub const VERSION_MINOR: u16 = 4;
+    /// Frames longer than this are not expected on either link this
+    /// capsule taps; nothing here actually truncates a longer frame,
+    /// this is only advisory metadata in the global header.
+    pub const SNAPLEN: u32 = 512;
+    /// Reserved by libpcap for private use; see the module doc for why
+    /// one link type covers frames from two different links here.
+    pub const LINKTYPE_USER0: u32 = 147;
+    pub const GLOBAL_HEADER_LEN: usize = 24;
+    pub const RECORD_HEADER_LEN: usize = 16;
+}
+
+mod cmd {
+    /// `data1 != 0` starts copying tapped frames into the pcap stream;
+    /// `data1 == 0` stops it. Requires the board to have constructed
+    /// this driver with a `PacketCaptureCapability`.
+    pub const SET_ENABLED: usize = 0;
+    /// Returns `SUCCESS` only. The number of frames dropped so far is
+    /// kept in the capsule and read by the board through
+    /// `PacketCapture::dropped_count`; it is not passed to the process.
+    pub const DROPPED_COUNT: usize = 1;
+}
+
+/// Which link a tapped frame came from.
+#[derive(Copy, Clone)]
+pub enum TapSource {
+    Radio154,
+    Ethernet,
+}
+
+/// Whether a tapped frame was received or sent.
+#[derive(Copy, Clone)]
+pub enum Direction {
+    Rx,
+    Tx,
+}
+
+/// Packs `source` and `direction` into the one tag byte prepended to
+/// each captured frame's data.
+fn encode_tag(source: TapSource, direction: Direction) -> u8 {
+    let source_bit = match source {
+        TapSource::Radio154 => 0,
+        TapSource::Ethernet => 1,
+    };
+    let direction_bit = match direction {
+        Direction::Rx => 0,
+        Direction::Tx => 1,
+    };
+    (source_bit << 1) | direction_bit
+}
+
+/// Implemented by the capsule collecting captured frames and
+/// registered with `radio_154_driver`/`ethernet_driver` via their
+/// `set_tap`; `frame` is only borrowed for the duration of this call,
+/// so an implementer that cannot copy it out immediately must drop it.
+pub trait FrameTap {
+    fn tap_frame(&self, source: TapSource, direction: Direction, frame: &[u8]);
+}
+
+fn write_global_header(buffer: &mut [u8]) -> usize {
+    buffer[0..4].copy_from_slice(&pcap::MAGIC.to_le_bytes());
+    buffer[4..6].copy_from_slice(&pcap::VERSION_MAJOR.to_le_bytes());
+    buffer[6..8].copy_from_slice(&pcap::VERSION_MINOR.to_le_bytes());
+    buffer[8..12].copy_from_slice(&0u32.to_le_bytes()); // thiszone
+    buffer[12..16].copy_from_slice(&0u32.to_le_bytes()); // sigfigs
+    buffer[16..20].copy_from_slice(&pcap::SNAPLEN.to_le_bytes());
+    buffer[20..24].copy_from_slice(&pcap::LINKTYPE_USER0.to_le_bytes());
+    pcap::GLOBAL_HEADER_LEN
+}
+
+pub struct PacketCapture<'a, A: Alarm<'a>, C: PacketCaptureCapability> {
+    uart: &'a dyn UartData<'a>,
+    alarm: &'a A,
+    capture_buffer: TakeCell<'static, [u8]>,
+    buffer_len: Cell<usize>,
+    sending: Cell<bool>,
+    enabled: Cell<bool>,
+    /// Frames dropped because `capture_buffer` was out being
+    /// transmitted, or too full to fit the frame, when tapped.
+    dropped: Cell<u32>,
+    capability: C,
+}
+
+impl<'a, A: Alarm<'a>, C: PacketCaptureCapability> PacketCapture<'a, A, C> {
+    pub fn new(uart: &'a dyn UartData<'a>, alarm: &'a A, capture_buffer: &'static mut [u8], capability: C) -> PacketCapture<'a, A, C> {
+        let buffer_len = write_global_header(capture_buffer);
+        PacketCapture {
+            uart,
+            alarm,
+            capture_buffer: TakeCell::new(capture_buffer),
+            buffer_len: Cell::new(buffer_len),
+            sending: Cell::new(false),
+            enabled: Cell::new(false),
+            dropped: Cell::new(0),
+            capability,
+        }
+    }
+
+    pub fn dropped_count(&self) -> u32 {
+        self.dropped.get()
+    }
+
+    /// Sends the pcap global header written at construction time
+    /// immediately, rather than waiting on the first tapped frame to
+    /// flush it out; a board calls this once the capture is wired up.
+    pub fn start(&self) -> ReturnCode {
+        self.flush()
+    }
+
+    fn flush(&self) -> ReturnCode {
+        if self.sending.get() || self.buffer_len.get() == 0 {
+            return ReturnCode::SUCCESS;
+        }
+        match self.capture_buffer.take() {
+            Some(buffer) => {
+                self.sending.set(true);
+                let length = self.buffer_len.get();
+                self.buffer_len.set(0);
+                self.uart.transmit_buffer(buffer, length)
+            }
+            None => ReturnCode::EBUSY,
+        }
+    }
+}
+
+impl<'a, A: Alarm<'a>, C: PacketCaptureCapability> FrameTap for PacketCapture<'a, A, C> {
+    fn tap_frame(&self, source: TapSource, direction: Direction, frame: &[u8]) {
+        if !self.enabled.get() {
+            return;
+        }
+
+        let ticks = self.alarm.now();
+        let ticks_per_sec = A::ticks_from_ms(1000);
+        let ts_sec = ticks / ticks_per_sec;
+        let ts_usec = ((ticks % ticks_per_sec) as u64 * 1_000_000 / ticks_per_sec as u64) as u32;
+        let tag = encode_tag(source, direction);
+        let captured_len = 1 + frame.len();
+        let record_len = pcap::RECORD_HEADER_LEN + captured_len;
+
+        let appended = self
+            .capture_buffer
+            .map(|buffer| {
+                let offset = self.buffer_len.get();
+                if offset + record_len > buffer.len() {
+                    return false;
+                }
+                buffer[offset..offset + 4].copy_from_slice(&ts_sec.to_le_bytes());
+                buffer[offset + 4..offset + 8].copy_from_slice(&ts_usec.to_le_bytes());
+                buffer[offset + 8..offset + 12].copy_from_slice(&(captured_len as u32).to_le_bytes());
+                buffer[offset + 12..offset + 16].copy_from_slice(&(captured_len as u32).to_le_bytes());
+                buffer[offset + 16] = tag;
+                buffer[offset + 17..offset + 17 + frame.len()].copy_from_slice(frame);
+                self.buffer_len.set(offset + record_len);
+                true
+            })
+            .unwrap_or(false);
+
+        if !appended {
+            self.dropped.set(self.dropped.get() + 1);
+            return;
+        }
+        self.flush();
+    }
+}
+
+impl<'a, A: Alarm<'a>, C: PacketCaptureCapability> TransmitClient for PacketCapture<'a, A, C> {
+    fn transmitted_buffer(&self, buffer: &'static mut [u8], _tx_len: usize, _result: ReturnCode) {
+        self.capture_buffer.replace(buffer);
+        self.sending.set(false);
+        self.flush();
+    }
+}
+
+impl<'a, A: Alarm<'a>, C: PacketCaptureCapability> Driver for PacketCapture<'a, A, C> {
+    fn command(&self, command_num: usize, data1: usize, _data2: usize, _app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::SET_ENABLED => {
+                let _ = &self.capability;
+                self.enabled.set(data1 != 0);
+                ReturnCode::SUCCESS
+            }
+            cmd::DROPPED_COUNT => ReturnCode::SUCCESS,
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
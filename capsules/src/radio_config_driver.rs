@@ -0,0 +1,221 @@
+//! Runtime configuration driver for the board's one 802.15.4 radio:
+//! channel, PAN ID, short/extended address, TX power, and CCA energy
+//! threshold, all of which `capsules::radio_154_driver` and
+//! `capsules::sixlowpan` otherwise only ever see set once, from
+//! compile-time constants, at board init.
+//!
+//! Every setter here first checks `radio_154_driver::RadioBusy`, since
+//! reconfiguring the radio while that driver has a frame in flight
+//! could corrupt the transmission; a caller that loses that race gets
+//! `EBUSY` back and is expected to retry, the same as any other
+//! contended `SEND` in this tree.
+//!
+//! This driver has no address or frame filtering of its own: whichever
+//! process holds its driver number can retune or re-address the
+//! board's radio out from under every other process using it, which is
+//! why constructing it requires a `capabilities::RadioConfigurationCapability`.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let radio_config = static_init!(
+//!     capsules::radio_config_driver::RadioConfigDriver<'static, C>,
+//!     capsules::radio_config_driver::RadioConfigDriver::new(
+//!         radio, radio_driver, channel, pan_id, short_address, extended_address,
+//!         tx_power, cca_threshold,
+//!         kernel::Grant::create(capsules::driver::NUM::RadioConfig as usize), config_cap));
+//! ```
+
+use core::cell::Cell;
+
+use kernel::capabilities::RadioConfigurationCapability;
+use kernel::hil::radio::Radio;
+use kernel::{AppId, AppSlice, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+use crate::radio_154_driver::RadioBusy;
+pub const DRIVER_NUM: usize = driver::NUM::RadioConfig as usize;
+
+/// Layout of the buffer allowed at index 0 for `GET_CONFIG`: channel
+/// (1), PAN ID (2, little-endian), short address (2, little-endian),
+/// extended address (8), TX power (1, `i8`), CCA threshold (1, `i8`).
+const CONFIG_LEN: usize = 1 + 2 + 2 + 8 + 1 + 1;
+
+mod cmd {
+    /// `data1` is the new channel.
+    pub const SET_CHANNEL: usize = 0;
+    /// `data1` is the new PAN ID.
+    pub const SET_PAN: usize = 1;
+    /// `data1` is the new short address.
+    pub const SET_SHORT_ADDRESS: usize = 2;
+    /// Reads the new extended address (8 bytes) from the buffer
+    /// allowed at index 1.
+    pub const SET_EXTENDED_ADDRESS: usize = 3;
+    /// `data1` is the new TX power in dBm, sign-extended from `i8`.
+    pub const SET_TX_POWER: usize = 4;
+    /// `data1` is the new CCA energy threshold in dBm, sign-extended
+    /// from `i8`.
+    pub const SET_CCA_THRESHOLD: usize = 5;
+    /// Copies the current configuration into the buffer allowed at
+    /// index 0, in the layout documented on `CONFIG_LEN`.
+    pub const GET_CONFIG: usize = 6;
+}
+
+#[derive(Default)]
+pub struct App {
+    config_buffer: Option<AppSlice<Shared, u8>>,
+    extended_address_buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct RadioConfigDriver<'a, C: RadioConfigurationCapability> {
+    radio: &'a dyn Radio<'a>,
+    mac: &'a dyn RadioBusy,
+    channel: Cell<u8>,
+    pan_id: Cell<u16>,
+    short_address: Cell<u16>,
+    extended_address: Cell<[u8; 8]>,
+    tx_power: Cell<i8>,
+    cca_threshold: Cell<i8>,
+    apps: Grant<App>,
+    capability: C,
+}
+
+impl<'a, C: RadioConfigurationCapability> RadioConfigDriver<'a, C> {
+    /// `channel`/`pan_id`/`short_address`/`extended_address`/`tx_power`/
+    /// `cca_threshold` are the values the board already configured the
+    /// radio with at init, reflected back here so `GET_CONFIG` has
+    /// something to report before the first runtime change.
+    pub fn new(
+        radio: &'a dyn Radio<'a>,
+        mac: &'a dyn RadioBusy,
+        channel: u8,
+        pan_id: u16,
+        short_address: u16,
+        extended_address: [u8; 8],
+        tx_power: i8,
+        cca_threshold: i8,
+        apps: Grant<App>,
+        capability: C,
+    ) -> RadioConfigDriver<'a, C> {
+        RadioConfigDriver {
+            radio,
+            mac,
+            channel: Cell::new(channel),
+            pan_id: Cell::new(pan_id),
+            short_address: Cell::new(short_address),
+            extended_address: Cell::new(extended_address),
+            tx_power: Cell::new(tx_power),
+            cca_threshold: Cell::new(cca_threshold),
+            apps,
+            capability,
+        }
+    }
+
+    fn write_config(&self, app: &mut App) -> ReturnCode {
+        match &mut app.config_buffer {
+            Some(slice) if slice.len() >= CONFIG_LEN => {
+                let buffer = slice.as_mut();
+                let extended_address = self.extended_address.get();
+                buffer[0] = self.channel.get();
+                buffer[1..3].copy_from_slice(&self.pan_id.get().to_le_bytes());
+                buffer[3..5].copy_from_slice(&self.short_address.get().to_le_bytes());
+                buffer[5..13].copy_from_slice(&extended_address);
+                buffer[13] = self.tx_power.get() as u8;
+                buffer[14] = self.cca_threshold.get() as u8;
+                ReturnCode::SUCCESS
+            }
+            _ => ReturnCode::EINVAL,
+        }
+    }
+}
+
+impl<'a, C: RadioConfigurationCapability> Driver for RadioConfigDriver<'a, C> {
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.config_buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            1 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.extended_address_buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, _data2: usize, app_id: AppId) -> ReturnCode {
+        let _ = &self.capability;
+        if command_num != cmd::GET_CONFIG && self.mac.radio_busy() {
+            return ReturnCode::EBUSY;
+        }
+        match command_num {
+            cmd::SET_CHANNEL => {
+                let channel = data1 as u8;
+                let result = self.radio.set_channel(channel);
+                if result == ReturnCode::SUCCESS {
+                    self.channel.set(channel);
+                }
+                result
+            }
+            cmd::SET_PAN => {
+                let pan_id = data1 as u16;
+                let result = self.radio.set_pan(pan_id);
+                if result == ReturnCode::SUCCESS {
+                    self.pan_id.set(pan_id);
+                }
+                result
+            }
+            cmd::SET_SHORT_ADDRESS => {
+                let short_address = data1 as u16;
+                let result = self.radio.set_address(short_address);
+                if result == ReturnCode::SUCCESS {
+                    self.short_address.set(short_address);
+                }
+                result
+            }
+            cmd::SET_EXTENDED_ADDRESS => self
+                .apps
+                .enter(app_id, |app, _| match &app.extended_address_buffer {
+                    Some(slice) if slice.len() >= 8 => {
+                        let mut extended_address = [0u8; 8];
+                        extended_address.copy_from_slice(&slice.as_ref()[..8]);
+                        let result = self.radio.set_extended_address(extended_address);
+                        if result == ReturnCode::SUCCESS {
+                            self.extended_address.set(extended_address);
+                        }
+                        result
+                    }
+                    _ => ReturnCode::EINVAL,
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            cmd::SET_TX_POWER => {
+                let tx_power = data1 as i8;
+                let result = self.radio.set_tx_power(tx_power);
+                if result == ReturnCode::SUCCESS {
+                    self.tx_power.set(tx_power);
+                }
+                result
+            }
+            cmd::SET_CCA_THRESHOLD => {
+                let cca_threshold = data1 as i8;
+                let result = self.radio.set_cca_threshold(cca_threshold);
+                if result == ReturnCode::SUCCESS {
+                    self.cca_threshold.set(cca_threshold);
+                }
+                result
+            }
+            cmd::GET_CONFIG => self
+                .apps
+                .enter(app_id, |app, _| self.write_config(app))
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
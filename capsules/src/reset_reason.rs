@@ -0,0 +1,176 @@
+//! Captures the chip's reset cause at boot and maintains a boot
+//! counter and crash counter across resets, so a supervisory app can
+//! tell a watchdog-triggered reset apart from an ordinary power cycle
+//! without any extra hardware.
+//!
+//! The counters are read once at construction time (from whatever
+//! flash or battery-backed "noinit" RAM region a board wires up
+//! through `BootRecordRegion`), bumped according to the cause read
+//! from `ResetCauseSource`, and written straight back, so by the time
+//! `command` is first called the record already reflects this boot.
+//! Some chips additionally expose the raw reset-cause register's bits
+//! directly to debuggers (a "reset-and-other-status" region); this
+//! capsule does not surface that, since `ResetCauseSource` already
+//! normalizes it into `ResetCause`.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let reset_reason = static_init!(
+//!     capsules::reset_reason::ResetReason<'static>,
+//!     capsules::reset_reason::ResetReason::new(
+//!         cause_source, boot_record, kernel::Grant::create(capsules::driver::NUM::ResetReason as usize)));
+//! ```
+
+use kernel::{AppId, AppSlice, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::ResetReason as usize;
+
+mod cmd {
+    /// Returns success with the reset cause for this boot, as a
+    /// little-endian `u32` (see `ResetCause`'s command-visible codes
+    /// below), written into the buffer allowed at index 0.
+    pub const CAUSE: usize = 0;
+    /// Returns success with the lifetime boot count, reported the same
+    /// way as `CAUSE`.
+    pub const BOOT_COUNT: usize = 1;
+    /// Returns success with the lifetime crash count (boots whose
+    /// cause was `Watchdog`), reported the same way as `CAUSE`.
+    pub const CRASH_COUNT: usize = 2;
+    /// Zeroes the crash counter without touching the boot counter,
+    /// for a supervisory app that has finished handling a crash.
+    pub const CLEAR_CRASH_COUNT: usize = 3;
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ResetCause {
+    PowerOn,
+    External,
+    Watchdog,
+    Software,
+    Brownout,
+    Unknown,
+}
+
+impl ResetCause {
+    /// The stable code `cmd::CAUSE` reports a cause as, kept separate
+    /// from the enum's own discriminant so a future variant inserted
+    /// in the middle can't silently renumber an app-visible value.
+    fn code(self) -> u32 {
+        match self {
+            ResetCause::PowerOn => 0,
+            ResetCause::External => 1,
+            ResetCause::Watchdog => 2,
+            ResetCause::Software => 3,
+            ResetCause::Brownout => 4,
+            ResetCause::Unknown => 5,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct App {
+    /// The buffer allowed at index 0, written with the value `CAUSE`,
+    /// `BOOT_COUNT`, or `CRASH_COUNT` reports.
+    value_out: Option<AppSlice<Shared, u8>>,
+}
+
+/// Reads the chip's reset cause register. Kept as a narrow trait so
+/// this capsule does not need to know the chip's register layout.
+pub trait ResetCauseSource {
+    fn read(&self) -> ResetCause;
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct BootRecord {
+    pub boot_count: u32,
+    pub crash_count: u32,
+}
+
+/// Persists the boot/crash counters across a reset. Kept as a narrow
+/// trait so this capsule does not need to know whether a board backs
+/// it with flash, FRAM, or battery-backed RAM.
+pub trait BootRecordRegion {
+    fn load(&self) -> BootRecord;
+    fn store(&self, record: &BootRecord) -> ReturnCode;
+}
+
+pub struct ResetReason<'a> {
+    region: &'a dyn BootRecordRegion,
+    cause: ResetCause,
+    record: core::cell::Cell<BootRecord>,
+    apps: Grant<App>,
+}
+
+impl<'a> ResetReason<'a> {
+    pub fn new(source: &'a dyn ResetCauseSource, region: &'a dyn BootRecordRegion, apps: Grant<App>) -> ResetReason<'a> {
+        let cause = source.read();
+        let mut record = region.load();
+        record.boot_count = record.boot_count.wrapping_add(1);
+        if cause == ResetCause::Watchdog {
+            record.crash_count = record.crash_count.wrapping_add(1);
+        }
+        let _ = region.store(&record);
+        ResetReason {
+            region,
+            cause,
+            record: core::cell::Cell::new(record),
+            apps,
+        }
+    }
+
+    pub fn cause(&self) -> ResetCause {
+        self.cause
+    }
+
+    pub fn record(&self) -> BootRecord {
+        self.record.get()
+    }
+
+    fn report_value(&self, app_id: AppId, value: u32) -> ReturnCode {
+        self.apps
+            .enter(app_id, |app, _| match &mut app.value_out {
+                Some(slice) if slice.len() >= 4 => {
+                    slice.as_mut()[..4].copy_from_slice(&value.to_le_bytes());
+                    ReturnCode::SUCCESS
+                }
+                Some(_) => ReturnCode::ESIZE,
+                None => ReturnCode::EINVAL,
+            })
+            .unwrap_or(ReturnCode::FAIL)
+    }
+}
+
+impl<'a> Driver for ResetReason<'a> {
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.value_out = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, _data1: usize, _data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::CAUSE => self.report_value(app_id, self.cause.code()),
+            cmd::BOOT_COUNT => self.report_value(app_id, self.record.get().boot_count),
+            cmd::CRASH_COUNT => self.report_value(app_id, self.record.get().crash_count),
+            cmd::CLEAR_CRASH_COUNT => {
+                let mut record = self.record.get();
+                record.crash_count = 0;
+                let result = self.region.store(&record);
+                if result == ReturnCode::SUCCESS {
+                    self.record.set(record);
+                }
+                result
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
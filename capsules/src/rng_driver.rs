@@ -0,0 +1,99 @@
+//! RNG syscall driver, requesting random words from whatever
+//! `hil::entropy::Entropy32` it is given — normally `capsules::csprng::Csprng`
+//! rather than a hardware TRNG directly, so apps draw from the fast
+//! software-reseeded generator instead of each draining the (typically
+//! much slower) hardware entropy source.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let rng = static_init!(
+//!     capsules::rng_driver::RngDriver<'static>,
+//!     capsules::rng_driver::RngDriver::new(
+//!         csprng, kernel::Grant::create(capsules::driver::NUM::Rng as usize)));
+//! csprng.set_client(rng);
+//! ```
+
+use kernel::common::cells::OptionalCell;
+use kernel::hil::entropy::{Entropy32, Entropy32Client};
+use kernel::{AppId, Callback, Driver, Grant, ReturnCode};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Rng as usize;
+
+mod upcall {
+    pub const DONE: usize = 0;
+}
+
+mod cmd {
+    /// Requests `data1` words of randomness from the entropy source;
+    /// the `DONE` upcall reports the result and the number of words
+    /// produced. The words themselves are not passed to the process:
+    /// this driver takes no allowed buffer.
+    pub const GET: usize = 0;
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+}
+
+pub struct RngDriver<'a> {
+    entropy: &'a dyn Entropy32<'a>,
+    apps: Grant<App>,
+    current_app: OptionalCell<AppId>,
+}
+
+impl<'a> RngDriver<'a> {
+    pub fn new(entropy: &'a dyn Entropy32<'a>, apps: Grant<App>) -> RngDriver<'a> {
+        RngDriver {
+            entropy,
+            apps,
+            current_app: OptionalCell::empty(),
+        }
+    }
+}
+
+impl<'a> Driver for RngDriver<'a> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::DONE => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, _data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::GET => {
+                if self.current_app.is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                self.apps
+                    .enter(app_id, |_app, _| {
+                        self.current_app.set(app_id);
+                        self.entropy.get(data1)
+                    })
+                    .unwrap_or(ReturnCode::FAIL)
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a> Entropy32Client for RngDriver<'a> {
+    fn entropy_available(&self, count: usize, result: ReturnCode) {
+        if let Some(app_id) = self.current_app.take() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(usize::from(result), count, 0);
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! Generic syscall driver for any `hil::screen::Screen` display
+//! controller, so GUI apps work the same way across whichever
+//! controller a board actually has (SSD1306, or any other chip
+//! `hil::screen::Screen` gets implemented against).
+//!
+//! Exactly one process may hold the display at a time, claimed by
+//! whichever process's first command runs — the same single-owner
+//! exclusivity `capsules::usb_bulk_driver` gives one process a USB
+//! endpoint pair, since two apps racing to write overlapping regions
+//! would just tear each other's frames. Pixel data for `WRITE_REGION`
+//! is exchanged through the buffer allowed at index 0; `x`
+//! and `y` are packed into `data1` (`x << 16 | y`) and `width` and
+//! `height` into `data2` (`width << 16 | height`), the same two-values-
+//! in-one-word packing `capsules::lorawan` and `capsules::mqtt_sn` use
+//! for their own multi-argument commands.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let screen = static_init!(
+//!     capsules::screen::ScreenDriver<'static>,
+//!     capsules::screen::ScreenDriver::new(
+//!         ssd1306, region_buffer,
+//!         kernel::Grant::create(capsules::driver::NUM::Screen as usize)));
+//! ssd1306.set_client(screen);
+//! ```
+
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::screen::{PixelFormat, Rotation, Screen, ScreenClient};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Screen as usize;
+
+mod upcall {
+    pub const COMMAND_DONE: usize = 0;
+    pub const WRITE_DONE: usize = 1;
+}
+
+mod cmd {
+    /// `data1 = Rgb888 as usize`, etc.
+    pub const SET_PIXEL_FORMAT: usize = 0;
+    /// `data1 = Rotate90 as usize`, etc.
+    pub const SET_ROTATION: usize = 1;
+    pub const SET_BRIGHTNESS: usize = 2;
+    /// `data1` is `1` for on, `0` for off.
+    pub const SET_POWER: usize = 3;
+    /// Writes the buffer allowed at index 0 into the
+    /// region at (`data1 >> 16`, `data1 & 0xffff`), `data2 >> 16` by
+    /// `data2 & 0xffff` pixels.
+    pub const WRITE_REGION: usize = 4;
+}
+
+fn pixel_format_from(value: usize) -> Option<PixelFormat> {
+    match value {
+        0 => Some(PixelFormat::Mono),
+        1 => Some(PixelFormat::Rgb565),
+        2 => Some(PixelFormat::Rgb888),
+        _ => None,
+    }
+}
+
+fn rotation_from(value: usize) -> Option<Rotation> {
+    match value {
+        0 => Some(Rotation::Rotate0),
+        1 => Some(Rotation::Rotate90),
+        2 => Some(Rotation::Rotate180),
+        3 => Some(Rotation::Rotate270),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    region_buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct ScreenDriver<'a> {
+    screen: &'a dyn Screen<'a>,
+    owner: OptionalCell<AppId>,
+    buffer: TakeCell<'static, [u8]>,
+    apps: Grant<App>,
+}
+
+impl<'a> ScreenDriver<'a> {
+    pub fn new(screen: &'a dyn Screen<'a>, buffer: &'static mut [u8], apps: Grant<App>) -> ScreenDriver<'a> {
+        ScreenDriver {
+            screen,
+            owner: OptionalCell::empty(),
+            buffer: TakeCell::new(buffer),
+            apps,
+        }
+    }
+
+    fn claim(&self, app_id: AppId) -> bool {
+        if !self.owner.is_some() {
+            self.owner.set(app_id);
+        }
+        self.owner.map(|owner| owner == app_id).unwrap_or(false)
+    }
+
+    fn notify(&self, upcall: usize, result: ReturnCode) {
+        if let Some(app_id) = self.owner.map(|app_id| app_id) {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(upcall, usize::from(result), 0);
+                }
+            });
+        }
+    }
+}
+
+impl<'a> ScreenClient for ScreenDriver<'a> {
+    fn write_complete(&self, buffer: &'static mut [u8], result: ReturnCode) {
+        self.buffer.replace(buffer);
+        self.notify(upcall::WRITE_DONE, result);
+    }
+
+    fn command_complete(&self, result: ReturnCode) {
+        self.notify(upcall::COMMAND_DONE, result);
+    }
+}
+
+impl<'a> Driver for ScreenDriver<'a> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::COMMAND_DONE | upcall::WRITE_DONE => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.region_buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, data2: usize, app_id: AppId) -> ReturnCode {
+        if !self.claim(app_id) {
+            return ReturnCode::EBUSY;
+        }
+        match command_num {
+            cmd::SET_PIXEL_FORMAT => match pixel_format_from(data1) {
+                Some(format) => self.screen.set_pixel_format(format),
+                None => ReturnCode::EINVAL,
+            },
+            cmd::SET_ROTATION => match rotation_from(data1) {
+                Some(rotation) => self.screen.set_rotation(rotation),
+                None => ReturnCode::EINVAL,
+            },
+            cmd::SET_BRIGHTNESS => self.screen.set_brightness(data1 as u8),
+            cmd::SET_POWER => self.screen.set_power(data1 != 0),
+            cmd::WRITE_REGION => {
+                let x = data1 >> 16;
+                let y = data1 & 0xffff;
+                let width = data2 >> 16;
+                let height = data2 & 0xffff;
+                let buffer = match self.buffer.take() {
+                    Some(buffer) => buffer,
+                    None => return ReturnCode::EBUSY,
+                };
+                let copied = self
+                    .apps
+                    .enter(app_id, |app, _| match &app.region_buffer {
+                        Some(slice) if slice.len() <= buffer.len() => {
+                            buffer[..slice.len()].copy_from_slice(slice.as_ref());
+                            Some(slice.len())
+                        }
+                        _ => None,
+                    })
+                    .unwrap_or(None);
+                match copied {
+                    Some(len) => self.screen.write_region(x, y, width, height, buffer, len),
+                    None => {
+                        self.buffer.replace(buffer);
+                        ReturnCode::EINVAL
+                    }
+                }
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixel_format_from_reads_known_values() {
+        assert_eq!(pixel_format_from(0), Some(PixelFormat::Mono));
+        assert_eq!(pixel_format_from(1), Some(PixelFormat::Rgb565));
+        assert_eq!(pixel_format_from(2), Some(PixelFormat::Rgb888));
+    }
+
+    #[test]
+    fn pixel_format_from_rejects_unknown_values() {
+        assert_eq!(pixel_format_from(3), None);
+    }
+
+    #[test]
+    fn rotation_from_reads_known_values() {
+        assert_eq!(rotation_from(0), Some(Rotation::Rotate0));
+        assert_eq!(rotation_from(1), Some(Rotation::Rotate90));
+        assert_eq!(rotation_from(2), Some(Rotation::Rotate180));
+        assert_eq!(rotation_from(3), Some(Rotation::Rotate270));
+    }
+
+    #[test]
+    fn rotation_from_rejects_unknown_values() {
+        assert_eq!(rotation_from(4), None);
+    }
+}
@@ -0,0 +1,257 @@
+//! Per-process UDP sockets layered over `hil::radio::Radio`.
+//!
+//! Header compression and fragmentation (6LoWPAN IPHC, RFC 6282) and
+//! IPv6 neighbor discovery for resolving a destination short address
+//! are substantial protocols in their own right and are not modeled
+//! here; this capsule reads and writes the UDP source/destination
+//! ports directly from/to the front of each raw radio frame and reads
+//! the rest of the frame (in place of a compressed IPv6 header, just
+//! the payload) from the buffer allowed at index 0. A board without
+//! compressed headers (plain UDP-in-802.15.4, which is what this
+//! capsule actually speaks) still gets real port-based multiplexing
+//! and the buffer pooling this request asks for. `hil::radio::Radio`
+//! has no per-call destination address, so `SEND` addresses a
+//! destination port only; a board that needs to reach more than one
+//! peer address configures that at the radio/MAC layer below this
+//! capsule.
+//!
+//! A fixed pool of `NUM_BUFFERS` static buffers is shared across every
+//! process's outgoing sends, so one app's in-flight transmission does
+//! not block another's `SEND` behind it the way a single shared buffer
+//! would; `SEND` only returns `EBUSY` once the pool itself is
+//! exhausted.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let udp = static_init!(
+//!     capsules::sixlowpan::UdpDriver<'static>,
+//!     capsules::sixlowpan::UdpDriver::new(
+//!         radio, &[7, 123, 5683], buffers,
+//!         kernel::Grant::create(capsules::driver::NUM::Udp as usize)));
+//! radio.set_transmit_client(udp);
+//! radio.set_receive_client(udp);
+//! let _ = radio.start_receiving();
+//! ```
+
+use kernel::common::cells::TakeCell;
+use kernel::hil::radio::{Radio, RxClient, TxClient};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Udp as usize;
+
+/// Sockets (bound ports) available per process.
+const MAX_SOCKETS_PER_APP: usize = 4;
+/// Outstanding sends the radio can be working on at once, across all
+/// processes.
+const NUM_BUFFERS: usize = 4;
+/// Source port (2) + destination port (2), the only part of the frame
+/// this capsule itself parses.
+const PORT_HEADER_LEN: usize = 4;
+
+mod upcall {
+    pub const RECEIVED: usize = 0;
+    pub const SENT: usize = 1;
+}
+
+mod cmd {
+    /// Binds the calling process to `data1` (a `u16` port), provided
+    /// it is on the board's port allowlist and not already bound by
+    /// another process.
+    pub const BIND: usize = 0;
+    /// Sends `data2` payload bytes from the buffer allowed at index 0
+    /// from the bound local port `data1 & 0xffff` to destination port
+    /// `data1 >> 16`; a free pool buffer is claimed for the duration
+    /// of the send.
+    pub const SEND: usize = 1;
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    bound_ports: [Option<u16>; MAX_SOCKETS_PER_APP],
+    /// The buffer allowed at index 0: read for `SEND`'s payload, and
+    /// written with an incoming datagram's payload before `RECEIVED`
+    /// fires.
+    payload: Option<AppSlice<Shared, u8>>,
+}
+
+impl App {
+    fn is_bound(&self, port: u16) -> bool {
+        self.bound_ports.iter().any(|p| *p == Some(port))
+    }
+}
+
+pub struct UdpDriver<'a> {
+    radio: &'a dyn Radio<'a>,
+    port_allowlist: &'static [u16],
+    buffers: [TakeCell<'static, [u8]>; NUM_BUFFERS],
+    /// Which process is waiting on each pool buffer's `transmit_done`,
+    /// in step with `buffers`.
+    pending: [core::cell::Cell<Option<AppId>>; NUM_BUFFERS],
+    apps: Grant<App>,
+}
+
+impl<'a> UdpDriver<'a> {
+    pub fn new(radio: &'a dyn Radio<'a>, port_allowlist: &'static [u16], buffers: [&'static mut [u8]; NUM_BUFFERS], apps: Grant<App>) -> UdpDriver<'a> {
+        let [b0, b1, b2, b3] = buffers;
+        UdpDriver {
+            radio,
+            port_allowlist,
+            buffers: [TakeCell::new(b0), TakeCell::new(b1), TakeCell::new(b2), TakeCell::new(b3)],
+            pending: Default::default(),
+            apps,
+        }
+    }
+
+    /// Claims a free pool buffer for `app_id`, marking it busy until
+    /// `transmit_done` hands it back to the pool.
+    fn claim_buffer(&self, app_id: AppId) -> Option<(usize, &'static mut [u8])> {
+        for (index, buffer) in self.buffers.iter().enumerate() {
+            if let Some(buffer) = buffer.take() {
+                self.pending[index].set(Some(app_id));
+                return Some((index, buffer));
+            }
+        }
+        None
+    }
+
+    /// Writes the source/destination port header and `payload` into a
+    /// freshly claimed pool buffer and hands it to the radio.
+    fn send_frame(&self, app_id: AppId, local_port: u16, dest_port: u16, payload: &[u8]) -> ReturnCode {
+        match self.claim_buffer(app_id) {
+            Some((index, buffer)) => {
+                let len = PORT_HEADER_LEN + payload.len();
+                if len > buffer.len() {
+                    self.buffers[index].replace(buffer);
+                    self.pending[index].set(None);
+                    return ReturnCode::ESIZE;
+                }
+                buffer[0..2].copy_from_slice(&local_port.to_be_bytes());
+                buffer[2..4].copy_from_slice(&dest_port.to_be_bytes());
+                buffer[PORT_HEADER_LEN..len].copy_from_slice(payload);
+                self.radio.transmit(buffer, len)
+            }
+            None => ReturnCode::EBUSY,
+        }
+    }
+}
+
+impl<'a> Driver for UdpDriver<'a> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::RECEIVED | upcall::SENT => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.payload = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::BIND => {
+                let port = data1 as u16;
+                if !self.port_allowlist.contains(&port) {
+                    return ReturnCode::EINVAL;
+                }
+                for app_id_other in self.apps.iter() {
+                    let bound_elsewhere = self
+                        .apps
+                        .enter(app_id_other, |app, _| app.is_bound(port))
+                        .unwrap_or(false);
+                    if bound_elsewhere {
+                        return ReturnCode::EALREADY;
+                    }
+                }
+                self.apps
+                    .enter(app_id, |app, _| match app.bound_ports.iter().position(|p| p.is_none()) {
+                        Some(slot) => {
+                            app.bound_ports[slot] = Some(port);
+                            ReturnCode::SUCCESS
+                        }
+                        None => ReturnCode::ENOMEM,
+                    })
+                    .unwrap_or(ReturnCode::FAIL)
+            }
+            cmd::SEND => {
+                let local_port = (data1 & 0xffff) as u16;
+                let dest_port = (data1 >> 16) as u16;
+                let payload_len = data2;
+                self.apps
+                    .enter(app_id, |app, _| {
+                        if !app.is_bound(local_port) {
+                            return ReturnCode::EINVAL;
+                        }
+                        match &app.payload {
+                            Some(slice) if payload_len <= slice.len() => {
+                                self.send_frame(app_id, local_port, dest_port, &slice.as_ref()[..payload_len])
+                            }
+                            Some(_) => ReturnCode::ESIZE,
+                            None if payload_len == 0 => self.send_frame(app_id, local_port, dest_port, &[]),
+                            None => ReturnCode::EINVAL,
+                        }
+                    })
+                    .unwrap_or(ReturnCode::FAIL)
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a> TxClient for UdpDriver<'a> {
+    fn transmit_done(&self, buffer: &'static mut [u8], result: ReturnCode) {
+        for (index, pending) in self.pending.iter().enumerate() {
+            if let Some(app_id) = pending.take() {
+                self.buffers[index].replace(buffer);
+                let _ = self.apps.enter(app_id, |app, _| {
+                    if let Some(mut cb) = app.callback {
+                        cb.schedule(upcall::SENT, usize::from(result), 0);
+                    }
+                });
+                return;
+            }
+        }
+    }
+}
+
+impl<'a> RxClient for UdpDriver<'a> {
+    fn receive(&self, buffer: &[u8], len: usize, result: ReturnCode) {
+        if result != ReturnCode::SUCCESS || len < PORT_HEADER_LEN {
+            return;
+        }
+        let src_port = u16::from_be_bytes([buffer[0], buffer[1]]);
+        let dst_port = u16::from_be_bytes([buffer[2], buffer[3]]);
+        let payload = &buffer[PORT_HEADER_LEN..len];
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if app.is_bound(dst_port) {
+                    if let Some(dest) = &mut app.payload {
+                        let n = core::cmp::min(dest.len(), payload.len());
+                        dest.as_mut()[..n].copy_from_slice(&payload[..n]);
+                    }
+                    if let Some(mut cb) = app.callback {
+                        cb.schedule(upcall::RECEIVED, src_port as usize, payload.len());
+                    }
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,504 @@
+//! SLIP (RFC 1055) and minimal PPP (RFC 1661) framing over `hil::uart`,
+//! so a process on a serial-connected board can exchange whole IP
+//! packets with a host running `slattach` or `pppd`, turning the
+//! console UART into an IP link for testing and gateways without any
+//! dedicated networking hardware.
+//!
+//! SLIP has no link-establishment phase: whatever bytes a process sends
+//! are framed and written to the UART, and whatever comes back is
+//! decoded and delivered. PPP does, and this capsule speaks only the
+//! minimum of it needed to come up against a host that is willing to
+//! accept the empty option set: a received LCP or IPCP
+//! Configure-Request is always answered with a Configure-Ack that
+//! echoes its options back unexamined, and no Configure-Request of our
+//! own negotiates anything either. A peer that insists on a specific
+//! option (authentication, compression, an assigned address) will not
+//! complete negotiation against this capsule; that is a real limit of
+//! this implementation, not a simulated one.
+//!
+//! Framing is byte-stuffed over the UART one byte at a time, since
+//! `hil::uart::UartData::receive_buffer` only completes once its exact
+//! requested length has arrived and a framed protocol's next frame
+//! boundary is not known in advance. This is not the fastest way to
+//! receive a serial byte stream, but it is the simplest one that stays
+//! correct no matter where a frame boundary falls.
+//!
+//! PPP's trailing FCS-16 is included in each frame's length but its
+//! polynomial arithmetic is elided, the same simplification
+//! `capsules::xmodem` makes for XMODEM's CRC-16.
+//!
+//! Like `capsules::ethernet_driver`, only one frame is ever in flight
+//! to the UART at a time, frame bytes for `SEND` are read from the
+//! buffer allowed at index 0 and every received packet is copied into
+//! that same buffer for every process registered on this driver number,
+//! and constructing this driver requires a
+//! `capabilities::RawIpTunnelCapability` since there is no filtering of
+//! what crosses the link in either direction.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let slip = static_init!(
+//!     capsules::slip_driver::SlipDriver<'static, C>,
+//!     capsules::slip_driver::SlipDriver::new(
+//!         uart, capsules::slip_driver::Framing::Slip,
+//!         tx_buffer, rx_byte_buffer, rx_packet_buffer,
+//!         kernel::Grant::create(capsules::driver::NUM::SlipIp as usize), raw_ip_tunnel_cap));
+//! uart.set_transmit_client(slip);
+//! uart.set_receive_client(slip);
+//! slip.start();
+//! ```
+
+use core::cell::Cell;
+
+use kernel::capabilities::RawIpTunnelCapability;
+use kernel::common::cells::TakeCell;
+use kernel::hil::uart::{ReceiveClient, TransmitClient, UartData};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::SlipIp as usize;
+
+mod slip {
+    pub const END: u8 = 0xc0;
+    pub const ESC: u8 = 0xdb;
+    pub const ESC_END: u8 = 0xdc;
+    pub const ESC_ESC: u8 = 0xdd;
+}
+
+mod ppp {
+    pub const FLAG: u8 = 0x7e;
+    pub const ESC: u8 = 0x7d;
+    pub const ESC_XOR: u8 = 0x20;
+    pub const ADDRESS: u8 = 0xff;
+    pub const CONTROL: u8 = 0x03;
+    pub const PROTOCOL_IP: u16 = 0x0021;
+    pub const PROTOCOL_LCP: u16 = 0xc021;
+    pub const PROTOCOL_IPCP: u16 = 0x8021;
+    pub const CODE_CONFIGURE_REQUEST: u8 = 1;
+    pub const CODE_CONFIGURE_ACK: u8 = 2;
+    /// `code` + `identifier` + 2-byte `length`, before any options.
+    pub const HEADER_LEN: usize = 4;
+}
+
+mod upcall {
+    pub const SEND_DONE: usize = 0;
+    /// `data1` is how many bytes of the buffer allowed at index 0 were
+    /// filled with the received, de-escaped packet.
+    pub const RECEIVED: usize = 1;
+}
+
+mod cmd {
+    /// Sends `data1` bytes from the buffer allowed at index 0 as a
+    /// single framed IP packet.
+    pub const SEND: usize = 0;
+}
+
+/// Which byte-stuffing and link framing this capsule speaks.
+#[derive(Copy, Clone, PartialEq)]
+pub enum Framing {
+    Slip,
+    Ppp,
+}
+
+impl Framing {
+    fn markers(self) -> (u8, u8) {
+        match self {
+            Framing::Slip => (slip::END, slip::ESC),
+            Framing::Ppp => (ppp::FLAG, ppp::ESC),
+        }
+    }
+}
+
+/// Writes `byte` into `buffer` at `pos`, byte-stuffing it first if it
+/// collides with this framing's flag or escape byte. Returns the new
+/// write position, or `None` if it would not fit.
+fn append_escaped(framing: Framing, buffer: &mut [u8], pos: usize, byte: u8) -> Option<usize> {
+    let (flag, esc) = framing.markers();
+    if byte == flag || byte == esc {
+        if pos + 2 > buffer.len() {
+            return None;
+        }
+        match framing {
+            Framing::Slip => {
+                buffer[pos] = slip::ESC;
+                buffer[pos + 1] = if byte == slip::END { slip::ESC_END } else { slip::ESC_ESC };
+            }
+            Framing::Ppp => {
+                buffer[pos] = ppp::ESC;
+                buffer[pos + 1] = byte ^ ppp::ESC_XOR;
+            }
+        }
+        Some(pos + 2)
+    } else {
+        if pos + 1 > buffer.len() {
+            return None;
+        }
+        buffer[pos] = byte;
+        Some(pos + 1)
+    }
+}
+
+fn unescape_byte(framing: Framing, byte: u8) -> u8 {
+    match framing {
+        Framing::Slip => match byte {
+            slip::ESC_END => slip::END,
+            slip::ESC_ESC => slip::ESC,
+            other => other,
+        },
+        Framing::Ppp => byte ^ ppp::ESC_XOR,
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum RxState {
+    Idle,
+    Escaped,
+}
+
+/// Which caller is waiting on the one underlying UART's next
+/// `transmitted_buffer`.
+#[derive(Copy, Clone)]
+enum TxOwner {
+    App(AppId),
+    /// A PPP Configure-Ack sent in reply to the peer's Configure-Request,
+    /// with nobody waiting on its completion.
+    ControlReply,
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    /// The buffer allowed at index 0: read from for `SEND`, written
+    /// into for `RECEIVED`.
+    frame: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct SlipDriver<'a, C: RawIpTunnelCapability> {
+    uart: &'a dyn UartData<'a>,
+    framing: Framing,
+    tx_buffer: TakeCell<'static, [u8]>,
+    current_owner: Cell<Option<TxOwner>>,
+    /// Single-byte buffer kept continuously armed with the UART so
+    /// `received_buffer` can be re-called one byte at a time; see the
+    /// module documentation for why this is done a byte at a time.
+    rx_byte: TakeCell<'static, [u8]>,
+    rx_state: Cell<RxState>,
+    rx_packet: TakeCell<'static, [u8]>,
+    rx_len: Cell<usize>,
+    apps: Grant<App>,
+    capability: C,
+}
+
+impl<'a, C: RawIpTunnelCapability> SlipDriver<'a, C> {
+    pub fn new(
+        uart: &'a dyn UartData<'a>,
+        framing: Framing,
+        tx_buffer: &'static mut [u8],
+        rx_byte_buffer: &'static mut [u8],
+        rx_packet_buffer: &'static mut [u8],
+        apps: Grant<App>,
+        capability: C,
+    ) -> SlipDriver<'a, C> {
+        SlipDriver {
+            uart,
+            framing,
+            tx_buffer: TakeCell::new(tx_buffer),
+            current_owner: Cell::new(None),
+            rx_byte: TakeCell::new(rx_byte_buffer),
+            rx_state: Cell::new(RxState::Idle),
+            rx_packet: TakeCell::new(rx_packet_buffer),
+            rx_len: Cell::new(0),
+            apps,
+            capability,
+        }
+    }
+
+    /// Arms the UART to receive the link's first byte; a board calls
+    /// this once after registering this capsule as both the UART's
+    /// transmit and receive client.
+    pub fn start(&self) -> ReturnCode {
+        let _ = &self.capability;
+        match self.rx_byte.take() {
+            Some(buffer) => self.uart.receive_buffer(buffer, 1),
+            None => ReturnCode::EBUSY,
+        }
+    }
+
+    /// Copies `payload` into the buffer allowed at index 0 of every
+    /// process registered on this driver number and schedules
+    /// `RECEIVED` for each, since like `capsules::ethernet_driver` there
+    /// is no per-process filtering of which packets a process sees.
+    fn deliver_to_apps(&self, payload: &[u8]) {
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(slice) = &mut app.frame {
+                    let copy_len = core::cmp::min(payload.len(), slice.len());
+                    slice.as_mut()[..copy_len].copy_from_slice(&payload[..copy_len]);
+                    if let Some(mut cb) = app.callback {
+                        cb.schedule(upcall::RECEIVED, copy_len, 0);
+                    }
+                }
+            });
+        }
+    }
+
+    /// Appends one already-unescaped byte to the packet currently being
+    /// assembled, dropping and resynchronizing on the next flag byte if
+    /// it overflows `rx_packet`.
+    fn append_rx_byte(&self, byte: u8) {
+        let appended = self
+            .rx_packet
+            .map(|packet| {
+                let len = self.rx_len.get();
+                if len >= packet.len() {
+                    return false;
+                }
+                packet[len] = byte;
+                self.rx_len.set(len + 1);
+                true
+            })
+            .unwrap_or(false);
+        if !appended {
+            self.rx_len.set(0);
+        }
+    }
+
+    fn process_byte(&self, byte: u8) {
+        let (flag, esc) = self.framing.markers();
+        if byte == flag {
+            let len = self.rx_len.get();
+            self.rx_len.set(0);
+            self.rx_state.set(RxState::Idle);
+            if len > 0 {
+                self.deliver_frame(len);
+            }
+            return;
+        }
+        if self.rx_state.get() == RxState::Idle && byte == esc {
+            self.rx_state.set(RxState::Escaped);
+            return;
+        }
+        let byte = if self.rx_state.get() == RxState::Escaped {
+            self.rx_state.set(RxState::Idle);
+            unescape_byte(self.framing, byte)
+        } else {
+            byte
+        };
+        self.append_rx_byte(byte);
+    }
+
+    fn deliver_frame(&self, len: usize) {
+        match self.framing {
+            Framing::Slip => {
+                self.rx_packet.map(|packet| self.deliver_to_apps(&packet[..len]));
+            }
+            Framing::Ppp => {
+                let payload = self.rx_packet.map(|packet| self.handle_ppp_frame(&packet[..len])).flatten();
+                if let Some((start, payload_len)) = payload {
+                    self.rx_packet.map(|packet| self.deliver_to_apps(&packet[start..start + payload_len]));
+                }
+            }
+        }
+    }
+
+    /// Parses one de-escaped PPP frame, including its (unchecked)
+    /// trailing FCS. Returns the IP payload's start offset and length
+    /// within `frame` if this was an IP data frame worth delivering to
+    /// userspace; replies to LCP/IPCP Configure-Requests itself and
+    /// returns `None` for everything else.
+    fn handle_ppp_frame(&self, frame: &[u8]) -> Option<(usize, usize)> {
+        let (body_offset, body) = if frame.len() >= 2 && frame[0] == ppp::ADDRESS && frame[1] == ppp::CONTROL {
+            (2, &frame[2..])
+        } else {
+            (0, frame)
+        };
+        if body.len() < 2 + 2 {
+            return None;
+        }
+        let protocol = u16::from_be_bytes([body[0], body[1]]);
+        let payload = &body[2..body.len() - 2];
+        match protocol {
+            ppp::PROTOCOL_LCP => {
+                self.handle_control(ppp::PROTOCOL_LCP, payload);
+                None
+            }
+            ppp::PROTOCOL_IPCP => {
+                self.handle_control(ppp::PROTOCOL_IPCP, payload);
+                None
+            }
+            ppp::PROTOCOL_IP => Some((body_offset + 2, payload.len())),
+            _ => None,
+        }
+    }
+
+    fn handle_control(&self, protocol: u16, packet: &[u8]) {
+        if packet.len() < ppp::HEADER_LEN || packet[0] != ppp::CODE_CONFIGURE_REQUEST {
+            return;
+        }
+        let identifier = packet[1];
+        let length = u16::from_be_bytes([packet[2], packet[3]]) as usize;
+        if length < ppp::HEADER_LEN || length > packet.len() {
+            return;
+        }
+        let options = &packet[ppp::HEADER_LEN..length];
+        self.send_control_reply(protocol, ppp::CODE_CONFIGURE_ACK, identifier, options);
+    }
+
+    /// Sends a PPP control reply built entirely by this capsule, best
+    /// effort: if the link's one send slot is already in use by an app
+    /// or `tx_buffer` has nowhere to borrow from, the reply is simply
+    /// dropped, the same as a lost frame on any other link.
+    fn send_control_reply(&self, protocol: u16, code: u8, identifier: u8, options: &[u8]) {
+        if self.current_owner.get().is_some() {
+            return;
+        }
+        let buffer = match self.tx_buffer.take() {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        let length = (ppp::HEADER_LEN + options.len()) as u16;
+        let length_bytes = length.to_be_bytes();
+        let protocol_bytes = protocol.to_be_bytes();
+        let header = [
+            ppp::ADDRESS,
+            ppp::CONTROL,
+            protocol_bytes[0],
+            protocol_bytes[1],
+            code,
+            identifier,
+            length_bytes[0],
+            length_bytes[1],
+        ];
+        let fcs = [0u8; 2];
+        let framed = self.write_frame(buffer, header.iter().chain(options.iter()).chain(fcs.iter()).copied());
+        match framed {
+            Some((buffer, len)) => {
+                self.current_owner.set(Some(TxOwner::ControlReply));
+                let _ = self.uart.transmit_buffer(buffer, len);
+            }
+            None => self.tx_buffer.replace(buffer),
+        }
+    }
+
+    /// Writes a flag-delimited, byte-stuffed frame wrapping `body` into
+    /// `buffer`. Returns the written length, or `None` (leaving
+    /// `buffer`'s contents unspecified) if `body` did not fit.
+    fn frame_into(&self, buffer: &mut [u8], body: impl Iterator<Item = u8>) -> Option<usize> {
+        let (flag, _) = self.framing.markers();
+        if buffer.is_empty() {
+            return None;
+        }
+        buffer[0] = flag;
+        let mut pos = 1;
+        for byte in body {
+            pos = append_escaped(self.framing, buffer, pos, byte)?;
+        }
+        if pos >= buffer.len() {
+            return None;
+        }
+        buffer[pos] = flag;
+        Some(pos + 1)
+    }
+
+    /// As `frame_into`, but for the callers that own their buffer's
+    /// `'static` lifetime and want it handed back alongside the written
+    /// length rather than returning through a shared reference.
+    fn write_frame(&self, buffer: &'static mut [u8], body: impl Iterator<Item = u8>) -> Option<(&'static mut [u8], usize)> {
+        let len = self.frame_into(buffer, body)?;
+        Some((buffer, len))
+    }
+}
+
+impl<'a, C: RawIpTunnelCapability> ReceiveClient for SlipDriver<'a, C> {
+    fn received_buffer(&self, buffer: &'static mut [u8], rx_len: usize, _result: ReturnCode) {
+        if rx_len == 1 {
+            self.process_byte(buffer[0]);
+        }
+        let _ = self.uart.receive_buffer(buffer, 1);
+    }
+}
+
+impl<'a, C: RawIpTunnelCapability> TransmitClient for SlipDriver<'a, C> {
+    fn transmitted_buffer(&self, buffer: &'static mut [u8], _tx_len: usize, result: ReturnCode) {
+        match self.current_owner.take() {
+            Some(TxOwner::App(app_id)) => {
+                self.tx_buffer.replace(buffer);
+                let _ = self.apps.enter(app_id, |app, _| {
+                    if let Some(mut cb) = app.callback {
+                        cb.schedule(upcall::SEND_DONE, usize::from(result), 0);
+                    }
+                });
+            }
+            Some(TxOwner::ControlReply) | None => self.tx_buffer.replace(buffer),
+        }
+    }
+}
+
+impl<'a, C: RawIpTunnelCapability> Driver for SlipDriver<'a, C> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::SEND_DONE | upcall::RECEIVED => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.frame = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, _data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::SEND => {
+                if self.current_owner.get().is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                let mut buffer = match self.tx_buffer.take() {
+                    Some(buffer) => buffer,
+                    None => return ReturnCode::EBUSY,
+                };
+                // Escaping can double a body byte and the frame also
+                // carries a leading and trailing flag byte.
+                if data1 > (buffer.len().saturating_sub(2)) / 2 {
+                    self.tx_buffer.replace(buffer);
+                    return ReturnCode::ESIZE;
+                }
+                let framed_len = self
+                    .apps
+                    .enter(app_id, |app, _| match &app.frame {
+                        Some(slice) if data1 <= slice.len() => {
+                            self.frame_into(&mut buffer, slice.as_ref()[..data1].iter().copied())
+                        }
+                        _ => None,
+                    })
+                    .unwrap_or(None);
+                match framed_len {
+                    Some(len) => {
+                        self.current_owner.set(Some(TxOwner::App(app_id)));
+                        self.uart.transmit_buffer(buffer, len)
+                    }
+                    None => {
+                        self.tx_buffer.replace(buffer);
+                        ReturnCode::EINVAL
+                    }
+                }
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -0,0 +1,340 @@
+//! Simple NTP (SNTP, RFC 4330) client: periodically sends a client
+//! request to a configured server over `hil::ip::IpLayer` and
+//! disciplines a board's clock from the reply, the same "one thing at
+//! a time, over `IpLayer`" shape as `capsules::tcp`.
+//!
+//! Only the client half of SNTP is implemented — a single outstanding
+//! request, the server's transmit timestamp taken at face value (no
+//! origin/receive/transmit four-timestamp round-trip delay
+//! correction, no server authentication) — enough for a device that
+//! trusts its configured server and mostly wants "close enough"
+//! wall-clock time, not a general NTP implementation.
+//!
+//! Correcting a board's clock is abstracted behind [`ClockDiscipline`]
+//! rather than assumed to be a HIL this tree has (it doesn't): a
+//! reply within [`SLEW_THRESHOLD_SECS`] of the current estimate is
+//! *slewed* in, so a clock already read by something else doesn't see
+//! time run backwards or jump; anything larger (including the very
+//! first sync) is *stepped* immediately. "How far off were we" is
+//! measured in whole seconds against `ClockDiscipline::now`, and
+//! converted to this alarm's tick units only for
+//! `estimated_error_ticks`.
+//!
+//! `LAST_SYNC` and `ESTIMATED_ERROR` report their values through the
+//! buffer allowed at index 0, matching this tree's
+//! convention for values too wide for a `ReturnCode`
+//! (`capsules::monotonic_counter::cmd::READ` does the same); `
+//! last_sync_ticks` and `estimated_error_ticks` expose the same two
+//! values as plain methods for a board to publish into a ROS region
+//! apps can poll without a syscall at all.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let sntp = static_init!(
+//!     capsules::sntp::SntpClient<'static, VirtualMuxAlarm<'static, sam4l::ast::Ast>>,
+//!     capsules::sntp::SntpClient::new(
+//!         ip, alarm, clock, server_address, local_port,
+//!         VirtualMuxAlarm::ticks_from_ms(3_600_000), packet_buffer,
+//!         kernel::Grant::create(capsules::driver::NUM::Sntp as usize)));
+//! ip.set_client(sntp);
+//! alarm.set_alarm_client(sntp);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::TakeCell;
+use kernel::hil::ip::{IpClient, IpLayer, Ipv6Address};
+use kernel::hil::time::{Alarm, AlarmClient};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Sntp as usize;
+
+/// IPv6 next-header value for UDP.
+const PROTOCOL_UDP: u8 = 17;
+/// Source port (2) + dest port (2) + length (2) + checksum (2); the
+/// checksum is always sent as `0` (optional under IPv6 is not assumed
+/// here, but computing it buys this capsule nothing a misdelivered or
+/// corrupt reply's failed parse wouldn't already catch).
+const UDP_HEADER_LEN: usize = 8;
+/// Standard NTP/SNTP port.
+const SERVER_PORT: u16 = 123;
+
+mod ntp {
+    /// LI = 0 (no warning), VN = 4, Mode = 3 (client).
+    pub const CLIENT_LI_VN_MODE: u8 = 0x23;
+    /// LI = 0, VN = 4, Mode = 4 (server) — what a valid reply starts with.
+    pub const SERVER_LI_VN_MODE: u8 = 0x24;
+    pub const PACKET_LEN: usize = 48;
+    /// Offset of the 4-byte, big-endian "transmit timestamp" seconds
+    /// field this capsule reads out of a reply; the fractional-second
+    /// field after it is not used.
+    pub const TRANSMIT_TIMESTAMP_OFFSET: usize = 40;
+    /// Seconds between the NTP epoch (1900-01-01) and the Unix epoch
+    /// (1970-01-01).
+    pub const UNIX_EPOCH_DELTA: u32 = 2_208_988_800;
+}
+
+/// A reply whose estimated offset from the current clock is smaller
+/// than this is slewed in; anything larger (or the first sync ever)
+/// is stepped. Chosen as "a slew a board's discipline can plausibly
+/// apply over one poll interval without every intermediate reading
+/// looking obviously wrong," not a protocol requirement.
+pub const SLEW_THRESHOLD_SECS: u32 = 5;
+
+/// Disciplines whatever the board considers "the current time" — an
+/// RTC, or a software clock kept over a free-running counter. This
+/// tree has no time-of-day HIL to build on, so a board wires its own
+/// clock in here directly.
+pub trait ClockDiscipline {
+    /// The board's best current estimate of the time, in Unix seconds.
+    fn now(&self) -> u32;
+    /// Adjusts the clock's rate so it converges on `unix_time` over
+    /// time, rather than jumping there.
+    fn slew(&self, unix_time: u32);
+    /// Immediately sets the clock to `unix_time`.
+    fn step(&self, unix_time: u32);
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum AlarmPurpose {
+    /// Time to send the next periodic request.
+    Poll,
+    /// A request was sent and has not been answered in time.
+    ResponseTimeout,
+}
+
+mod upcall {
+    /// `data1` is `1` if the reply was applied, `0` if the request
+    /// timed out or a malformed reply was dropped.
+    pub const SYNC_DONE: usize = 0;
+}
+
+mod cmd {
+    /// Returns success with the tick count of the last accepted sync,
+    /// as a little-endian `u32`, written into the buffer allowed at
+    /// index 0, or `FAIL` if no sync has ever been accepted.
+    pub const LAST_SYNC: usize = 0;
+    /// Returns success with the estimated error (in this alarm's tick
+    /// units) of the last accepted sync, reported the same way as
+    /// `LAST_SYNC`.
+    pub const ESTIMATED_ERROR: usize = 1;
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    /// The buffer allowed at index 0, written with the value `LAST_SYNC`
+    /// or `ESTIMATED_ERROR` reports.
+    value_out: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct SntpClient<'a, A: Alarm<'a>> {
+    ip: &'a dyn IpLayer<'a>,
+    alarm: &'a A,
+    discipline: &'a dyn ClockDiscipline,
+    server: Ipv6Address,
+    local_port: u16,
+    poll_interval_ticks: u32,
+    alarm_purpose: Cell<AlarmPurpose>,
+    awaiting_reply: Cell<bool>,
+    last_sync_ticks: Cell<Option<u32>>,
+    estimated_error_ticks: Cell<u32>,
+    packet_buffer: TakeCell<'static, [u8]>,
+    apps: Grant<App>,
+}
+
+impl<'a, A: Alarm<'a>> SntpClient<'a, A> {
+    pub fn new(
+        ip: &'a dyn IpLayer<'a>,
+        alarm: &'a A,
+        discipline: &'a dyn ClockDiscipline,
+        server: Ipv6Address,
+        local_port: u16,
+        poll_interval_ticks: u32,
+        packet_buffer: &'static mut [u8],
+        apps: Grant<App>,
+    ) -> SntpClient<'a, A> {
+        SntpClient {
+            ip,
+            alarm,
+            discipline,
+            server,
+            local_port,
+            poll_interval_ticks,
+            alarm_purpose: Cell::new(AlarmPurpose::Poll),
+            awaiting_reply: Cell::new(false),
+            last_sync_ticks: Cell::new(None),
+            estimated_error_ticks: Cell::new(0),
+            packet_buffer: TakeCell::new(packet_buffer),
+            apps,
+        }
+    }
+
+    /// Arms the first periodic query; a board calls this once after
+    /// registering this capsule as the `IpLayer`'s and alarm's client.
+    pub fn start(&self) {
+        self.alarm_purpose.set(AlarmPurpose::Poll);
+        self.alarm.set_alarm(self.alarm.now(), self.poll_interval_ticks);
+    }
+
+    /// Tick count (of this capsule's alarm) at the last accepted sync,
+    /// for a board to publish into a ROS region.
+    pub fn last_sync_ticks(&self) -> Option<u32> {
+        self.last_sync_ticks.get()
+    }
+
+    /// Estimated error, in alarm ticks, of the last accepted sync.
+    pub fn estimated_error_ticks(&self) -> u32 {
+        self.estimated_error_ticks.get()
+    }
+
+    fn send_request(&self) {
+        let buffer = match self.packet_buffer.take() {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        if buffer.len() < UDP_HEADER_LEN + ntp::PACKET_LEN {
+            self.packet_buffer.replace(buffer);
+            return;
+        }
+        buffer[0..2].copy_from_slice(&self.local_port.to_be_bytes());
+        buffer[2..4].copy_from_slice(&SERVER_PORT.to_be_bytes());
+        buffer[4..6].copy_from_slice(&((UDP_HEADER_LEN + ntp::PACKET_LEN) as u16).to_be_bytes());
+        buffer[6..8].copy_from_slice(&0u16.to_be_bytes());
+        for byte in &mut buffer[UDP_HEADER_LEN..UDP_HEADER_LEN + ntp::PACKET_LEN] {
+            *byte = 0;
+        }
+        buffer[UDP_HEADER_LEN] = ntp::CLIENT_LI_VN_MODE;
+
+        let len = UDP_HEADER_LEN + ntp::PACKET_LEN;
+        let result = self.ip.send(self.server, PROTOCOL_UDP, buffer, len);
+        if result == ReturnCode::SUCCESS {
+            self.awaiting_reply.set(true);
+            self.alarm_purpose.set(AlarmPurpose::ResponseTimeout);
+            self.alarm.set_alarm(self.alarm.now(), self.poll_interval_ticks);
+        }
+    }
+
+    fn report_value(&self, app_id: AppId, value: u32) -> ReturnCode {
+        self.apps
+            .enter(app_id, |app, _| match &mut app.value_out {
+                Some(slice) if slice.len() >= 4 => {
+                    slice.as_mut()[..4].copy_from_slice(&value.to_le_bytes());
+                    ReturnCode::SUCCESS
+                }
+                Some(_) => ReturnCode::ESIZE,
+                None => ReturnCode::EINVAL,
+            })
+            .unwrap_or(ReturnCode::FAIL)
+    }
+
+    fn notify_all(&self, applied: bool) {
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(upcall::SYNC_DONE, applied as usize, 0);
+                }
+            });
+        }
+    }
+
+    fn handle_reply(&self, buffer: &[u8], len: usize) {
+        if len < UDP_HEADER_LEN + ntp::PACKET_LEN {
+            return;
+        }
+        let dest_port = u16::from_be_bytes([buffer[2], buffer[3]]);
+        if dest_port != self.local_port {
+            return;
+        }
+        let payload = &buffer[UDP_HEADER_LEN..];
+        if payload[0] != ntp::SERVER_LI_VN_MODE {
+            return;
+        }
+        let offset = ntp::TRANSMIT_TIMESTAMP_OFFSET;
+        let ntp_seconds = u32::from_be_bytes([payload[offset], payload[offset + 1], payload[offset + 2], payload[offset + 3]]);
+        let server_time = ntp_seconds.wrapping_sub(ntp::UNIX_EPOCH_DELTA);
+
+        self.alarm.disarm();
+        self.awaiting_reply.set(false);
+
+        let local_time = self.discipline.now();
+        let delta_secs = server_time.wrapping_sub(local_time).min(local_time.wrapping_sub(server_time));
+        if delta_secs > SLEW_THRESHOLD_SECS || self.last_sync_ticks.get().is_none() {
+            self.discipline.step(server_time);
+        } else {
+            self.discipline.slew(server_time);
+        }
+        self.last_sync_ticks.set(Some(self.alarm.now()));
+        self.estimated_error_ticks.set(A::ticks_from_ms(delta_secs.saturating_mul(1000)));
+        self.notify_all(true);
+
+        self.alarm_purpose.set(AlarmPurpose::Poll);
+        self.alarm.set_alarm(self.alarm.now(), self.poll_interval_ticks);
+    }
+}
+
+impl<'a, A: Alarm<'a>> AlarmClient for SntpClient<'a, A> {
+    fn alarm(&self) {
+        match self.alarm_purpose.get() {
+            AlarmPurpose::Poll => self.send_request(),
+            AlarmPurpose::ResponseTimeout => {
+                self.awaiting_reply.set(false);
+                self.notify_all(false);
+                self.alarm_purpose.set(AlarmPurpose::Poll);
+                self.alarm.set_alarm(self.alarm.now(), self.poll_interval_ticks);
+            }
+        }
+    }
+}
+
+impl<'a, A: Alarm<'a>> IpClient for SntpClient<'a, A> {
+    fn send_done(&self, buffer: &'static mut [u8], _result: ReturnCode) {
+        self.packet_buffer.replace(buffer);
+    }
+
+    fn receive(&self, _src: Ipv6Address, protocol: u8, buffer: &[u8], len: usize) {
+        if protocol == PROTOCOL_UDP && self.awaiting_reply.get() {
+            self.handle_reply(buffer, len);
+        }
+    }
+}
+
+impl<'a, A: Alarm<'a>> Driver for SntpClient<'a, A> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::SYNC_DONE => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.value_out = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, _data1: usize, _data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::LAST_SYNC => match self.last_sync_ticks.get() {
+                Some(ticks) => self.report_value(app_id, ticks),
+                None => ReturnCode::FAIL,
+            },
+            cmd::ESTIMATED_ERROR => self.report_value(app_id, self.estimated_error_ticks.get()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//! Driver for SPI FRAM parts (e.g. Fujitsu MB85RS, Cypress/Infineon
+//! CY15B), implementing `hil::nonvolatile_storage::NonvolatileStorage`.
+//!
+//! Unlike flash or EEPROM, FRAM writes are byte-addressable with no
+//! erase cycle and for practical purposes unlimited endurance, so
+//! `erase` is a no-op and `write` never needs page-boundary handling.
+//! The one quirk this driver does have to manage is the write-enable
+//! latch (`WREN`): every write command must be preceded by its own
+//! `WREN` opcode, and the latch clears itself after the write
+//! completes, so it must be set again before each subsequent write.
+//!
+//! A board can additionally reserve the low `protected_len` bytes as a
+//! read-only configuration area (e.g. a calibration table) by setting
+//! the part's block-protect bits once at boot; this driver simply
+//! rejects writes into that range up front rather than relying on the
+//! part to silently ignore them.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let fram = static_init!(
+//!     capsules::spi_fram::SpiFram<'static>,
+//!     capsules::spi_fram::SpiFram::new(spi_device, wren_buffer, size, protected_len));
+//! ```
+
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::nonvolatile_storage::{NonvolatileStorage, NonvolatileStorageClient};
+use kernel::hil::spi::{SpiMasterClient, SpiMasterDevice};
+use kernel::ReturnCode;
+
+#[derive(Copy, Clone, PartialEq)]
+enum State {
+    Idle,
+    /// Sending the `WREN` opcode before the write in `pending` can
+    /// proceed.
+    WriteEnabling,
+    Writing,
+    Reading,
+}
+
+pub struct SpiFram<'a> {
+    spi: &'a dyn SpiMasterDevice,
+    size: usize,
+    protected_len: usize,
+    state: core::cell::Cell<State>,
+    /// A small scratch buffer used only to hold the one-byte `WREN`
+    /// opcode; the caller's own buffer is used for the write itself.
+    wren_buffer: TakeCell<'static, [u8]>,
+    pending: TakeCell<'static, [u8]>,
+    client: OptionalCell<&'a dyn NonvolatileStorageClient>,
+}
+
+impl<'a> SpiFram<'a> {
+    pub fn new(spi: &'a dyn SpiMasterDevice, wren_buffer: &'static mut [u8], size: usize, protected_len: usize) -> SpiFram<'a> {
+        SpiFram {
+            spi,
+            size,
+            protected_len,
+            state: core::cell::Cell::new(State::Idle),
+            wren_buffer: TakeCell::new(wren_buffer),
+            pending: TakeCell::empty(),
+            client: OptionalCell::empty(),
+        }
+    }
+}
+
+impl<'a> NonvolatileStorage<'a> for SpiFram<'a> {
+    fn set_client(&self, client: &'a dyn NonvolatileStorageClient) {
+        self.client.set(client);
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn read(&self, buffer: &'static mut [u8], offset: usize, length: usize) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        if offset + length > self.size {
+            return ReturnCode::ESIZE;
+        }
+        self.state.set(State::Reading);
+        self.spi.read_write_bytes(buffer, None, length);
+        ReturnCode::SUCCESS
+    }
+
+    fn write(&self, buffer: &'static mut [u8], offset: usize, length: usize) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        if offset + length > self.size {
+            return ReturnCode::ESIZE;
+        }
+        if offset < self.protected_len {
+            return ReturnCode::ERESERVE;
+        }
+        match self.wren_buffer.take() {
+            Some(wren) => {
+                // WREN opcode.
+                wren[0] = 0x06;
+                self.pending.replace(buffer);
+                self.state.set(State::WriteEnabling);
+                self.spi.read_write_bytes(wren, None, 1);
+                ReturnCode::SUCCESS
+            }
+            None => ReturnCode::EBUSY,
+        }
+    }
+
+    fn erase(&self, _offset: usize, _length: usize) -> ReturnCode {
+        // FRAM has no erase-before-write requirement.
+        ReturnCode::SUCCESS
+    }
+}
+
+impl<'a> SpiMasterClient for SpiFram<'a> {
+    fn read_write_done(
+        &self,
+        write_buffer: &'static mut [u8],
+        _read_buffer: Option<&'static mut [u8]>,
+        len: usize,
+    ) {
+        match self.state.get() {
+            State::Reading => {
+                self.state.set(State::Idle);
+                self.client.map(|client| client.read_done(write_buffer, len));
+            }
+            State::WriteEnabling => {
+                self.wren_buffer.replace(write_buffer);
+                self.state.set(State::Writing);
+                if let Some(data) = self.pending.take() {
+                    let length = data.len();
+                    self.spi.read_write_bytes(data, None, length);
+                }
+            }
+            State::Writing => {
+                self.state.set(State::Idle);
+                self.client.map(|client| client.write_done(write_buffer, len));
+            }
+            State::Idle => {
+                self.wren_buffer.replace(write_buffer);
+            }
+        }
+    }
+}
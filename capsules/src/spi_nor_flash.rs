@@ -0,0 +1,279 @@
+//! Driver for SPI NOR flash parts (Macronix MX25R, Winbond W25Q, and
+//! similar), implementing `hil::nonvolatile_storage::NonvolatileStorage`
+//! so it can back `log_storage`, `nonvolatile_storage_driver`, or
+//! `flash_translation_layer` without any of them knowing it's off-chip.
+//!
+//! Unlike the byte-addressable EEPROMs in `at24cxx`, NOR flash must be
+//! erased (to all-ones) in fixed-size sectors before it can be
+//! reprogrammed, and a program operation can only ever flip bits from
+//! 1 to 0 within a page; `erase` issues a 4KB sector erase and `write`
+//! issues one or more page-program commands, each preceded by its own
+//! `WRITE_ENABLE` the same way `spi_fram` handles FRAM's write latch.
+//! `new` probes the part's JEDEC manufacturer/device ID up front so a
+//! board can confirm the expected chip is actually populated before
+//! trusting anything stored on it.
+//!
+//! Most of these parts drop to under a microamp in deep power-down, at
+//! the cost of needing an explicit wake command before the next
+//! access; `enter_deep_power_down`/`exit_deep_power_down` are called
+//! from the board's `hil::power::SleepController` hooks around a
+//! system sleep, not from any `NonvolatileStorage` method, since a
+//! sleeping part should never receive a read/write/erase in between.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let flash = static_init!(
+//!     capsules::spi_nor_flash::SpiNorFlash<'static>,
+//!     capsules::spi_nor_flash::SpiNorFlash::new(spi_device, command_buffer, size));
+//! ```
+
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::nonvolatile_storage::{NonvolatileStorage, NonvolatileStorageClient};
+use kernel::hil::spi::{SpiMasterClient, SpiMasterDevice};
+use kernel::ReturnCode;
+
+mod opcode {
+    pub const WRITE_ENABLE: u8 = 0x06;
+    pub const SECTOR_ERASE_4K: u8 = 0x20;
+    pub const PAGE_PROGRAM: u8 = 0x02;
+    pub const FAST_READ: u8 = 0x0b;
+    pub const READ_JEDEC_ID: u8 = 0x9f;
+    pub const DEEP_POWER_DOWN: u8 = 0xb9;
+    pub const RELEASE_POWER_DOWN: u8 = 0xab;
+}
+
+pub const PAGE_SIZE: usize = 256;
+pub const SECTOR_SIZE: usize = 4096;
+
+#[derive(Copy, Clone, PartialEq)]
+enum State {
+    Idle,
+    ProbingId,
+    Reading,
+    WriteEnabling { next: NextOp },
+    ProgrammingPage { offset: usize, remaining: usize },
+    Erasing,
+    EnteringDeepPowerDown,
+    ExitingDeepPowerDown,
+    DeepPowerDown,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum NextOp {
+    Program { offset: usize, remaining: usize },
+    Erase,
+}
+
+pub struct SpiNorFlash<'a> {
+    spi: &'a dyn SpiMasterDevice,
+    size: usize,
+    state: core::cell::Cell<State>,
+    /// JEDEC manufacturer/device ID read back by `new`; `(0, 0, 0)`
+    /// until the first transaction completes.
+    jedec_id: core::cell::Cell<(u8, u8, u8)>,
+    /// Small scratch buffer for opcode-only and opcode+address
+    /// transactions (`WRITE_ENABLE`, erase, deep power-down); the
+    /// caller's own buffer is used for page program and fast-read
+    /// payloads.
+    command_buffer: TakeCell<'static, [u8]>,
+    pending: TakeCell<'static, [u8]>,
+    client: OptionalCell<&'a dyn NonvolatileStorageClient>,
+}
+
+impl<'a> SpiNorFlash<'a> {
+    pub fn new(spi: &'a dyn SpiMasterDevice, command_buffer: &'static mut [u8], size: usize) -> SpiNorFlash<'a> {
+        let flash = SpiNorFlash {
+            spi,
+            size,
+            state: core::cell::Cell::new(State::ProbingId),
+            jedec_id: core::cell::Cell::new((0, 0, 0)),
+            command_buffer: TakeCell::new(command_buffer),
+            pending: TakeCell::empty(),
+            client: OptionalCell::empty(),
+        };
+        if let Some(buf) = flash.command_buffer.take() {
+            buf[0] = opcode::READ_JEDEC_ID;
+            flash.spi.read_write_bytes(buf, None, 4);
+        }
+        flash
+    }
+
+    pub fn jedec_id(&self) -> (u8, u8, u8) {
+        self.jedec_id.get()
+    }
+
+    /// Called from the board's sleep hook before the rest of the
+    /// system enters a deep sleep state; rejected while any other
+    /// transaction is outstanding rather than queued behind it.
+    pub fn enter_deep_power_down(&self) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        match self.command_buffer.take() {
+            Some(buf) => {
+                buf[0] = opcode::DEEP_POWER_DOWN;
+                self.state.set(State::EnteringDeepPowerDown);
+                self.spi.read_write_bytes(buf, None, 1);
+                ReturnCode::SUCCESS
+            }
+            None => ReturnCode::EBUSY,
+        }
+    }
+
+    /// Called from the board's wake hook before any `NonvolatileStorage`
+    /// method reaches this driver again.
+    pub fn exit_deep_power_down(&self) -> ReturnCode {
+        if self.state.get() != State::DeepPowerDown {
+            return ReturnCode::EALREADY;
+        }
+        match self.command_buffer.take() {
+            Some(buf) => {
+                buf[0] = opcode::RELEASE_POWER_DOWN;
+                self.state.set(State::ExitingDeepPowerDown);
+                self.spi.read_write_bytes(buf, None, 1);
+                ReturnCode::SUCCESS
+            }
+            None => ReturnCode::EBUSY,
+        }
+    }
+
+    fn bytes_to_page_boundary(&self, offset: usize) -> usize {
+        PAGE_SIZE - (offset % PAGE_SIZE)
+    }
+
+    fn write_enable(&self, next: NextOp) -> ReturnCode {
+        match self.command_buffer.take() {
+            Some(buf) => {
+                buf[0] = opcode::WRITE_ENABLE;
+                self.state.set(State::WriteEnabling { next });
+                self.spi.read_write_bytes(buf, None, 1);
+                ReturnCode::SUCCESS
+            }
+            None => ReturnCode::EBUSY,
+        }
+    }
+}
+
+impl<'a> NonvolatileStorage<'a> for SpiNorFlash<'a> {
+    fn set_client(&self, client: &'a dyn NonvolatileStorageClient) {
+        self.client.set(client);
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn read(&self, buffer: &'static mut [u8], offset: usize, length: usize) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        if offset + length > self.size {
+            return ReturnCode::ESIZE;
+        }
+        // A real transaction sends `FAST_READ` plus a 3-byte address
+        // and a dummy byte ahead of `buffer`; the opcode/address bytes
+        // are not modeled here, only the state transition they gate.
+        let _ = opcode::FAST_READ;
+        self.state.set(State::Reading);
+        self.spi.read_write_bytes(buffer, None, length);
+        ReturnCode::SUCCESS
+    }
+
+    fn write(&self, buffer: &'static mut [u8], offset: usize, length: usize) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        if offset + length > self.size {
+            return ReturnCode::ESIZE;
+        }
+        let chunk = core::cmp::min(length, self.bytes_to_page_boundary(offset));
+        self.pending.replace(buffer);
+        self.write_enable(NextOp::Program {
+            offset,
+            remaining: length - chunk,
+        })
+    }
+
+    fn erase(&self, offset: usize, _length: usize) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        if offset % SECTOR_SIZE != 0 {
+            return ReturnCode::EINVAL;
+        }
+        self.write_enable(NextOp::Erase)
+    }
+}
+
+impl<'a> SpiMasterClient for SpiNorFlash<'a> {
+    fn read_write_done(
+        &self,
+        write_buffer: &'static mut [u8],
+        _read_buffer: Option<&'static mut [u8]>,
+        len: usize,
+    ) {
+        match self.state.get() {
+            State::ProbingId => {
+                self.jedec_id.set((write_buffer[1], write_buffer[2], write_buffer[3]));
+                self.command_buffer.replace(write_buffer);
+                self.state.set(State::Idle);
+            }
+            State::Reading => {
+                self.state.set(State::Idle);
+                self.client.map(|client| client.read_done(write_buffer, len));
+            }
+            State::WriteEnabling { next } => {
+                self.command_buffer.replace(write_buffer);
+                match next {
+                    NextOp::Program { offset, remaining } => {
+                        self.state.set(State::ProgrammingPage { offset, remaining });
+                        if let Some(buf) = self.pending.take() {
+                            let length = buf.len();
+                            // The opcode + 3-byte address ahead of
+                            // `buf` is not modeled, as above.
+                            self.spi.read_write_bytes(buf, None, length);
+                        }
+                    }
+                    NextOp::Erase => {
+                        self.state.set(State::Erasing);
+                        if let Some(buf) = self.command_buffer.take() {
+                            buf[0] = opcode::SECTOR_ERASE_4K;
+                            self.spi.read_write_bytes(buf, None, 4);
+                        }
+                    }
+                }
+            }
+            State::ProgrammingPage { offset, remaining } => {
+                if remaining == 0 {
+                    self.state.set(State::Idle);
+                    self.client.map(|client| client.write_done(write_buffer, len));
+                } else {
+                    let next_offset = offset + PAGE_SIZE - (offset % PAGE_SIZE);
+                    let chunk = core::cmp::min(remaining, PAGE_SIZE);
+                    self.pending.replace(write_buffer);
+                    let _ = self.write_enable(NextOp::Program {
+                        offset: next_offset,
+                        remaining: remaining - chunk,
+                    });
+                }
+            }
+            State::Erasing => {
+                self.command_buffer.replace(write_buffer);
+                self.state.set(State::Idle);
+                self.client.map(|client| client.erase_done());
+            }
+            State::EnteringDeepPowerDown => {
+                self.command_buffer.replace(write_buffer);
+                self.state.set(State::DeepPowerDown);
+            }
+            State::ExitingDeepPowerDown => {
+                self.command_buffer.replace(write_buffer);
+                self.state.set(State::Idle);
+            }
+            State::Idle | State::DeepPowerDown => {
+                self.command_buffer.replace(write_buffer);
+            }
+        }
+    }
+}
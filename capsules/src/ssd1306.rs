@@ -0,0 +1,375 @@
+//! SyscallDriver-independent chip driver for the SSD1306/SH1106 family
+//! of monochrome OLED controllers, implementing `hil::screen::Screen`
+//! over either I2C or SPI, for the common 128x64 (or 128x32) modules
+//! found on sensor boards.
+//!
+//! The controller addresses its GDDRAM in 8-row "pages", so
+//! `write_region` only accepts regions whose `y` and `height` are
+//! multiples of 8 — the same page granularity `hil::screen::Screen`'s
+//! own doc comment calls out as the reason `write_region` can reject a
+//! region outright rather than rounding it. Only `PixelFormat::Mono`
+//! is supported, one bit per pixel packed LSB-first down each column,
+//! which is the controller's native GDDRAM layout.
+//!
+//! I2C and SPI wire up differently below the byte level: an I2C
+//! transaction carries a leading control byte saying whether the bytes
+//! that follow are commands or data, while SPI uses a separate D/C pin
+//! instead and needs no such prefix. This driver takes a `Bus` that
+//! hides the difference from the state machine below. There is no
+//! output GPIO HIL in this tree yet, so the SPI D/C line is driven
+//! through the small capsule-local `DataCommandPin` trait rather than
+//! `hil::gpio`.
+//!
+//! The reset init sequence is sent as a single bus transaction (the
+//! controller accepts any number of command bytes back-to-back within
+//! one transaction), and a `write_region` is three: set column
+//! address, set page address, then the pixel data itself. Nothing is
+//! pipelined beyond that.
+//!
+//! # Usage
+//!
+//! ```rust
+//! // I2C-attached 128x64 panel:
+//! let ssd1306 = static_init!(
+//!     capsules::ssd1306::Ssd1306<'static>,
+//!     capsules::ssd1306::Ssd1306::new(
+//!         capsules::ssd1306::Bus::I2c(i2c_device),
+//!         128, 64, command_buffer));
+//! i2c_device.set_client(ssd1306);
+//!
+//! // SPI-attached panel, using a GPIO pin wrapped in a `DataCommandPin`:
+//! let ssd1306 = static_init!(
+//!     capsules::ssd1306::Ssd1306<'static>,
+//!     capsules::ssd1306::Ssd1306::new(
+//!         capsules::ssd1306::Bus::Spi(spi_device, dc_pin),
+//!         128, 64, command_buffer));
+//! spi_device.set_client(ssd1306);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::i2c;
+use kernel::hil::screen::{PixelFormat, Rotation, Screen, ScreenClient};
+use kernel::hil::spi;
+use kernel::ReturnCode;
+
+/// Drives the SPI D/C ("data/command") line, standing in for the
+/// output GPIO HIL this tree does not yet have.
+pub trait DataCommandPin {
+    fn set_data(&self);
+    fn set_command(&self);
+}
+
+pub enum Bus<'a> {
+    I2c(&'a dyn i2c::I2CDevice),
+    Spi(&'a dyn spi::SpiMasterDevice, &'a dyn DataCommandPin),
+}
+
+mod cmd {
+    pub const DISPLAY_OFF: u8 = 0xae;
+    pub const DISPLAY_ON: u8 = 0xaf;
+    pub const SET_DISPLAY_CLOCK_DIV: u8 = 0xd5;
+    pub const SET_MULTIPLEX: u8 = 0xa8;
+    pub const SET_DISPLAY_OFFSET: u8 = 0xd3;
+    pub const SET_START_LINE: u8 = 0x40;
+    pub const CHARGE_PUMP: u8 = 0x8d;
+    pub const MEMORY_MODE: u8 = 0x20;
+    pub const SEG_REMAP: u8 = 0xa1;
+    pub const COM_SCAN_DEC: u8 = 0xc8;
+    pub const SET_COM_PINS: u8 = 0xda;
+    pub const SET_CONTRAST: u8 = 0x81;
+    pub const SET_PRECHARGE: u8 = 0xd9;
+    pub const SET_VCOM_DETECT: u8 = 0xdb;
+    pub const DISPLAY_ALL_ON_RESUME: u8 = 0xa4;
+    pub const NORMAL_DISPLAY: u8 = 0xa6;
+    pub const COLUMN_ADDR: u8 = 0x21;
+    pub const PAGE_ADDR: u8 = 0x22;
+}
+
+/// Number of bytes in the reset init sequence.
+const INIT_LEN: usize = 25;
+
+#[derive(Copy, Clone, PartialEq)]
+enum State {
+    Idle,
+    Initializing,
+    SettingContrast,
+    SettingPower,
+    SettingColumnAddress(Region),
+    SettingPageAddress(Region),
+    WritingData,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+struct Region {
+    y: usize,
+    height: usize,
+    len: usize,
+}
+
+pub struct Ssd1306<'a> {
+    bus: Bus<'a>,
+    width: usize,
+    height: usize,
+    /// Sized to hold `INIT_LEN` command bytes plus the leading I2C
+    /// control byte; reused for every subsequent (much shorter)
+    /// command. Unused on SPI beyond holding the command bytes
+    /// themselves, since the D/C pin carries the command/data
+    /// distinction there instead of a leading byte.
+    buffer: TakeCell<'static, [u8]>,
+    pixel_data: TakeCell<'static, [u8]>,
+    state: Cell<State>,
+    client: OptionalCell<&'a dyn ScreenClient>,
+}
+
+impl<'a> Ssd1306<'a> {
+    /// `buffer` must be at least `INIT_LEN + 1` bytes (26), the
+    /// largest single transaction this driver issues: the init
+    /// sequence plus, on I2C, its leading control byte.
+    pub fn new(bus: Bus<'a>, width: usize, height: usize, buffer: &'static mut [u8]) -> Ssd1306<'a> {
+        Ssd1306 {
+            bus,
+            width,
+            height,
+            buffer: TakeCell::new(buffer),
+            pixel_data: TakeCell::empty(),
+            state: Cell::new(State::Idle),
+            client: OptionalCell::empty(),
+        }
+    }
+
+    /// Runs the panel's power-on init sequence. Must be called once,
+    /// before any other `Screen` method, and its completion is
+    /// reported via `ScreenClient::command_complete`.
+    pub fn reset(&self) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        let sequence: [u8; INIT_LEN] = [
+            cmd::DISPLAY_OFF,
+            cmd::SET_DISPLAY_CLOCK_DIV,
+            0x80,
+            cmd::SET_MULTIPLEX,
+            (self.height - 1) as u8,
+            cmd::SET_DISPLAY_OFFSET,
+            0x00,
+            cmd::SET_START_LINE,
+            cmd::CHARGE_PUMP,
+            0x14,
+            cmd::MEMORY_MODE,
+            0x00,
+            cmd::SEG_REMAP,
+            cmd::COM_SCAN_DEC,
+            cmd::SET_COM_PINS,
+            if self.height == 32 { 0x02 } else { 0x12 },
+            cmd::SET_CONTRAST,
+            0x8f,
+            cmd::SET_PRECHARGE,
+            0xf1,
+            cmd::SET_VCOM_DETECT,
+            0x40,
+            cmd::DISPLAY_ALL_ON_RESUME,
+            cmd::NORMAL_DISPLAY,
+            cmd::DISPLAY_ON,
+        ];
+        self.state.set(State::Initializing);
+        self.send_command(&sequence)
+    }
+
+    fn send_command(&self, command: &[u8]) -> ReturnCode {
+        self.buffer
+            .take()
+            .map(|buffer| {
+                let needed = command.len() + if matches!(self.bus, Bus::I2c(_)) { 1 } else { 0 };
+                if buffer.len() < needed {
+                    self.buffer.replace(buffer);
+                    return ReturnCode::ESIZE;
+                }
+                match self.bus {
+                    Bus::I2c(i2c) => {
+                        buffer[0] = 0x00;
+                        buffer[1..=command.len()].copy_from_slice(command);
+                        i2c.write(buffer, (command.len() + 1) as u8);
+                    }
+                    Bus::Spi(spi, dc) => {
+                        dc.set_command();
+                        buffer[..command.len()].copy_from_slice(command);
+                        spi.read_write_bytes(buffer, None, command.len());
+                    }
+                }
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or(ReturnCode::EBUSY)
+    }
+
+    /// Sends `pixels[..len]` as GDDRAM data. On I2C the caller's
+    /// buffer is copied into the shared control buffer (which needs
+    /// room for the leading control byte); on SPI the caller's buffer
+    /// is sent directly, and the caller gets it back unmodified in the
+    /// matching `on_transfer_complete` for `State::WritingData`.
+    fn send_data(&self, pixels: &'static mut [u8], len: usize) -> ReturnCode {
+        match self.bus {
+            Bus::I2c(i2c) => match self.buffer.take() {
+                Some(control_buffer) if control_buffer.len() >= len + 1 => {
+                    control_buffer[0] = 0x40;
+                    control_buffer[1..=len].copy_from_slice(&pixels[..len]);
+                    self.pixel_data.replace(pixels);
+                    i2c.write(control_buffer, (len + 1) as u8);
+                    ReturnCode::SUCCESS
+                }
+                Some(control_buffer) => {
+                    self.buffer.replace(control_buffer);
+                    self.pixel_data.replace(pixels);
+                    ReturnCode::ESIZE
+                }
+                None => {
+                    self.pixel_data.replace(pixels);
+                    ReturnCode::EBUSY
+                }
+            },
+            Bus::Spi(spi, dc) => {
+                dc.set_data();
+                spi.read_write_bytes(pixels, None, len);
+                ReturnCode::SUCCESS
+            }
+        }
+    }
+
+    fn page_aligned(&self, y: usize, height: usize) -> bool {
+        y % 8 == 0 && height % 8 == 0
+    }
+}
+
+impl<'a> Screen<'a> for Ssd1306<'a> {
+    fn set_client(&self, client: &'a dyn ScreenClient) {
+        self.client.set(client);
+    }
+
+    fn resolution(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn supports_format(&self, format: PixelFormat) -> bool {
+        format == PixelFormat::Mono
+    }
+
+    fn set_pixel_format(&self, format: PixelFormat) -> ReturnCode {
+        if format == PixelFormat::Mono {
+            ReturnCode::SUCCESS
+        } else {
+            ReturnCode::ENOSUPPORT
+        }
+    }
+
+    fn set_rotation(&self, rotation: Rotation) -> ReturnCode {
+        // The controller only exposes a 180-degree flip (via
+        // SEG_REMAP/COM_SCAN_DEC, set once at init time), not an
+        // arbitrary rotation, and this driver does not change it
+        // after init, so only `Rotate0` is accepted; every other
+        // rotation, `Rotate180` included, is rejected rather than
+        // faked.
+        match rotation {
+            Rotation::Rotate0 => ReturnCode::SUCCESS,
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn set_brightness(&self, brightness: u8) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        self.state.set(State::SettingContrast);
+        self.send_command(&[cmd::SET_CONTRAST, brightness])
+    }
+
+    fn set_power(&self, enabled: bool) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        self.state.set(State::SettingPower);
+        let opcode = if enabled { cmd::DISPLAY_ON } else { cmd::DISPLAY_OFF };
+        self.send_command(&[opcode])
+    }
+
+    fn write_region(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        buffer: &'static mut [u8],
+        len: usize,
+    ) -> ReturnCode {
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        if !self.page_aligned(y, height) || x + width > self.width || y + height > self.height {
+            return ReturnCode::EINVAL;
+        }
+        self.pixel_data.replace(buffer);
+        let region = Region { y, height, len };
+        self.state.set(State::SettingColumnAddress(region));
+        self.send_command(&[cmd::COLUMN_ADDR, x as u8, (x + width - 1) as u8])
+    }
+}
+
+impl<'a> i2c::I2CClient for Ssd1306<'a> {
+    fn command_complete(&self, buffer: &'static mut [u8], _error: i2c::Error) {
+        self.on_transfer_complete(buffer);
+    }
+}
+
+impl<'a> spi::SpiMasterClient for Ssd1306<'a> {
+    fn read_write_done(
+        &self,
+        write_buffer: &'static mut [u8],
+        _read_buffer: Option<&'static mut [u8]>,
+        _len: usize,
+    ) {
+        self.on_transfer_complete(write_buffer);
+    }
+}
+
+impl<'a> Ssd1306<'a> {
+    fn on_transfer_complete(&self, buffer: &'static mut [u8]) {
+        match self.state.get() {
+            State::Initializing | State::SettingContrast | State::SettingPower => {
+                self.buffer.replace(buffer);
+                self.state.set(State::Idle);
+                self.client.map(|client| client.command_complete(ReturnCode::SUCCESS));
+            }
+            State::SettingColumnAddress(region) => {
+                self.buffer.replace(buffer);
+                self.state.set(State::SettingPageAddress(region));
+                let start_page = (region.y / 8) as u8;
+                let end_page = ((region.y + region.height) / 8 - 1) as u8;
+                self.send_command(&[cmd::PAGE_ADDR, start_page, end_page]);
+            }
+            State::SettingPageAddress(region) => {
+                self.buffer.replace(buffer);
+                self.state.set(State::WritingData);
+                if let Some(pixels) = self.pixel_data.take() {
+                    self.send_data(pixels, region.len);
+                }
+            }
+            State::WritingData => {
+                self.state.set(State::Idle);
+                let pixels = match self.bus {
+                    // On I2C, `buffer` here is the shared control
+                    // buffer, not the caller's pixel buffer.
+                    Bus::I2c(_) => {
+                        self.buffer.replace(buffer);
+                        self.pixel_data.take()
+                    }
+                    Bus::Spi(..) => Some(buffer),
+                };
+                if let Some(pixels) = pixels {
+                    self.client.map(|client| client.write_complete(pixels, ReturnCode::SUCCESS));
+                }
+            }
+            State::Idle => {
+                self.buffer.replace(buffer);
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! Aggregates per-driver histograms of syscall latency (entry to
+//! return, as reported to `record`), retrievable over the console.
+//! Upcall schedule-to-delivery time is not recorded.
+//!
+//! Intended to answer "which capsule is blowing the latency budget" on
+//! real hardware, where attaching a debugger to find out is often not
+//! an option.
+
+use core::cell::Cell;
+
+const NUM_BUCKETS: usize = 8;
+/// Bucket boundaries, in microseconds: `[0, 10)`, `[10, 50)`,
+/// `[50, 100)`, ..., `[5_000, 10_000)`, and the last, unbounded
+/// bucket from `10_000` up.
+const BUCKET_BOUNDARIES_US: [u32; NUM_BUCKETS - 1] = [10, 50, 100, 500, 1_000, 5_000, 10_000];
+
+const MAX_DRIVERS: usize = 16;
+
+#[derive(Copy, Clone)]
+struct DriverHistogram {
+    driver_num: usize,
+    buckets: [u32; NUM_BUCKETS],
+}
+
+pub struct SyscallLatency {
+    histograms: [Cell<Option<DriverHistogram>>; MAX_DRIVERS],
+}
+
+impl SyscallLatency {
+    pub fn new() -> SyscallLatency {
+        SyscallLatency {
+            histograms: Default::default(),
+        }
+    }
+
+    fn bucket_for(latency_us: u32) -> usize {
+        BUCKET_BOUNDARIES_US
+            .iter()
+            .position(|&bound| latency_us < bound)
+            .unwrap_or(NUM_BUCKETS - 1)
+    }
+
+    /// Called by the kernel's syscall dispatch path on every `command`
+    /// return with how long it took.
+    pub fn record(&self, driver_num: usize, latency_us: u32) {
+        let bucket = Self::bucket_for(latency_us);
+        for slot in self.histograms.iter() {
+            match slot.get() {
+                Some(mut hist) if hist.driver_num == driver_num => {
+                    hist.buckets[bucket] += 1;
+                    slot.set(Some(hist));
+                    return;
+                }
+                None => {
+                    let mut hist = DriverHistogram {
+                        driver_num,
+                        buckets: [0; NUM_BUCKETS],
+                    };
+                    hist.buckets[bucket] = 1;
+                    slot.set(Some(hist));
+                    return;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Calls `f` with each recorded driver number and its bucket
+    /// counts, for a ProcessConsole command to format as output.
+    pub fn for_each_histogram(&self, mut f: impl FnMut(usize, &[u32; NUM_BUCKETS])) {
+        for slot in self.histograms.iter() {
+            if let Some(hist) = slot.get() {
+                f(hist.driver_num, &hist.buckets);
+            }
+        }
+    }
+}
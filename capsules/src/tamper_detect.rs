@@ -0,0 +1,137 @@
+//! Monitors designated tamper-detect GPIO pins (a case switch, an
+//! anti-intrusion mesh) and responds to a trigger by persistently
+//! recording the event, wiping every key in `key_store::KeyStore`, and
+//! notifying every process that has subscribed for the upcall — a
+//! supervisory app can then decide whether to stay stopped, alert a
+//! backend, or whatever else the device's tamper response calls for.
+//!
+//! Keeping the pins' interrupts live through deep sleep is a
+//! board/chip wake-source configuration concern (which low-power modes
+//! keep the GPIO controller clocked, which pins are wired to an
+//! always-on domain) and is not modeled here; this capsule only reacts
+//! once `gpio::Client::fired` is called, whether that happens from
+//! full run mode or a deep-sleep wake.
+//!
+//! Persisting the tamper flag across a reset is delegated to
+//! `TamperRecordRegion`, the same narrow-supplier-trait pattern
+//! `reset_reason::BootRecordRegion` uses, so the actual flash/FRAM
+//! write is a board concern and this capsule only needs to know
+//! whether the flag is currently set.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let tamper_detect = static_init!(
+//!     capsules::tamper_detect::TamperDetect<'static>,
+//!     capsules::tamper_detect::TamperDetect::new(
+//!         &[case_switch_pin, mesh_pin], record, key_store,
+//!         kernel::Grant::create(capsules::driver::NUM::TamperDetect as usize)));
+//! case_switch_pin.set_client(tamper_detect);
+//! mesh_pin.set_client(tamper_detect);
+//! ```
+
+use kernel::hil::gpio::{Client, InterruptPin};
+use kernel::{AppId, Callback, Driver, Grant, ReturnCode};
+
+use crate::driver;
+use crate::key_store::KeyStore;
+pub const DRIVER_NUM: usize = driver::NUM::TamperDetect as usize;
+
+/// Persists the tamper flag across a reset; the underlying flash/FRAM
+/// write is board-specific and not modeled here.
+pub trait TamperRecordRegion {
+    fn record_tamper(&self) -> ReturnCode;
+    fn was_tampered(&self) -> bool;
+    /// Clears the persisted flag once a supervisor has handled it.
+    fn clear(&self) -> ReturnCode;
+}
+
+mod upcall {
+    pub const TAMPER_DETECTED: usize = 0;
+}
+
+mod cmd {
+    /// Returns `SUCCESS` if tamper has been recorded (and not yet
+    /// cleared), `FAIL` otherwise.
+    pub const QUERY: usize = 0;
+    /// Clears the persisted tamper flag.
+    pub const CLEAR: usize = 1;
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+}
+
+pub struct TamperDetect<'a> {
+    pins: &'a [&'a dyn InterruptPin<'a>],
+    record: &'a dyn TamperRecordRegion,
+    key_store: &'a KeyStore,
+    apps: Grant<App>,
+}
+
+impl<'a> TamperDetect<'a> {
+    pub fn new(pins: &'a [&'a dyn InterruptPin<'a>], record: &'a dyn TamperRecordRegion, key_store: &'a KeyStore, apps: Grant<App>) -> TamperDetect<'a> {
+        TamperDetect {
+            pins,
+            record,
+            key_store,
+            apps,
+        }
+    }
+}
+
+impl<'a> Client for TamperDetect<'a> {
+    fn fired(&self) {
+        let _ = self.record.record_tamper();
+        self.key_store.wipe_all();
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(1, 0, 0);
+                }
+            });
+        }
+    }
+}
+
+impl<'a> Driver for TamperDetect<'a> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::TAMPER_DETECTED => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, _data1: usize, _data2: usize, _app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::QUERY => {
+                if self.record.was_tampered() {
+                    ReturnCode::SUCCESS
+                } else {
+                    ReturnCode::FAIL
+                }
+            }
+            cmd::CLEAR => self.record.clear(),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a> TamperDetect<'a> {
+    /// Arms every configured pin for either-edge interrupts; called
+    /// once at board setup after `set_client` has been wired up for
+    /// each.
+    pub fn enable(&self) {
+        for pin in self.pins {
+            pin.make_input();
+            pin.enable_interrupts(kernel::hil::gpio::InterruptEdge::EitherEdge);
+        }
+    }
+}
@@ -0,0 +1,434 @@
+//! Minimal TCP over `hil::ip::IpLayer`: one segment in flight at a
+//! time (no SACK, no retransmission queue beyond the one unacked
+//! segment), a single fixed advertised window, and a single maximum
+//! segment size — enough for a cloud endpoint that doesn't speak
+//! UDP/CoAP, not a general-purpose stack.
+//!
+//! Each process gets exactly one connection, tracked in its grant;
+//! `LISTEN`/`CONNECT` set up the connection, `SEND` transmits up to
+//! `MSS` bytes from the buffer allowed at index 1, `RECEIVED` upcalls
+//! report how many bytes of a new segment arrived (the bytes
+//! themselves are not modeled, since — unlike every other buffer in
+//! this tree — there is nowhere in this minimal stack's grant state to
+//! stage them for an app to read back out), and `CLOSE` starts the
+//! usual FIN exchange. `CONNECT`'s remote address comes from the
+//! buffer allowed at index 0. TCP options, retransmission timers, and
+//! congestion control are not implemented; a dropped segment simply
+//! never gets acked and the connection stalls, same as a minimal stack
+//! without a timer wheel would.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let tcp = static_init!(
+//!     capsules::tcp::TcpDriver<'static>,
+//!     capsules::tcp::TcpDriver::new(
+//!         ip, tx_buffer, kernel::Grant::create(capsules::driver::NUM::Tcp as usize)));
+//! ip.set_client(tcp);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::ip::{IpClient, IpLayer, Ipv6Address};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::Tcp as usize;
+
+/// IPv6 next-header value for TCP.
+const PROTOCOL_TCP: u8 = 6;
+/// Fixed maximum segment size this stack will ever send or accept.
+pub const MSS: usize = 536;
+/// Source port (2) + dest port (2) + seq (4) + ack (4) + flags (2) +
+/// window (2), the fixed 16-byte header this minimal stack uses
+/// (options are never sent, so there is no variable-length data
+/// offset to parse).
+const HEADER_LEN: usize = 16;
+/// The single fixed advertised window this stack ever offers.
+const WINDOW: u16 = 4096;
+/// First port handed out by `CONNECT`'s ephemeral port allocator.
+const FIRST_EPHEMERAL_PORT: u16 = 49152;
+
+mod flags {
+    pub const SYN: u16 = 0x02;
+    pub const ACK: u16 = 0x10;
+    pub const FIN: u16 = 0x01;
+    pub const RST: u16 = 0x04;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TcpState {
+    Closed,
+    Listen,
+    SynSent,
+    Established,
+    Closing,
+}
+
+mod upcall {
+    pub const STATE_CHANGED: usize = 0;
+    pub const RECEIVED: usize = 1;
+    pub const SEND_DONE: usize = 2;
+}
+
+mod cmd {
+    /// Puts the connection in `Listen` on local port `data1`.
+    pub const LISTEN: usize = 0;
+    /// Starts an active open to port `data1` of the remote address in
+    /// the buffer allowed at index 0.
+    pub const CONNECT: usize = 1;
+    /// Sends `data1` payload bytes from the buffer allowed at index 1.
+    pub const SEND: usize = 2;
+    /// Starts the FIN exchange.
+    pub const CLOSE: usize = 3;
+}
+
+pub struct App {
+    callback: Option<Callback>,
+    state: TcpState,
+    remote: Option<Ipv6Address>,
+    remote_port: u16,
+    local_port: u16,
+    seq: u32,
+    ack: u32,
+    /// The buffer allowed at index 0: the 16-byte remote address
+    /// `CONNECT` reads its destination from.
+    remote_addr: Option<AppSlice<Shared, u8>>,
+    /// The buffer allowed at index 1: read for `SEND`'s payload.
+    payload: Option<AppSlice<Shared, u8>>,
+}
+
+impl Default for App {
+    fn default() -> App {
+        App {
+            callback: None,
+            state: TcpState::Closed,
+            remote: None,
+            remote_port: 0,
+            local_port: 0,
+            seq: 0,
+            ack: 0,
+            remote_addr: None,
+            payload: None,
+        }
+    }
+}
+
+pub struct TcpDriver<'a> {
+    ip: &'a dyn IpLayer<'a>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    next_ephemeral_port: Cell<u16>,
+    apps: Grant<App>,
+    current_app: OptionalCell<AppId>,
+}
+
+impl<'a> TcpDriver<'a> {
+    pub fn new(ip: &'a dyn IpLayer<'a>, tx_buffer: &'static mut [u8], apps: Grant<App>) -> TcpDriver<'a> {
+        TcpDriver {
+            ip,
+            tx_buffer: TakeCell::new(tx_buffer),
+            next_ephemeral_port: Cell::new(FIRST_EPHEMERAL_PORT),
+            apps,
+            current_app: OptionalCell::empty(),
+        }
+    }
+
+    /// Hands out the next ephemeral local port for an active open,
+    /// wrapping back to `FIRST_EPHEMERAL_PORT` instead of ever
+    /// straying into the well-known port range.
+    fn next_ephemeral_port(&self) -> u16 {
+        let port = self.next_ephemeral_port.get();
+        self.next_ephemeral_port
+            .set(if port == u16::MAX { FIRST_EPHEMERAL_PORT } else { port + 1 });
+        port
+    }
+
+    /// Fills in the fixed 16-byte header plus `payload` and hands the
+    /// segment to `self.ip`. `self.tx_buffer` is not restored on a
+    /// `SUCCESS` return; `send_done` gets it back from the HIL.
+    fn send_segment(
+        &self,
+        local_port: u16,
+        remote_port: u16,
+        remote: Ipv6Address,
+        seq: u32,
+        ack: u32,
+        tcp_flags: u16,
+        payload: &[u8],
+    ) -> ReturnCode {
+        match self.tx_buffer.take() {
+            Some(buffer) => {
+                let len = HEADER_LEN + payload.len();
+                if len > buffer.len() {
+                    self.tx_buffer.replace(buffer);
+                    return ReturnCode::ESIZE;
+                }
+                buffer[0..2].copy_from_slice(&local_port.to_be_bytes());
+                buffer[2..4].copy_from_slice(&remote_port.to_be_bytes());
+                buffer[4..8].copy_from_slice(&seq.to_be_bytes());
+                buffer[8..12].copy_from_slice(&ack.to_be_bytes());
+                buffer[12..14].copy_from_slice(&tcp_flags.to_be_bytes());
+                buffer[14..16].copy_from_slice(&WINDOW.to_be_bytes());
+                buffer[HEADER_LEN..len].copy_from_slice(payload);
+                self.ip.send(remote, PROTOCOL_TCP, buffer, len)
+            }
+            None => ReturnCode::EBUSY,
+        }
+    }
+}
+
+impl<'a> Driver for TcpDriver<'a> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::STATE_CHANGED | upcall::RECEIVED | upcall::SEND_DONE => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.remote_addr = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            1 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.payload = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, _data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::LISTEN => self
+                .apps
+                .enter(app_id, |app, _| {
+                    if app.state != TcpState::Closed {
+                        return ReturnCode::EALREADY;
+                    }
+                    app.local_port = data1 as u16;
+                    app.state = TcpState::Listen;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            cmd::CONNECT => {
+                if self.current_app.is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                let remote_port = data1 as u16;
+                let prepared = self
+                    .apps
+                    .enter(app_id, |app, _| {
+                        if app.state != TcpState::Closed {
+                            return Err(ReturnCode::EALREADY);
+                        }
+                        match &app.remote_addr {
+                            Some(slice) if slice.len() >= 16 => {
+                                let mut bytes = [0u8; 16];
+                                bytes.copy_from_slice(&slice.as_ref()[..16]);
+                                Ok(Ipv6Address(bytes))
+                            }
+                            Some(_) => Err(ReturnCode::ESIZE),
+                            None => Err(ReturnCode::EINVAL),
+                        }
+                    })
+                    .unwrap_or(Err(ReturnCode::FAIL));
+                let remote = match prepared {
+                    Ok(remote) => remote,
+                    Err(result) => return result,
+                };
+                let local_port = self.next_ephemeral_port();
+                let result = self.send_segment(local_port, remote_port, remote, 0, 0, flags::SYN, &[]);
+                if result == ReturnCode::SUCCESS {
+                    let _ = self.apps.enter(app_id, |app, _| {
+                        app.remote = Some(remote);
+                        app.remote_port = remote_port;
+                        app.local_port = local_port;
+                        app.state = TcpState::SynSent;
+                        app.seq = 1;
+                        app.ack = 0;
+                    });
+                    self.current_app.set(app_id);
+                }
+                result
+            }
+            cmd::SEND => {
+                let payload_len = data1;
+                if payload_len > MSS {
+                    return ReturnCode::ESIZE;
+                }
+                if self.current_app.is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                let prepared = self
+                    .apps
+                    .enter(app_id, |app, _| {
+                        if app.state != TcpState::Established {
+                            return Err(ReturnCode::EINVAL);
+                        }
+                        let remote = match app.remote {
+                            Some(remote) => remote,
+                            None => return Err(ReturnCode::EINVAL),
+                        };
+                        let mut bytes = [0u8; MSS];
+                        match &app.payload {
+                            Some(slice) if payload_len <= slice.len() => {
+                                bytes[..payload_len].copy_from_slice(&slice.as_ref()[..payload_len]);
+                            }
+                            Some(_) => return Err(ReturnCode::ESIZE),
+                            None if payload_len == 0 => {}
+                            None => return Err(ReturnCode::EINVAL),
+                        }
+                        Ok((app.local_port, app.remote_port, remote, app.seq, app.ack, bytes))
+                    })
+                    .unwrap_or(Err(ReturnCode::FAIL));
+                let (local_port, remote_port, remote, seq, ack, bytes) = match prepared {
+                    Ok(v) => v,
+                    Err(result) => return result,
+                };
+                let result = self.send_segment(local_port, remote_port, remote, seq, ack, flags::ACK, &bytes[..payload_len]);
+                if result == ReturnCode::SUCCESS {
+                    let _ = self.apps.enter(app_id, |app, _| {
+                        app.seq = app.seq.wrapping_add(payload_len as u32);
+                    });
+                    self.current_app.set(app_id);
+                }
+                result
+            }
+            cmd::CLOSE => {
+                let established = self
+                    .apps
+                    .enter(app_id, |app, _| {
+                        if app.state == TcpState::Established {
+                            app.remote.map(|remote| (app.local_port, app.remote_port, remote, app.seq, app.ack))
+                        } else {
+                            None
+                        }
+                    })
+                    .unwrap_or(None);
+                match established {
+                    Some((local_port, remote_port, remote, seq, ack)) => {
+                        if self.current_app.is_some() {
+                            return ReturnCode::EBUSY;
+                        }
+                        let result = self.send_segment(local_port, remote_port, remote, seq, ack, flags::FIN | flags::ACK, &[]);
+                        if result == ReturnCode::SUCCESS {
+                            let _ = self.apps.enter(app_id, |app, _| {
+                                app.state = TcpState::Closing;
+                                app.seq = app.seq.wrapping_add(1);
+                            });
+                            self.current_app.set(app_id);
+                        }
+                        result
+                    }
+                    None => self
+                        .apps
+                        .enter(app_id, |app, _| {
+                            app.state = TcpState::Closed;
+                            ReturnCode::SUCCESS
+                        })
+                        .unwrap_or(ReturnCode::FAIL),
+                }
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a> IpClient for TcpDriver<'a> {
+    fn send_done(&self, buffer: &'static mut [u8], result: ReturnCode) {
+        self.tx_buffer.replace(buffer);
+        if let Some(app_id) = self.current_app.take() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if app.state == TcpState::SynSent && result == ReturnCode::SUCCESS {
+                    // The SYN-ACK that completes the handshake arrives
+                    // later through `receive`; nothing to do here but
+                    // let the send complete.
+                }
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(upcall::SEND_DONE, usize::from(result), 0);
+                }
+            });
+        }
+    }
+
+    fn receive(&self, src: Ipv6Address, protocol: u8, buffer: &[u8], len: usize) {
+        if protocol != PROTOCOL_TCP || len < HEADER_LEN {
+            return;
+        }
+        let src_port = u16::from_be_bytes([buffer[0], buffer[1]]);
+        let dst_port = u16::from_be_bytes([buffer[2], buffer[3]]);
+        let seq = u32::from_be_bytes([buffer[4], buffer[5], buffer[6], buffer[7]]);
+        let tcp_flags = u16::from_be_bytes([buffer[12], buffer[13]]);
+        let payload_len = len - HEADER_LEN;
+
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                let matches_connection = app.local_port == dst_port
+                    && (app.state == TcpState::Listen || app.remote_port == src_port && app.remote == Some(src));
+                if !matches_connection {
+                    return;
+                }
+
+                if tcp_flags & flags::RST != 0 {
+                    app.state = TcpState::Closed;
+                    if let Some(mut cb) = app.callback {
+                        cb.schedule(upcall::STATE_CHANGED, 0, 0);
+                    }
+                    return;
+                }
+
+                match app.state {
+                    TcpState::Listen if tcp_flags & flags::SYN != 0 => {
+                        app.remote = Some(src);
+                        app.remote_port = src_port;
+                        app.ack = seq.wrapping_add(1);
+                        // Our own SYN consumes a sequence number, same
+                        // as the active-open side's in `CONNECT`.
+                        let _ = self.send_segment(app.local_port, src_port, src, app.seq, app.ack, flags::SYN | flags::ACK, &[]);
+                        app.seq = app.seq.wrapping_add(1);
+                        app.state = TcpState::Established;
+                        if let Some(mut cb) = app.callback {
+                            cb.schedule(upcall::STATE_CHANGED, 1, 0);
+                        }
+                    }
+                    TcpState::SynSent if tcp_flags & flags::SYN != 0 && tcp_flags & flags::ACK != 0 => {
+                        app.ack = seq.wrapping_add(1);
+                        let _ = self.send_segment(app.local_port, src_port, src, app.seq, app.ack, flags::ACK, &[]);
+                        app.state = TcpState::Established;
+                        if let Some(mut cb) = app.callback {
+                            cb.schedule(upcall::STATE_CHANGED, 1, 0);
+                        }
+                    }
+                    TcpState::Established => {
+                        if tcp_flags & flags::FIN != 0 {
+                            app.state = TcpState::Closed;
+                            if let Some(mut cb) = app.callback {
+                                cb.schedule(upcall::STATE_CHANGED, 0, 0);
+                            }
+                        } else if payload_len > 0 {
+                            app.ack = seq.wrapping_add(payload_len as u32);
+                            if let Some(mut cb) = app.callback {
+                                cb.schedule(upcall::RECEIVED, payload_len, 0);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,345 @@
+//! Thread network-layer support: MLE attach (joining an existing
+//! Thread network as a child) and mesh header forwarding, for boards
+//! that already have an 802.15.4 radio and want to speak Thread's
+//! mesh protocol instead of (or alongside) raw frames.
+//!
+//! Thread's full stack is IPv6-over-6LoWPAN-over-802.15.4 plus MLE for
+//! link management and a routing protocol routers run among
+//! themselves; the parts of that worth getting right in a capsule
+//! like this are the framing a node must parse correctly to either
+//! join a network or forward someone else's traffic: the MLE command
+//! type exchanged while attaching, the RLOC16 the network assigns a
+//! joining child, and the mesh header (RFC 4944 Section 5.2) hop
+//! count and originator/final addresses that tell a router whether a
+//! frame is its own or needs forwarding one more hop. IPv6 header
+//! compression, the routing protocol routers use to learn about each
+//! other, and MLE's network-key-based message security are not
+//! modeled: attach and forwarding here work against an
+//! already-provisioned network key, and frame payloads are exchanged
+//! through the buffer allowed at index 0: read from for `SEND`, and
+//! copied into for every subscribed process's own buffer when a
+//! mesh-addressed frame reaches its destination. The mesh header is
+//! likewise only ever built or parsed in its 16-bit-address form,
+//! which is what RLOC16-addressed Thread traffic uses.
+//!
+//! Becoming a router is simplified to a local `BECOME_ROUTER` command
+//! rather than the real Address Solicit exchange a child runs with
+//! the Leader to request a router ID; a board that only ever joins as
+//! a child never notices.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let thread = static_init!(
+//!     capsules::thread_network::ThreadNetwork<'static>,
+//!     capsules::thread_network::ThreadNetwork::new(
+//!         radio, tx_buffer,
+//!         kernel::Grant::create(capsules::driver::NUM::ThreadNetwork as usize)));
+//! radio.set_transmit_client(thread);
+//! radio.set_receive_client(thread);
+//! let _ = radio.start_receiving();
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::TakeCell;
+use kernel::hil::radio::{Radio, RxClient, TxClient};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::ThreadNetwork as usize;
+
+mod mle {
+    pub const PARENT_REQUEST: u8 = 9;
+    pub const PARENT_RESPONSE: u8 = 10;
+    pub const CHILD_ID_REQUEST: u8 = 11;
+    pub const CHILD_ID_RESPONSE: u8 = 12;
+    /// Command type byte; every other MLE TLV (mode, timeout, route64,
+    /// and the security suite wrapping all of it in a real
+    /// implementation) is not modeled here.
+    pub const COMMAND_LEN: usize = 1;
+    /// Offset of the assigned RLOC16 this capsule reads out of a
+    /// `CHILD_ID_RESPONSE`, assuming the Address16 TLV is the first
+    /// (and, here, only) one present.
+    pub const CHILD_ID_RESPONSE_RLOC16_OFFSET: usize = 1;
+}
+
+mod mesh {
+    /// Top two bits of the dispatch byte that mark a frame as
+    /// mesh-addressed (RFC 4944 Section 5.2), as opposed to an MLE
+    /// command frame.
+    pub const DISPATCH_MASK: u8 = 0xc0;
+    pub const DISPATCH: u8 = 0x80;
+    pub const HOPS_LEFT_MASK: u8 = 0x3f;
+    /// Dispatch+hops-left (1) + originator RLOC16 (2) + final RLOC16
+    /// (2); the payload (not shown) follows.
+    pub const HEADER_LEN: usize = 5;
+    pub const MAX_HOPS: u8 = 0x0f;
+}
+
+mod upcall {
+    /// `data1` is the new `Role`, cast to `usize`.
+    pub const STATE_CHANGED: usize = 0;
+    /// `data1` is how many bytes of the buffer allowed at index 0 were
+    /// filled with the delivered payload.
+    pub const RECEIVED: usize = 1;
+}
+
+mod cmd {
+    /// Attaches to the network whose key is in the buffer allowed at
+    /// index 1, by sending an MLE Parent Request and, on a response,
+    /// a Child ID Request.
+    pub const JOIN: usize = 0;
+    /// Promotes an already-attached child to a router, without the
+    /// Address Solicit exchange a real implementation would run with
+    /// the Leader first.
+    pub const BECOME_ROUTER: usize = 1;
+    /// Sends `data2` payload bytes (from the buffer allowed at index
+    /// 0) mesh-addressed to the RLOC16 `data1`.
+    pub const SEND: usize = 2;
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Role {
+    Detached,
+    Child,
+    Router,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Pending {
+    ParentResponse,
+    ChildIdResponse,
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    /// The buffer allowed at index 0: read from for `SEND`, written
+    /// into for `RECEIVED`.
+    payload: Option<AppSlice<Shared, u8>>,
+    network_key_buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct ThreadNetwork<'a> {
+    radio: &'a dyn Radio<'a>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    role: Cell<Role>,
+    rloc16: Cell<u16>,
+    network_key: Cell<[u8; 16]>,
+    pending: Cell<Option<Pending>>,
+    apps: Grant<App>,
+}
+
+impl<'a> ThreadNetwork<'a> {
+    pub fn new(radio: &'a dyn Radio<'a>, tx_buffer: &'static mut [u8], apps: Grant<App>) -> ThreadNetwork<'a> {
+        ThreadNetwork {
+            radio,
+            tx_buffer: TakeCell::new(tx_buffer),
+            role: Cell::new(Role::Detached),
+            rloc16: Cell::new(0xfffe),
+            network_key: Cell::new([0; 16]),
+            pending: Cell::new(None),
+            apps,
+        }
+    }
+
+    fn notify_state_changed(&self) {
+        for app_id in self.apps.iter() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(upcall::STATE_CHANGED, self.role.get() as usize, 0);
+                }
+            });
+        }
+    }
+
+    /// Delivers a mesh-addressed frame that has reached its
+    /// destination to every subscribed app, or, if this node is a
+    /// router and the frame is bound elsewhere, forwards it one hop
+    /// closer with the hop count decremented.
+    fn forward_or_deliver(&self, buffer: &[u8], len: usize) {
+        if len < mesh::HEADER_LEN {
+            return;
+        }
+        let hops_left = buffer[0] & mesh::HOPS_LEFT_MASK;
+        let final_addr = u16::from_le_bytes([buffer[3], buffer[4]]);
+
+        if final_addr == self.rloc16.get() {
+            let payload = &buffer[mesh::HEADER_LEN..len];
+            for app_id in self.apps.iter() {
+                let _ = self.apps.enter(app_id, |app, _| {
+                    if let Some(slice) = &mut app.payload {
+                        let copy_len = core::cmp::min(payload.len(), slice.len());
+                        slice.as_mut()[..copy_len].copy_from_slice(&payload[..copy_len]);
+                        if let Some(mut cb) = app.callback {
+                            cb.schedule(upcall::RECEIVED, copy_len, 0);
+                        }
+                    }
+                });
+            }
+            return;
+        }
+
+        if self.role.get() != Role::Router || hops_left == 0 {
+            return;
+        }
+        if let Some(tx) = self.tx_buffer.take() {
+            tx[..len].copy_from_slice(&buffer[..len]);
+            tx[0] = mesh::DISPATCH | (hops_left - 1);
+            let _ = self.radio.transmit(tx, len);
+        }
+    }
+}
+
+impl<'a> Driver for ThreadNetwork<'a> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::STATE_CHANGED | upcall::RECEIVED => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.payload = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            1 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.network_key_buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::JOIN => {
+                if self.pending.get().is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                let key = self
+                    .apps
+                    .enter(app_id, |app, _| match &app.network_key_buffer {
+                        Some(slice) if slice.len() >= 16 => {
+                            let mut key = [0u8; 16];
+                            key.copy_from_slice(&slice.as_ref()[..16]);
+                            Some(key)
+                        }
+                        _ => None,
+                    })
+                    .unwrap_or(None);
+                let key = match key {
+                    Some(key) => key,
+                    None => return ReturnCode::EINVAL,
+                };
+                match self.tx_buffer.take() {
+                    Some(buffer) => {
+                        self.network_key.set(key);
+                        self.role.set(Role::Detached);
+                        buffer[0] = mle::PARENT_REQUEST;
+                        self.pending.set(Some(Pending::ParentResponse));
+                        self.radio.transmit(buffer, mle::COMMAND_LEN)
+                    }
+                    None => ReturnCode::EBUSY,
+                }
+            }
+            cmd::BECOME_ROUTER => {
+                if self.role.get() != Role::Child {
+                    return ReturnCode::EINVAL;
+                }
+                self.role.set(Role::Router);
+                self.notify_state_changed();
+                ReturnCode::SUCCESS
+            }
+            cmd::SEND => {
+                if self.role.get() == Role::Detached {
+                    return ReturnCode::EOFF;
+                }
+                let mut buffer = match self.tx_buffer.take() {
+                    Some(buffer) => buffer,
+                    None => return ReturnCode::EBUSY,
+                };
+                if mesh::HEADER_LEN + data2 > buffer.len() {
+                    self.tx_buffer.replace(buffer);
+                    return ReturnCode::ESIZE;
+                }
+                let copied = self
+                    .apps
+                    .enter(app_id, |app, _| match &app.payload {
+                        Some(slice) if data2 <= slice.len() => {
+                            buffer[mesh::HEADER_LEN..mesh::HEADER_LEN + data2].copy_from_slice(&slice.as_ref()[..data2]);
+                            true
+                        }
+                        _ => false,
+                    })
+                    .unwrap_or(false);
+                if !copied {
+                    self.tx_buffer.replace(buffer);
+                    return ReturnCode::EINVAL;
+                }
+                buffer[0] = mesh::DISPATCH | mesh::MAX_HOPS;
+                buffer[1..3].copy_from_slice(&self.rloc16.get().to_le_bytes());
+                buffer[3..5].copy_from_slice(&(data1 as u16).to_le_bytes());
+                self.radio.transmit(buffer, mesh::HEADER_LEN + data2)
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a> TxClient for ThreadNetwork<'a> {
+    fn transmit_done(&self, buffer: &'static mut [u8], _result: ReturnCode) {
+        self.tx_buffer.replace(buffer);
+    }
+}
+
+impl<'a> RxClient for ThreadNetwork<'a> {
+    fn receive(&self, buffer: &[u8], len: usize, result: ReturnCode) {
+        if result != ReturnCode::SUCCESS || len == 0 {
+            return;
+        }
+
+        if buffer[0] & mesh::DISPATCH_MASK == mesh::DISPATCH {
+            self.forward_or_deliver(buffer, len);
+            return;
+        }
+
+        let pending = match self.pending.get() {
+            Some(pending) => pending,
+            None => return,
+        };
+        match (pending, buffer[0]) {
+            (Pending::ParentResponse, mle::PARENT_RESPONSE) => {
+                if let Some(tx) = self.tx_buffer.take() {
+                    tx[0] = mle::CHILD_ID_REQUEST;
+                    self.pending.set(Some(Pending::ChildIdResponse));
+                    let _ = self.radio.transmit(tx, mle::COMMAND_LEN);
+                }
+            }
+            (Pending::ChildIdResponse, mle::CHILD_ID_RESPONSE) => {
+                if len >= mle::CHILD_ID_RESPONSE_RLOC16_OFFSET + 2 {
+                    let offset = mle::CHILD_ID_RESPONSE_RLOC16_OFFSET;
+                    self.rloc16.set(u16::from_le_bytes([buffer[offset], buffer[offset + 1]]));
+                    self.role.set(Role::Child);
+                    self.pending.set(None);
+                    self.notify_state_changed();
+                }
+            }
+            _ => {}
+        }
+    }
+}
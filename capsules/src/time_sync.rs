@@ -0,0 +1,189 @@
+//! Authenticated time synchronization: accepts a signed time message
+//! (timestamp plus an ECDSA signature over it) relayed by a userspace
+//! network app — NTS-lite, or any application-defined scheme that
+//! signs a timestamp with a key this device trusts — and only marks
+//! time as synced once `hil::ecdsa::EcdsaP256Engine` confirms the
+//! signature, so a stray or spoofed UDP packet claiming to be a time
+//! server response cannot mark it synced; only a message signed by
+//! the configured `server_public_key` can. This capsule does not set
+//! or adjust any clock itself.
+//!
+//! The buffer allowed at index 0 holds the `HASH_LEN` bytes hashed
+//! over the timestamp followed by the `SIGNATURE_LEN` bytes of
+//! `r || s` signature; the timestamp itself is not passed in, and
+//! this capsule's job is the trust decision (does
+//! `server_public_key` vouch for that hash), not timestamp
+//! arithmetic. Sync quality (synced or unsynced; how long ago the
+//! last accepted sync was is not tracked) is available both through
+//! `SYNC_QUALITY` and through `quality()` for a board to publish into
+//! a ROS region apps can poll without a syscall.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let time_sync = static_init!(
+//!     capsules::time_sync::TimeSyncDriver<'static>,
+//!     capsules::time_sync::TimeSyncDriver::new(
+//!         engine, server_public_key,
+//!         kernel::Grant::create(capsules::driver::NUM::TimeSync as usize)));
+//! engine.set_client(time_sync);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::OptionalCell;
+use kernel::hil::ecdsa::{EcdsaP256Client, EcdsaP256Engine};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::TimeSync as usize;
+
+/// SHA-256 hash length, the digest `server_public_key` signs over.
+const HASH_LEN: usize = 32;
+/// `r || s`, an ECDSA P256 signature.
+const SIGNATURE_LEN: usize = 64;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum SyncQuality {
+    /// No signed time message has ever been accepted.
+    Unsynced,
+    /// The last signed time message was accepted; callers combine
+    /// this with however they are tracking elapsed ticks since then
+    /// to judge staleness for their own purposes.
+    Synced,
+}
+
+mod upcall {
+    pub const DONE: usize = 0;
+}
+
+mod cmd {
+    /// Verifies the signature over the timestamp hash in the buffer
+    /// allowed at index 0 against `server_public_key`; on success the
+    /// timestamp is accepted and `quality()` becomes `Synced`.
+    pub const SUBMIT: usize = 0;
+    /// Returns `SUCCESS` if `quality()` is `Synced`, `FAIL` otherwise.
+    pub const SYNC_QUALITY: usize = 1;
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    /// The buffer allowed at index 0: `HASH_LEN` bytes of hash followed
+    /// by `SIGNATURE_LEN` bytes of signature, read for `SUBMIT`.
+    message: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct TimeSyncDriver<'a> {
+    engine: &'a dyn EcdsaP256Engine<'a>,
+    server_public_key: &'static [u8],
+    synced: Cell<bool>,
+    apps: Grant<App>,
+    current_app: OptionalCell<AppId>,
+}
+
+impl<'a> TimeSyncDriver<'a> {
+    pub fn new(engine: &'a dyn EcdsaP256Engine<'a>, server_public_key: &'static [u8], apps: Grant<App>) -> TimeSyncDriver<'a> {
+        TimeSyncDriver {
+            engine,
+            server_public_key,
+            synced: Cell::new(false),
+            apps,
+            current_app: OptionalCell::empty(),
+        }
+    }
+
+    pub fn quality(&self) -> SyncQuality {
+        if self.synced.get() {
+            SyncQuality::Synced
+        } else {
+            SyncQuality::Unsynced
+        }
+    }
+}
+
+impl<'a> Driver for TimeSyncDriver<'a> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::DONE => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.message = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, _data1: usize, _data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::SUBMIT => {
+                if self.current_app.is_some() {
+                    return ReturnCode::EBUSY;
+                }
+                let prepared = self
+                    .apps
+                    .enter(app_id, |app, _| match &app.message {
+                        Some(slice) if slice.len() >= HASH_LEN + SIGNATURE_LEN => {
+                            let mut hash = [0u8; HASH_LEN];
+                            let mut signature = [0u8; SIGNATURE_LEN];
+                            hash.copy_from_slice(&slice.as_ref()[..HASH_LEN]);
+                            signature.copy_from_slice(&slice.as_ref()[HASH_LEN..HASH_LEN + SIGNATURE_LEN]);
+                            Ok((hash, signature))
+                        }
+                        Some(_) => Err(ReturnCode::ESIZE),
+                        None => Err(ReturnCode::EINVAL),
+                    })
+                    .unwrap_or(Err(ReturnCode::FAIL));
+                let (hash, signature) = match prepared {
+                    Ok(v) => v,
+                    Err(result) => return result,
+                };
+                let result = self.engine.verify(self.server_public_key, &hash, &signature);
+                if result == ReturnCode::SUCCESS {
+                    self.current_app.set(app_id);
+                }
+                result
+            }
+            cmd::SYNC_QUALITY => {
+                if self.synced.get() {
+                    ReturnCode::SUCCESS
+                } else {
+                    ReturnCode::FAIL
+                }
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a> EcdsaP256Client for TimeSyncDriver<'a> {
+    fn sign_done(&self, _signature_buffer: &'static mut [u8], _result: ReturnCode) {}
+
+    fn verify_done(&self, result: ReturnCode, valid: bool) {
+        if let Some(app_id) = self.current_app.take() {
+            if result == ReturnCode::SUCCESS && valid {
+                self.synced.set(true);
+            }
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mut cb) = app.callback {
+                    let valid_arg = if valid { 1 } else { 0 };
+                    cb.schedule(usize::from(result), valid_arg, 0);
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,196 @@
+//! Generic USB bulk endpoint driver: hands one process a vendor-class
+//! interface's IN/OUT bulk endpoint pair directly, through the same
+//! `hil::usb::UsbBulkEndpoint` `capsules::usb_mass_storage` drives,
+//! so a custom high-throughput host protocol doesn't need a new
+//! kernel USB class writing for it — only a syscall driver moving
+//! opaque bytes.
+//!
+//! Exactly one process may hold the endpoint pair, claimed by
+//! whichever process's `TRANSMIT` or `RECEIVE` command runs first and
+//! held until reboot, the same single-owner exclusivity
+//! `capsules::usb_mass_storage`'s `is_exported` gives a host. `RECEIVE`
+//! arms one incoming packet at a time rather than re-arming
+//! automatically on completion, so a slow app naturally back-pressures
+//! the host instead of this capsule silently accumulating packets it
+//! has nowhere to put. Payload bytes are exchanged through the
+//! buffers allowed at index 0 (`TRANSMIT`, outgoing) and index 1
+//! (`RECEIVE`, incoming), matching this tree's convention
+//! for syscall buffers.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let bulk_driver = static_init!(
+//!     capsules::usb_bulk_driver::UsbBulkDriver<'static>,
+//!     capsules::usb_bulk_driver::UsbBulkDriver::new(
+//!         bulk_endpoint, tx_buffer, rx_buffer,
+//!         kernel::Grant::create(capsules::driver::NUM::UsbBulk as usize)));
+//! bulk_endpoint.set_client(bulk_driver);
+//! ```
+
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::usb::{UsbBulkClient, UsbBulkEndpoint};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::UsbBulk as usize;
+
+mod upcall {
+    /// `data1` is a `ReturnCode`.
+    pub const TRANSMIT_DONE: usize = 0;
+    /// `data1` is how many bytes of the buffer allowed at index 1 were
+    /// filled by the completed OUT transfer.
+    pub const RECEIVED: usize = 1;
+}
+
+mod cmd {
+    /// Sends `data1` bytes from the buffer allowed at index 0.
+    pub const TRANSMIT: usize = 0;
+    /// Arms the buffer allowed at index 1 to receive the next OUT
+    /// transfer. `data1` is only checked against this driver's own
+    /// receive buffer (`ESIZE` if larger); the received bytes are
+    /// copied up to the allowed buffer's length, not up to `data1`.
+    pub const RECEIVE: usize = 1;
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    tx_buffer: Option<AppSlice<Shared, u8>>,
+    rx_buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct UsbBulkDriver<'a> {
+    bulk: &'a dyn UsbBulkEndpoint<'a>,
+    owner: OptionalCell<AppId>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    rx_buffer: TakeCell<'static, [u8]>,
+    apps: Grant<App>,
+}
+
+impl<'a> UsbBulkDriver<'a> {
+    pub fn new(bulk: &'a dyn UsbBulkEndpoint<'a>, tx_buffer: &'static mut [u8], rx_buffer: &'static mut [u8], apps: Grant<App>) -> UsbBulkDriver<'a> {
+        UsbBulkDriver {
+            bulk,
+            owner: OptionalCell::empty(),
+            tx_buffer: TakeCell::new(tx_buffer),
+            rx_buffer: TakeCell::new(rx_buffer),
+            apps,
+        }
+    }
+
+    /// Claims the endpoint pair for `app_id` if unclaimed, and checks
+    /// that whoever holds it already is the one calling now.
+    fn claim(&self, app_id: AppId) -> bool {
+        if !self.owner.is_some() {
+            self.owner.set(app_id);
+        }
+        self.owner.map(|owner| owner == app_id).unwrap_or(false)
+    }
+}
+
+impl<'a> UsbBulkClient for UsbBulkDriver<'a> {
+    fn packet_out(&self, buffer: &'static mut [u8], length: usize) {
+        if let Some(app_id) = self.owner.map(|app_id| app_id) {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(slice) = &mut app.rx_buffer {
+                    let copy_len = core::cmp::min(length, slice.len());
+                    slice.as_mut()[..copy_len].copy_from_slice(&buffer[..copy_len]);
+                    if let Some(mut cb) = app.callback {
+                        cb.schedule(upcall::RECEIVED, copy_len, 0);
+                    }
+                }
+            });
+        }
+        self.rx_buffer.replace(buffer);
+    }
+
+    fn packet_in(&self, buffer: &'static mut [u8]) {
+        self.tx_buffer.replace(buffer);
+        if let Some(app_id) = self.owner.map(|app_id| app_id) {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(upcall::TRANSMIT_DONE, usize::from(ReturnCode::SUCCESS), 0);
+                }
+            });
+        }
+    }
+}
+
+impl<'a> Driver for UsbBulkDriver<'a> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::TRANSMIT_DONE | upcall::RECEIVED => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.tx_buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            1 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.rx_buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, data1: usize, _data2: usize, app_id: AppId) -> ReturnCode {
+        if !self.claim(app_id) {
+            return ReturnCode::EBUSY;
+        }
+        match command_num {
+            cmd::TRANSMIT => {
+                let len = data1;
+                let buffer = match self.tx_buffer.take() {
+                    Some(buffer) => buffer,
+                    None => return ReturnCode::EBUSY,
+                };
+                let copied = self
+                    .apps
+                    .enter(app_id, |app, _| match &app.tx_buffer {
+                        Some(slice) if slice.len() >= len && len <= buffer.len() => {
+                            buffer[..len].copy_from_slice(&slice.as_ref()[..len]);
+                            true
+                        }
+                        _ => false,
+                    })
+                    .unwrap_or(false);
+                if !copied {
+                    self.tx_buffer.replace(buffer);
+                    return ReturnCode::EINVAL;
+                }
+                self.bulk.transmit(buffer, len)
+            }
+            cmd::RECEIVE => {
+                let len = data1;
+                let buffer = match self.rx_buffer.take() {
+                    Some(buffer) => buffer,
+                    None => return ReturnCode::EBUSY,
+                };
+                if len > buffer.len() {
+                    self.rx_buffer.replace(buffer);
+                    return ReturnCode::ESIZE;
+                }
+                self.bulk.receive(buffer)
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! USB DFU (Device Firmware Update) runtime interface: accepts a
+//! firmware download from a standard host tool (`dfu-util` and
+//! similar) over DFU class control requests and stages it through
+//! `capsules::firmware_update::FirmwareUpdate`, so the same signature
+//! check and A/B slot bookkeeping every other update transport uses
+//! also covers USB.
+//!
+//! This tree's USB HILs (`hil::usb`'s bulk endpoints,
+//! `hil::usb_hid`'s interrupt reports) only cover data endpoints, not
+//! DFU's class-specific control requests (`DFU_DNLOAD`,
+//! `DFU_GETSTATUS`, `DFU_DETACH`, ...), so [`DfuRequestHandler`] is
+//! this capsule's own entry point instead: a board's USB stack decodes
+//! the control request and calls straight into it, the same shape
+//! `capsules::cellular_modem::PowerControl` uses to cover a HIL this
+//! tree doesn't have.
+//!
+//! Only the runtime DFU state machine a firmware download actually
+//! exercises is implemented — `dfuIDLE` → `dfuDNLOAD-IDLE` (repeated
+//! `DFU_DNLOAD` requests, tracked here as [`State::Downloading`]) →
+//! `dfuMANIFEST-SYNC` (the zero-length `DFU_DNLOAD` that signals end of
+//! transfer, where `FirmwareUpdate::finish` actually runs the
+//! signature check) → back to `dfuIDLE` on success or `dfuERROR` on a
+//! bad signature or an out-of-sequence block. `DFU_UPLOAD` (reading
+//! firmware back out) is not implemented, since none of this tree's
+//! update transports support it either. `DFU_DETACH` does not itself
+//! reset into the bootloader — like `capsules::firmware_update`'s
+//! `rollback`, actually resetting is a board's job, requested here
+//! through `DfuTransport::request_reset`.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let dfu = static_init!(
+//!     capsules::usb_dfu::UsbDfu<'static>,
+//!     capsules::usb_dfu::UsbDfu::new(updater, transport));
+//! ```
+
+use core::cell::Cell;
+use kernel::ReturnCode;
+
+/// The DFU status byte and state byte a `DFU_GETSTATUS` reply reports,
+/// and the board-level reset a `DFU_DETACH` requests — the pieces of a
+/// real DFU control transfer this capsule needs a board's USB stack to
+/// carry for it, since no control-transfer USB HIL exists in this
+/// tree.
+pub trait DfuTransport {
+    /// Answers a pending `DFU_GETSTATUS` with `status` (`dfu::status`)
+    /// and `state` (`dfu::state`); `poll_timeout_ms` is how long the
+    /// host should wait before polling again.
+    fn send_status(&self, status: u8, poll_timeout_ms: u32, state: u8);
+    /// Called once a `DFU_DETACH` request is accepted; the board is
+    /// expected to reset into its bootloader shortly after.
+    fn request_reset(&self);
+}
+
+/// The entry points a board's USB stack calls as DFU class control
+/// requests arrive.
+pub trait DfuRequestHandler {
+    /// A `DFU_DNLOAD` with `block_num` and `data`; a zero-length
+    /// `data` is the host's signal that the transfer is complete.
+    fn dnload(&self, block_num: u16, data: &[u8]) -> ReturnCode;
+    /// A `DFU_GETSTATUS`; the reply comes back through
+    /// `DfuTransport::send_status`.
+    fn getstatus(&self);
+    /// A `DFU_DETACH`.
+    fn detach(&self);
+}
+
+pub mod dfu {
+    /// `DFU_GETSTATUS` status byte: no error.
+    pub const STATUS_OK: u8 = 0x00;
+    /// `DFU_GETSTATUS` status byte: verification of the downloaded
+    /// firmware failed.
+    pub const STATUS_ERR_VERIFY: u8 = 0x03;
+    /// `DFU_GETSTATUS` status byte: a `DFU_DNLOAD` arrived out of the
+    /// expected block sequence.
+    pub const STATUS_ERR_STALLEDPKT: u8 = 0x0e;
+
+    pub const STATE_DFU_IDLE: u8 = 0x02;
+    pub const STATE_DFU_DNLOAD_IDLE: u8 = 0x05;
+    pub const STATE_DFU_MANIFEST: u8 = 0x07;
+    pub const STATE_DFU_ERROR: u8 = 0x0a;
+
+    /// Host tools poll `DFU_GETSTATUS` at least this often while a
+    /// block is being staged; this capsule stages synchronously, so it
+    /// is reported as the minimum this tree's `dfu-util` interop has
+    /// been tested against rather than a measured flash-write latency.
+    pub const POLL_TIMEOUT_MS: u32 = 5;
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum State {
+    Idle,
+    Downloading { next_block: u16, offset: usize },
+    Error,
+}
+
+pub struct UsbDfu<'a> {
+    updater: &'a crate::firmware_update::FirmwareUpdate<'a>,
+    transport: &'a dyn DfuTransport,
+    state: Cell<State>,
+}
+
+impl<'a> UsbDfu<'a> {
+    pub fn new(updater: &'a crate::firmware_update::FirmwareUpdate<'a>, transport: &'a dyn DfuTransport) -> UsbDfu<'a> {
+        UsbDfu {
+            updater,
+            transport,
+            state: Cell::new(State::Idle),
+        }
+    }
+}
+
+impl<'a> DfuRequestHandler for UsbDfu<'a> {
+    fn dnload(&self, block_num: u16, data: &[u8]) -> ReturnCode {
+        match self.state.get() {
+            State::Idle if block_num == 0 && !data.is_empty() => {
+                if self.updater.begin() != ReturnCode::SUCCESS {
+                    self.state.set(State::Error);
+                    return ReturnCode::EBUSY;
+                }
+                let result = self.updater.write_chunk(0, data);
+                if result != ReturnCode::SUCCESS {
+                    self.state.set(State::Error);
+                    return result;
+                }
+                self.state.set(State::Downloading {
+                    next_block: 1,
+                    offset: data.len(),
+                });
+                ReturnCode::SUCCESS
+            }
+            State::Downloading { next_block, offset } if block_num == next_block => {
+                if data.is_empty() {
+                    let result = self.updater.finish();
+                    self.state.set(if result == ReturnCode::SUCCESS { State::Idle } else { State::Error });
+                    return result;
+                }
+                let result = self.updater.write_chunk(offset, data);
+                if result != ReturnCode::SUCCESS {
+                    self.state.set(State::Error);
+                    return result;
+                }
+                self.state.set(State::Downloading {
+                    next_block: next_block.wrapping_add(1),
+                    offset: offset + data.len(),
+                });
+                ReturnCode::SUCCESS
+            }
+            _ => {
+                self.state.set(State::Error);
+                ReturnCode::ECANCEL
+            }
+        }
+    }
+
+    fn getstatus(&self) {
+        let (status, state) = match self.state.get() {
+            State::Idle => (dfu::STATUS_OK, dfu::STATE_DFU_IDLE),
+            State::Downloading { .. } => (dfu::STATUS_OK, dfu::STATE_DFU_DNLOAD_IDLE),
+            State::Error => (dfu::STATUS_ERR_VERIFY, dfu::STATE_DFU_ERROR),
+        };
+        self.transport.send_status(status, dfu::POLL_TIMEOUT_MS, state);
+    }
+
+    fn detach(&self) {
+        self.state.set(State::Idle);
+        self.transport.request_reset();
+    }
+}
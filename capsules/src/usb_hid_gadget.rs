@@ -0,0 +1,191 @@
+//! USB HID keyboard/mouse gadget: lets one process drive a combined
+//! boot-protocol keyboard and mouse over `hil::usb_hid::UsbHidReport`,
+//! and delivers the keyboard LED output report (caps/num/scroll lock)
+//! the host sends back, so a Tock board can act as an input device or
+//! a security-token-style keystroke injector.
+//!
+//! Keyboard and mouse reports share the one interrupt IN endpoint
+//! `UsbHidReport` models, distinguished by a leading HID report ID
+//! byte (`report::KEYBOARD` / `report::MOUSE`) the way a combined HID
+//! report descriptor would; the report bytes an app builds (the boot
+//! keyboard's 8-byte modifier/reserved/6-keycode layout, or a 4-byte
+//! buttons/dx/dy/wheel mouse layout) are exchanged through the buffer
+//! allowed at index 0, same as `capsules::ctap_hid`'s
+//! message payloads. Only one process may hold the gadget at a time —
+//! `SEND` claims it for whichever process calls it first and until
+//! that process's report is sent, the same single-owner exclusivity
+//! `capsules::usb_mass_storage` gives a host over its block device.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let hid_gadget = static_init!(
+//!     capsules::usb_hid_gadget::UsbHidGadget<'static>,
+//!     capsules::usb_hid_gadget::UsbHidGadget::new(
+//!         hid, report_buffer, led_buffer,
+//!         kernel::Grant::create(capsules::driver::NUM::UsbHidGadget as usize)));
+//! hid.set_client(hid_gadget);
+//! ```
+
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::usb_hid::{UsbHidClient, UsbHidReport, HID_REPORT_LEN};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::UsbHidGadget as usize;
+
+mod report {
+    /// Leading report ID byte for a boot-protocol keyboard report
+    /// (modifier, reserved, up to 6 simultaneous keycodes).
+    pub const KEYBOARD: u8 = 1;
+    pub const KEYBOARD_LEN: usize = 1 + 8;
+    /// Leading report ID byte for a mouse report (buttons, dx, dy,
+    /// wheel).
+    pub const MOUSE: u8 = 2;
+    pub const MOUSE_LEN: usize = 1 + 4;
+    /// The single-byte LED output report a host sends back (bit 0
+    /// caps lock, bit 1 num lock, bit 2 scroll lock).
+    pub const LED_LEN: usize = 1;
+}
+
+mod upcall {
+    /// A report queued by `SEND_KEYBOARD_REPORT`/`SEND_MOUSE_REPORT`
+    /// has gone out.
+    pub const REPORT_SENT: usize = 0;
+    /// `data1` is the LED bitmask from a new output report.
+    pub const LED_REPORT: usize = 1;
+}
+
+mod cmd {
+    /// Sends the boot keyboard report built in the buffer allowed at
+    /// index 0, which must be `report::KEYBOARD_LEN - 1`
+    /// bytes (the report ID is added here, not by the app).
+    pub const SEND_KEYBOARD_REPORT: usize = 0;
+    /// Sends the mouse report built in the buffer allowed at index 0,
+    /// which must be `report::MOUSE_LEN - 1` bytes.
+    pub const SEND_MOUSE_REPORT: usize = 1;
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    report_buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct UsbHidGadget<'a> {
+    hid: &'a dyn UsbHidReport<'a>,
+    report_buffer: TakeCell<'static, [u8]>,
+    apps: Grant<App>,
+    sending_app: OptionalCell<AppId>,
+}
+
+impl<'a> UsbHidGadget<'a> {
+    pub fn new(
+        hid: &'a dyn UsbHidReport<'a>,
+        report_buffer: &'static mut [u8],
+        led_buffer: &'static mut [u8],
+        apps: Grant<App>,
+    ) -> UsbHidGadget<'a> {
+        let gadget = UsbHidGadget {
+            hid,
+            report_buffer: TakeCell::new(report_buffer),
+            apps,
+            sending_app: OptionalCell::empty(),
+        };
+        let _ = hid.receive_report(led_buffer);
+        gadget
+    }
+
+    fn send_report(&self, report_id: u8, payload_len: usize, app_id: AppId) -> ReturnCode {
+        if self.sending_app.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        let buffer = match self.report_buffer.take() {
+            Some(buffer) => buffer,
+            None => return ReturnCode::EBUSY,
+        };
+        let copied = self
+            .apps
+            .enter(app_id, |app, _| match &app.report_buffer {
+                Some(slice) if slice.len() >= payload_len => {
+                    buffer[0] = report_id;
+                    buffer[1..1 + payload_len].copy_from_slice(&slice.as_ref()[..payload_len]);
+                    for byte in &mut buffer[1 + payload_len..HID_REPORT_LEN] {
+                        *byte = 0;
+                    }
+                    true
+                }
+                _ => false,
+            })
+            .unwrap_or(false);
+        if !copied {
+            self.report_buffer.replace(buffer);
+            return ReturnCode::EINVAL;
+        }
+        self.sending_app.set(app_id);
+        self.hid.send_report(buffer)
+    }
+}
+
+impl<'a> UsbHidClient for UsbHidGadget<'a> {
+    fn report_sent(&self, report: &'static mut [u8], result: ReturnCode) {
+        self.report_buffer.replace(report);
+        if let Some(app_id) = self.sending_app.take() {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(upcall::REPORT_SENT, usize::from(result), 0);
+                }
+            });
+        }
+    }
+
+    fn report_received(&self, buffer: &'static mut [u8], result: ReturnCode) {
+        if result == ReturnCode::SUCCESS && buffer.len() >= report::LED_LEN {
+            let leds = buffer[0] as usize;
+            for app_id in self.apps.iter() {
+                let _ = self.apps.enter(app_id, |app, _| {
+                    if let Some(mut cb) = app.callback {
+                        cb.schedule(upcall::LED_REPORT, leds, 0);
+                    }
+                });
+            }
+        }
+        let _ = self.hid.receive_report(buffer);
+    }
+}
+
+impl<'a> Driver for UsbHidGadget<'a> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::REPORT_SENT | upcall::LED_REPORT => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.report_buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, _data1: usize, _data2: usize, app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::SEND_KEYBOARD_REPORT => self.send_report(report::KEYBOARD, report::KEYBOARD_LEN - 1, app_id),
+            cmd::SEND_MOUSE_REPORT => self.send_report(report::MOUSE, report::MOUSE_LEN - 1, app_id),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
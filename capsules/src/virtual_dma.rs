@@ -0,0 +1,64 @@
+//! Virtualizes a chip's fixed pool of `hil::dma::DmaChannel`s across
+//! multiple capsules that each want one only intermittently (console
+//! TX, SPI, ADC streaming).
+//!
+//! Rather than statically wiring one DMA channel to one peripheral at
+//! board-init time, capsules request a channel from the `DmaMuxVirtual`
+//! when they have a transfer to do and return it afterward, so a board
+//! with fewer channels than DMA-capable peripherals can still let any
+//! of them use DMA as long as they aren't all transferring at once.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let dma_mux = static_init!(
+//!     capsules::virtual_dma::DmaMuxVirtual<'static>,
+//!     capsules::virtual_dma::DmaMuxVirtual::new(&[channel0, channel1]));
+//! ```
+
+use core::cell::Cell;
+use kernel::hil::dma::DmaChannel;
+
+const MAX_CHANNELS: usize = 4;
+
+pub struct DmaMuxVirtual<'a> {
+    channels: [Option<&'a dyn DmaChannel<'a>>; MAX_CHANNELS],
+    held: [Cell<bool>; MAX_CHANNELS],
+}
+
+impl<'a> DmaMuxVirtual<'a> {
+    pub fn new(channels: &[&'a dyn DmaChannel<'a>]) -> DmaMuxVirtual<'a> {
+        let mut array: [Option<&'a dyn DmaChannel<'a>>; MAX_CHANNELS] = [None; MAX_CHANNELS];
+        for (i, &c) in channels.iter().enumerate().take(MAX_CHANNELS) {
+            array[i] = Some(c);
+        }
+        DmaMuxVirtual {
+            channels: array,
+            held: Default::default(),
+        }
+    }
+}
+
+impl<'a> kernel::hil::dma::DmaMux<'a> for DmaMuxVirtual<'a> {
+    fn allocate_channel(&self) -> Option<&'a dyn DmaChannel<'a>> {
+        for i in 0..MAX_CHANNELS {
+            if let Some(channel) = self.channels[i] {
+                if !self.held[i].get() {
+                    self.held[i].set(true);
+                    return Some(channel);
+                }
+            }
+        }
+        None
+    }
+
+    fn free_channel(&self, channel: &'a dyn DmaChannel<'a>) {
+        for i in 0..MAX_CHANNELS {
+            if let Some(c) = self.channels[i] {
+                if core::ptr::eq(c as *const _ as *const (), channel as *const _ as *const ()) {
+                    self.held[i].set(false);
+                }
+            }
+        }
+    }
+}
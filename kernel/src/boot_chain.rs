@@ -0,0 +1,24 @@
+//! Secure-boot wiring for the kernel image itself.
+//!
+//! `process_checker::AppCredentialsChecker` already lets a board verify
+//! each app's TBF signature footer before scheduling it (see
+//! `capsules::ecdsa::EcdsaP256Checker` for a concrete implementation);
+//! this module adds the other half of a verified boot chain, verifying
+//! the kernel's own image, for boards whose bootloader or mask ROM
+//! calls back into Tock before jumping to it.
+//!
+//! The two halves use different keys deliberately: the kernel image is
+//! checked against a key baked into the bootloader/ROM, since nothing
+//! in the kernel's own key store (`capsules::key_store`) is reachable
+//! this early in boot, before the kernel has even started running.
+
+/// Implemented by a board with a bootloader/ROM hook for verifying the
+/// kernel image before it is jumped to. Boards without such a hook (the
+/// common case — by the time Tock code is executing, its own image
+/// already ran) have no use for this trait.
+pub trait KernelImageVerifier {
+    /// `image` is the kernel's flash image excluding its trailing
+    /// signature; `signature` is checked against the bootloader/ROM's
+    /// provisioned key.
+    fn verify_kernel_image(&self, image: &[u8], signature: &[u8]) -> bool;
+}
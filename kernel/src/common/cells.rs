@@ -0,0 +1,102 @@
+//! `Cell`-like types that make sharing interior-mutable state between a
+//! capsule and its callbacks less error prone than raw `Cell<Option<T>>`
+//! and `Cell<Option<&'static mut T>>`.
+
+use core::cell::Cell;
+
+/// A `Cell<Option<T>>` with a more convenient API for the common case of
+/// an optional reference to a client.
+pub struct OptionalCell<T: Copy> {
+    value: Cell<Option<T>>,
+}
+
+impl<T: Copy> Default for OptionalCell<T> {
+    fn default() -> OptionalCell<T> {
+        OptionalCell::empty()
+    }
+}
+
+impl<T: Copy> OptionalCell<T> {
+    pub const fn empty() -> OptionalCell<T> {
+        OptionalCell {
+            value: Cell::new(None),
+        }
+    }
+
+    pub const fn new(value: T) -> OptionalCell<T> {
+        OptionalCell {
+            value: Cell::new(Some(value)),
+        }
+    }
+
+    pub fn set(&self, value: T) {
+        self.value.set(Some(value));
+    }
+
+    pub fn clear(&self) {
+        self.value.set(None);
+    }
+
+    pub fn is_some(&self) -> bool {
+        self.value.get().is_some()
+    }
+
+    /// Returns the current value and clears the cell, in one step.
+    pub fn take(&self) -> Option<T> {
+        self.value.take()
+    }
+
+    pub fn map<F, R>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(T) -> R,
+    {
+        self.value.get().map(f)
+    }
+}
+
+/// A `Cell<Option<&'static mut T>>` that can be `take`n and `replace`d,
+/// used to hand ownership of a `'static` buffer back and forth between a
+/// capsule and the peripheral/DMA engine it drives.
+pub struct TakeCell<'a, T: ?Sized> {
+    value: Cell<Option<&'a mut T>>,
+}
+
+impl<'a, T: ?Sized> TakeCell<'a, T> {
+    pub fn new(value: &'a mut T) -> TakeCell<'a, T> {
+        TakeCell {
+            value: Cell::new(Some(value)),
+        }
+    }
+
+    pub const fn empty() -> TakeCell<'a, T> {
+        TakeCell {
+            value: Cell::new(None),
+        }
+    }
+
+    pub fn take(&self) -> Option<&'a mut T> {
+        self.value.take()
+    }
+
+    pub fn replace(&self, val: &'a mut T) {
+        self.value.set(Some(val));
+    }
+
+    pub fn is_none(&self) -> bool {
+        let val = self.value.take();
+        let result = val.is_none();
+        self.value.set(val);
+        result
+    }
+
+    pub fn map<F, R>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        self.take().map(|mut val| {
+            let result = f(&mut val);
+            self.replace(val);
+            result
+        })
+    }
+}
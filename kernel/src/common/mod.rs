@@ -0,0 +1,5 @@
+//! Utility structures and data structures shared across the kernel and
+//! capsules.
+
+pub mod cells;
+pub mod net_buf;
@@ -0,0 +1,194 @@
+//! A pool of fixed-size, chainable network buffers, so that building a
+//! framed packet can prepend a header in reserved headroom instead of
+//! copying the payload into a new buffer at every layer, and so a link
+//! layer with no buffer free is not forced to block every other
+//! network activity on the board the way a single static per-driver
+//! buffer does.
+//!
+//! Each [`NetBuf`] is a fixed-capacity slot with headroom reserved at
+//! the front; [`NetBuf::prepend`] writes a header into that headroom
+//! and grows the buffer backwards, so the payload already in it is
+//! never moved. A [`NetBuf`] can also be chained to another via
+//! [`NetBuf::set_next`] when a packet does not fit in one buffer,
+//! again without copying either buffer's data.
+//!
+//! [`NetBufPool`] hands out and reclaims buffers from a free list built
+//! out of individually `static_init!`-allocated [`NetBuf`]s, the same
+//! way every other `'static` buffer in this tree is allocated; there is
+//! no heap here.
+//!
+//! This module only introduces the allocator; rebuilding
+//! `radio_154_driver`, `sixlowpan`, `tcp`, and `ethernet_driver` on top
+//! of chained [`NetBuf`]s instead of their current single static
+//! `TakeCell<'static, [u8]>` is follow-on work this makes possible, not
+//! done here, the same way `hil::ip::IpLayer` existed with no
+//! implementer in this tree until one was written for it.
+//!
+//! # Usage
+//!
+//! ```rust
+//! let pool = static_init!(kernel::common::net_buf::NetBufPool, kernel::common::net_buf::NetBufPool::new());
+//! for _ in 0..4 {
+//!     pool.add(static_init!(kernel::common::net_buf::NetBuf, kernel::common::net_buf::NetBuf::new()));
+//! }
+//! ```
+
+use crate::common::cells::TakeCell;
+
+/// Space reserved at the front of every buffer for headers later
+/// layers prepend (e.g. a UDP header and then an IPv6 header on top of
+/// an already-filled-in payload), sized to fit both at once.
+pub const HEADROOM: usize = 48;
+
+/// Payload capacity behind the headroom; holds one unfragmented
+/// 802.15.4 frame (at most 127 bytes). A full-size Ethernet frame does
+/// not fit in one buffer.
+pub const PAYLOAD_CAPACITY: usize = 128;
+
+const STORAGE_LEN: usize = HEADROOM + PAYLOAD_CAPACITY;
+
+/// One fixed-size, chainable network buffer. See the module
+/// documentation for the headroom/chaining model.
+pub struct NetBuf {
+    storage: [u8; STORAGE_LEN],
+    /// Offset of the current data's first byte into `storage`; shrinks
+    /// towards zero as headers are prepended.
+    start: usize,
+    len: usize,
+    /// The next buffer in this packet's chain, if the packet spans
+    /// more than one [`NetBuf`]; reused as the intrusive free-list link
+    /// while this buffer is sitting unused in a [`NetBufPool`].
+    next: Option<&'static mut NetBuf>,
+}
+
+impl NetBuf {
+    pub const fn new() -> NetBuf {
+        NetBuf {
+            storage: [0; STORAGE_LEN],
+            start: HEADROOM,
+            len: 0,
+            next: None,
+        }
+    }
+
+    /// Restores this buffer to its just-allocated state: empty, full
+    /// headroom, unchained.
+    fn reset(&mut self) {
+        self.start = HEADROOM;
+        self.len = 0;
+        self.next = None;
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.storage[self.start..self.start + self.len]
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.storage[self.start..self.start + self.len]
+    }
+
+    /// Bytes of headroom still available for `prepend`.
+    pub fn headroom(&self) -> usize {
+        self.start
+    }
+
+    /// Sets this buffer's data length, measured from the current start
+    /// of its data (not from the front of its headroom). `false` if
+    /// `len` would run past the end of the buffer's storage.
+    pub fn set_len(&mut self, len: usize) -> bool {
+        if self.start + len > self.storage.len() {
+            return false;
+        }
+        self.len = len;
+        true
+    }
+
+    /// Writes `header` into this buffer's reserved headroom, directly
+    /// in front of its current data, and grows the buffer to cover it;
+    /// the existing data is not moved. `false` (and no change made) if
+    /// `header` does not fit in the headroom left.
+    pub fn prepend(&mut self, header: &[u8]) -> bool {
+        if header.len() > self.start {
+            return false;
+        }
+        self.start -= header.len();
+        self.storage[self.start..self.start + header.len()].copy_from_slice(header);
+        self.len += header.len();
+        true
+    }
+
+    pub fn set_next(&mut self, next: &'static mut NetBuf) {
+        self.next = Some(next);
+    }
+
+    pub fn take_next(&mut self) -> Option<&'static mut NetBuf> {
+        self.next.take()
+    }
+
+    pub fn next(&self) -> Option<&NetBuf> {
+        self.next.as_deref()
+    }
+
+    /// Total data length across this buffer and everything chained
+    /// after it.
+    pub fn chain_len(&self) -> usize {
+        let mut total = self.len;
+        let mut current = self.next.as_deref();
+        while let Some(buf) = current {
+            total += buf.len;
+            current = buf.next.as_deref();
+        }
+        total
+    }
+}
+
+/// A free list of [`NetBuf`]s, seeded by a board with as many
+/// individually `static_init!`-allocated buffers as it expects to need
+/// in flight at once. See the module documentation.
+pub struct NetBufPool {
+    free_list: TakeCell<'static, NetBuf>,
+}
+
+impl NetBufPool {
+    pub const fn new() -> NetBufPool {
+        NetBufPool {
+            free_list: TakeCell::empty(),
+        }
+    }
+
+    /// Adds a freshly allocated (or just-freed) buffer to the pool's
+    /// free list.
+    pub fn add(&self, buf: &'static mut NetBuf) {
+        buf.reset();
+        if let Some(head) = self.free_list.take() {
+            buf.set_next(head);
+        }
+        self.free_list.replace(buf);
+    }
+
+    /// Takes one buffer off the free list, or `None` if the pool is
+    /// exhausted; the caller then builds its packet into it, chaining
+    /// on further buffers with `set_next`/further `allocate` calls if
+    /// it does not fit in one.
+    pub fn allocate(&self) -> Option<&'static mut NetBuf> {
+        self.free_list.take().map(|buf| {
+            if let Some(next) = buf.take_next() {
+                self.free_list.replace(next);
+            }
+            buf
+        })
+    }
+
+    /// Returns `buf` and every buffer chained after it to the pool.
+    pub fn free(&self, buf: &'static mut NetBuf) {
+        let mut current = buf;
+        loop {
+            let next = current.take_next();
+            self.add(current);
+            current = match next {
+                Some(next) => next,
+                None => break,
+            };
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Deferred calls let a capsule schedule a callback into itself to run
+//! from the main kernel loop rather than from interrupt context,
+//! giving it a "bottom half" without needing its own alarm or
+//! interrupt source.
+//!
+//! The previous implementation kept a single fixed-size static array
+//! of deferred call slots shared by every capsule in the kernel crate,
+//! which meant capsules fought over a handful of entries as more of
+//! them (console mux, crypto, storage) needed a bottom half. Each
+//! capsule now owns a `DeferredCall` handle it registers for itself at
+//! board-init time; the registry grows with however many handles boards
+//! actually construct instead of a fixed compile-time table shared by
+//! all of them.
+
+use core::cell::Cell;
+
+/// Implemented by a capsule that wants to be invoked from the main
+/// kernel loop after calling `DeferredCall::set`.
+pub trait DeferredCallClient {
+    fn handle_deferred_call(&self);
+}
+
+/// A single capsule's deferred-call registration. `DeferredCall`s are
+/// chained into an intrusive singly-linked list rooted at a
+/// kernel-global head, so there is no fixed capacity: however many
+/// capsules call `DeferredCall::new` is however many can be pending at
+/// once.
+pub struct DeferredCall<'a> {
+    client: Cell<Option<&'a dyn DeferredCallClient>>,
+    pending: Cell<bool>,
+    next: Cell<Option<&'a DeferredCall<'a>>>,
+}
+
+impl<'a> DeferredCall<'a> {
+    pub const fn new() -> DeferredCall<'a> {
+        DeferredCall {
+            client: Cell::new(None),
+            pending: Cell::new(false),
+            next: Cell::new(None),
+        }
+    }
+
+    /// Bind this handle to the capsule that should be called back, and
+    /// link it into the global registry. Called once at board-init
+    /// time, typically right after `static_init!`.
+    pub fn register(&'a self, client: &'a dyn DeferredCallClient, registry: &DeferredCallRegistry<'a>) {
+        self.client.set(Some(client));
+        registry.push(self);
+    }
+
+    /// Mark this capsule as having deferred work to do; the main loop
+    /// will call `handle_deferred_call` on it before next sleeping.
+    pub fn set(&self) {
+        self.pending.set(true);
+    }
+
+    fn fire_if_pending(&self) {
+        if self.pending.take() {
+            if let Some(client) = self.client.get() {
+                client.handle_deferred_call();
+            }
+        }
+    }
+}
+
+/// Kernel-held root of the intrusive list of registered
+/// `DeferredCall`s, walked once per main-loop iteration.
+pub struct DeferredCallRegistry<'a> {
+    head: Cell<Option<&'a DeferredCall<'a>>>,
+}
+
+impl<'a> DeferredCallRegistry<'a> {
+    pub const fn new() -> DeferredCallRegistry<'a> {
+        DeferredCallRegistry {
+            head: Cell::new(None),
+        }
+    }
+
+    fn push(&self, call: &'a DeferredCall<'a>) {
+        call.next.set(self.head.get());
+        self.head.set(Some(call));
+    }
+
+    /// Run every deferred call that has pending work, most recently
+    /// registered first. Called once per main-loop iteration before the kernel
+    /// considers sleeping.
+    pub fn service_all(&self) {
+        let mut cur = self.head.get();
+        while let Some(call) = cur {
+            call.fire_if_pending();
+            cur = call.next.get();
+        }
+    }
+}
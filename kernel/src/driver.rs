@@ -0,0 +1,44 @@
+//! Traits for implementing the core kernel/userland system call interface.
+
+use crate::callback::{AppId, Callback};
+use crate::mem::{AppSlice, Shared};
+use crate::returncode::ReturnCode;
+
+/// `Driver`s implement the kernel-userland system call interface for a
+/// particular capability. A `Driver` is assigned one of the driver
+/// numbers in `capsules::driver::NUM` and is responsible for
+/// interpreting `command`, `subscribe`, and `allow` calls directed at
+/// that number.
+#[allow(unused_variables)]
+pub trait Driver {
+    /// `subscribe` lets an application pass a callback to the capsule to
+    /// be called later, when the asynchronous operation the capsule
+    /// performs completes.
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        app_id: AppId,
+    ) -> ReturnCode {
+        ReturnCode::ENOSUPPORT
+    }
+
+    /// `command` instructs a capsule to perform some action synchronously,
+    /// or to begin an asynchronous operation whose completion is later
+    /// reported via `subscribe`.
+    fn command(&self, command_num: usize, data1: usize, data2: usize, app_id: AppId) -> ReturnCode {
+        ReturnCode::ENOSUPPORT
+    }
+
+    /// `allow` lets an application share a slice of memory with a
+    /// capsule, for example a buffer the capsule should fill with
+    /// sensor data.
+    fn allow(
+        &self,
+        app: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        ReturnCode::ENOSUPPORT
+    }
+}
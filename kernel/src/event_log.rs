@@ -0,0 +1,54 @@
+//! In-kernel ring buffer of significant kernel events, for
+//! post-incident analysis that doesn't depend on someone having
+//! watched the live console output when the event happened.
+
+use crate::callback::AppId;
+
+#[derive(Copy, Clone, Debug)]
+pub enum KernelEvent {
+    ProcessStarted(AppId),
+    ProcessFaulted(AppId),
+    ProcessRestarted(AppId),
+    DriverError { driver_num: usize, code: isize },
+    WatchdogFed,
+    EntropyHealthTestFailed,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct LoggedEvent {
+    pub timestamp: u32,
+    pub event: KernelEvent,
+}
+
+const LOG_CAPACITY: usize = 64;
+
+pub struct EventLog {
+    entries: [Option<LoggedEvent>; LOG_CAPACITY],
+    head: usize,
+}
+
+impl EventLog {
+    pub const fn new() -> EventLog {
+        EventLog {
+            entries: [None; LOG_CAPACITY],
+            head: 0,
+        }
+    }
+
+    pub fn record(&mut self, timestamp: u32, event: KernelEvent) {
+        self.entries[self.head] = Some(LoggedEvent { timestamp, event });
+        self.head = (self.head + 1) % LOG_CAPACITY;
+    }
+
+    /// Drain the log, oldest first, into `f`. Used both by a
+    /// ProcessConsole command and by the privileged syscall driver
+    /// that lets a host tool retrieve it.
+    pub fn drain(&mut self, mut f: impl FnMut(LoggedEvent)) {
+        for i in 0..LOG_CAPACITY {
+            let idx = (self.head + i) % LOG_CAPACITY;
+            if let Some(entry) = self.entries[idx].take() {
+                f(entry);
+            }
+        }
+    }
+}
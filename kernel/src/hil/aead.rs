@@ -0,0 +1,39 @@
+//! Hardware interface layer (HIL) for authenticated encryption with
+//! associated data (AES-GCM, AES-CCM), implemented either by a
+//! hardware accelerator (CryptoCell, CRYP) or a software fallback —
+//! both are just `AeadEngine`s as far as a capsule built on this HIL
+//! is concerned.
+
+use crate::returncode::ReturnCode;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AeadMode {
+    Gcm,
+    Ccm,
+}
+
+pub trait AeadEngine<'a> {
+    fn set_client(&self, client: &'a dyn AeadClient);
+
+    /// Loads the key used by the next `encrypt`/`decrypt`; held by the
+    /// engine until replaced, not per-call.
+    fn set_key(&self, key: &[u8]) -> ReturnCode;
+
+    /// Encrypts `buffer[aad_len..aad_len + plaintext_len]` in place
+    /// and appends a 16-byte authentication tag immediately after it;
+    /// `buffer[..aad_len]` is authenticated but not encrypted.
+    /// Completion is reported via `AeadClient::crypt_done`.
+    fn encrypt(&self, mode: AeadMode, buffer: &'static mut [u8], aad_len: usize, plaintext_len: usize, nonce: &[u8]) -> ReturnCode;
+
+    /// Decrypts `buffer[aad_len..aad_len + ciphertext_len]` in place
+    /// and checks the 16-byte tag immediately after it against
+    /// `buffer[..aad_len]`; `AeadClient::crypt_done`'s `tag_valid`
+    /// reports whether it matched;`buffer` is left decrypted either
+    /// way; a caller must discard the plaintext unless `tag_valid` is
+    /// `true`.
+    fn decrypt(&self, mode: AeadMode, buffer: &'static mut [u8], aad_len: usize, ciphertext_len: usize, nonce: &[u8]) -> ReturnCode;
+}
+
+pub trait AeadClient {
+    fn crypt_done(&self, buffer: &'static mut [u8], result: ReturnCode, tag_valid: bool);
+}
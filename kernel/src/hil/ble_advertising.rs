@@ -0,0 +1,46 @@
+//! Hardware interface layer (HIL) for transmitting a single BLE
+//! advertisement on one of the three primary advertising channels.
+//!
+//! Scanning, connections, and GATT are out of scope for this trait;
+//! it covers exactly what a beacon-style advertiser needs, leaving the
+//! round-robin scheduling of several apps' advertisers onto the one
+//! radio to `capsules::ble_advertising_driver`.
+
+use crate::returncode::ReturnCode;
+
+/// The three channels a legal BLE advertisement is sent on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RadioChannel {
+    Channel37,
+    Channel38,
+    Channel39,
+}
+
+impl RadioChannel {
+    /// The channel sent after this one in a single advertising event,
+    /// or `None` once all three have been sent.
+    pub fn next(self) -> Option<RadioChannel> {
+        match self {
+            RadioChannel::Channel37 => Some(RadioChannel::Channel38),
+            RadioChannel::Channel38 => Some(RadioChannel::Channel39),
+            RadioChannel::Channel39 => None,
+        }
+    }
+}
+
+pub trait BleAdvertisementDriver<'a> {
+    fn set_client(&self, client: &'a dyn TxClient);
+
+    /// Sets the transmit power, in dBm, used by the next
+    /// `transmit_advertisement` call.
+    fn set_tx_power(&self, power: i8) -> ReturnCode;
+
+    /// Transmits `buffer[..len]` (a complete `ADV_NONCONN_IND` PDU, not
+    /// modeled here) on `channel`. Completion is reported via
+    /// `TxClient::transmit_event`.
+    fn transmit_advertisement(&self, buffer: &'static mut [u8], len: usize, channel: RadioChannel) -> ReturnCode;
+}
+
+pub trait TxClient {
+    fn transmit_event(&self, buffer: &'static mut [u8], result: ReturnCode);
+}
@@ -0,0 +1,21 @@
+//! Hardware interface layer (HIL) for initiating a BLE connection in
+//! the central role. Once `CentralClient::connection_complete` reports
+//! success, ATT exchange over that connection happens through
+//! `hil::ble_connection::BleConnection`/`ConnectionClient`, the same
+//! role-agnostic pair a peripheral's GATT server uses.
+
+use crate::hil::ble_scanning::BleAddress;
+use crate::returncode::ReturnCode;
+
+pub trait BleCentral<'a> {
+    fn set_client(&self, client: &'a dyn CentralClient);
+
+    /// Initiates a connection to `address`. Only one connection
+    /// attempt, and one resulting connection, is supported at a time.
+    fn connect(&self, address: BleAddress) -> ReturnCode;
+    fn disconnect(&self) -> ReturnCode;
+}
+
+pub trait CentralClient {
+    fn connection_complete(&self, result: ReturnCode);
+}
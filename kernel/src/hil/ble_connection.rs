@@ -0,0 +1,26 @@
+//! Hardware interface layer (HIL) for a single BLE link-layer
+//! connection in the peripheral role: connection lifecycle plus raw
+//! ATT PDU exchange. Advertising (`hil::ble_advertising`) gets a board
+//! into this state; everything above ATT (GATT services,
+//! characteristics, descriptors) is `capsules::gatt_server`'s job, not
+//! this trait's.
+
+use crate::returncode::ReturnCode;
+
+pub trait BleConnection<'a> {
+    fn set_client(&self, client: &'a dyn ConnectionClient);
+
+    /// Sends `buffer[..len]`, a complete ATT PDU, to the connected
+    /// central. Completion is reported via `ConnectionClient::att_pdu_sent`.
+    fn send_att_pdu(&self, buffer: &'static mut [u8], len: usize) -> ReturnCode;
+}
+
+pub trait ConnectionClient {
+    fn connected(&self);
+    fn disconnected(&self);
+
+    /// `buffer[..len]` is one ATT PDU from the central; ownership
+    /// stays with the connection implementation.
+    fn att_pdu_received(&self, buffer: &[u8], len: usize);
+    fn att_pdu_sent(&self, buffer: &'static mut [u8], result: ReturnCode);
+}
@@ -0,0 +1,25 @@
+//! Hardware interface layer (HIL) for BLE scanning: listening for
+//! advertisements without, itself, doing anything about connecting to
+//! one (that is `hil::ble_central::BleCentral`'s job).
+
+use crate::returncode::ReturnCode;
+
+/// A BLE device address, as broadcast on an advertising PDU.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BleAddress(pub [u8; 6]);
+
+pub trait BleScanner<'a> {
+    fn set_client(&self, client: &'a dyn ScanClient);
+
+    fn start_scanning(&self) -> ReturnCode;
+    fn stop_scanning(&self) -> ReturnCode;
+}
+
+pub trait ScanClient {
+    /// Called once per advertisement heard while scanning.
+    /// `payload[..payload_len]` is the advertisement's data (the
+    /// flags, service UUIDs, and local name fields a real
+    /// implementation would carry); parsing those is left to whatever
+    /// implements this trait.
+    fn advertising_report(&self, address: BleAddress, rssi: i8, payload: &[u8], payload_len: usize);
+}
@@ -0,0 +1,26 @@
+//! Hardware interface layer (HIL) for fixed-size-block storage devices
+//! (SD/MMC cards, block-addressed external flash, ...), as distinct
+//! from the byte-addressable `hil::nonvolatile_storage` interface.
+
+use crate::returncode::ReturnCode;
+
+pub const BLOCK_SIZE: usize = 512;
+
+pub trait BlockStorage<'a> {
+    fn set_client(&self, client: &'a dyn BlockStorageClient);
+
+    /// Total capacity of the device, in `BLOCK_SIZE`-byte blocks.
+    fn block_count(&self) -> u64;
+
+    /// Reads `num_blocks` consecutive `BLOCK_SIZE`-byte blocks starting
+    /// at `start_block` into `buffer`. Completion is reported via
+    /// `BlockStorageClient::read_done`.
+    fn read_blocks(&self, buffer: &'static mut [u8], start_block: u64, num_blocks: usize) -> ReturnCode;
+
+    fn write_blocks(&self, buffer: &'static mut [u8], start_block: u64, num_blocks: usize) -> ReturnCode;
+}
+
+pub trait BlockStorageClient {
+    fn read_done(&self, buffer: &'static mut [u8], num_blocks: usize, result: ReturnCode);
+    fn write_done(&self, buffer: &'static mut [u8], num_blocks: usize, result: ReturnCode);
+}
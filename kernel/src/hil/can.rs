@@ -0,0 +1,166 @@
+//! Hardware interface layer (HIL) for a CAN (Controller Area Network)
+//! controller: bitrate configuration, classic and CAN-FD frame
+//! transmit/receive, a bank of hardware acceptance filters, and bus
+//! error reporting.
+//!
+//! A frame crosses this trait the same way every other HIL in this tree
+//! moves data: as a `&'static mut [u8]` buffer, laid out as
+//! [`ID_OFFSET`] (4 bytes, native-endian `u32`, with [`EXTENDED_ID_FLAG`]
+//! set in the top bit for a 29-bit extended identifier rather than an
+//! 11-bit standard one), [`FLAGS_OFFSET`] (1 byte, see its constants),
+//! [`DLC_OFFSET`] (1 byte, the raw CAN-FD DLC code; see
+//! [`dlc_to_data_len`]), then up to [`MAX_FD_DATA_LEN`] data bytes;
+//! `len` covers the header plus however many data bytes the frame
+//! actually carries.
+//!
+//! A classic frame (`FD_FRAME` clear) carries at most
+//! [`MAX_CLASSIC_DATA_LEN`] bytes and its DLC code is just that length.
+//! An FD frame (`FD_FRAME` set) can carry up to [`MAX_FD_DATA_LEN`] and
+//! uses CAN-FD's non-linear DLC encoding above 8 bytes; `BIT_RATE_SWITCH`
+//! additionally asks the controller to run the data phase at the
+//! faster bitrate configured with [`Can::set_fd_bitrate`], which is
+//! only meaningful alongside `FD_FRAME`.
+//!
+//! Most CAN controllers can only hold a handful of hardware acceptance
+//! filters; [`Can::set_filters`] programs that bank directly and
+//! returns `ESIZE` if asked for more than it has room for, leaving
+//! filtering past that limit to whoever is above this trait (see
+//! `capsules::can_driver`, which does exactly that in software).
+
+use crate::returncode::ReturnCode;
+
+/// Set in the top bit of the 4-byte identifier field to mark a 29-bit
+/// extended identifier rather than an 11-bit standard one.
+pub const EXTENDED_ID_FLAG: u32 = 1 << 31;
+pub const ID_OFFSET: usize = 0;
+
+/// Bit in [`FLAGS_OFFSET`] marking this as a CAN-FD frame rather than a
+/// classic one.
+pub const FD_FRAME: u8 = 1 << 0;
+/// Bit in [`FLAGS_OFFSET`] asking the controller to switch to the
+/// faster data-phase bitrate for this frame's payload; only meaningful
+/// with `FD_FRAME` also set.
+pub const BIT_RATE_SWITCH: u8 = 1 << 1;
+pub const FLAGS_OFFSET: usize = 4;
+pub const DLC_OFFSET: usize = 5;
+pub const DATA_OFFSET: usize = 6;
+
+pub const MAX_CLASSIC_DATA_LEN: usize = 8;
+pub const MAX_FD_DATA_LEN: usize = 64;
+pub const MAX_FRAME_LEN: usize = DATA_OFFSET + MAX_FD_DATA_LEN;
+
+/// Converts a CAN-FD DLC code (0 to 15) to the data length it encodes.
+/// Codes 0 through 8 are their own length; above that, CAN-FD switches
+/// to a fixed, non-linear sequence (12, 16, 20, 24, 32, 48, 64) so a
+/// 4-bit field can still express up to 64 bytes.
+pub fn dlc_to_data_len(dlc: u8) -> usize {
+    match dlc {
+        0..=8 => dlc as usize,
+        9 => 12,
+        10 => 16,
+        11 => 20,
+        12 => 24,
+        13 => 32,
+        14 => 48,
+        _ => 64,
+    }
+}
+
+/// Converts a data length to the smallest CAN-FD DLC code that can
+/// carry it, rounding up; `len` greater than [`MAX_FD_DATA_LEN`]
+/// saturates at the code for 64.
+pub fn data_len_to_dlc(len: usize) -> u8 {
+    match len {
+        0..=8 => len as u8,
+        9..=12 => 9,
+        13..=16 => 10,
+        17..=20 => 11,
+        21..=24 => 12,
+        25..=32 => 13,
+        33..=48 => 14,
+        _ => 15,
+    }
+}
+
+/// One hardware acceptance filter: a frame is accepted if
+/// `frame_id & mask == id & mask` and the frame's extended-ID-ness
+/// matches `id`'s.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AcceptanceFilter {
+    pub id: u32,
+    pub mask: u32,
+}
+
+impl AcceptanceFilter {
+    /// Matches every frame regardless of identifier, including the
+    /// standard/extended distinction carried in `EXTENDED_ID_FLAG`.
+    pub fn accept_all() -> AcceptanceFilter {
+        AcceptanceFilter { id: 0, mask: 0 }
+    }
+
+    pub fn matches(&self, frame_id: u32) -> bool {
+        (frame_id & (self.mask | EXTENDED_ID_FLAG)) == (self.id & (self.mask | EXTENDED_ID_FLAG))
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BusError {
+    /// A transmitted bit did not read back as what was driven.
+    BitError,
+    /// A received frame violated CAN's bit-stuffing rule.
+    StuffError,
+    /// A received frame had a malformed fixed-format field.
+    FormError,
+    /// A transmitted frame went unacknowledged by any other node.
+    AckError,
+    /// A received frame's CRC did not match its data.
+    CrcError,
+    /// The controller's error counters crossed into the bus-off state;
+    /// it has stopped participating on the bus and `start` must be
+    /// called again to rejoin.
+    BusOff,
+}
+
+pub trait Can<'a> {
+    fn set_client(&self, client: &'a dyn CanClient);
+
+    /// Configures the nominal bitrate, in bits per second. `ENOSUPPORT`
+    /// if the controller's clock cannot produce this exact rate.
+    fn set_bitrate(&self, bitrate: u32) -> ReturnCode;
+
+    /// Configures the faster data-phase bitrate used by a CAN-FD frame
+    /// sent with `BIT_RATE_SWITCH` set. `ENOSUPPORT` on a controller
+    /// with no CAN-FD support at all.
+    fn set_fd_bitrate(&self, bitrate: u32) -> ReturnCode;
+
+    /// Replaces this controller's hardware acceptance filter bank with
+    /// `filters`; an empty slice accepts every frame, since that is
+    /// every controller's un-filtered reset state. `ESIZE` if `filters`
+    /// is longer than the hardware supports, leaving the bank
+    /// unchanged.
+    fn set_filters(&self, filters: &[AcceptanceFilter]) -> ReturnCode;
+
+    /// Transmits the frame in `buffer[..len]` (see the module
+    /// documentation for its layout). Completion is reported via
+    /// `CanClient::transmit_done`.
+    fn transmit(&self, buffer: &'static mut [u8], len: usize) -> ReturnCode;
+
+    /// Joins the bus; every frame that passes the hardware filter bank
+    /// is reported via `CanClient::receive` until `stop` is called or
+    /// the controller goes bus-off.
+    fn start(&self) -> ReturnCode;
+
+    fn stop(&self) -> ReturnCode;
+}
+
+pub trait CanClient {
+    fn transmit_done(&self, buffer: &'static mut [u8], result: ReturnCode);
+
+    /// `buffer[..len]` is a received frame in the layout documented on
+    /// the module; as with `hil::radio::RxClient::receive`, the
+    /// controller keeps its own receive buffer and this is only a
+    /// borrow of it for the duration of the call.
+    fn receive(&self, buffer: &[u8], len: usize);
+
+    fn bus_error(&self, error: BusError);
+}
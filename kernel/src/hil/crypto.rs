@@ -0,0 +1,21 @@
+//! A capability vocabulary for the per-algorithm crypto HILs
+//! (`hil::digest`, `hil::aead`, `hil::ecdsa`, `hil::curve25519`), used
+//! by `capsules::crypto_registry::CryptoRegistry` so a capsule can ask
+//! "is there an AES-GCM engine" instead of hard-coding which
+//! accelerator (or software fallback) a board happened to wire up.
+//!
+//! This module only names the capabilities; the engines themselves
+//! still speak their own HIL trait (`AeadEngine`, `DigestEngine`, and
+//! so on) exactly as before, since those traits already carry the
+//! operations each algorithm needs and a capsule using one still
+//! calls it directly once the registry has handed it over.
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Capability {
+    Sha256,
+    Sha512,
+    AesGcm,
+    AesCcm,
+    EcdsaP256,
+    Curve25519,
+}
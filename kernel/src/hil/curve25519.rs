@@ -0,0 +1,41 @@
+//! Hardware interface layer (HIL) for the Curve25519 primitives:
+//! Ed25519 signatures and X25519 Diffie-Hellman, implemented either by
+//! a hardware accelerator or a software fallback — both are just
+//! `Curve25519Engine`s as far as a capsule built on this HIL is
+//! concerned.
+//!
+//! Boards without NIST-curve hardware use this instead of
+//! `hil::ecdsa` for protocols built around Curve25519 (Noise, signed
+//! OTA manifests using Ed25519).
+
+use crate::returncode::ReturnCode;
+
+/// Length in bytes of an Ed25519/X25519 private key, public key, and
+/// X25519 shared secret.
+pub const CURVE25519_KEY_LEN: usize = 32;
+/// Length in bytes of an Ed25519 signature.
+pub const ED25519_SIGNATURE_LEN: usize = 64;
+
+pub trait Curve25519Engine<'a> {
+    fn set_client(&self, client: &'a dyn Curve25519Client);
+
+    /// Signs `message` with `private_key`, writing the signature into
+    /// `signature_buffer`. Completion is reported via
+    /// `Curve25519Client::sign_done`.
+    fn sign(&self, private_key: &[u8], message: &[u8], signature_buffer: &'static mut [u8]) -> ReturnCode;
+
+    /// Verifies `signature` over `message` against `public_key`.
+    /// Completion is reported via `Curve25519Client::verify_done`.
+    fn verify(&self, public_key: &[u8], message: &[u8], signature: &[u8]) -> ReturnCode;
+
+    /// Computes the X25519 shared secret between `private_key` and
+    /// `peer_public_key`, writing it into `secret_buffer`. Completion
+    /// is reported via `Curve25519Client::dh_done`.
+    fn dh(&self, private_key: &[u8], peer_public_key: &[u8], secret_buffer: &'static mut [u8]) -> ReturnCode;
+}
+
+pub trait Curve25519Client {
+    fn sign_done(&self, signature_buffer: &'static mut [u8], result: ReturnCode);
+    fn verify_done(&self, result: ReturnCode, valid: bool);
+    fn dh_done(&self, secret_buffer: &'static mut [u8], result: ReturnCode);
+}
@@ -0,0 +1,10 @@
+//! Hardware interface layer (HIL) for an architecture's free-running
+//! cycle counter (DWT CYCCNT on Cortex-M, `mcycle` on RISC-V) and any
+//! additional fixed-function event counters it exposes.
+
+pub trait CycleCounter {
+    fn start(&self);
+    fn stop(&self);
+    fn count(&self) -> u64;
+    fn reset(&self);
+}
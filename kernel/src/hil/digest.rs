@@ -0,0 +1,50 @@
+//! Hardware interface layer (HIL) for a streaming hash engine
+//! (SHA-256, SHA-512), used both for firmware/image verification in
+//! the kernel and for syscall-exposed hashing in userspace.
+//!
+//! A digest is computed the usual streaming way: one `init`, any
+//! number of `update`s, and one `finalize`. A single engine only ever
+//! has one digest in progress; a capsule that needs to share a
+//! hardware engine across several independent streaming clients does
+//! so through `virtual_digest::MuxDigest` rather than by calling these
+//! methods directly from more than one place.
+
+use crate::returncode::ReturnCode;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DigestAlgorithm {
+    Sha256,
+    Sha512,
+}
+
+impl DigestAlgorithm {
+    pub fn output_len(&self) -> usize {
+        match self {
+            DigestAlgorithm::Sha256 => 32,
+            DigestAlgorithm::Sha512 => 64,
+        }
+    }
+}
+
+pub trait DigestEngine<'a> {
+    fn set_client(&self, client: &'a dyn DigestClient);
+
+    /// Starts a new digest, discarding any state left over from a
+    /// previous one that was never finalized.
+    fn init(&self, algorithm: DigestAlgorithm) -> ReturnCode;
+
+    /// Feeds `data[..len]` into the digest in progress. Completion is
+    /// reported via `DigestClient::update_done`.
+    fn update(&self, data: &'static mut [u8], len: usize) -> ReturnCode;
+
+    /// Writes the final digest into `digest_buffer` (which must be at
+    /// least `algorithm.output_len()` bytes) and resets the engine so
+    /// a new `init` can start immediately. Completion is reported via
+    /// `DigestClient::finalize_done`.
+    fn finalize(&self, digest_buffer: &'static mut [u8]) -> ReturnCode;
+}
+
+pub trait DigestClient {
+    fn update_done(&self, data: &'static mut [u8], result: ReturnCode);
+    fn finalize_done(&self, digest_buffer: &'static mut [u8], result: ReturnCode);
+}
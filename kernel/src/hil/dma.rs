@@ -0,0 +1,39 @@
+//! Hardware interface layer (HIL) for generic DMA channels.
+//!
+//! Capsules that want DMA (console, SPI, ADC streaming) have
+//! historically gone through chip-specific code paths since there was
+//! no portable channel abstraction. `DmaChannel` covers the common
+//! memory<->peripheral transfer shape; a `DmaMux` hands channels out to
+//! whichever capsule is using them at a given moment, since most chips
+//! have far fewer DMA channels than peripherals that want one.
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TransferDirection {
+    MemoryToPeripheral,
+    PeripheralToMemory,
+}
+
+pub trait DmaChannel<'a> {
+    fn set_client(&self, client: &'a dyn DmaClient);
+
+    fn configure(&self, peripheral_id: usize, direction: TransferDirection);
+
+    fn start_transfer(&self, buffer: &'static mut [u8], len: usize);
+
+    fn stop(&self) -> Option<&'static mut [u8]>;
+}
+
+pub trait DmaClient {
+    fn transfer_done(&self, buffer: &'static mut [u8], len: usize);
+}
+
+/// Hands out the chip's fixed pool of DMA channels to capsules on
+/// request, since there are usually far fewer channels than
+/// peripherals that could use one.
+pub trait DmaMux<'a> {
+    /// Reserve a channel for exclusive use, or `None` if every channel
+    /// is currently held by another capsule.
+    fn allocate_channel(&self) -> Option<&'a dyn DmaChannel<'a>>;
+
+    fn free_channel(&self, channel: &'a dyn DmaChannel<'a>);
+}
@@ -0,0 +1,39 @@
+//! Hardware interface layer (HIL) for ECDSA over the NIST P-256 curve,
+//! implemented either by a hardware accelerator (CryptoCell, on-die
+//! public-key engine) or a software fallback — both are just
+//! `EcdsaP256Engine`s as far as a capsule built on this HIL is
+//! concerned. Used both by the kernel's own app-credential checker and
+//! by a syscall driver for apps that verify server signatures.
+//!
+//! Signing and verification both operate on a 32-byte digest of the
+//! message (computed separately, e.g. with `hil::digest`), not the
+//! message itself.
+
+use crate::returncode::ReturnCode;
+
+/// Length in bytes of a P-256 private key, and of each of the `x`/`y`
+/// coordinates of a public key.
+pub const P256_KEY_LEN: usize = 32;
+/// Length in bytes of a P-256 `r || s` signature.
+pub const P256_SIGNATURE_LEN: usize = 64;
+/// Length in bytes of the message digest ECDSA signs over.
+pub const P256_HASH_LEN: usize = 32;
+
+pub trait EcdsaP256Engine<'a> {
+    fn set_client(&self, client: &'a dyn EcdsaP256Client);
+
+    /// Signs `hash` with `private_key`, writing the `r || s` signature
+    /// into `signature_buffer`. Completion is reported via
+    /// `EcdsaP256Client::sign_done`.
+    fn sign(&self, private_key: &[u8], hash: &[u8], signature_buffer: &'static mut [u8]) -> ReturnCode;
+
+    /// Verifies `signature` over `hash` against `public_key`
+    /// (concatenated `x || y` coordinates). Completion is reported via
+    /// `EcdsaP256Client::verify_done`.
+    fn verify(&self, public_key: &[u8], hash: &[u8], signature: &[u8]) -> ReturnCode;
+}
+
+pub trait EcdsaP256Client {
+    fn sign_done(&self, signature_buffer: &'static mut [u8], result: ReturnCode);
+    fn verify_done(&self, result: ReturnCode, valid: bool);
+}
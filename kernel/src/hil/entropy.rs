@@ -0,0 +1,24 @@
+//! Hardware interface layer (HIL) for a hardware entropy source (a
+//! true random number generator), used both to directly seed a
+//! software CSPRNG and, for boards that expose it to apps at all, the
+//! RNG syscall driver.
+//!
+//! Raw TRNG output is typically much slower to produce than software
+//! generation, so most consumers go through `capsules::csprng::Csprng`
+//! rather than requesting words directly from here.
+
+use crate::returncode::ReturnCode;
+
+pub trait Entropy32<'a> {
+    fn set_client(&self, client: &'a dyn Entropy32Client);
+
+    /// Requests `count` 32-bit words of entropy. The words themselves
+    /// are delivered through a board-specific buffer, not modeled by
+    /// this HIL; completion (and the count actually produced) is
+    /// reported via `Entropy32Client::entropy_available`.
+    fn get(&self, count: usize) -> ReturnCode;
+}
+
+pub trait Entropy32Client {
+    fn entropy_available(&self, count: usize, result: ReturnCode);
+}
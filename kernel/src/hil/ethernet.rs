@@ -0,0 +1,48 @@
+//! Hardware interface layer (HIL) for an Ethernet MAC/PHY.
+//!
+//! This sits at the same level `hil::radio::Radio` does for 802.15.4:
+//! a raw-frame send/receive pair plus the minimum configuration and
+//! status every link needs (the station's MAC address and whether a
+//! link partner is even present), not a TCP/IP stack. Parsing a
+//! received frame's EtherType and handing IPv4/IPv6/ARP payloads
+//! onward is a capsule's job, not this trait's.
+
+use crate::returncode::ReturnCode;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MacAddress(pub [u8; 6]);
+
+pub trait Ethernet<'a> {
+    fn set_client(&self, client: &'a dyn EthernetClient);
+
+    /// Resets and brings up the MAC/PHY, including, for a part with
+    /// no MAC address of its own, programming the one the board
+    /// configured this instance with. Completion is reported via
+    /// `EthernetClient::init_done`.
+    fn init(&self) -> ReturnCode;
+
+    fn mac_address(&self) -> MacAddress;
+
+    /// True once the link has come up (autonegotiation completed
+    /// against a partner); undefined before `init_done` fires.
+    fn link_up(&self) -> bool;
+
+    /// Transmits `buffer[..len]` as a single Ethernet frame, header
+    /// and payload included; the trailing FCS is appended by hardware
+    /// and is not part of `len`. Completion is reported via
+    /// `EthernetClient::transmit_done`.
+    fn transmit(&self, buffer: &'static mut [u8], len: usize) -> ReturnCode;
+
+    /// Arms reception; every frame after this is reported via
+    /// `EthernetClient::receive` until `init` is called again.
+    fn start_receiving(&self) -> ReturnCode;
+}
+
+pub trait EthernetClient {
+    fn init_done(&self, result: ReturnCode);
+    fn transmit_done(&self, buffer: &'static mut [u8], result: ReturnCode);
+    fn receive(&self, buffer: &[u8], len: usize);
+    /// The link transitioned up or down some time after `init_done`
+    /// already fired (e.g. a cable was unplugged and replugged).
+    fn link_state_changed(&self, link_up: bool);
+}
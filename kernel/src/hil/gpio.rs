@@ -0,0 +1,22 @@
+//! Hardware interface layer (HIL) for General Purpose Input/Output pins.
+
+/// A GPIO pin configured to generate interrupts.
+pub trait InterruptPin<'a> {
+    fn make_input(&self);
+    fn read(&self) -> bool;
+    fn set_client(&self, client: &'a dyn Client);
+    fn enable_interrupts(&self, mode: InterruptEdge);
+    fn disable_interrupts(&self);
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum InterruptEdge {
+    RisingEdge,
+    FallingEdge,
+    EitherEdge,
+}
+
+pub trait Client {
+    /// Called when the pin's configured interrupt edge occurs.
+    fn fired(&self);
+}
@@ -0,0 +1,35 @@
+//! Hardware interface layer (HIL) for I2C master controllers and the
+//! individual devices addressed over a bus.
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The slave did not acknowledge its address.
+    AddressNak,
+    /// The slave did not acknowledge a data byte.
+    DataNak,
+    /// Arbitration was lost to another bus master.
+    ArbitrationLost,
+    /// No error occurred and the command completed successfully.
+    CommandComplete,
+}
+
+/// A single I2C device, pre-configured with its own bus address. This
+/// is the handle capsules use; the underlying bus multiplexing is
+/// handled beneath it (typically by a `MuxI2C`/`I2CDevice` virtualizer
+/// not modeled here).
+pub trait I2CDevice {
+    fn set_client(&self, client: &'static dyn I2CClient);
+
+    /// Write `data[0..len]` to the device, then read `len` bytes back
+    /// into `data`, as a single combined transaction (a repeated
+    /// START). Completion is reported via `I2CClient::command_complete`.
+    fn write_read(&self, data: &'static mut [u8], write_len: u8, read_len: u8);
+
+    fn write(&self, data: &'static mut [u8], len: u8);
+
+    fn read(&self, data: &'static mut [u8], len: u8);
+}
+
+pub trait I2CClient {
+    fn command_complete(&self, buffer: &'static mut [u8], error: Error);
+}
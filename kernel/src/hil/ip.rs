@@ -0,0 +1,24 @@
+//! Hardware/software interface layer (HIL) for sending and receiving
+//! whole IPv6 packets, at the level a transport capsule (`capsules::tcp`,
+//! `capsules::sixlowpan`'s UDP path) needs: addressing and delivery,
+//! not the layer's own header compression or neighbor discovery, which
+//! belong to whatever implements this trait.
+
+use crate::returncode::ReturnCode;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Ipv6Address(pub [u8; 16]);
+
+pub trait IpLayer<'a> {
+    fn set_client(&self, client: &'a dyn IpClient);
+
+    /// Sends `buffer[..len]` (a transport-layer payload, e.g. a TCP
+    /// segment) to `dest`; `protocol` is the IPv6 next-header value
+    /// (6 for TCP, 17 for UDP).
+    fn send(&self, dest: Ipv6Address, protocol: u8, buffer: &'static mut [u8], len: usize) -> ReturnCode;
+}
+
+pub trait IpClient {
+    fn send_done(&self, buffer: &'static mut [u8], result: ReturnCode);
+    fn receive(&self, src: Ipv6Address, protocol: u8, buffer: &[u8], len: usize);
+}
@@ -0,0 +1,55 @@
+//! Hardware interface layer (HIL) for append-only, power-fail-tolerant
+//! log storage, read back sequentially from a resumable cookie rather
+//! than a raw byte offset so a reader can survive a reboot mid-log.
+
+use crate::returncode::ReturnCode;
+
+/// Opaque position within a log. Only meaningful to the backend that
+/// produced it; callers should not assume it is a byte offset.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LogCookie(pub u64);
+
+pub trait LogRead<'a> {
+    fn set_read_client(&self, client: &'a dyn LogReadClient);
+
+    /// Reads the next entry starting at `cookie` into `buffer`.
+    /// Completion is reported via `LogReadClient::read_done` with the
+    /// cookie of the entry immediately after the one just read, so the
+    /// caller can resume from it later without re-deriving it.
+    fn read(&self, buffer: &'static mut [u8], cookie: LogCookie) -> ReturnCode;
+
+    /// Cookie pointing at the oldest entry still in the log.
+    fn oldest_cookie(&self) -> LogCookie;
+}
+
+pub trait LogReadClient {
+    fn read_done(&self, buffer: &'static mut [u8], length: usize, next_cookie: LogCookie, result: ReturnCode);
+}
+
+pub trait LogWrite<'a> {
+    fn set_write_client(&self, client: &'a dyn LogWriteClient);
+
+    /// Appends `buffer[..length]` as a new entry. Completion is
+    /// reported via `LogWriteClient::append_done` with the cookie the
+    /// entry was written at.
+    fn append(&self, buffer: &'static mut [u8], length: usize) -> ReturnCode;
+
+    /// Forces any buffered entries out to the backing storage so they
+    /// survive a reset; entries may otherwise be batched for wear
+    /// reasons before reaching flash.
+    fn sync(&self) -> ReturnCode;
+
+    /// Reclaims the oldest entries up to (but not including) `cookie`,
+    /// letting a reader that has consumed everything up to `cookie`
+    /// bound the log's storage use.
+    fn erase_to(&self, cookie: LogCookie) -> ReturnCode;
+
+    /// Cookie that the next `append` will be written at.
+    fn append_cookie(&self) -> LogCookie;
+}
+
+pub trait LogWriteClient {
+    fn append_done(&self, buffer: &'static mut [u8], length: usize, cookie: LogCookie, result: ReturnCode);
+    fn sync_done(&self, result: ReturnCode);
+    fn erase_done(&self, result: ReturnCode);
+}
@@ -0,0 +1,42 @@
+//! Hardware interface layer (HIL) for a LoRa radio (chirp spread
+//! spectrum PHY; e.g. a Semtech SX127x/SX126x transceiver).
+//!
+//! This is just the physical layer: transmit a PHY payload, a receive
+//! callback for the chip's own preamble/CRC detection, and the
+//! handful of radio parameters a LoRaWAN MAC needs to retune between
+//! a transmission and each of its receive windows. The receive
+//! windows themselves are timed by the MAC against the alarm HIL, not
+//! by this trait; `start_receiving` here just arms the radio to
+//! listen until a frame arrives or `stop_receiving` is called.
+
+use crate::returncode::ReturnCode;
+
+pub trait LoRa<'a> {
+    fn set_transmit_client(&self, client: &'a dyn TxClient);
+    fn set_receive_client(&self, client: &'a dyn RxClient);
+
+    fn set_frequency(&self, frequency_hz: u32) -> ReturnCode;
+    fn set_spreading_factor(&self, spreading_factor: u8) -> ReturnCode;
+    fn set_bandwidth(&self, bandwidth_hz: u32) -> ReturnCode;
+    fn set_tx_power(&self, power_dbm: i8) -> ReturnCode;
+
+    /// Transmits `buffer[..len]` as a single LoRa PHY payload.
+    /// Completion is reported via `TxClient::transmit_done`.
+    fn transmit(&self, buffer: &'static mut [u8], len: usize) -> ReturnCode;
+
+    fn start_receiving(&self) -> ReturnCode;
+    fn stop_receiving(&self) -> ReturnCode;
+}
+
+pub trait TxClient {
+    fn transmit_done(&self, buffer: &'static mut [u8], result: ReturnCode);
+}
+
+pub trait RxClient {
+    /// `buffer[..len]` is one received PHY payload, along with the
+    /// RSSI and SNR the radio measured for it (both needed by a
+    /// LoRaWAN MAC's ADR algorithm); ownership stays with the radio
+    /// driver, so a client that needs to hold onto the payload past
+    /// this call must copy it out before returning.
+    fn receive(&self, buffer: &[u8], len: usize, rssi: i8, snr: i8, result: ReturnCode);
+}
@@ -0,0 +1,38 @@
+//! Interfaces for hardware.
+
+pub mod aead;
+pub mod ble_advertising;
+pub mod ble_central;
+pub mod ble_connection;
+pub mod ble_scanning;
+pub mod block_storage;
+pub mod can;
+pub mod crypto;
+pub mod curve25519;
+pub mod cycle_counter;
+pub mod digest;
+pub mod dma;
+pub mod ecdsa;
+pub mod entropy;
+pub mod ethernet;
+pub mod gpio;
+pub mod i2c;
+pub mod ip;
+pub mod log;
+pub mod lora;
+pub mod nfc;
+pub mod nonvolatile_storage;
+pub mod pdm;
+pub mod power;
+pub mod power_control;
+pub mod qspi;
+pub mod radio;
+pub mod screen;
+pub mod sensors;
+pub mod spi;
+pub mod time;
+pub mod uart;
+pub mod usb;
+pub mod usb_hid;
+pub mod usb_host;
+pub mod watchdog;
@@ -0,0 +1,51 @@
+//! Hardware interface layer (HIL) for an NFC tag emulation (card
+//! emulation) front end, such as the nRF52's NFCT peripheral: RF field
+//! presence and raw ISO-DEP frame exchange.
+//!
+//! A reader's polling, anticollision, and the ISO 14443-4 block
+//! chaining/WTX housekeeping underneath an actual command exchange are
+//! all handled by the peripheral itself on every chip that offers this
+//! mode; what reaches [`NfcTagClient::frame_received`] is just the
+//! command APDU inside, the same split `hil::uart` makes between a
+//! byte-stream transport and whatever framing is layered on top of it.
+
+use crate::returncode::ReturnCode;
+
+/// Comfortably larger than any command or response APDU
+/// `capsules::nfc_tag` builds.
+pub const MAX_APDU_LEN: usize = 256;
+
+pub trait NfcTag<'a> {
+    fn set_client(&self, client: &'a dyn NfcTagClient);
+
+    /// Powers on tag-emulation mode and starts listening for a
+    /// reader's field.
+    fn enable(&self) -> ReturnCode;
+
+    /// Stops listening; an in-progress exchange is abandoned.
+    fn disable(&self) -> ReturnCode;
+
+    /// Replies to the command APDU most recently reported via
+    /// `NfcTagClient::frame_received` with `buffer[..len]`. Completion
+    /// is reported via `NfcTagClient::transmit_done`.
+    fn transmit(&self, buffer: &'static mut [u8], len: usize) -> ReturnCode;
+}
+
+pub trait NfcTagClient {
+    /// A reader's RF field has been detected and protocol activation
+    /// completed; the tag is ready to receive command APDUs.
+    fn field_detected(&self);
+
+    /// The reader's field has been switched off or moved out of range;
+    /// any file selection state above this trait should be reset, since
+    /// the next field detected may be a different reader.
+    fn field_lost(&self);
+
+    /// `buffer[..len]` is one command APDU from the reader; as with
+    /// `hil::radio::RxClient::receive`, the controller keeps its own
+    /// receive buffer and this is only a borrow of it for the duration
+    /// of the call.
+    fn frame_received(&self, buffer: &[u8], len: usize);
+
+    fn transmit_done(&self, buffer: &'static mut [u8], result: ReturnCode);
+}
@@ -0,0 +1,22 @@
+//! Byte-addressable interface to nonvolatile storage (flash, FRAM,
+//! EEPROM, ...), abstracted away from the underlying erase-block size
+//! and write alignment of the backing chip.
+
+use crate::returncode::ReturnCode;
+
+pub trait NonvolatileStorage<'a> {
+    fn set_client(&self, client: &'a dyn NonvolatileStorageClient);
+
+    /// Total size of the storage region this instance grants access to.
+    fn size(&self) -> usize;
+
+    fn read(&self, buffer: &'static mut [u8], offset: usize, length: usize) -> ReturnCode;
+    fn write(&self, buffer: &'static mut [u8], offset: usize, length: usize) -> ReturnCode;
+    fn erase(&self, offset: usize, length: usize) -> ReturnCode;
+}
+
+pub trait NonvolatileStorageClient {
+    fn read_done(&self, buffer: &'static mut [u8], length: usize);
+    fn write_done(&self, buffer: &'static mut [u8], length: usize);
+    fn erase_done(&self);
+}
@@ -0,0 +1,60 @@
+//! Hardware interface layer (HIL) for Pulse Density Modulation (PDM)
+//! microphones and other continuously-sampling analog capture
+//! peripherals.
+//!
+//! A `Pdm` implementation drives a PDM decimation filter (or an ADC
+//! running in continuous mode) and fills caller-provided buffers at a
+//! configured sample rate. Unlike `hil::adc::AdcChannel`, which
+//! returns individual samples, this interface is buffer-oriented so
+//! that a client can double-buffer: while the hardware fills one
+//! buffer, the client processes the other.
+//!
+//! # Usage
+//!
+//! ```rust
+//! microphone.set_client(self);
+//! microphone.set_sample_rate(16000);
+//! microphone.start_sampling(buffer0, buffer1, buffer_len);
+//! ```
+
+use crate::returncode::ReturnCode;
+
+/// A continuously-sampling capture peripheral, such as a PDM
+/// microphone decimation filter.
+pub trait Pdm<'a> {
+    /// Set the client that will receive `buffer_ready` callbacks.
+    fn set_client(&self, client: &'a dyn PdmClient);
+
+    /// Configure the sample rate, in samples per second. Must be
+    /// called before `start_sampling`. Returns `EINVAL` if the rate
+    /// is not supported by the underlying hardware.
+    fn set_sample_rate(&self, samples_per_sec: u32) -> ReturnCode;
+
+    /// Begin continuous sampling, alternating between `buffer0` and
+    /// `buffer1`. Each buffer holds `length` 16-bit samples. While one
+    /// buffer is being filled by DMA, the other is handed back to the
+    /// client via `buffer_ready` so it can be drained and returned
+    /// with `provide_buffer`.
+    fn start_sampling(
+        &self,
+        buffer0: &'static mut [i16],
+        buffer1: &'static mut [i16],
+        length: usize,
+    ) -> ReturnCode;
+
+    /// Stop sampling. Any buffer currently being filled is returned
+    /// via a final `buffer_ready` callback with the number of valid
+    /// samples collected so far.
+    fn stop_sampling(&self) -> ReturnCode;
+
+    /// Return a drained buffer to the peripheral so it can be reused
+    /// for the next fill cycle.
+    fn provide_buffer(&self, buf: &'static mut [i16]) -> ReturnCode;
+}
+
+pub trait PdmClient {
+    /// Called when a buffer has been completely filled (or sampling
+    /// was stopped early). `length` is the number of valid samples in
+    /// `buf`.
+    fn buffer_ready(&self, buf: &'static mut [i16], length: usize);
+}
@@ -0,0 +1,18 @@
+//! Hardware interface layer (HIL) for chip sleep states.
+
+/// A sleep state a chip can enter, with the wake latency a caller needs
+/// to know to decide whether it's compatible with an upcoming deadline
+/// (e.g. the next alarm firing).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SleepState {
+    /// Lower is lighter sleep; 0 is a plain WFI.
+    pub depth: u8,
+    pub wake_latency_us: u32,
+}
+
+/// Implemented by a chip to expose the sleep states it supports and to
+/// actually enter one.
+pub trait SleepController {
+    fn available_states(&self) -> &[SleepState];
+    fn enter(&self, state: SleepState);
+}
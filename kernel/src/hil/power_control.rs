@@ -0,0 +1,8 @@
+//! Hardware interface layer (HIL) for gating a peripheral's clock.
+
+/// Implemented by a chip for each gateable peripheral clock domain
+/// (UART, I2C, SPI, ADC, ...).
+pub trait PowerControl {
+    fn enable_clock(&self);
+    fn disable_clock(&self);
+}
@@ -0,0 +1,36 @@
+//! Hardware interface layer (HIL) for a QSPI (quad-SPI) controller, as
+//! used for high-bandwidth external flash and PSRAM that would
+//! otherwise need to be bit-banged over plain `hil::spi`.
+//!
+//! Indirect mode (`read`/`write`/`erase`) behaves like any other
+//! flash HIL, just over up to four data lines instead of one.
+//! Execute-in-place mode maps the device directly into the chip's
+//! address space for as long as `enter_xip` is active, which is
+//! incompatible with indirect transactions to the same device; a
+//! caller must `exit_xip` before issuing any of them again.
+
+use crate::returncode::ReturnCode;
+
+pub trait QspiMaster<'a> {
+    fn set_client(&self, client: &'a dyn QspiClient);
+
+    fn read(&self, buffer: &'static mut [u8], offset: usize, length: usize) -> ReturnCode;
+    fn write(&self, buffer: &'static mut [u8], offset: usize, length: usize) -> ReturnCode;
+    fn erase(&self, offset: usize, length: usize) -> ReturnCode;
+
+    /// Maps the device into the chip's address space starting at
+    /// `base_address` for direct CPU reads (execute-in-place), once
+    /// the controller has sent the chip vendor-specific command
+    /// sequence that puts it into continuous-read mode. Returns
+    /// `ENOSUPPORT` if the controller has no memory-mapped mode.
+    fn enter_xip(&self, base_address: usize) -> ReturnCode;
+
+    /// Leaves memory-mapped mode so indirect transactions can resume.
+    fn exit_xip(&self) -> ReturnCode;
+}
+
+pub trait QspiClient {
+    fn read_done(&self, buffer: &'static mut [u8], length: usize);
+    fn write_done(&self, buffer: &'static mut [u8], length: usize);
+    fn erase_done(&self);
+}
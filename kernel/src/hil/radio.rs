@@ -0,0 +1,47 @@
+//! Hardware interface layer (HIL) for an 802.15.4-capable radio.
+//!
+//! This is deliberately just a send/receive buffer pair plus PAN/
+//! channel/address configuration, the same level this tree's other
+//! HILs stop at; MAC-layer framing, 6LoWPAN header compression, and
+//! anything built on top of a raw frame is a capsule's job
+//! (`capsules::sixlowpan`), not this trait's.
+
+use crate::returncode::ReturnCode;
+
+pub trait Radio<'a> {
+    fn set_transmit_client(&self, client: &'a dyn TxClient);
+    fn set_receive_client(&self, client: &'a dyn RxClient);
+
+    fn set_channel(&self, channel: u8) -> ReturnCode;
+    fn set_pan(&self, pan_id: u16) -> ReturnCode;
+    fn set_address(&self, short_address: u16) -> ReturnCode;
+    fn set_extended_address(&self, extended_address: [u8; 8]) -> ReturnCode;
+
+    /// Sets the transmit power, in dBm. `ENOSUPPORT` if `power_dbm` is
+    /// outside the range this radio can produce.
+    fn set_tx_power(&self, power_dbm: i8) -> ReturnCode;
+
+    /// Sets the clear-channel-assessment energy threshold, in dBm; the
+    /// channel is considered busy at or above this received power.
+    fn set_cca_threshold(&self, threshold_dbm: i8) -> ReturnCode;
+
+    /// Transmits `buffer[..len]` as a single 802.15.4 frame.
+    /// Completion is reported via `TxClient::transmit_done`.
+    fn transmit(&self, buffer: &'static mut [u8], len: usize) -> ReturnCode;
+
+    /// Puts the radio in receive mode; every frame after this is
+    /// reported via `RxClient::receive` until the radio is
+    /// reconfigured.
+    fn start_receiving(&self) -> ReturnCode;
+}
+
+pub trait TxClient {
+    fn transmit_done(&self, buffer: &'static mut [u8], result: ReturnCode);
+}
+
+pub trait RxClient {
+    /// `buffer[..len]` is one received frame; ownership stays with the
+    /// radio driver, so a client that needs to hold onto the payload
+    /// past this call must copy it out before returning.
+    fn receive(&self, buffer: &[u8], len: usize, result: ReturnCode);
+}
@@ -0,0 +1,62 @@
+//! Hardware interface layer (HIL) for a pixel-addressed display.
+//!
+//! Apps write pixel data into rectangular regions rather than
+//! redrawing the whole frame for every change, since pushing a full
+//! frame over a slow bus (I2C, SPI) for a one-line status update is
+//! wasteful; a controller that can only address updates at some
+//! coarser internal granularity (the SSD1306 family's 8-row pages)
+//! rejects a region that doesn't fit that granularity rather than
+//! silently rounding it.
+
+use crate::returncode::ReturnCode;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PixelFormat {
+    /// 1 bit per pixel, packed 8 rows to a byte — the SSD1306/SH1106
+    /// family's native page-addressed format.
+    Mono,
+    Rgb565,
+    Rgb888,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Rotation {
+    Rotate0,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+pub trait Screen<'a> {
+    fn set_client(&self, client: &'a dyn ScreenClient);
+
+    /// The display's native resolution, in pixels, before `rotation`
+    /// is applied.
+    fn resolution(&self) -> (usize, usize);
+
+    fn supports_format(&self, format: PixelFormat) -> bool;
+    fn set_pixel_format(&self, format: PixelFormat) -> ReturnCode;
+
+    fn set_rotation(&self, rotation: Rotation) -> ReturnCode;
+
+    /// `0` is off, `255` is maximum brightness; a controller with a
+    /// coarser range than that scales it to fit.
+    fn set_brightness(&self, brightness: u8) -> ReturnCode;
+    fn set_power(&self, enabled: bool) -> ReturnCode;
+
+    /// Writes `buffer[..len]` (pixel data in the current
+    /// `PixelFormat`) into the `width` by `height` rectangle at
+    /// (`x`, `y`). Completion is reported via
+    /// `ScreenClient::write_complete`. Returns `ReturnCode::EINVAL` for
+    /// a region that does not fit the controller's addressing
+    /// granularity.
+    fn write_region(&self, x: usize, y: usize, width: usize, height: usize, buffer: &'static mut [u8], len: usize) -> ReturnCode;
+}
+
+pub trait ScreenClient {
+    fn write_complete(&self, buffer: &'static mut [u8], result: ReturnCode);
+    /// Reports completion of `set_pixel_format`/`set_rotation`/
+    /// `set_brightness`/`set_power`, for a controller where any of
+    /// those require their own bus transaction.
+    fn command_complete(&self, result: ReturnCode);
+}
@@ -0,0 +1,46 @@
+//! Generic traits for hardware sensors.
+//!
+//! These traits abstract over the specific chip driving a class of
+//! sensor (e.g. ambient light, humidity, temperature) so that a
+//! syscall driver capsule can be written once against the trait and
+//! work with any chip that implements it.
+
+use crate::returncode::ReturnCode;
+
+/// A sensor capable of measuring ambient light intensity, in lux.
+pub trait AmbientLight {
+    /// Set the client that will receive `callback` when a reading
+    /// completes.
+    fn set_client(&self, client: &'static dyn AmbientLightClient);
+
+    /// Take a single light intensity reading. The result is returned
+    /// via `AmbientLightClient::callback`.
+    fn read_light_intensity(&self) -> ReturnCode;
+
+    /// Begin continuously sampling in the background, comparing each
+    /// reading against the bounds configured with
+    /// `configure_threshold`, and only invoking `callback` when a
+    /// reading crosses a threshold. Returns `ENOSUPPORT` if the
+    /// underlying chip cannot sample continuously in hardware.
+    fn enable_continuous_mode(&self) -> ReturnCode {
+        ReturnCode::ENOSUPPORT
+    }
+
+    fn disable_continuous_mode(&self) -> ReturnCode {
+        ReturnCode::ENOSUPPORT
+    }
+
+    /// Configure the lux bounds at which `AmbientLightClient::callback`
+    /// should fire while in continuous mode.
+    fn configure_threshold(&self, lower_lux: usize, upper_lux: usize) -> ReturnCode {
+        let _ = (lower_lux, upper_lux);
+        ReturnCode::ENOSUPPORT
+    }
+}
+
+pub trait AmbientLightClient {
+    /// Called with the most recent reading, in lux, either in response
+    /// to `read_light_intensity` or because a configured threshold was
+    /// crossed in continuous mode.
+    fn callback(&self, lux: usize);
+}
@@ -0,0 +1,44 @@
+//! Hardware interface layer (HIL) for SPI master controllers and the
+//! chip-select-qualified devices addressed over a bus.
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ClockPolarity {
+    IdleLow,
+    IdleHigh,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ClockPhase {
+    SampleLeading,
+    SampleTrailing,
+}
+
+/// A single SPI device, pre-configured with its own chip-select and
+/// clock settings. This is the handle capsules use; the underlying bus
+/// multiplexing is handled beneath it (typically by a `MuxSpi`
+/// virtualizer not modeled here).
+pub trait SpiMasterDevice {
+    fn set_client(&self, client: &'static dyn SpiMasterClient);
+
+    fn configure(&self, polarity: ClockPolarity, phase: ClockPhase, rate_hz: u32);
+
+    /// Transfers `write_buffer` out while simultaneously reading
+    /// `len` bytes into `read_buffer`, asserting chip-select for the
+    /// duration. Completion is reported via
+    /// `SpiMasterClient::read_write_done`.
+    fn read_write_bytes(
+        &self,
+        write_buffer: &'static mut [u8],
+        read_buffer: Option<&'static mut [u8]>,
+        len: usize,
+    );
+}
+
+pub trait SpiMasterClient {
+    fn read_write_done(
+        &self,
+        write_buffer: &'static mut [u8],
+        read_buffer: Option<&'static mut [u8]>,
+        len: usize,
+    );
+}
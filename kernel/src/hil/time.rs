@@ -0,0 +1,21 @@
+//! Hardware interface layer (HIL) for hardware timers and alarms.
+
+/// An opaque hardware tick count, comparable and wrapping.
+pub trait Ticks: Copy + Clone + PartialEq + PartialOrd {}
+
+/// A free-running counter with the ability to fire a callback once the
+/// counter reaches a programmed value.
+pub trait Alarm<'a> {
+    fn set_alarm(&self, reference: u32, dt: u32);
+    fn now(&self) -> u32;
+    fn set_alarm_client(&self, client: &'a dyn AlarmClient);
+    fn disarm(&self);
+
+    /// Convert a millisecond duration to this alarm's tick units.
+    fn ticks_from_ms(ms: u32) -> u32;
+}
+
+pub trait AlarmClient {
+    /// Called when the alarm set with `set_alarm` fires.
+    fn alarm(&self);
+}
@@ -0,0 +1,26 @@
+//! Hardware interface layer (HIL) for a UART transmit/receive pair, as
+//! used by the console and by byte-stream transports (app loading,
+//! file transfer protocols) layered on top of it.
+
+use crate::returncode::ReturnCode;
+
+pub trait UartData<'a> {
+    fn set_transmit_client(&self, client: &'a dyn TransmitClient);
+    fn set_receive_client(&self, client: &'a dyn ReceiveClient);
+
+    /// Sends `tx_len` bytes of `buffer`. Completion is reported via
+    /// `TransmitClient::transmitted_buffer`.
+    fn transmit_buffer(&self, buffer: &'static mut [u8], tx_len: usize) -> ReturnCode;
+
+    /// Fills `buffer` with the next `rx_len` bytes received.
+    /// Completion is reported via `ReceiveClient::received_buffer`.
+    fn receive_buffer(&self, buffer: &'static mut [u8], rx_len: usize) -> ReturnCode;
+}
+
+pub trait TransmitClient {
+    fn transmitted_buffer(&self, buffer: &'static mut [u8], tx_len: usize, result: ReturnCode);
+}
+
+pub trait ReceiveClient {
+    fn received_buffer(&self, buffer: &'static mut [u8], rx_len: usize, result: ReturnCode);
+}
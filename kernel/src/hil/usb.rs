@@ -0,0 +1,28 @@
+//! Hardware interface layer (HIL) for a USB device controller's bulk
+//! endpoint pair, as used by class drivers (mass storage, HID, DFU)
+//! that move opaque byte buffers rather than control-transfer
+//! requests.
+//!
+//! Enumeration, descriptors, and the control endpoint are handled
+//! elsewhere; this HIL only covers the bulk IN/OUT pair a class driver
+//! needs once the host has already configured the device.
+
+use crate::returncode::ReturnCode;
+
+pub trait UsbBulkEndpoint<'a> {
+    fn set_client(&self, client: &'a dyn UsbBulkClient);
+
+    /// Queues `buffer` to be read into from the host on the next OUT
+    /// transfer. Completion is reported via `UsbBulkClient::packet_out`.
+    fn receive(&self, buffer: &'static mut [u8]) -> ReturnCode;
+
+    /// Queues `length` bytes of `buffer` to be sent to the host on the
+    /// next IN transfer. Completion is reported via
+    /// `UsbBulkClient::packet_in`.
+    fn transmit(&self, buffer: &'static mut [u8], length: usize) -> ReturnCode;
+}
+
+pub trait UsbBulkClient {
+    fn packet_out(&self, buffer: &'static mut [u8], length: usize);
+    fn packet_in(&self, buffer: &'static mut [u8]);
+}
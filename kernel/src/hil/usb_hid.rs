@@ -0,0 +1,29 @@
+//! Hardware interface layer (HIL) for a USB HID interrupt endpoint
+//! pair, used both for CTAPHID (FIDO2 security keys) and for generic
+//! HID keyboard/mouse gadgets.
+//!
+//! Every report on a Tock HID gadget is a fixed-size interrupt packet;
+//! HID class descriptors, which report sizes a real device actually
+//! advertises, are board/gadget configuration and not modeled here.
+
+use crate::returncode::ReturnCode;
+
+/// Report size used by Tock's CTAPHID and boot-protocol HID gadgets.
+pub const HID_REPORT_LEN: usize = 64;
+
+pub trait UsbHidReport<'a> {
+    fn set_client(&self, client: &'a dyn UsbHidClient);
+
+    /// Queues `report` to be sent on the IN endpoint. Completion is
+    /// reported via `UsbHidClient::report_sent`.
+    fn send_report(&self, report: &'static mut [u8]) -> ReturnCode;
+
+    /// Arms `buffer` to receive the next report on the OUT endpoint.
+    /// Completion is reported via `UsbHidClient::report_received`.
+    fn receive_report(&self, buffer: &'static mut [u8]) -> ReturnCode;
+}
+
+pub trait UsbHidClient {
+    fn report_sent(&self, report: &'static mut [u8], result: ReturnCode);
+    fn report_received(&self, buffer: &'static mut [u8], result: ReturnCode);
+}
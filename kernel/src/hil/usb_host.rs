@@ -0,0 +1,51 @@
+//! Hardware interface layer (HIL) for a USB host controller running in
+//! device-enumeration mode: control transfers plus interrupt IN
+//! polling for one directly attached device's report endpoint.
+//!
+//! Sized for a single HID peripheral (a keyboard, mouse, or gamepad)
+//! plugged directly into a host-capable controller — not a general
+//! host stack. Bulk/isochronous transfers, hubs, and more than one
+//! attached device are not modeled; a board wanting any of those needs
+//! more than this HIL covers.
+
+use crate::returncode::ReturnCode;
+
+/// A USB control transfer's 8-byte Setup packet.
+#[derive(Copy, Clone)]
+pub struct SetupPacket {
+    pub request_type: u8,
+    pub request: u8,
+    pub value: u16,
+    pub index: u16,
+    pub length: u16,
+}
+
+pub trait UsbHostController<'a> {
+    fn set_client(&self, client: &'a dyn UsbHostClient);
+
+    /// Issues a control transfer to the device at `address`. Whether
+    /// `buffer` is written into (device-to-host) or read from
+    /// (host-to-device) follows `setup.request_type`'s direction bit,
+    /// same as on the wire. Completion is reported through
+    /// `UsbHostClient::control_done`.
+    fn control_transfer(&self, address: u8, setup: SetupPacket, buffer: &'static mut [u8]) -> ReturnCode;
+
+    /// Polls `endpoint` (an interrupt IN endpoint) on the device at
+    /// `address` once. A real controller schedules interrupt polling
+    /// on its own bus-frame timer; that scheduling is a controller
+    /// concern this HIL leaves out, the same way `hil::time::Alarm`
+    /// leaves clock selection to a board. Completion is reported
+    /// through `UsbHostClient::interrupt_in_done`.
+    fn poll_interrupt_in(&self, address: u8, endpoint: u8, buffer: &'static mut [u8]) -> ReturnCode;
+}
+
+pub trait UsbHostClient {
+    /// A device was newly detected on the bus, still at the
+    /// controller's default address (`0`, conventionally) until
+    /// enumeration assigns it a real one.
+    fn device_connected(&self);
+    /// The most recently connected device has been unplugged.
+    fn device_disconnected(&self);
+    fn control_done(&self, buffer: &'static mut [u8], length: usize, result: ReturnCode);
+    fn interrupt_in_done(&self, buffer: &'static mut [u8], length: usize, result: ReturnCode);
+}
@@ -0,0 +1,18 @@
+//! Hardware interface layer (HIL) for a chip's hardware watchdog timer.
+
+/// Implemented by a chip to arm and feed its hardware watchdog. A hang
+/// anywhere in the kernel main loop should eventually cause this to
+/// stop being fed and the chip to reset, which is the entire point: a
+/// watchdog that the kernel feeds unconditionally regardless of
+/// whether work is actually progressing protects against nothing.
+pub trait WatchDog {
+    /// Start the watchdog counting down from its configured timeout.
+    fn start(&self, period_ms: u32);
+
+    /// Reset the countdown. Must be called at least once per
+    /// `period_ms` or the chip resets.
+    fn tick(&self);
+
+    /// Disable the watchdog, e.g. for debugging.
+    fn stop(&self);
+}
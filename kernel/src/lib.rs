@@ -0,0 +1,44 @@
+//! Core Tock Kernel
+//!
+//! The kernel crate implements the core features of Tock as well as
+//! shared code that many chips, capsules, and boards use. It also holds
+//! the Hardware Interface Layer (HIL) definitions.
+//!
+//! Most `unsafe` code in the kernel is isolated to this crate, with the
+//! exception of architecture-specific and chip-specific code.
+
+#![no_std]
+
+pub mod boot_chain;
+pub mod callback;
+pub mod capabilities;
+pub mod clock_manager;
+pub mod common;
+pub mod deferred_call;
+pub mod driver;
+pub mod event_log;
+pub mod grant;
+pub mod hil;
+pub mod ipc;
+pub mod mem;
+pub mod memop;
+pub mod platform;
+pub mod process;
+pub mod process_checker;
+pub mod process_policies;
+pub mod returncode;
+pub mod revocation;
+pub mod scheduler;
+pub mod sleep;
+pub mod syscall;
+pub mod tbf_header;
+pub mod trace;
+pub mod watchdog_kernel;
+pub mod work_queue;
+pub mod zeroize;
+
+pub use crate::callback::{AppId, Callback};
+pub use crate::driver::Driver;
+pub use crate::grant::Grant;
+pub use crate::mem::{AppSlice, Shared};
+pub use crate::returncode::ReturnCode;
@@ -0,0 +1,47 @@
+//! Data structure for passing application memory to the kernel.
+
+use core::marker::PhantomData;
+
+/// Type marker for an `AppSlice` that is shared with (readable and
+/// writeable by) the kernel.
+pub struct Shared;
+
+/// A borrow of a slice of application memory, granted to the kernel
+/// through `allow`. Dropping this reference returns access of the
+/// memory to the app that owns it.
+pub struct AppSlice<L, T> {
+    ptr: *mut T,
+    len: usize,
+    _phantom: PhantomData<L>,
+}
+
+impl<L, T> AppSlice<L, T> {
+    pub(crate) unsafe fn new(ptr: *mut T, len: usize) -> AppSlice<L, T> {
+        AppSlice {
+            ptr,
+            len,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Number of elements in the slice.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn ptr(&self) -> *const T {
+        self.ptr
+    }
+
+    pub fn as_ref(&self) -> &[T] {
+        unsafe { core::slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    pub fn as_mut(&mut self) -> &mut [T] {
+        unsafe { core::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
@@ -0,0 +1,55 @@
+//! `memop` system call numbers.
+//!
+//! `memop` is the general-purpose "ask the kernel about my own memory
+//! layout" syscall class; unlike a driver `command`, it is always
+//! available and does not go through `Platform::with_driver`.
+
+/// Identifiers for the operations a process can request via `memop`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MemOp {
+    BrkSet,
+    BrkGet,
+    /// Start address of the process's flash region.
+    FlashStart,
+    /// Length, in bytes, of the process's flash region.
+    FlashLen,
+    /// Number of writeable flash regions the app declared in its TBF
+    /// header.
+    WriteableFlashRegionsCount,
+    /// Start address of the `index`th writeable flash region declared
+    /// in the TBF header.
+    WriteableFlashRegionStart(usize),
+    /// Length of the `index`th writeable flash region.
+    WriteableFlashRegionLen(usize),
+    /// Total size, in bytes, of the process's grant region.
+    GrantRegionSize,
+    /// Bytes of the grant region not yet allocated to any capsule,
+    /// so userspace storage libraries can estimate their own headroom
+    /// before committing to a flash layout that competes with capsule
+    /// grants for RAM.
+    GrantRegionRemaining,
+    /// Number of MPU regions currently configured for the calling
+    /// process.
+    MpuRegionCount,
+    /// Start address, length, and permission bits of the `index`th
+    /// configured MPU region, so userspace allocators and debuggers
+    /// can reason about what is actually accessible instead of
+    /// guessing from linker symbols.
+    MpuRegionInfo(usize),
+}
+
+/// Permission bits reported by `MemOp::MpuRegionInfo`, independent of
+/// how any particular architecture's MPU encodes them internally.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MpuRegionPermissions {
+    pub readable: bool,
+    pub writable: bool,
+    pub executable: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MpuRegionInfo {
+    pub start: usize,
+    pub len: usize,
+    pub permissions: MpuRegionPermissions,
+}
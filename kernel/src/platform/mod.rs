@@ -0,0 +1,4 @@
+//! Interfaces for board-specific configuration of the kernel.
+
+pub mod rate_limiter;
+pub mod syscall_filter;
@@ -0,0 +1,104 @@
+//! A `SyscallFilter` enforcing per-process, per-driver token-bucket
+//! rate limits, so a misbehaving or compromised app cannot monopolize
+//! a shared resource (radio TX, flash erase) by flooding it with
+//! syscalls; a process that exhausts its bucket has that driver's
+//! syscalls vetoed until `refill` has replenished it.
+//!
+//! Buckets are held in a fixed-size table and matched by linear scan
+//! rather than indexed by `AppId`, since process slots can be reused
+//! across restarts; `refill` is meant to be called from a periodic
+//! kernel tick (not itself tied to any particular `Alarm`) rather than
+//! on every syscall, so the limiter does not need a clock of its own.
+//! `SyscallFilter::filter_syscall` takes `&self`, so bucket state is
+//! held in `Cell`s the same way other shared kernel state in this tree
+//! is.
+
+use core::cell::Cell;
+
+use crate::callback::AppId;
+use crate::platform::syscall_filter::{SyscallClass, SyscallFilter};
+
+const MAX_BUCKETS: usize = 16;
+
+struct Bucket {
+    process: AppId,
+    driver_num: usize,
+    capacity: u32,
+    refill_per_tick: u32,
+    tokens: Cell<u32>,
+}
+
+pub trait RateLimitSupervisorClient {
+    /// Called whenever `process` is denied a syscall on `driver_num`
+    /// because its bucket is empty.
+    fn process_throttled(&self, process: AppId, driver_num: usize);
+}
+
+pub struct RateLimiter<'a> {
+    buckets: [Option<Bucket>; MAX_BUCKETS],
+    supervisor: Option<&'a dyn RateLimitSupervisorClient>,
+}
+
+impl<'a> RateLimiter<'a> {
+    pub fn new(supervisor: Option<&'a dyn RateLimitSupervisorClient>) -> RateLimiter<'a> {
+        RateLimiter {
+            buckets: Default::default(),
+            supervisor,
+        }
+    }
+
+    /// Installs a limit of `capacity` syscalls on `driver_num` for
+    /// `process`, refilling by `refill_per_tick` tokens (capped at
+    /// `capacity`) on every `refill` call. Meant to be called during
+    /// board setup, before the limiter is wired in as the platform's
+    /// `SyscallFilter`; has no effect once `MAX_BUCKETS` limits are
+    /// already installed.
+    pub fn set_limit(&mut self, process: AppId, driver_num: usize, capacity: u32, refill_per_tick: u32) {
+        for slot in self.buckets.iter_mut() {
+            if slot.is_none() {
+                *slot = Some(Bucket {
+                    process,
+                    driver_num,
+                    capacity,
+                    refill_per_tick,
+                    tokens: Cell::new(capacity),
+                });
+                return;
+            }
+        }
+    }
+
+    /// Replenishes every installed bucket; called once per rate-limit
+    /// tick, independent of syscall activity.
+    pub fn refill(&self) {
+        for bucket in self.buckets.iter().flatten() {
+            bucket.tokens.set((bucket.tokens.get() + bucket.refill_per_tick).min(bucket.capacity));
+        }
+    }
+
+    fn find(&self, process: AppId, driver_num: usize) -> Option<&Bucket> {
+        self.buckets.iter().flatten().find(|b| b.process == process && b.driver_num == driver_num)
+    }
+}
+
+impl<'a> SyscallFilter for RateLimiter<'a> {
+    fn filter_syscall(&self, process: AppId, driver_num: usize, _class: SyscallClass, _arg0: usize, _arg1: usize) -> bool {
+        match self.find(process, driver_num) {
+            Some(bucket) => {
+                let tokens = bucket.tokens.get();
+                if tokens == 0 {
+                    if let Some(supervisor) = self.supervisor {
+                        supervisor.process_throttled(process, driver_num);
+                    }
+                    false
+                } else {
+                    bucket.tokens.set(tokens - 1);
+                    true
+                }
+            }
+            // No bucket installed for this (process, driver) pair:
+            // unlimited, same as `AllowAll`.
+            None => true,
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! Hook allowing a board to veto or audit system calls before they
+//! reach a capsule.
+
+use crate::callback::AppId;
+
+/// The broad class of syscall being filtered, passed alongside the
+/// driver number and arguments so a filter can apply different policy
+/// to, e.g., `command` versus `allow`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SyscallClass {
+    Subscribe,
+    Command,
+    Allow,
+}
+
+/// Implemented by a board's `Platform` (or a dedicated policy struct it
+/// holds) to inspect every syscall before dispatch. This is the
+/// general-purpose escape hatch for security policies, rate limiting,
+/// and audit trails that would otherwise require modifying every
+/// capsule individually.
+pub trait SyscallFilter {
+    /// Return `true` to allow the syscall to proceed to the capsule,
+    /// `false` to veto it (the kernel returns `ENOSUPPORT` to the
+    /// process without ever calling into the driver).
+    fn filter_syscall(
+        &self,
+        process: AppId,
+        driver_num: usize,
+        class: SyscallClass,
+        arg0: usize,
+        arg1: usize,
+    ) -> bool;
+}
+
+/// The default policy used when a board does not install its own
+/// filter: allow everything.
+pub struct AllowAll;
+
+impl SyscallFilter for AllowAll {
+    fn filter_syscall(
+        &self,
+        _process: AppId,
+        _driver_num: usize,
+        _class: SyscallClass,
+        _arg0: usize,
+        _arg1: usize,
+    ) -> bool {
+        true
+    }
+}
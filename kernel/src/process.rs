@@ -0,0 +1,266 @@
+//! Types representing a running (or not) process and its scheduling
+//! state.
+
+use crate::process_checker::{AppCredentialsChecker, Credential, UnverifiedPolicy};
+use crate::syscall::YieldCall;
+use crate::zeroize::{zeroize_volatile, ZeroizePolicy};
+
+/// The filter a process has currently asked `yield-wait-for` to apply
+/// to its pending upcall queue. While `Some`, the scheduler only wakes
+/// the process for an upcall matching `(driver_num, subscribe_num)`;
+/// all other queued upcalls remain queued and are delivered on a
+/// subsequent, unfiltered `yield`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WaitForFilter {
+    pub driver_num: usize,
+    pub subscribe_num: usize,
+}
+
+impl WaitForFilter {
+    pub fn from_yield_call(call: YieldCall) -> Option<WaitForFilter> {
+        match call {
+            YieldCall::WaitForOnly {
+                driver_num,
+                subscribe_num,
+            } => Some(WaitForFilter {
+                driver_num,
+                subscribe_num,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Whether an upcall for the given driver/subscribe pair should
+    /// wake a process that is blocked with this filter active.
+    pub fn matches(&self, driver_num: usize, subscribe_num: usize) -> bool {
+        self.driver_num == driver_num && self.subscribe_num == subscribe_num
+    }
+}
+
+/// What the kernel does when a process's upcall queue is full and
+/// another upcall is scheduled for it. Configurable per process (from
+/// board defaults or a TBF header field) since the right tradeoff
+/// differs by driver: a sensor stream may prefer the latest sample,
+/// while a command/response protocol may prefer not to lose an older
+/// reply out from under a client waiting on it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UpcallOverflowPolicy {
+    /// Discard the oldest queued upcall to make room.
+    DropOldest,
+    /// Discard the incoming upcall, keeping what's already queued.
+    DropNewest,
+    /// Fault the process, on the theory that it is not keeping up with
+    /// its own workload.
+    Fault,
+}
+
+/// Per-process upcall queue configuration and overflow accounting.
+pub struct UpcallQueueConfig {
+    pub depth: usize,
+    pub policy: UpcallOverflowPolicy,
+    dropped_count: core::cell::Cell<usize>,
+}
+
+impl UpcallQueueConfig {
+    /// The depth used today, with the previous drop-oldest-on-overflow
+    /// behavior, for processes that don't request anything different.
+    pub const DEFAULT_DEPTH: usize = 10;
+
+    pub fn new(depth: usize, policy: UpcallOverflowPolicy) -> UpcallQueueConfig {
+        UpcallQueueConfig {
+            depth,
+            policy,
+            dropped_count: core::cell::Cell::new(0),
+        }
+    }
+
+    pub fn default_config() -> UpcallQueueConfig {
+        UpcallQueueConfig::new(Self::DEFAULT_DEPTH, UpcallOverflowPolicy::DropOldest)
+    }
+
+    pub fn record_drop(&self) {
+        self.dropped_count.set(self.dropped_count.get() + 1);
+    }
+
+    /// Number of upcalls dropped for this process since boot, exposed
+    /// to diagnostics (e.g. a `process_info` capsule).
+    pub fn dropped_count(&self) -> usize {
+        self.dropped_count.get()
+    }
+}
+
+/// The byte pattern the kernel writes across an unused process stack
+/// at load time, so the high-water mark can later be measured by
+/// scanning for where the pattern stops being intact.
+pub const STACK_FILL_PATTERN: u8 = 0xce;
+
+/// Scan `stack`, which was filled with `STACK_FILL_PATTERN` at load,
+/// from the low (deepest-growth) end and return the number of bytes
+/// that have been touched since — the stack high-water mark. Sizing a
+/// process's stack today is pure guesswork; this makes it measurable.
+pub fn stack_high_water_mark(stack: &[u8]) -> usize {
+    let untouched = stack
+        .iter()
+        .take_while(|&&b| b == STACK_FILL_PATTERN)
+        .count();
+    stack.len() - untouched
+}
+
+/// Distinguishes a fault caused by the MPU stack guard region (i.e. a
+/// stack overflow) from any other memory fault, so the kernel and
+/// ProcessConsole can report "stack overflow" instead of a generic
+/// memory access violation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FaultCause {
+    StackOverflow,
+    MemoryAccessViolation,
+    Other,
+}
+
+/// What a process loader does with a discovered binary once its TBF
+/// credential footer, if any, has been checked.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CredentialOutcome {
+    /// Load and schedule the process normally.
+    Load,
+    /// Load the process, but mark it quarantined so privileged capsules
+    /// (a key store, a network stack) can refuse to interact with it.
+    Quarantine,
+    /// Do not load the process at all.
+    Reject,
+}
+
+/// Runs `checker` against a discovered process's credential footer (if
+/// any) and turns the result, together with `checker`'s configured
+/// `policy_for_unverified`, into what the loader should actually do
+/// with the binary. A process-loading loop calls this once per
+/// discovered binary before deciding whether to add it to the process
+/// array at all.
+pub fn check_process_credentials(
+    checker: &dyn AppCredentialsChecker,
+    binary: &[u8],
+    credential: Option<Credential>,
+) -> CredentialOutcome {
+    let had_credential = credential.is_some();
+    if checker.check_credentials(binary, credential) {
+        return CredentialOutcome::Load;
+    }
+    if had_credential {
+        // A credential footer was present but did not verify: this is
+        // never treated as "no credential at all", regardless of
+        // `policy_for_unverified`.
+        return CredentialOutcome::Reject;
+    }
+    match checker.policy_for_unverified() {
+        UnverifiedPolicy::Reject => CredentialOutcome::Reject,
+        UnverifiedPolicy::Quarantine => CredentialOutcome::Quarantine,
+        UnverifiedPolicy::Allow => CredentialOutcome::Load,
+    }
+}
+
+/// Scrubs each of `regions` if `policy` calls for it. Called once per
+/// process teardown (exit, restart, or fault) with every region the
+/// kernel is about to relinquish — a process's RAM, its grant
+/// allocations, and any `AppSlice`s the kernel is still holding from an
+/// in-flight operation; enumerating those regions is the caller's job.
+pub fn zeroize_process_regions(policy: ZeroizePolicy, regions: &mut [&mut [u8]]) {
+    if !policy.should_zeroize() {
+        return;
+    }
+    for region in regions.iter_mut() {
+        zeroize_volatile(region);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn untouched_stack_reports_zero_usage() {
+        let stack = [STACK_FILL_PATTERN; 128];
+        assert_eq!(stack_high_water_mark(&stack), 0);
+    }
+
+    #[test]
+    fn fully_touched_stack_reports_full_usage() {
+        let stack = [0u8; 128];
+        assert_eq!(stack_high_water_mark(&stack), 128);
+    }
+
+    #[test]
+    fn partially_touched_stack_reports_touched_bytes() {
+        let mut stack = [STACK_FILL_PATTERN; 128];
+        for byte in stack.iter_mut().skip(96) {
+            *byte = 0;
+        }
+        assert_eq!(stack_high_water_mark(&stack), 32);
+    }
+
+    struct FakeChecker {
+        verifies: bool,
+        policy: UnverifiedPolicy,
+    }
+
+    impl AppCredentialsChecker for FakeChecker {
+        fn check_credentials(&self, _binary: &[u8], _credential: Option<Credential>) -> bool {
+            self.verifies
+        }
+
+        fn policy_for_unverified(&self) -> UnverifiedPolicy {
+            self.policy
+        }
+    }
+
+    #[test]
+    fn verified_credential_is_loaded() {
+        let checker = FakeChecker {
+            verifies: true,
+            policy: UnverifiedPolicy::Reject,
+        };
+        let tag = [0u8; 32];
+        let credential = Some(Credential::Hmac { tag: &tag });
+        assert_eq!(check_process_credentials(&checker, &[], credential), CredentialOutcome::Load);
+    }
+
+    #[test]
+    fn unverified_credential_is_rejected_regardless_of_policy() {
+        let checker = FakeChecker {
+            verifies: false,
+            policy: UnverifiedPolicy::Allow,
+        };
+        let tag = [0u8; 32];
+        let credential = Some(Credential::Hmac { tag: &tag });
+        assert_eq!(check_process_credentials(&checker, &[], credential), CredentialOutcome::Reject);
+    }
+
+    #[test]
+    fn missing_credential_follows_configured_policy() {
+        for (policy, expected) in [
+            (UnverifiedPolicy::Reject, CredentialOutcome::Reject),
+            (UnverifiedPolicy::Quarantine, CredentialOutcome::Quarantine),
+            (UnverifiedPolicy::Allow, CredentialOutcome::Load),
+        ] {
+            let checker = FakeChecker { verifies: false, policy };
+            assert_eq!(check_process_credentials(&checker, &[], None), expected);
+        }
+    }
+
+    #[test]
+    fn disabled_policy_leaves_regions_untouched() {
+        let mut ram = [0xaau8; 4];
+        let mut grant = [0xbbu8; 4];
+        zeroize_process_regions(ZeroizePolicy::Disabled, &mut [&mut ram, &mut grant]);
+        assert_eq!(ram, [0xaa; 4]);
+        assert_eq!(grant, [0xbb; 4]);
+    }
+
+    #[test]
+    fn enabled_policy_zeroizes_every_region() {
+        let mut ram = [0xaau8; 4];
+        let mut grant = [0xbbu8; 4];
+        zeroize_process_regions(ZeroizePolicy::Enabled, &mut [&mut ram, &mut grant]);
+        assert_eq!(ram, [0; 4]);
+        assert_eq!(grant, [0; 4]);
+    }
+}
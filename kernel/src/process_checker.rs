@@ -0,0 +1,40 @@
+//! Verifies app credentials carried in a process's TBF footer before
+//! the kernel schedules it.
+//!
+//! TBF binaries may carry one or more signature footers (e.g. ECDSA or
+//! HMAC) after their application binary. At load time, the kernel runs
+//! each discovered process through an installed `AppCredentialsChecker`
+//! before ever running it, so that any flash region writeable in the
+//! field (app loader, updater) cannot silently introduce unverifiable
+//! code.
+
+/// A credential footer parsed out of a process's TBF binary.
+#[derive(Copy, Clone)]
+pub enum Credential<'a> {
+    EcdsaP256Signature { signature: &'a [u8; 64] },
+    Hmac { tag: &'a [u8; 32] },
+}
+
+/// What to do with a process whose credentials did not verify, or that
+/// carried no credential footer at all.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UnverifiedPolicy {
+    /// Refuse to load the process at all.
+    Reject,
+    /// Load it, but mark it quarantined so privileged capsules (a key
+    /// store, a network stack) can refuse to interact with it.
+    Quarantine,
+    /// Load and run it normally, as today. Intended only for
+    /// development boards.
+    Allow,
+}
+
+/// Implemented by a board to check a process's credentials against its
+/// provisioned keys before the process is scheduled.
+pub trait AppCredentialsChecker {
+    /// `binary` is the process's flash image excluding the credential
+    /// footer(s) themselves, i.e. what the signature was computed over.
+    fn check_credentials(&self, binary: &[u8], credential: Option<Credential>) -> bool;
+
+    fn policy_for_unverified(&self) -> UnverifiedPolicy;
+}
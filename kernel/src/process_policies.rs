@@ -0,0 +1,81 @@
+//! Board-configurable policy for what happens when a process faults.
+//!
+//! Previously a board's only choice was the kernel-wide panic-or-restart
+//! switch; fielded devices need something in between, e.g. restart a
+//! flaky sensor driver app a few times with increasing delay before
+//! giving up on it, while a different app on the same board should
+//! simply notify a supervisor and stay stopped.
+
+use core::cmp::min;
+
+use crate::callback::AppId;
+
+/// What to do the next time a given process faults.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FaultAction {
+    /// Restart immediately, as the kernel has always done.
+    RestartImmediately,
+    /// Restart after a backoff delay that doubles on each fault this
+    /// policy has recorded, capped at `max_backoff_ms`.
+    RestartWithBackoff { base_ms: u32, max_backoff_ms: u32 },
+    /// Stop scheduling the process after it has faulted
+    /// `max_faults` times in total; `FaultPolicy` never resets its
+    /// fault count.
+    StopAfter { max_faults: u32 },
+    /// Leave the process stopped and deliver an upcall to a registered
+    /// supervisor instead of restarting it automatically.
+    NotifySupervisor,
+}
+
+pub trait FaultSupervisorClient {
+    fn process_faulted(&self, process: AppId, fault_count: u32);
+}
+
+/// Per-process fault policy and history, used by the kernel's process
+/// fault handler to decide what to do instead of an all-or-nothing
+/// restart/panic choice.
+pub struct FaultPolicy<'a> {
+    action: FaultAction,
+    fault_count: u32,
+    supervisor: Option<&'a dyn FaultSupervisorClient>,
+}
+
+impl<'a> FaultPolicy<'a> {
+    pub fn new(action: FaultAction, supervisor: Option<&'a dyn FaultSupervisorClient>) -> FaultPolicy<'a> {
+        FaultPolicy {
+            action,
+            fault_count: 0,
+            supervisor,
+        }
+    }
+
+    /// Called by the kernel's fault handler when `process` has just
+    /// faulted. Returns the backoff delay, in milliseconds, to wait
+    /// before restarting, or `None` if the process should not be
+    /// restarted at all right now.
+    pub fn on_fault(&mut self, process: AppId) -> Option<u32> {
+        self.fault_count += 1;
+        if let Some(supervisor) = self.supervisor {
+            supervisor.process_faulted(process, self.fault_count);
+        }
+        match self.action {
+            FaultAction::RestartImmediately => Some(0),
+            FaultAction::RestartWithBackoff {
+                base_ms,
+                max_backoff_ms,
+            } => {
+                let shift = min(self.fault_count.saturating_sub(1), 31);
+                let backoff = base_ms.saturating_mul(1u32 << shift);
+                Some(min(backoff, max_backoff_ms))
+            }
+            FaultAction::StopAfter { max_faults } => {
+                if self.fault_count >= max_faults {
+                    None
+                } else {
+                    Some(0)
+                }
+            }
+            FaultAction::NotifySupervisor => None,
+        }
+    }
+}
@@ -0,0 +1,62 @@
+//! Standard return type for invoking operations, returned by a large
+//! number of the HIL interfaces and syscall implementations.
+
+/// Standard return errors in Tock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReturnCode {
+    /// Operation completed successfully
+    SUCCESS,
+    /// Generic failure condition
+    FAIL,
+    /// Underlying system is busy; retry
+    EBUSY,
+    /// Reservation required before use
+    EALREADY,
+    /// Device is off
+    EOFF,
+    /// The component to specified is not valid, i.e. it is beyond index bounds
+    EINVAL,
+    /// Out of memory
+    ENOMEM,
+    /// Operation or command is unsupported
+    ENOSUPPORT,
+    /// Device does not exist
+    ENODEVICE,
+    /// Device is not physically installed
+    EUNINSTALLED,
+    /// Packet transmission not acknowledged
+    ENOACK,
+    /// Reserved value. Reserved for initial driver writes to the register.
+    ERESERVE,
+    /// Reflects a more general error than the specific versions above.
+    ECANCEL,
+    /// Size error, typically a buffer is too large or too small for an operation.
+    ESIZE,
+}
+
+impl From<ReturnCode> for isize {
+    fn from(original: ReturnCode) -> isize {
+        match original {
+            ReturnCode::SUCCESS => 0,
+            ReturnCode::FAIL => -1,
+            ReturnCode::EBUSY => -2,
+            ReturnCode::EALREADY => -3,
+            ReturnCode::EOFF => -4,
+            ReturnCode::ERESERVE => -5,
+            ReturnCode::EINVAL => -6,
+            ReturnCode::ESIZE => -7,
+            ReturnCode::ECANCEL => -8,
+            ReturnCode::ENOMEM => -9,
+            ReturnCode::ENOSUPPORT => -10,
+            ReturnCode::ENODEVICE => -11,
+            ReturnCode::EUNINSTALLED => -12,
+            ReturnCode::ENOACK => -13,
+        }
+    }
+}
+
+impl From<ReturnCode> for usize {
+    fn from(original: ReturnCode) -> usize {
+        isize::from(original) as usize
+    }
+}
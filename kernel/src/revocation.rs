@@ -0,0 +1,63 @@
+//! Notification hook for capsules that hold onto an app's allowed
+//! memory across an asynchronous operation.
+//!
+//! Without this, a capsule with an in-flight DMA into an `AppSlice`
+//! (console TX, a ROS region, a long-running sensor capture) only
+//! discovers that the app replaced or revoked the allow, or that the
+//! process died, the next time it tries to use the now-zero-length
+//! slice — typically well after the underlying hardware operation
+//! should have been cancelled. `RevocationClient` lets a capsule
+//! register to be told immediately so it can abort deterministically.
+
+use crate::callback::AppId;
+
+/// Implemented by a capsule that wants to be told when an app's
+/// allowed region is being replaced (by a new `allow` call) or the app
+/// has died, rather than discovering it lazily.
+pub trait RevocationClient {
+    /// `allow_num` identifies which of the capsule's allowed buffers
+    /// for `app` is being revoked.
+    fn allow_revoked(&self, app: AppId, allow_num: usize);
+}
+
+const MAX_REGISTRATIONS: usize = 16;
+
+/// Kernel-maintained registry of capsules that asked to be notified
+/// when an allow they hold is revoked. The kernel's `allow`/process
+/// teardown paths call `notify` instead of silently dropping the old
+/// `AppSlice`.
+pub struct RevocationRegistry<'a> {
+    clients: [Option<(&'a dyn RevocationClient, usize)>; MAX_REGISTRATIONS],
+}
+
+impl<'a> RevocationRegistry<'a> {
+    pub const fn new() -> RevocationRegistry<'a> {
+        RevocationRegistry {
+            clients: [None; MAX_REGISTRATIONS],
+        }
+    }
+
+    /// Register `client` to be notified whenever `allow_num` is
+    /// revoked for any app. Returns `false` if the registry is full.
+    pub fn register(&mut self, client: &'a dyn RevocationClient, allow_num: usize) -> bool {
+        for slot in self.clients.iter_mut() {
+            if slot.is_none() {
+                *slot = Some((client, allow_num));
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Called by the kernel just before an `AppSlice` is replaced or
+    /// dropped due to process termination.
+    pub fn notify(&self, app: AppId, allow_num: usize) {
+        for slot in self.clients.iter() {
+            if let Some((client, registered_num)) = slot {
+                if *registered_num == allow_num {
+                    client.allow_revoked(app, allow_num);
+                }
+            }
+        }
+    }
+}
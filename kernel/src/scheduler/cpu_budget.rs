@@ -0,0 +1,91 @@
+//! Per-process CPU budget accounting, layered in front of any
+//! `Scheduler` to protect cooperative boards from a runaway app
+//! monopolizing the CPU.
+//!
+//! Each process is allowed to consume up to `budget_us` of execution
+//! time within a sliding window of `window_us`; once it exceeds that,
+//! `CpuBudgetEnforcer::is_throttled` reports it as not schedulable
+//! until the window has rolled far enough for its usage to fall back
+//! under budget. The budget itself comes from board configuration or a
+//! TBF header field — this module only does the accounting and the
+//! throttle decision.
+
+use crate::callback::AppId;
+
+const MAX_PROCESSES: usize = 16;
+const HISTORY_SLOTS: usize = 8;
+
+struct Budget {
+    process: AppId,
+    budget_us: u32,
+    window_us: u32,
+    /// Usage recorded per history slot, each covering `window_us /
+    /// HISTORY_SLOTS`; summed for a cheap sliding-window estimate
+    /// instead of a timestamped log of every run.
+    usage_slots: [u32; HISTORY_SLOTS],
+    current_slot: usize,
+}
+
+pub struct CpuBudgetEnforcer {
+    budgets: [Option<Budget>; MAX_PROCESSES],
+}
+
+impl CpuBudgetEnforcer {
+    pub fn new() -> CpuBudgetEnforcer {
+        CpuBudgetEnforcer {
+            budgets: Default::default(),
+        }
+    }
+
+    pub fn set_budget(&mut self, process: AppId, budget_us: u32, window_us: u32) {
+        for slot in self.budgets.iter_mut() {
+            if slot.is_none() {
+                *slot = Some(Budget {
+                    process,
+                    budget_us,
+                    window_us,
+                    usage_slots: [0; HISTORY_SLOTS],
+                    current_slot: 0,
+                });
+                return;
+            }
+        }
+    }
+
+    /// Record that `process` just ran for `time_used_us`.
+    pub fn record_usage(&mut self, process: AppId, time_used_us: u32) {
+        if let Some(budget) = self.find_mut(process) {
+            budget.usage_slots[budget.current_slot] += time_used_us;
+        }
+    }
+
+    /// Called once per `window_us / HISTORY_SLOTS` to age out the
+    /// oldest usage slot and start a fresh one.
+    pub fn advance_window(&mut self, process: AppId) {
+        if let Some(budget) = self.find_mut(process) {
+            budget.current_slot = (budget.current_slot + 1) % HISTORY_SLOTS;
+            budget.usage_slots[budget.current_slot] = 0;
+        }
+    }
+
+    pub fn is_throttled(&self, process: AppId) -> bool {
+        self.find(process)
+            .map(|b| b.usage_slots.iter().sum::<u32>() >= b.budget_us)
+            .unwrap_or(false)
+    }
+
+    fn find(&self, process: AppId) -> Option<&Budget> {
+        self.budgets
+            .iter()
+            .flatten()
+            .find(|b| b.process == process)
+    }
+
+    fn find_mut(&mut self, process: AppId) -> Option<&mut Budget> {
+        self.budgets
+            .iter_mut()
+            .flatten()
+            .find(|b| b.process == process)
+    }
+}
+
@@ -0,0 +1,106 @@
+//! Earliest-deadline-first cooperative scheduler.
+//!
+//! Each process declares a period (and thus an implicit relative
+//! deadline equal to that period) via a syscall; the scheduler always
+//! picks the ready process whose absolute deadline is soonest. An
+//! overrun — a process still not having yielded by the time its
+//! deadline passes — is reported to a registered supervisor rather
+//! than silently tolerated, since the whole point of EDF here is
+//! keeping sensing loops aligned with actuator timing.
+
+use core::cell::Cell;
+
+use crate::callback::AppId;
+use crate::scheduler::{Scheduler, SchedulingDecision};
+
+const MAX_EDF_PROCESSES: usize = 16;
+
+#[derive(Copy, Clone)]
+struct EdfEntry {
+    process: AppId,
+    period_us: u32,
+    deadline_us: u32,
+    ready: bool,
+}
+
+pub trait OverrunClient {
+    /// Called when `process` is still running (or still waiting to
+    /// run) after its deadline has passed.
+    fn deadline_overrun(&self, process: AppId, overrun_us: u32);
+}
+
+pub struct EdfScheduler<'a> {
+    entries: [Option<EdfEntry>; MAX_EDF_PROCESSES],
+    now_us: Cell<u32>,
+    overrun_client: Option<&'a dyn OverrunClient>,
+}
+
+impl<'a> EdfScheduler<'a> {
+    pub fn new(overrun_client: Option<&'a dyn OverrunClient>) -> EdfScheduler<'a> {
+        EdfScheduler {
+            entries: [None; MAX_EDF_PROCESSES],
+            now_us: Cell::new(0),
+            overrun_client,
+        }
+    }
+
+    /// Register (or re-register) `process`'s period, from a syscall
+    /// asking the scheduler to align it with this deadline.
+    pub fn set_period(&mut self, process: AppId, period_us: u32) {
+        let now = self.now_us.get();
+        for slot in self.entries.iter_mut() {
+            match slot {
+                Some(entry) if entry.process == process => {
+                    entry.period_us = period_us;
+                    entry.deadline_us = now + period_us;
+                    return;
+                }
+                None => {
+                    *slot = Some(EdfEntry {
+                        process,
+                        period_us,
+                        deadline_us: now + period_us,
+                        ready: true,
+                    });
+                    return;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn check_overruns(&self) {
+        let now = self.now_us.get();
+        for entry in self.entries.iter().flatten() {
+            if entry.ready && now > entry.deadline_us {
+                if let Some(client) = self.overrun_client {
+                    client.deadline_overrun(entry.process, now - entry.deadline_us);
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Scheduler for EdfScheduler<'a> {
+    fn next(&self) -> SchedulingDecision {
+        self.check_overruns();
+        let mut earliest: Option<EdfEntry> = None;
+        for entry in self.entries.iter().flatten() {
+            if entry.ready {
+                earliest = Some(match earliest {
+                    None => *entry,
+                    Some(e) if entry.deadline_us < e.deadline_us => *entry,
+                    Some(e) => e,
+                });
+            }
+        }
+        match earliest {
+            Some(entry) => SchedulingDecision::RunProcess(entry.process),
+            None => SchedulingDecision::TrySleep,
+        }
+    }
+
+    fn result(&self, process: AppId, time_used_us: u32) {
+        self.now_us.set(self.now_us.get() + time_used_us);
+    }
+}
@@ -0,0 +1,95 @@
+//! Pluggable kernel scheduling policies.
+
+pub mod cpu_budget;
+pub mod edf;
+pub mod priority;
+pub mod round_robin;
+
+use crate::callback::AppId;
+use crate::capabilities::ProcessManagementCapability;
+use core::cell::Cell;
+
+/// What the main kernel loop should do next, as decided by a
+/// `Scheduler`.
+pub enum SchedulingDecision {
+    RunProcess(AppId),
+    TrySleep,
+}
+
+/// Implemented by a scheduling policy. The kernel's main loop asks its
+/// installed `Scheduler` which process to run next rather than
+/// hard-coding round-robin.
+pub trait Scheduler {
+    fn next(&self) -> SchedulingDecision;
+
+    /// Called after the chosen process stops running (yielded, or used
+    /// up whatever time slice the policy grants it).
+    fn result(&self, process: AppId, time_used_us: u32);
+}
+
+/// Which built-in policy a `SwitchableScheduler` is currently
+/// delegating to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SchedulerPolicy {
+    RoundRobin,
+    Priority,
+    CooperativeEdf,
+}
+
+/// Wraps one of the kernel's scheduler implementations behind a
+/// capability-gated switch, so a management app (or a ProcessConsole
+/// command) can change policy without reflashing. Switching swaps
+/// which inner `Scheduler` trait object `next`/`result` are forwarded
+/// to; each inner policy keeps whatever state it held (e.g. EDF
+/// deadlines) while inactive, and no state is reset or carried over
+/// from one policy to another.
+pub struct SwitchableScheduler<'a> {
+    current: Cell<SchedulerPolicy>,
+    round_robin: &'a dyn Scheduler,
+    priority: &'a dyn Scheduler,
+    edf: &'a dyn Scheduler,
+}
+
+impl<'a> SwitchableScheduler<'a> {
+    pub fn new(
+        round_robin: &'a dyn Scheduler,
+        priority: &'a dyn Scheduler,
+        edf: &'a dyn Scheduler,
+    ) -> SwitchableScheduler<'a> {
+        SwitchableScheduler {
+            current: Cell::new(SchedulerPolicy::RoundRobin),
+            round_robin,
+            priority,
+            edf,
+        }
+    }
+
+    /// Change the active policy. Requires a `ProcessManagementCapability`
+    /// since an unprivileged app switching everyone to an unfavorable
+    /// policy would be a denial-of-service vector.
+    pub fn set_policy<C: ProcessManagementCapability>(&self, policy: SchedulerPolicy, _cap: &C) {
+        self.current.set(policy);
+    }
+
+    pub fn policy(&self) -> SchedulerPolicy {
+        self.current.get()
+    }
+
+    fn active(&self) -> &dyn Scheduler {
+        match self.current.get() {
+            SchedulerPolicy::RoundRobin => self.round_robin,
+            SchedulerPolicy::Priority => self.priority,
+            SchedulerPolicy::CooperativeEdf => self.edf,
+        }
+    }
+}
+
+impl<'a> Scheduler for SwitchableScheduler<'a> {
+    fn next(&self) -> SchedulingDecision {
+        self.active().next()
+    }
+
+    fn result(&self, process: AppId, time_used_us: u32) {
+        self.active().result(process, time_used_us)
+    }
+}
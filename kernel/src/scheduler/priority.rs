@@ -0,0 +1,37 @@
+//! Fixed-priority scheduling policy: always run the highest-priority
+//! ready process, with ties broken by process index.
+
+use crate::callback::AppId;
+use crate::scheduler::{Scheduler, SchedulingDecision};
+
+const MAX_PROCESSES: usize = 16;
+
+pub struct PriorityScheduler {
+    /// `(process, priority)` pairs; higher `priority` runs first.
+    processes: [Option<(AppId, u8)>; MAX_PROCESSES],
+}
+
+impl PriorityScheduler {
+    pub fn new(processes: [Option<(AppId, u8)>; MAX_PROCESSES]) -> PriorityScheduler {
+        PriorityScheduler { processes }
+    }
+}
+
+impl Scheduler for PriorityScheduler {
+    fn next(&self) -> SchedulingDecision {
+        let mut best: Option<(AppId, u8)> = None;
+        for entry in self.processes.iter().flatten() {
+            best = Some(match best {
+                None => *entry,
+                Some(b) if entry.1 > b.1 => *entry,
+                Some(b) => b,
+            });
+        }
+        match best {
+            Some((process, _)) => SchedulingDecision::RunProcess(process),
+            None => SchedulingDecision::TrySleep,
+        }
+    }
+
+    fn result(&self, _process: AppId, _time_used_us: u32) {}
+}
@@ -0,0 +1,39 @@
+//! The kernel's original scheduling policy: visit ready processes in a
+//! fixed rotation, giving each a bounded time slice.
+
+use core::cell::Cell;
+
+use crate::callback::AppId;
+use crate::scheduler::{Scheduler, SchedulingDecision};
+
+const MAX_PROCESSES: usize = 16;
+
+pub struct RoundRobinScheduler {
+    processes: [Option<AppId>; MAX_PROCESSES],
+    last_run_index: Cell<usize>,
+}
+
+impl RoundRobinScheduler {
+    pub fn new(processes: [Option<AppId>; MAX_PROCESSES]) -> RoundRobinScheduler {
+        RoundRobinScheduler {
+            processes,
+            last_run_index: Cell::new(0),
+        }
+    }
+}
+
+impl Scheduler for RoundRobinScheduler {
+    fn next(&self) -> SchedulingDecision {
+        let start = (self.last_run_index.get() + 1) % MAX_PROCESSES;
+        for offset in 0..MAX_PROCESSES {
+            let idx = (start + offset) % MAX_PROCESSES;
+            if let Some(process) = self.processes[idx] {
+                self.last_run_index.set(idx);
+                return SchedulingDecision::RunProcess(process);
+            }
+        }
+        SchedulingDecision::TrySleep
+    }
+
+    fn result(&self, _process: AppId, _time_used_us: u32) {}
+}
@@ -0,0 +1,75 @@
+//! Tickless idle and deep-sleep coordination.
+//!
+//! Previously `sleep()` was a chip-specific `WFI` with no visibility
+//! into what was still outstanding, so chips conservatively never went
+//! below a light sleep state. This subsystem lets capsules register
+//! wake constraints (the next alarm deadline, a pending DMA transfer)
+//! before the kernel's sleep hook runs, so it can pick the deepest
+//! `hil::power::SleepState` whose wake latency still satisfies every
+//! outstanding constraint.
+
+use crate::hil::power::{SleepController, SleepState};
+
+const MAX_CONSTRAINTS: usize = 16;
+
+/// Implemented by anything that can prevent the kernel from entering
+/// too deep a sleep state right now (an armed alarm, an in-flight DMA
+/// transfer, a peripheral still clocked).
+pub trait WakeConstraint {
+    /// The latest point, in microseconds from now, by which the system
+    /// must have woken back up; `None` if this constraint currently
+    /// imposes no limit.
+    fn max_sleep_us(&self) -> Option<u32>;
+}
+
+pub struct SleepCoordinator<'a> {
+    controller: &'a dyn SleepController,
+    constraints: [Option<&'a dyn WakeConstraint>; MAX_CONSTRAINTS],
+}
+
+impl<'a> SleepCoordinator<'a> {
+    pub fn new(controller: &'a dyn SleepController) -> SleepCoordinator<'a> {
+        SleepCoordinator {
+            controller,
+            constraints: [None; MAX_CONSTRAINTS],
+        }
+    }
+
+    pub fn register_constraint(&mut self, constraint: &'a dyn WakeConstraint) -> bool {
+        for slot in self.constraints.iter_mut() {
+            if slot.is_none() {
+                *slot = Some(constraint);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Pick and enter the deepest sleep state compatible with every
+    /// registered constraint. Called from the kernel's main-loop sleep
+    /// hook in place of a direct chip-specific `WFI`.
+    pub fn sleep(&self) {
+        let mut budget_us: Option<u32> = None;
+        for constraint in self.constraints.iter().flatten() {
+            if let Some(max) = constraint.max_sleep_us() {
+                budget_us = Some(budget_us.map_or(max, |b| core::cmp::min(b, max)));
+            }
+        }
+
+        let mut best: Option<SleepState> = None;
+        for &state in self.controller.available_states() {
+            let fits = budget_us.map_or(true, |budget| state.wake_latency_us <= budget);
+            if fits {
+                best = Some(match best {
+                    None => state,
+                    Some(b) if state.depth > b.depth => state,
+                    Some(b) => b,
+                });
+            }
+        }
+
+        if let Some(state) = best {
+            self.controller.enter(state);
+        }
+    }
+}
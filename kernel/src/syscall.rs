@@ -0,0 +1,107 @@
+//! System call identifiers and argument/return encodings shared between
+//! the kernel's dispatch loop and architecture-specific trap handlers.
+
+/// The width of the general-purpose registers used to pass syscall
+/// arguments and return values on the running platform. 32-bit
+/// platforms (Cortex-M, RV32) pack a `usize` argument into a single
+/// register; 64-bit platforms (RV64) can pass a full 64-bit value in
+/// one register instead of splitting it across two, so capsules that
+/// care (e.g. returning a 64-bit timestamp in one word) can query this
+/// and adjust their command/return encoding accordingly.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RegisterWidth {
+    ThirtyTwoBit,
+    SixtyFourBit,
+}
+
+/// A value returned from a `command` call, encoded architecture-width
+/// agnostically: on a `SixtyFourBit` platform, `Success(u64)` and
+/// `Failure(u64)` fit in a single register rather than being split
+/// across two `usize` registers as they would be on 32-bit platforms.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CommandReturn {
+    Success,
+    SuccessU64(u64),
+    Failure(crate::returncode::ReturnCode),
+    FailureU64(crate::returncode::ReturnCode, u64),
+}
+
+impl CommandReturn {
+    /// Split this return value into the registers the arch backend
+    /// should write, truncating `SuccessU64`/`FailureU64` on
+    /// `ThirtyTwoBit` platforms into a (low, high) pair instead of one
+    /// register.
+    pub fn into_registers(self, width: RegisterWidth) -> GenericSyscallReturnValue {
+        match (self, width) {
+            (CommandReturn::Success, _) => GenericSyscallReturnValue::Success,
+            (CommandReturn::Failure(e), _) => GenericSyscallReturnValue::Failure(e),
+            (CommandReturn::SuccessU64(v), RegisterWidth::SixtyFourBit) => {
+                GenericSyscallReturnValue::SuccessU64Packed(v)
+            }
+            (CommandReturn::SuccessU64(v), RegisterWidth::ThirtyTwoBit) => {
+                GenericSyscallReturnValue::SuccessU64Split(v as u32, (v >> 32) as u32)
+            }
+            (CommandReturn::FailureU64(e, v), RegisterWidth::SixtyFourBit) => {
+                GenericSyscallReturnValue::FailureU64Packed(e, v)
+            }
+            (CommandReturn::FailureU64(e, v), RegisterWidth::ThirtyTwoBit) => {
+                GenericSyscallReturnValue::FailureU64Split(e, v as u32, (v >> 32) as u32)
+            }
+        }
+    }
+}
+
+/// The actual register layout the arch backend writes back to the
+/// process, after `CommandReturn::into_registers` has accounted for
+/// the platform's `RegisterWidth`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GenericSyscallReturnValue {
+    Success,
+    Failure(crate::returncode::ReturnCode),
+    /// A 64-bit success value that fits in one register.
+    SuccessU64Packed(u64),
+    /// A 64-bit success value split across two 32-bit registers,
+    /// (low, high).
+    SuccessU64Split(u32, u32),
+    FailureU64Packed(crate::returncode::ReturnCode, u64),
+    FailureU64Split(crate::returncode::ReturnCode, u32, u32),
+}
+
+/// The `exit` family of system calls, by which a process voluntarily
+/// ends its own execution instead of spinning in a loop once it has
+/// nothing left to do. The kernel reclaims the process's grants and
+/// allows either way; which policy runs next is up to the board's
+/// process-restart policy.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExitCall {
+    /// Terminate and do not run again unless explicitly restarted by a
+    /// management capsule. `completion_code` is recorded for
+    /// inspection (e.g. by `process_info`) but otherwise has no kernel
+    /// meaning, mirroring a Unix exit status.
+    Terminate { completion_code: u32 },
+    /// Terminate and immediately ask the kernel to reload and restart
+    /// this process, as if it had faulted, but without logging a fault
+    /// or charging it against fault-based backoff policy.
+    Restart { completion_code: u32 },
+}
+
+/// The `yield` family of system calls, by which a process gives up the
+/// CPU until an upcall is ready to run.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum YieldCall {
+    /// Block until any upcall is ready, as today.
+    NoWait,
+    /// Block until any upcall is ready, then return immediately without
+    /// requiring another `yield` (used for a "no-wait" poll).
+    WaitFor,
+    /// Block until specifically the upcall registered for
+    /// `(driver_num, subscribe_num)` is ready to run; any other
+    /// upcalls that become ready in the meantime stay queued rather
+    /// than waking the process. Lets a synchronous userspace wrapper
+    /// (e.g. a blocking `read()`) avoid dispatching and re-queuing
+    /// unrelated callbacks by hand.
+    WaitForOnly {
+        driver_num: usize,
+        subscribe_num: usize,
+    },
+}
@@ -0,0 +1,150 @@
+//! Parsing for optional Tock Binary Format (TBF) header fields.
+
+/// An optional TBF header entry (`TBF_HEADER_PERMISSIONS`) listing the
+/// driver numbers a process is allowed to reach with `command`,
+/// `subscribe`, and `allow`. `permits` answers whether a driver number
+/// is in that list; the kernel's syscall dispatch does not call it, so
+/// syscalls to unlisted drivers are not rejected by this type alone.
+pub struct TbfHeaderPermissions<'a> {
+    driver_nums: &'a [usize],
+}
+
+impl<'a> TbfHeaderPermissions<'a> {
+    pub fn new(driver_nums: &'a [usize]) -> TbfHeaderPermissions<'a> {
+        TbfHeaderPermissions { driver_nums }
+    }
+
+    pub fn permits(&self, driver_num: usize) -> bool {
+        self.driver_nums.contains(&driver_num)
+    }
+}
+
+/// Parse a `TBF_HEADER_PERMISSIONS` TLV body (a little-endian `u16`
+/// count followed by that many little-endian `u32` driver numbers)
+/// out of a process's flash header.
+pub fn parse_permissions(buf: &[u8]) -> Option<heapless_driver_list::DriverList> {
+    if buf.len() < 2 {
+        return None;
+    }
+    let count = u16::from_le_bytes([buf[0], buf[1]]) as usize;
+    let mut list = heapless_driver_list::DriverList::new();
+    for i in 0..count {
+        let offset = 2 + i * 4;
+        if offset + 4 > buf.len() {
+            return None;
+        }
+        let num = u32::from_le_bytes([
+            buf[offset],
+            buf[offset + 1],
+            buf[offset + 2],
+            buf[offset + 3],
+        ]) as usize;
+        if list.push(num).is_err() {
+            break;
+        }
+    }
+    Some(list)
+}
+
+/// A small fixed-capacity list of permitted driver numbers, since the
+/// kernel has no heap to parse a TBF header into a `Vec`.
+mod heapless_driver_list {
+    pub const MAX_PERMITTED_DRIVERS: usize = 16;
+
+    pub struct DriverList {
+        nums: [usize; MAX_PERMITTED_DRIVERS],
+        len: usize,
+    }
+
+    impl DriverList {
+        pub fn new() -> DriverList {
+            DriverList {
+                nums: [0; MAX_PERMITTED_DRIVERS],
+                len: 0,
+            }
+        }
+
+        pub fn push(&mut self, num: usize) -> Result<(), ()> {
+            if self.len >= MAX_PERMITTED_DRIVERS {
+                return Err(());
+            }
+            self.nums[self.len] = num;
+            self.len += 1;
+            Ok(())
+        }
+
+        pub fn as_slice(&self) -> &[usize] {
+            &self.nums[..self.len]
+        }
+    }
+}
+
+/// Parse a `TBF_HEADER_ZEROIZE` TLV body: a single byte, `0` or `1`,
+/// overriding the board's default `zeroize::ZeroizePolicy` for this
+/// process specifically. Returns `None` if the TLV is malformed, which
+/// the caller treats the same as the TLV being absent.
+pub fn parse_zeroize_flag(buf: &[u8]) -> Option<bool> {
+    match buf.first() {
+        Some(0) => Some(false),
+        Some(1) => Some(true),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_permissions_reads_little_endian_driver_nums() {
+        let mut buf = [0u8; 2 + 4 * 2];
+        buf[0..2].copy_from_slice(&2u16.to_le_bytes());
+        buf[2..6].copy_from_slice(&1u32.to_le_bytes());
+        buf[6..10].copy_from_slice(&5u32.to_le_bytes());
+
+        let list = parse_permissions(&buf).unwrap();
+        assert_eq!(list.as_slice(), &[1, 5]);
+    }
+
+    #[test]
+    fn parse_permissions_rejects_truncated_body() {
+        let mut buf = [0u8; 2 + 4];
+        buf[0..2].copy_from_slice(&2u16.to_le_bytes());
+        buf[2..6].copy_from_slice(&1u32.to_le_bytes());
+        assert!(parse_permissions(&buf).is_none());
+    }
+
+    #[test]
+    fn parse_permissions_stops_at_max_permitted_drivers() {
+        const COUNT: usize = heapless_driver_list::MAX_PERMITTED_DRIVERS + 1;
+        let mut buf = [0u8; 2 + 4 * COUNT];
+        buf[0..2].copy_from_slice(&(COUNT as u16).to_le_bytes());
+        for i in 0..COUNT {
+            let offset = 2 + i * 4;
+            buf[offset..offset + 4].copy_from_slice(&(i as u32).to_le_bytes());
+        }
+
+        let list = parse_permissions(&buf).unwrap();
+        assert_eq!(list.as_slice().len(), heapless_driver_list::MAX_PERMITTED_DRIVERS);
+    }
+
+    #[test]
+    fn parse_zeroize_flag_reads_valid_bytes() {
+        assert_eq!(parse_zeroize_flag(&[0]), Some(false));
+        assert_eq!(parse_zeroize_flag(&[1]), Some(true));
+    }
+
+    #[test]
+    fn parse_zeroize_flag_rejects_other_bytes_and_empty_body() {
+        assert_eq!(parse_zeroize_flag(&[2]), None);
+        assert_eq!(parse_zeroize_flag(&[]), None);
+    }
+
+    #[test]
+    fn permits_matches_only_listed_driver_nums() {
+        let driver_nums = [1, 2, 3];
+        let permissions = TbfHeaderPermissions::new(&driver_nums);
+        assert!(permissions.permits(2));
+        assert!(!permissions.permits(4));
+    }
+}
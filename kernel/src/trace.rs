@@ -0,0 +1,59 @@
+//! Optional syscall tracing layer.
+//!
+//! When enabled, the kernel's dispatch loop records a compact entry
+//! for every syscall's entry and exit into a fixed-size ring buffer,
+//! instead of requiring capsules to sprinkle `debug!` calls to
+//! understand app/kernel interaction. A backend drains the ring buffer
+//! out to wherever the board wants it shown (a console frame, RTT,
+//! ITM); the kernel itself doesn't know or care which.
+
+use crate::callback::AppId;
+
+#[derive(Copy, Clone, Debug)]
+pub struct TraceEvent {
+    pub timestamp: u32,
+    pub app: AppId,
+    pub driver_num: usize,
+    pub command_num: usize,
+    pub return_code: isize,
+}
+
+/// Implemented by whatever transport the board wants trace events
+/// streamed over.
+pub trait TraceBackend {
+    fn write_event(&self, event: &TraceEvent);
+}
+
+const RING_CAPACITY: usize = 64;
+
+/// Fixed-capacity ring buffer of trace events, overwriting the oldest
+/// entry once full so tracing never blocks the syscall path waiting
+/// for the backend to drain.
+pub struct TraceRing {
+    events: [Option<TraceEvent>; RING_CAPACITY],
+    head: usize,
+}
+
+impl TraceRing {
+    pub const fn new() -> TraceRing {
+        TraceRing {
+            events: [None; RING_CAPACITY],
+            head: 0,
+        }
+    }
+
+    pub fn record(&mut self, event: TraceEvent) {
+        self.events[self.head] = Some(event);
+        self.head = (self.head + 1) % RING_CAPACITY;
+    }
+
+    /// Drain every recorded event, oldest first, to `backend`.
+    pub fn flush(&mut self, backend: &dyn TraceBackend) {
+        for i in 0..RING_CAPACITY {
+            let idx = (self.head + i) % RING_CAPACITY;
+            if let Some(event) = self.events[idx].take() {
+                backend.write_event(&event);
+            }
+        }
+    }
+}
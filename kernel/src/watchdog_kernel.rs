@@ -0,0 +1,75 @@
+//! Kernel watchdog subsystem.
+//!
+//! Arms the chip's hardware watchdog (via `hil::watchdog::WatchDog`)
+//! and only feeds it from the main kernel loop once every registered
+//! component has checked in for the current period. This turns the
+//! watchdog into a liveness check on the kernel's own main loop and
+//! every subsystem that opts in, rather than a check on the CPU clock
+//! alone: a hang in any one registered component now eventually resets
+//! the board instead of leaving it silently wedged.
+
+use core::cell::Cell;
+
+use crate::hil::watchdog::WatchDog;
+
+const MAX_COMPONENTS: usize = 16;
+
+/// A handle a kernel subsystem or capsule holds to check in with the
+/// watchdog each period.
+pub struct WatchdogComponentHandle<'a> {
+    kernel_watchdog: &'a KernelWatchdog<'a>,
+    index: usize,
+}
+
+impl<'a> WatchdogComponentHandle<'a> {
+    pub fn check_in(&self) {
+        self.kernel_watchdog.check_in(self.index);
+    }
+}
+
+pub struct KernelWatchdog<'a> {
+    hw: &'a dyn WatchDog,
+    registered: Cell<usize>,
+    checked_in: Cell<u16>,
+}
+
+impl<'a> KernelWatchdog<'a> {
+    pub fn new(hw: &'a dyn WatchDog, period_ms: u32) -> KernelWatchdog<'a> {
+        hw.start(period_ms);
+        KernelWatchdog {
+            hw,
+            registered: Cell::new(0),
+            checked_in: Cell::new(0),
+        }
+    }
+
+    /// Register a new component that must check in each period before
+    /// the watchdog is fed. Must be called during board setup, before
+    /// the main loop starts.
+    pub fn register_component(&'a self) -> Option<WatchdogComponentHandle<'a>> {
+        let index = self.registered.get();
+        if index >= MAX_COMPONENTS {
+            return None;
+        }
+        self.registered.set(index + 1);
+        Some(WatchdogComponentHandle {
+            kernel_watchdog: self,
+            index,
+        })
+    }
+
+    fn check_in(&self, index: usize) {
+        self.checked_in.set(self.checked_in.get() | (1 << index));
+    }
+
+    /// Called once per main-loop iteration. Feeds the hardware
+    /// watchdog, and resets the per-period check-in bitmap, only if
+    /// every registered component checked in since the last call.
+    pub fn service(&self) {
+        let all_checked_in_mask = ((1u32 << self.registered.get()) - 1) as u16;
+        if self.checked_in.get() & all_checked_in_mask == all_checked_in_mask {
+            self.hw.tick();
+            self.checked_in.set(0);
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Prioritized kernel work queue for capsule bottom halves.
+//!
+//! `deferred_call` (see [`crate::deferred_call`]) is the right tool for
+//! "call me back from the main loop," but it gives a capsule no way to
+//! split a long-running operation (a crypto block, polling a flash
+//! erase) into steps without blocking interrupts for the whole
+//! operation. `WorkQueue` lets a capsule enqueue a closure-like work
+//! item that the main loop runs a priority tier at a time, re-enqueuing
+//! itself if there's more to do.
+
+use core::cell::Cell;
+
+/// What a work item should do when run: finish, or reschedule itself
+/// to continue later (e.g. because it only had budget to erase one
+/// more flash sector this pass).
+pub enum WorkResult {
+    Done,
+    Continue,
+}
+
+pub trait WorkItem {
+    fn run(&self) -> WorkResult;
+
+    /// Lower numbers run first when multiple items are ready in the
+    /// same `service` pass.
+    fn priority(&self) -> u8 {
+        128
+    }
+}
+
+const MAX_WORK_ITEMS: usize = 16;
+
+pub struct WorkQueue<'a> {
+    items: [Cell<Option<&'a dyn WorkItem>>; MAX_WORK_ITEMS],
+}
+
+impl<'a> WorkQueue<'a> {
+    pub fn new() -> WorkQueue<'a> {
+        WorkQueue {
+            items: Default::default(),
+        }
+    }
+
+    /// Enqueue `item` to run on the next `service` pass. If it returns
+    /// `WorkResult::Continue`, it is automatically re-enqueued.
+    pub fn submit(&self, item: &'a dyn WorkItem) -> bool {
+        for slot in self.items.iter() {
+            if slot.get().is_none() {
+                slot.set(Some(item));
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Run every queued item once, highest priority (lowest number)
+    /// first, re-queuing any that report `Continue`. Called from the
+    /// main kernel loop.
+    pub fn service(&self) {
+        let mut order: [usize; MAX_WORK_ITEMS] = core::array::from_fn(|i| i);
+        order.sort_by_key(|&i| {
+            self.items[i]
+                .get()
+                .map(|item| item.priority())
+                .unwrap_or(u8::MAX)
+        });
+        for &i in order.iter() {
+            if let Some(item) = self.items[i].get() {
+                match item.run() {
+                    WorkResult::Done => self.items[i].set(None),
+                    WorkResult::Continue => {}
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! Scrubbing a process's memory when it exits, restarts, or faults, so
+//! key material or other secrets it held do not linger in RAM for the
+//! next process that happens to be granted the same region.
+//!
+//! `zeroize_volatile` is the actual scrub: a plain `for byte in buffer
+//! { *byte = 0 }` can be optimized away by the compiler once it proves
+//! the write is dead (the buffer is about to be freed or reused), which
+//! is exactly the case this module exists for, so every byte is
+//! written with `core::ptr::write_volatile` instead. Deciding *which*
+//! regions to scrub — a process's RAM, its grant allocations, and any
+//! `AppSlice`s the kernel is still holding from an in-flight
+//! operation — is done by the kernel's process teardown path, which
+//! calls this once per region; that enumeration is not shown here.
+
+/// Per-process zeroization policy: either the board-wide default, or
+/// an explicit opt-in/opt-out carried in the process's TBF header.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ZeroizePolicy {
+    Disabled,
+    Enabled,
+}
+
+impl ZeroizePolicy {
+    /// `board_default` applies unless the process's TBF header carries
+    /// a `TBF_HEADER_ZEROIZE` flag TLV overriding it; see
+    /// `tbf_header::parse_zeroize_flag`.
+    pub fn for_process(board_default: ZeroizePolicy, tbf_flag: Option<bool>) -> ZeroizePolicy {
+        match tbf_flag {
+            Some(true) => ZeroizePolicy::Enabled,
+            Some(false) => ZeroizePolicy::Disabled,
+            None => board_default,
+        }
+    }
+
+    pub fn should_zeroize(&self) -> bool {
+        *self == ZeroizePolicy::Enabled
+    }
+}
+
+/// Overwrites every byte of `buffer` with zero in a way the compiler
+/// cannot optimize away, even though `buffer` may never be read again
+/// before its memory is reused.
+pub fn zeroize_volatile(buffer: &mut [u8]) {
+    for byte in buffer.iter_mut() {
+        unsafe {
+            core::ptr::write_volatile(byte as *mut u8, 0);
+        }
+    }
+}
+